@@ -30,6 +30,9 @@ pub mod gb_memory_map {
         use std::ops::RangeInclusive;
 
         pub const CARTRIDGE_ROM_BANK_0: RangeInclusive<u16> = 0x0000..=0x3FFF;
+        pub const BOOT_ROM: RangeInclusive<u16> = 0x0000..=0x00FF;
+        // Second part of the CGB boot ROM, the cartridge header stays visible in between
+        pub const CGB_BOOT_ROM_HIGH: RangeInclusive<u16> = 0x0200..=0x08FF;
         pub const CARTRIDGE_ROM_BANK_1_N: RangeInclusive<u16> = 0x4000..=0x7FFF;
         pub const VIDEO_RAM: RangeInclusive<u16> = 0x8000..=0x9FFF;
         pub const CARTRIDGE_RAM: RangeInclusive<u16> = 0xA000..=0xBFFF;
@@ -42,10 +45,14 @@ pub mod gb_memory_map {
 
         pub const HARDWARE_IO_SERIAL: RangeInclusive<u16> = 0xFF01..=0xFF02;
         pub const HARDWARE_IO_TIMER: RangeInclusive<u16> = 0xFF04..=0xFF07;
+        pub const HARDWARE_IO_SOUND: RangeInclusive<u16> = 0xFF10..=0xFF26;
+        pub const WAVE_PATTERN_RAM: RangeInclusive<u16> = 0xFF30..=0xFF3F;
         pub const HARDWARE_IO_GRAPHICS: RangeInclusive<u16> = 0xFF40..=0xFF4B;
 
         pub const HARDWARE_IO_GRAPHICS_1: RangeInclusive<u16> = 0xFF40..=0xFF45;
         pub const HARDWARE_IO_GRAPHICS_2: RangeInclusive<u16> = 0xFF47..=0xFF4B;
+        pub const HARDWARE_IO_COLOR_PALETTES: RangeInclusive<u16> = 0xFF68..=0xFF6B;
+        pub const HARDWARE_IO_VRAM_DMA: RangeInclusive<u16> = 0xFF51..=0xFF55;
 
         //pub const HARDWARE_IO_REGISTERS_1: RangeInclusive<u16> = 0xFF00..=0xFF0E;
         //pub const HARDWARE_IO_REGISTERS_2: RangeInclusive<u16> = 0xFF10..=0xFF7F;
@@ -54,6 +61,7 @@ pub mod gb_memory_map {
         pub mod cartridge_header {
             //CARTRIDGE HEADER
             pub const ENTRY_POINT: u16 = 0x0100;
+            pub const CGB_FLAG: u16 = 0x0143;
             pub const CARTRIDGE_TYPE: u16 = 0x0147;
             pub const ROM_SIZE: u16 = 0x0148;
             pub const RAM_SIZE: u16 = 0x0149;
@@ -71,6 +79,28 @@ pub mod gb_memory_map {
             pub const TIMER_TMA: u16 = 0xFF06;
             pub const TIMER_TAC: u16 = 0xFF07;
 
+            pub const NR10: u16 = 0xFF10; // Channel 1 sweep
+            pub const NR11: u16 = 0xFF11; // Channel 1 length timer & duty cycle
+            pub const NR12: u16 = 0xFF12; // Channel 1 volume & envelope
+            pub const NR13: u16 = 0xFF13; // Channel 1 period low
+            pub const NR14: u16 = 0xFF14; // Channel 1 period high & control
+            pub const NR21: u16 = 0xFF16; // Channel 2 length timer & duty cycle
+            pub const NR22: u16 = 0xFF17; // Channel 2 volume & envelope
+            pub const NR23: u16 = 0xFF18; // Channel 2 period low
+            pub const NR24: u16 = 0xFF19; // Channel 2 period high & control
+            pub const NR30: u16 = 0xFF1A; // Channel 3 DAC enable
+            pub const NR31: u16 = 0xFF1B; // Channel 3 length timer
+            pub const NR32: u16 = 0xFF1C; // Channel 3 output level
+            pub const NR33: u16 = 0xFF1D; // Channel 3 period low
+            pub const NR34: u16 = 0xFF1E; // Channel 3 period high & control
+            pub const NR41: u16 = 0xFF20; // Channel 4 length timer
+            pub const NR42: u16 = 0xFF21; // Channel 4 volume & envelope
+            pub const NR43: u16 = 0xFF22; // Channel 4 frequency & randomness
+            pub const NR44: u16 = 0xFF23; // Channel 4 control
+            pub const NR50: u16 = 0xFF24; // Master volume & VIN panning
+            pub const NR51: u16 = 0xFF25; // Sound panning
+            pub const NR52: u16 = 0xFF26; // Sound on/off
+
             pub const LCD_CONTROL: u16 = 0xFF40;
             pub const LCD_STATUS: u16 = 0xFF41;
             pub const SCY: u16 = 0xFF42; // Viewport Y position
@@ -83,6 +113,21 @@ pub mod gb_memory_map {
             pub const OBP1: u16 = 0xFF49; // OBJ palette 1 data
             pub const WY: u16 = 0xFF4A; // Window Y position
             pub const WX: u16 = 0xFF4B; // Window X position plus 7
+
+            pub const KEY0: u16 = 0xFF4C; // CGB DMG compatibility mode, set by the boot ROM
+            pub const KEY1: u16 = 0xFF4D; // CGB prepare speed switch
+            pub const VBK: u16 = 0xFF4F; // CGB VRAM bank
+            pub const BOOT_ROM_DISABLE: u16 = 0xFF50; // Non-zero write unmaps the boot ROM
+            pub const HDMA1: u16 = 0xFF51; // CGB VRAM DMA source high
+            pub const HDMA2: u16 = 0xFF52; // CGB VRAM DMA source low
+            pub const HDMA3: u16 = 0xFF53; // CGB VRAM DMA destination high
+            pub const HDMA4: u16 = 0xFF54; // CGB VRAM DMA destination low
+            pub const HDMA5: u16 = 0xFF55; // CGB VRAM DMA length, mode and start
+            pub const BCPS: u16 = 0xFF68; // CGB background palette specification
+            pub const BCPD: u16 = 0xFF69; // CGB background palette data
+            pub const OCPS: u16 = 0xFF6A; // CGB OBJ palette specification
+            pub const OCPD: u16 = 0xFF6B; // CGB OBJ palette data
+            pub const SVBK: u16 = 0xFF70; // CGB WRAM bank
         }
         pub const INTF_REGISTER: u16 = 0xFF0F;
         pub const INTE_REGISTER: u16 = 0xFFFF;
@@ -90,11 +135,20 @@ pub mod gb_memory_map {
 
     pub mod memory {
         pub const DEFAULT_INIT_VALUE: u8 = 0;
+        // Read back when no device drives the data bus
+        pub const OPEN_BUS_VALUE: u8 = 0xFF;
         pub const HIGH_RAM_SIZE: usize = 0x7F;
         pub const WRAM_SIZE: usize = 0x2000;
+        // 8 banks of 4 KiB on CGB
+        pub const CGB_WRAM_SIZE: usize = 0x8000;
+        pub const WRAM_BANK_SIZE: usize = 0x1000;
         pub const WRAM_ADDRESS_MASK: usize = 0x0FFF;
         pub const VRAM_SIZE: usize = 0x2000;
+        pub const CGB_VRAM_BANKS: usize = 2;
+        pub const COLOR_PALETTE_RAM_SIZE: usize = 0x40;
         pub const VOAM_SIZE: usize = 0xA0;
+        pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+        pub const CGB_BOOT_ROM_SIZE: usize = 0x900;
     }
 
     /// ISR_ADDRESS
@@ -1,69 +1,177 @@
 #![allow(dead_code)]
 use super::constants::gb_memory_map::{address, isr_adress};
 use super::cpu_data::{ControlFlags, FlagsRegister, Registers};
-use super::iommu::IOMMU;
+use super::iommu::Iommu;
+use crate::debugger::CpuRegisters;
+use crate::emulator_constants::HardwareModel;
+use crate::error::{EmulatorError, Result};
 use crate::instructions::{
-    self, arithmetic_logic, cpu_control, jump, load, rotate_and_shift, single_bit_operation,
+    arithmetic_logic, cpu_control, jump, load,
+    opcode_table::{CB_OPCODE_TABLE, OPCODE_TABLE},
+    rotate_and_shift, single_bit_operation,
 };
 use crate::peripheral::interrupt_controller::InterruptRegister;
+use crate::save_state::{Snapshot, StateReader, StateWriter};
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
+
+/// # opcode_field
+/// Operands encoded in the opcode bits
+mod opcode_field {
+    /// Bits 2-0: source register of LD r,r, ALU and 0xCB prefixed instructions
+    pub fn low(opcode: u8) -> u8 {
+        opcode & 0x07
+    }
+
+    /// Bits 5-3: destination register, bit number of BIT/RES/SET or RST vector
+    pub fn middle(opcode: u8) -> u8 {
+        opcode.rotate_right(3) & 0x07
+    }
+
+    /// Bits 5-4: register pair
+    pub fn pair(opcode: u8) -> u8 {
+        opcode.rotate_right(4) & 0x03
+    }
+}
+
 /// # DMG-CPU
 /// 8-bit 8080-like Sharp CPU
 pub struct Cpu {
     register: Registers,
     cycles: u32,
     control: ControlFlags,
-    iommu: Rc<RefCell<IOMMU>>,
+    // Address and opcode of the illegal instruction which hung the CPU
+    locked_instruction: Option<(u16, u8)>,
+    iommu: Rc<RefCell<Iommu>>,
 }
 impl Cpu {
-    pub fn new(iommu: Rc<RefCell<IOMMU>>) -> Self {
+    pub fn new(iommu: Rc<RefCell<Iommu>>) -> Self {
         Cpu {
             register: Registers::default(),
             cycles: 0,
             control: ControlFlags::default(),
+            locked_instruction: None,
             iommu,
         }
     }
 
-    pub fn init(&mut self) {
-        self.register.a = 0x01;
-        self.register.flag = FlagsRegister::from(0xB0);
-        self.register.b = 0x00;
-        self.register.c = 0x13;
-        self.register.d = 0x00;
-        self.register.e = 0xD8;
-        self.register.h = 0x01;
-        self.register.l = 0x4D;
+    /// # init
+    /// Register values left by the boot ROM of the model, A=0x11 tells the game it runs on CGB
+    pub fn init(&mut self, model: HardwareModel, cgb_mode: bool) {
+        let [a, flag, b, c, d, e, h, l] = match (model, cgb_mode) {
+            (HardwareModel::Dmg0, _) => [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+            (HardwareModel::Dmg, _) => [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            (HardwareModel::Mgb, _) => [0xFF, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            (HardwareModel::Sgb, _) => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            (HardwareModel::Sgb2, _) => [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            (HardwareModel::Cgb, true) => [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+            // DMG compatibility mode, B, H and L depend on the cartridge title
+            (HardwareModel::Cgb, false) => [0x11, 0x80, 0x00, 0x00, 0x00, 0x08, 0x00, 0x7C],
+            // AGB boot ROM ends with INC B
+            (HardwareModel::Agb, true) => [0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+            (HardwareModel::Agb, false) => [0x11, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x7C],
+        };
+        self.register.a = a;
+        self.register.flag = FlagsRegister::from(flag);
+        self.register.b = b;
+        self.register.c = c;
+        self.register.d = d;
+        self.register.e = e;
+        self.register.h = h;
+        self.register.l = l;
         self.register.sp = *address::HIGH_RAM.end();
         self.register.pc = address::cartridge_header::ENTRY_POINT;
     }
 
-    pub fn process(&mut self) -> u32 {
-        //todo HALT handling support
-        self.interrupt_handling();
+    /// # init_for_boot_rom
+    /// Power on state, the boot ROM at 0x0000 sets up the registers itself
+    pub fn init_for_boot_rom(&mut self) {
+        self.register.a = 0;
+        self.register.flag = FlagsRegister::from(0);
+        self.register.b = 0;
+        self.register.c = 0;
+        self.register.d = 0;
+        self.register.e = 0;
+        self.register.h = 0;
+        self.register.l = 0;
+        self.register.sp = 0;
+        self.register.pc = 0;
+    }
+
+    /// # process
+    /// Executes one instruction and clocks the peripherals.
+    ///
+    /// Once an illegal opcode is executed the CPU stops fetching (peripherals are still clocked)
+    /// and every step reports the lock-up
+    pub fn process(&mut self) -> Result<u32> {
+        if self.locked_instruction.is_some() {
+            self.cycles = 4;
+            self.iommu.borrow_mut().process(self.cycles);
+            return self.lock_up_status();
+        }
+
+        if self.control.halted {
+            // Any pending interrupt wakes the CPU up, even with IME=0
+            if self.pending_interrupts() == 0 {
+                self.cycles = 4;
+                self.iommu.borrow_mut().process(self.cycles);
+                self.cycles += self.dma_stall_handling();
+                return Ok(self.cycles);
+            }
+            self.control.halted = false;
+        }
+
+        let dispatch_cycles = self.interrupt_handling();
+        // EI takes effect after the instruction which follows it
+        let is_ime_enable_scheduled = self.control.ime_scheduled;
         let opcode = self.fetch_byte();
+        self.execute(opcode);
 
-        if self.is_prefix_instruction(opcode) {
-            let opcode = self.fetch_byte();
-            self.execute_cbprefixed_instruction(opcode);
-        } else {
-            self.execute(opcode);
+        if is_ime_enable_scheduled && self.control.ime_scheduled {
+            self.control.ime_scheduled = false;
+            self.control.ime = true;
         }
+        self.cycles += dispatch_cycles;
 
         self.iommu.borrow_mut().process(self.cycles);
+        self.cycles += self.dma_stall_handling();
+        if self.locked_instruction.is_some() {
+            return self.lock_up_status();
+        }
         // 1 machine cycle = 4 clock cycles
-        self.cycles
+        Ok(self.cycles)
+    }
+
+    /// # dma_stall_handling
+    /// The CPU does not run while the VRAM DMA copies data, the peripherals keep running
+    fn dma_stall_handling(&mut self) -> u32 {
+        let stall_cycles = self.iommu.borrow_mut().take_dma_stall_cycles();
+        for _ in 0..stall_cycles / 4 {
+            self.iommu.borrow_mut().process(4);
+        }
+        stall_cycles
+    }
+
+    fn lock_up_status(&self) -> Result<u32> {
+        match self.locked_instruction {
+            Some((address, opcode)) => Err(EmulatorError::CpuLocked { opcode, address }),
+            None => Ok(self.cycles),
+        }
     }
 
-    fn is_prefix_instruction(&self, opcode: u8) -> bool {
-        opcode == 0xCB
+    /// # is_locked
+    /// CPU hangs after an illegal opcode until the machine is reset
+    pub fn is_locked(&self) -> bool {
+        self.locked_instruction.is_some()
     }
 
     fn fetch_byte(&mut self) -> u8 {
         let byte = self.iommu.borrow_mut().read_byte(self.register.pc);
-        self.register.pc = self.register.pc.wrapping_add(1);
+        if self.control.halt_bug {
+            self.control.halt_bug = false;
+        } else {
+            self.register.pc = self.register.pc.wrapping_add(1);
+        }
         byte
     }
 
@@ -73,1464 +181,599 @@ impl Cpu {
         (high_byte as u16).rotate_left(8) | (low_byte as u16)
     }
 
-    fn interrupt_handling(&mut self) {
-        if !self.control.ime {
-            return;
-        }
-        let intf = self.iommu.borrow_mut().read_byte(address::INTF_REGISTER);
-        let inte = self.iommu.borrow_mut().read_byte(address::INTE_REGISTER);
-
-        if inte & intf != 0 {
-            self.control.ime = false;
-            load::push(
-                &mut self.iommu.borrow_mut(),
-                &mut self.register.sp,
-                self.register.pc,
-            );
-            let mut isr_reg = InterruptRegister::from(intf);
-            if isr_reg.v_blank {
-                self.register.pc = isr_adress::V_BLANK;
-                isr_reg.v_blank = false;
-            } else if isr_reg.lcd {
-                self.register.pc = isr_adress::LCD_STATUS;
-                isr_reg.lcd = false;
-            } else if isr_reg.timer {
-                self.register.pc = isr_adress::TIMER;
-                isr_reg.timer = false;
-            } else if isr_reg.serial_link {
-                self.register.pc = isr_adress::SERIAL_LINK;
-                isr_reg.serial_link = false;
-            } else if isr_reg.joypad {
-                self.register.pc = isr_adress::JOYPAD;
-                isr_reg.joypad = false;
-            } else { //Error
-            }
-            let mask: u8 = InterruptRegister::into(isr_reg);
-            let reg_val_for_reset_isr = mask & intf;
-            self.iommu
-                .borrow_mut()
-                .write_byte(address::INTF_REGISTER, reg_val_for_reset_isr);
-            self.cycles = 16;
-        }
+    /// # pending_interrupts
+    /// Requested and enabled interrupts (IE & IF)
+    fn pending_interrupts(&self) -> u8 {
+        self.iommu.borrow().pending_interrupts()
     }
 
-    fn arithmetic_logic_instruction_dispatcher(&mut self, opcode: u8) {
-        match opcode {
-            // .::ADD operation::.
-            0x09 | 0x19 | 0x29 | 0x39 => {
-                let value = self
-                    .register
-                    .get_reg16_value_from_opcode_array(&[0x09, 0x19, 0x29, 0x39], opcode);
-                arithmetic_logic::add_hl(
-                    &mut self.register.flag,
-                    &mut self.register.h,
-                    &mut self.register.l,
-                    value,
-                );
-                self.cycles = 8;
-            }
-            0x80 | 0x81 | 0x82 | 0x83 | 0x84 | 0x85 | 0x87 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x87],
-                    opcode,
-                );
-                arithmetic_logic::add(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                    0,
-                );
-                self.cycles = 4;
-            }
-            0x86 => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::add(&mut self.register.flag, &mut self.register.a, val, 0);
-                self.cycles = 8;
-            }
-            0xC6 => {
-                let val = self.fetch_byte();
-                arithmetic_logic::add(&mut self.register.flag, &mut self.register.a, val, 0);
-                self.cycles = 8;
-            }
-            0xE8 => {
-                let val = self.fetch_byte() as i8;
-                arithmetic_logic::add_sp(&mut self.register.flag, &mut self.register.sp, val);
-                self.cycles = 16;
-            }
-            // .::ADC operation::.
-            0x88 | 0x89 | 0x8A | 0x8B | 0x8C | 0x8D | 0x8F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x88, 0x89, 0x8A, 0x8B, 0x8C, 0x8D, 0x8F],
-                    opcode,
-                );
-                arithmetic_logic::adc(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                );
-                self.cycles = 4;
-            }
-            0xCE => {
-                let val = self.fetch_byte();
-                arithmetic_logic::adc(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            0x8E => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::adc(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            // .::SUB operation::.
-            0x90 | 0x91 | 0x92 | 0x93 | 0x94 | 0x95 | 0x97 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x90, 0x91, 0x92, 0x93, 0x94, 0x95, 0x97],
-                    opcode,
-                );
-                arithmetic_logic::sub(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                    0,
-                );
-                self.cycles = 4;
-            }
-            0x96 => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::sub(&mut self.register.flag, &mut self.register.a, val, 0);
-                self.cycles = 8;
-            }
-            0xD6 => {
-                let val = self.fetch_byte();
-                arithmetic_logic::sub(&mut self.register.flag, &mut self.register.a, val, 0);
-                self.cycles = 8;
-            }
-            // .::SBC operation::.
-            0x98 | 0x99 | 0x9A | 0x9B | 0x9C | 0x9D | 0x9F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x98, 0x99, 0x9A, 0x9B, 0x9C, 0x9D, 0x9F],
-                    opcode,
-                );
-                arithmetic_logic::sbc(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                );
-                self.cycles = 4;
-            }
-            0x9E => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::sbc(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            0xDE => {
-                let val = self.fetch_byte();
-                arithmetic_logic::sbc(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            // .::AND operation::.
-            0xA0 | 0xA1 | 0xA2 | 0xA3 | 0xA4 | 0xA5 | 0xA7 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5, 0xA7],
-                    opcode,
-                );
-                arithmetic_logic::and(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                );
-                self.cycles = 4;
-            }
-            0xA6 => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::and(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            0xE6 => {
-                let val = self.fetch_byte();
-                arithmetic_logic::and(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            // .::XOR operation::.
-            0xA8 | 0xA9 | 0xAA | 0xAB | 0xAC | 0xAD | 0xAF => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0xA8, 0xA9, 0xAA, 0xAB, 0xAC, 0xAD, 0xAF],
-                    opcode,
-                );
-                arithmetic_logic::xor(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                );
-                self.cycles = 4;
-            }
-            0xAE => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::xor(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            0xEE => {
-                let val = self.fetch_byte();
-                arithmetic_logic::xor(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            // .::OR operation::.
-            0xB0 | 0xB1 | 0xB2 | 0xB3 | 0xB4 | 0xB5 | 0xB7 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0xB0, 0xB1, 0xB2, 0xB3, 0xB4, 0xB5, 0xB7],
-                    opcode,
-                );
-                arithmetic_logic::or(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                );
-                self.cycles = 4;
-            }
-            0xB6 => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::or(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            0xF6 => {
-                let val = self.fetch_byte();
-                arithmetic_logic::or(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            // .::CP operation::.
-            0xB8 | 0xB9 | 0xBA | 0xBB | 0xBC | 0xBD | 0xBF => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0xB8, 0xB9, 0xBA, 0xBB, 0xBC, 0xBD, 0xBF],
-                    opcode,
-                );
-                arithmetic_logic::cp(
-                    &mut self.register.flag,
-                    &mut self.register.a,
-                    register_value,
-                );
-                self.cycles = 4;
-            }
-            0xBE => {
-                let val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::cp(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
-            0xFE => {
-                let val = self.fetch_byte();
-                arithmetic_logic::cp(&mut self.register.flag, &mut self.register.a, val);
-                self.cycles = 8;
-            }
+    /// # interrupt_handling
+    /// Interrupt dispatch takes 5 machine cycles: 2 wait states, PC push (high, low byte) and the jump.
+    ///
+    /// The interrupt vector is chosen after the high byte of PC is pushed. When this push overwrites IE
+    /// and no enabled interrupt remains, the dispatch is cancelled and the CPU jumps to 0x0000.
+    ///
+    /// Returns the number of cycles spent on dispatch
+    fn interrupt_handling(&mut self) -> u32 {
+        const INTERRUPT_DISPATCH_CYCLES: u32 = 20;
 
-            // .::INC operation::.
-            0x03 => {
-                arithmetic_logic::inc_16(&mut self.register.b, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x13 => {
-                arithmetic_logic::inc_16(&mut self.register.d, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x23 => {
-                arithmetic_logic::inc_16(&mut self.register.h, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x33 => {
-                let mut sp_low: u8 = self.register.sp as u8;
-                let mut sp_high: u8 = ((self.register.sp & 0xFF00) >> 8) as u8;
-                arithmetic_logic::inc_16(&mut sp_high, &mut sp_low);
+        if !self.control.ime || self.pending_interrupts() == 0 {
+            return 0;
+        }
+        self.control.ime = false;
 
-                self.register.sp = (sp_high as u16).rotate_left(8) | (sp_low as u16);
-                self.cycles = 8;
-            }
-            0x04 => {
-                arithmetic_logic::inc(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 4;
-            }
-            0x0C => {
-                arithmetic_logic::inc(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 4;
-            }
-            0x14 => {
-                arithmetic_logic::inc(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 4;
-            }
-            0x1C => {
-                arithmetic_logic::inc(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 4;
-            }
-            0x24 => {
-                arithmetic_logic::inc(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 4;
-            }
-            0x2C => {
-                arithmetic_logic::inc(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 4;
-            }
-            0x3C => {
-                arithmetic_logic::inc(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            0x34 => {
-                let mut val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::inc(&mut self.register.flag, &mut val);
-                self.iommu
-                    .borrow_mut()
-                    .write_byte(self.register.get_hl(), val);
-                self.cycles = 12;
-            }
-            // .::DEC operation::.
-            0x0B => {
-                arithmetic_logic::dec_16(&mut self.register.b, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x1B => {
-                arithmetic_logic::dec_16(&mut self.register.d, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x2B => {
-                arithmetic_logic::dec_16(&mut self.register.h, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x3B => {
-                let mut sp_low: u8 = self.register.sp as u8;
-                let mut sp_high: u8 = ((self.register.sp & 0xFF00) >> 8) as u8;
-                arithmetic_logic::dec_16(&mut sp_high, &mut sp_low);
+        let pc_high_byte = (self.register.pc & 0xFF00).rotate_right(8) as u8;
+        let pc_low_byte = (self.register.pc & 0x00FF) as u8;
 
-                self.register.sp = (sp_high as u16).rotate_left(8) | (sp_low as u16);
-                self.cycles = 8;
-            }
-            0x05 => {
-                arithmetic_logic::dec(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 4;
-            }
-            0x0D => {
-                arithmetic_logic::dec(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 4;
-            }
-            0x15 => {
-                arithmetic_logic::dec(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 4;
-            }
-            0x1D => {
-                arithmetic_logic::dec(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 4;
-            }
-            0x25 => {
-                arithmetic_logic::dec(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 4;
-            }
-            0x2D => {
-                arithmetic_logic::dec(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 4;
-            }
-            0x3D => {
-                arithmetic_logic::dec(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            0x35 => {
-                let mut val = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                arithmetic_logic::dec(&mut self.register.flag, &mut val);
-                self.iommu
-                    .borrow_mut()
-                    .write_byte(self.register.get_hl(), val);
-                self.cycles = 12;
-            }
-            // .::DAA operation::.
-            0x27 => {
-                arithmetic_logic::daa(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            // .::CPL operation::.
-            0x2F => {
-                arithmetic_logic::cpl(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            // .::LD HL operation::.
-            0xF8 => {
-                let val = self.fetch_byte() as i8;
-                arithmetic_logic::ld_hl(
-                    &mut self.register.flag,
-                    &mut self.register.h,
-                    &mut self.register.l,
-                    self.register.sp,
-                    val,
-                );
-                self.cycles = 12;
-            }
-            _ => panic!("arithmetic_logic opcode [{:#02x?}] not supported!", opcode),
+        self.register.sp = self.register.sp.wrapping_sub(1);
+        self.iommu
+            .borrow_mut()
+            .write_byte(self.register.sp, pc_high_byte);
+
+        let pending_interrupts = self.pending_interrupts();
+
+        self.register.sp = self.register.sp.wrapping_sub(1);
+        self.iommu
+            .borrow_mut()
+            .write_byte(self.register.sp, pc_low_byte);
+
+        let mut isr_reg = InterruptRegister::from(pending_interrupts);
+        if isr_reg.v_blank {
+            self.register.pc = isr_adress::V_BLANK;
+            isr_reg.v_blank = false;
+        } else if isr_reg.lcd {
+            self.register.pc = isr_adress::LCD_STATUS;
+            isr_reg.lcd = false;
+        } else if isr_reg.timer {
+            self.register.pc = isr_adress::TIMER;
+            isr_reg.timer = false;
+        } else if isr_reg.serial_link {
+            self.register.pc = isr_adress::SERIAL_LINK;
+            isr_reg.serial_link = false;
+        } else if isr_reg.joypad {
+            self.register.pc = isr_adress::JOYPAD;
+            isr_reg.joypad = false;
+        } else {
+            // Dispatch cancelled by the IE write during push
+            self.register.pc = 0x0000;
+            return INTERRUPT_DISPATCH_CYCLES;
         }
+
+        // Only the serviced request is cleared in IF
+        let serviced_interrupt = pending_interrupts & !u8::from(isr_reg);
+        self.iommu
+            .borrow_mut()
+            .acknowledge_interrupt(serviced_interrupt);
+        INTERRUPT_DISPATCH_CYCLES
     }
 
-    fn load_instruction_dispatcher(&mut self, opcode: u8) {
-        match opcode {
-            0x01 => {
-                let value = self.fetch_word();
-                load::ld_16(&mut self.register.b, &mut self.register.c, value);
-                self.cycles = 12;
-            }
-            0x02 => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_bc());
-                load::ld(&mut self.register.a, value);
-                self.cycles = 8;
-            }
-            0x06 => {
-                let value = self.fetch_byte();
-                load::ld(&mut self.register.b, value);
-                self.cycles = 8;
-            }
-            0x08 => {
-                let address = self.fetch_word();
-                self.iommu
-                    .borrow_mut()
-                    .write_word(address, self.register.sp);
-                self.cycles = 20;
-            }
-            0x0A => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_bc());
-                load::ld(&mut self.register.b, value);
-                self.cycles = 8;
-            }
-            0x0E => {
-                let value = self.fetch_byte();
-                load::ld(&mut self.register.c, value);
-                self.cycles = 8;
-            }
-            0x11 => {
-                let value = self.fetch_word();
-                load::ld_16(&mut self.register.d, &mut self.register.e, value);
-                self.cycles = 12;
-            }
-            0x12 => {
-                let address = self.register.get_de();
-                self.iommu.borrow_mut().write_byte(address, self.register.a);
-                self.cycles = 8;
-            }
-            0x16 => {
-                let value = self.fetch_byte();
-                load::ld(&mut self.register.d, value);
-                self.cycles = 8;
-            }
-            0x1A => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_de());
-                load::ld(&mut self.register.a, value);
-                self.cycles = 8;
-            }
-            0x1E => {
-                let value = self.fetch_byte();
-                load::ld(&mut self.register.e, value);
-                self.cycles = 8;
-            }
-            0x21 => {
-                let value = self.fetch_word();
-                load::ld_16(&mut self.register.h, &mut self.register.l, value);
-                self.cycles = 12;
-            }
-            0x22 => {
-                let address = load::hli(&mut self.register.h, &mut self.register.l);
-                self.iommu.borrow_mut().write_byte(address, self.register.a);
-                self.cycles = 8;
-            }
-            0x26 => {
-                let value = self.fetch_byte();
-                load::ld(&mut self.register.h, value);
-                self.cycles = 8;
-            }
-            0x2A => {
-                let address = load::hli(&mut self.register.h, &mut self.register.l);
-                self.register.a = self.iommu.borrow_mut().read_byte(address);
-                self.cycles = 8;
-            }
-            0x2E => {
-                let value = self.fetch_byte();
-                load::ld(&mut self.register.l, value);
-                self.cycles = 8;
-            }
-            0x31 => {
-                self.register.sp = self.fetch_word();
-                self.cycles = 12;
-            }
-            0x32 => {
-                let address = load::hld(&mut self.register.h, &mut self.register.l);
-                self.iommu.borrow_mut().write_byte(address, self.register.a);
-                self.cycles = 8;
-            }
-            0x36 => {
-                let value = self.fetch_byte();
-                self.iommu
-                    .borrow_mut()
-                    .write_byte(self.register.get_hl(), value);
-                self.cycles = 12;
-            }
-            0x3A => {
-                let address = load::hld(&mut self.register.h, &mut self.register.l);
-                self.register.a = self.iommu.borrow_mut().read_byte(address);
-                self.cycles = 8;
-            }
-            0x3E => {
-                let value = self.fetch_byte();
-                load::ld(&mut self.register.a, value);
-                self.cycles = 8;
-            }
-            // LD B, r
-            0x40 | 0x41 | 0x42 | 0x43 | 0x44 | 0x45 | 0x47 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x47],
-                    opcode,
-                );
-                load::ld(&mut self.register.b, register_value);
-                self.cycles = 4;
-            }
-            0x46 => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                load::ld(&mut self.register.b, value);
-                self.cycles = 8;
-            }
-            // LD C, r
-            0x48 | 0x49 | 0x4A | 0x4B | 0x4C | 0x4D | 0x4F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x48, 0x49, 0x4A, 0x4B, 0x4C, 0x4D, 0x4F],
-                    opcode,
-                );
-                load::ld(&mut self.register.c, register_value);
-                self.cycles = 4;
-            }
-            0x4E => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                load::ld(&mut self.register.c, value);
-                self.cycles = 8;
-            }
-            // LD D, r
-            0x50 | 0x51 | 0x52 | 0x53 | 0x54 | 0x55 | 0x57 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x57],
-                    opcode,
-                );
-                load::ld(&mut self.register.d, register_value);
-                self.cycles = 4;
-            }
-            0x56 => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                load::ld(&mut self.register.d, value);
-                self.cycles = 8;
-            }
-            // LD E, r
-            0x58 | 0x59 | 0x5A | 0x5B | 0x5C | 0x5D | 0x5F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x58, 0x59, 0x5A, 0x5B, 0x5C, 0x5D, 0x5F],
-                    opcode,
-                );
-                load::ld(&mut self.register.e, register_value);
-                self.cycles = 4;
-            }
-            0x5E => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                load::ld(&mut self.register.e, value);
-                self.cycles = 8;
-            }
-            // LD H, r
-            0x60 | 0x61 | 0x62 | 0x63 | 0x64 | 0x65 | 0x67 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x67],
-                    opcode,
-                );
-                load::ld(&mut self.register.h, register_value);
-                self.cycles = 4;
-            }
-            0x66 => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                load::ld(&mut self.register.h, value);
-                self.cycles = 8;
-            }
-            // LD L, r
-            0x68 | 0x69 | 0x6A | 0x6B | 0x6C | 0x6D | 0x6F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x68, 0x69, 0x6A, 0x6B, 0x6C, 0x6D, 0x6F],
-                    opcode,
-                );
-                load::ld(&mut self.register.l, register_value);
-                self.cycles = 4;
-            }
-            0x6E => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                load::ld(&mut self.register.l, value);
-                self.cycles = 8;
-            }
-            // LD(HL), reg
-            0x70 | 0x71 | 0x72 | 0x73 | 0x74 | 0x75 | 0x77 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x77],
-                    opcode,
-                );
+    // ------------------------ OPERANDS ------------------------
+    /// # read_r8
+    /// 8-bit operand: 0 - B, 1 - C, 2 - D, 3 - E, 4 - H, 5 - L, 6 - (HL), 7 - A
+    fn read_r8(&mut self, index: u8) -> u8 {
+        match index & 0x07 {
+            0 => self.register.b,
+            1 => self.register.c,
+            2 => self.register.d,
+            3 => self.register.e,
+            4 => self.register.h,
+            5 => self.register.l,
+            6 => self.iommu.borrow_mut().read_byte(self.register.get_hl()),
+            _ => self.register.a,
+        }
+    }
+
+    /// # write_r8
+    /// Same operand encoding as `read_r8`
+    fn write_r8(&mut self, index: u8, value: u8) {
+        let register = match index & 0x07 {
+            0 => &mut self.register.b,
+            1 => &mut self.register.c,
+            2 => &mut self.register.d,
+            3 => &mut self.register.e,
+            4 => &mut self.register.h,
+            5 => &mut self.register.l,
+            6 => {
                 let address = self.register.get_hl();
-                self.iommu.borrow_mut().write_byte(address, register_value);
-                self.cycles = 8;
-            }
-            // LD A, r
-            0x78 | 0x79 | 0x7A | 0x7B | 0x7C | 0x7D | 0x7F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x78, 0x79, 0x7A, 0x7B, 0x7C, 0x7D, 0x7F],
-                    opcode,
-                );
-                load::ld(&mut self.register.a, register_value);
-                self.cycles = 4;
-            }
-            0x7E => {
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                load::ld(&mut self.register.a, value);
-                self.cycles = 8;
-            }
-            // write to io-port n (memory FF00+n)
-            0xE0 => {
-                let address = self.fetch_byte();
-                let port_address = load::calculate_address_for_io_port(address);
-                self.iommu
-                    .borrow_mut()
-                    .write_byte(port_address, self.register.a);
-                self.cycles = 12;
+                self.iommu.borrow_mut().write_byte(address, value);
+                return;
             }
+            _ => &mut self.register.a,
+        };
+        load::ld(register, value);
+    }
 
-            //write to io-port C
-            0xE2 => {
-                let port_address = load::calculate_address_for_io_port(self.register.c);
-                self.iommu
-                    .borrow_mut()
-                    .write_byte(port_address, self.register.a);
-                self.cycles = 8;
-            }
-            0xEA => {
-                let address = self.fetch_word();
-                self.iommu.borrow_mut().write_byte(address, self.register.a);
-                self.cycles = 16;
-            }
-            //read from io-port n (memory FF00+n)
-            0xF0 => {
-                let address = self.fetch_byte();
-                let port_address = load::calculate_address_for_io_port(address);
-                self.register.a = self.iommu.borrow_mut().read_byte(port_address);
-                self.cycles = 12;
-            }
-            // read from io-port C
-            0xF2 => {
-                let port_address = load::calculate_address_for_io_port(self.register.c);
-                self.register.a = self.iommu.borrow_mut().read_byte(port_address);
-                self.cycles = 8;
-            }
-            //0xF8 in ADD instructions
-            0xF9 => {
-                self.register.sp = self.register.get_hl();
-                self.cycles = 8;
-            }
-            0xFA => {
-                let address = self.fetch_word();
-                self.register.a = self.iommu.borrow_mut().read_byte(address);
-                self.cycles = 16;
-            }
-            // PUSH
-            0xC5 | 0xD5 | 0xE5 => {
-                let reg_val = self
-                    .register
-                    .get_reg16_value_from_opcode_array(&[0xC5, 0xD5, 0xE5], opcode);
-                load::push(&mut self.iommu.borrow_mut(), &mut self.register.sp, reg_val);
-                self.cycles = 16;
-            }
-            //AF
-            0xF5 => {
-                let reg_val = self.register.get_af();
-                load::push(&mut self.iommu.borrow_mut(), &mut self.register.sp, reg_val);
-                self.cycles = 16;
-            }
-            // POP
-            0xC1 => {
-                let value = load::pop(&mut self.iommu.borrow_mut(), &mut self.register.sp);
-                self.register.set_bc(value);
-                self.cycles = 12;
-            }
-            0xD1 => {
-                let value = load::pop(&mut self.iommu.borrow_mut(), &mut self.register.sp);
-                self.register.set_de(value);
-                self.cycles = 12;
-            }
-            0xE1 => {
-                let value = load::pop(&mut self.iommu.borrow_mut(), &mut self.register.sp);
-                self.register.set_hl(value);
-                self.cycles = 12;
-            }
-            0xF1 => {
-                let value = load::pop(&mut self.iommu.borrow_mut(), &mut self.register.sp);
-                self.register.set_af(value);
-                self.cycles = 12;
-            }
-            _ => panic!("load opcode [{:#02x?}] not supported!", opcode),
+    /// # modify_r8
+    /// Read-modify-write of an 8-bit operand, (HL) is read and written back once
+    fn modify_r8(&mut self, index: u8, operation: fn(&mut FlagsRegister, &mut u8)) {
+        let mut value = self.read_r8(index);
+        operation(&mut self.register.flag, &mut value);
+        self.write_r8(index, value);
+    }
+
+    /// # alu_operand
+    /// Register or (HL) for 0x80-0xBF, immediate byte for 0xC6-0xFE
+    fn alu_operand(&mut self, opcode: u8) -> u8 {
+        if opcode & 0x40 != 0 {
+            self.fetch_byte()
+        } else {
+            self.read_r8(opcode_field::low(opcode))
         }
     }
 
-    fn accumulator_rotate_and_shift_operation_for_dispatcher(&mut self, opcode: u8) {
-        match opcode {
-            0x07 => {
-                rotate_and_shift::rlca(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            0x17 => {
-                rotate_and_shift::rla(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            0x0F => {
-                rotate_and_shift::rrca(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            0x1F => {
-                rotate_and_shift::rra(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 4;
-            }
-            _ => panic!(
-                "acc rotate and shift opcode [{:#02x?}] not supported!",
-                opcode
-            ),
+    /// # indirect_address
+    /// Address of LD (r16),A and LD A,(r16): 0 - BC, 1 - DE, 2 - HL+, 3 - HL-
+    fn indirect_address(&mut self, opcode: u8) -> u16 {
+        match opcode_field::pair(opcode) {
+            0 => self.register.get_bc(),
+            1 => self.register.get_de(),
+            2 => load::hli(&mut self.register.h, &mut self.register.l),
+            _ => load::hld(&mut self.register.h, &mut self.register.l),
         }
     }
-    fn rotate_and_shift_operation_dispatcher(&mut self, opcode: u8) {
-        match opcode {
-            //RLC
-            0x00 => {
-                rotate_and_shift::rlc(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x01 => {
-                rotate_and_shift::rlc(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x02 => {
-                rotate_and_shift::rlc(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x03 => {
-                rotate_and_shift::rlc(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x04 => {
-                rotate_and_shift::rlc(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x05 => {
-                rotate_and_shift::rlc(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x06 => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::rlc(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x07 => {
-                rotate_and_shift::rlc(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            //RRC
-            0x08 => {
-                rotate_and_shift::rrc(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x09 => {
-                rotate_and_shift::rrc(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x0A => {
-                rotate_and_shift::rrc(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x0B => {
-                rotate_and_shift::rrc(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x0C => {
-                rotate_and_shift::rrc(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x0D => {
-                rotate_and_shift::rrc(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x0E => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::rrc(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x0F => {
-                rotate_and_shift::rrc(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            // RL
-            0x11 => {
-                rotate_and_shift::rl(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x12 => {
-                rotate_and_shift::rl(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x13 => {
-                rotate_and_shift::rl(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x14 => {
-                rotate_and_shift::rl(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x10 => {
-                rotate_and_shift::rl(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x15 => {
-                rotate_and_shift::rl(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x16 => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::rl(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x17 => {
-                rotate_and_shift::rl(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            //RR
-            0x18 => {
-                rotate_and_shift::rr(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x19 => {
-                rotate_and_shift::rr(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x1A => {
-                rotate_and_shift::rr(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x1B => {
-                rotate_and_shift::rr(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x1C => {
-                rotate_and_shift::rr(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x1D => {
-                rotate_and_shift::rr(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x1E => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::rr(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x1F => {
-                rotate_and_shift::rr(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            //SLA
-            0x20 => {
-                rotate_and_shift::sla(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x21 => {
-                rotate_and_shift::sla(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x22 => {
-                rotate_and_shift::sla(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x23 => {
-                rotate_and_shift::sla(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x24 => {
-                rotate_and_shift::sla(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x25 => {
-                rotate_and_shift::sla(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x26 => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::sla(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x27 => {
-                rotate_and_shift::sla(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            //SRA
-            0x28 => {
-                rotate_and_shift::sra(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x29 => {
-                rotate_and_shift::sra(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x2A => {
-                rotate_and_shift::sra(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x2B => {
-                rotate_and_shift::sra(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x2C => {
-                rotate_and_shift::sra(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x2D => {
-                rotate_and_shift::sra(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x2E => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::sra(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x2F => {
-                rotate_and_shift::sra(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            //Swap
-            0x30 => {
-                rotate_and_shift::swap(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x31 => {
-                rotate_and_shift::swap(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x32 => {
-                rotate_and_shift::swap(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x33 => {
-                rotate_and_shift::swap(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x34 => {
-                rotate_and_shift::swap(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x35 => {
-                rotate_and_shift::swap(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x36 => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::swap(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x37 => {
-                rotate_and_shift::swap(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            //SRL
-            0x38 => {
-                rotate_and_shift::srl(&mut self.register.flag, &mut self.register.b);
-                self.cycles = 8;
-            }
-            0x39 => {
-                rotate_and_shift::srl(&mut self.register.flag, &mut self.register.c);
-                self.cycles = 8;
-            }
-            0x3A => {
-                rotate_and_shift::srl(&mut self.register.flag, &mut self.register.d);
-                self.cycles = 8;
-            }
-            0x3B => {
-                rotate_and_shift::srl(&mut self.register.flag, &mut self.register.e);
-                self.cycles = 8;
-            }
-            0x3C => {
-                rotate_and_shift::srl(&mut self.register.flag, &mut self.register.h);
-                self.cycles = 8;
-            }
-            0x3D => {
-                rotate_and_shift::srl(&mut self.register.flag, &mut self.register.l);
-                self.cycles = 8;
-            }
-            0x3E => {
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                rotate_and_shift::srl(&mut self.register.flag, &mut value);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            0x3F => {
-                rotate_and_shift::srl(&mut self.register.flag, &mut self.register.a);
-                self.cycles = 8;
-            }
-            _ => panic!("rotate and shift opcode [{:#02x?}] not supported!", opcode),
-        }
+
+    // ------------------------ 8-BIT ARITHMETIC AND LOGIC ------------------------
+    pub(crate) fn add(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::add(&mut self.register.flag, &mut self.register.a, value, 0);
     }
-    fn single_bit_operation_dispatcher(&mut self, opcode: u8) {
-        match opcode {
-            // BIT
-            0x40 | 0x41 | 0x42 | 0x43 | 0x44 | 0x45 | 0x47 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x47],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 0);
-                self.cycles = 8;
-            }
-            0x48 | 0x49 | 0x4A | 0x4B | 0x4C | 0x4D | 0x4F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x48, 0x49, 0x4A, 0x4B, 0x4C, 0x4D, 0x4F],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 1);
-                self.cycles = 8;
-            }
-            0x50 | 0x51 | 0x52 | 0x53 | 0x54 | 0x55 | 0x57 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x57],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 2);
-                self.cycles = 8;
-            }
-            0x58 | 0x59 | 0x5A | 0x5B | 0x5C | 0x5D | 0x5F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x58, 0x59, 0x5A, 0x5B, 0x5C, 0x5D, 0x5F],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 3);
-                self.cycles = 8;
-            }
-            0x60 | 0x61 | 0x62 | 0x63 | 0x64 | 0x65 | 0x67 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x67],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 4);
-                self.cycles = 8;
-            }
-            0x68 | 0x69 | 0x6A | 0x6B | 0x6C | 0x6D | 0x6F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x68, 0x69, 0x6A, 0x6B, 0x6C, 0x6D, 0x6F],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 5);
-                self.cycles = 8;
-            }
-            0x70 | 0x71 | 0x72 | 0x73 | 0x74 | 0x75 | 0x77 => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x77],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 6);
-                self.cycles = 8;
-            }
-            0x78 | 0x79 | 0x7A | 0x7B | 0x7C | 0x7D | 0x7F => {
-                let register_value = self.register.get_reg_value_from_opcode_range(
-                    &[0x78, 0x79, 0x7A, 0x7B, 0x7C, 0x7D, 0x7F],
-                    opcode,
-                );
-                single_bit_operation::bit(&mut self.register.flag, register_value, 7);
-                self.cycles = 8;
-            }
-            0x46 | 0x4E | 0x56 | 0x5E | 0x66 | 0x6E | 0x76 | 0x7E => {
-                let opcode_with_bit_pos: [u8; 8] = [0x46, 0x4E, 0x56, 0x5E, 0x66, 0x6E, 0x76, 0x7E];
-                let bit_number = opcode_with_bit_pos
-                    .iter()
-                    .position(|&x| x == opcode)
-                    .unwrap() as u8;
-
-                let value = self.iommu.borrow_mut().read_byte(self.register.get_hl());
-                single_bit_operation::bit(&mut self.register.flag, value, bit_number);
-                self.cycles = 12;
-            }
-            // RES
-            0x80 | 0x81 | 0x82 | 0x83 | 0x84 | 0x85 | 0x87 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x87],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 0);
-                self.cycles = 8;
-            }
-            0x88 | 0x89 | 0x8A | 0x8B | 0x8C | 0x8D | 0x8F => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0x88, 0x89, 0x8A, 0x8B, 0x8C, 0x8D, 0x8F],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 1);
-                self.cycles = 8;
-            }
-            0x90 | 0x91 | 0x92 | 0x93 | 0x94 | 0x95 | 0x97 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0x90, 0x91, 0x92, 0x93, 0x94, 0x95, 0x97],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 2);
-                self.cycles = 8;
-            }
-            0x98 | 0x99 | 0x9A | 0x9B | 0x9C | 0x9D | 0x9F => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0x98, 0x99, 0x9A, 0x9B, 0x9C, 0x9D, 0x9F],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 3);
-                self.cycles = 8;
-            }
-            0xA0 | 0xA1 | 0xA2 | 0xA3 | 0xA4 | 0xA5 | 0xA7 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5, 0xA7],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 4);
-                self.cycles = 8;
-            }
-            0xA8 | 0xA9 | 0xAA | 0xAB | 0xAC | 0xAD | 0xAF => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xA8, 0xA9, 0xAA, 0xAB, 0xAC, 0xAD, 0xAF],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 5);
-                self.cycles = 8;
-            }
-            0xB0 | 0xB1 | 0xB2 | 0xB3 | 0xB4 | 0xB5 | 0xB7 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xB0, 0xB1, 0xB2, 0xB3, 0xB4, 0xB5, 0xB7],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 6);
-                self.cycles = 8;
-            }
-            0xB9 | 0xBA | 0xBB | 0xB8 | 0xBC | 0xBD | 0xBF => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xB9, 0xBA, 0xBB, 0xB8, 0xBC, 0xBD, 0xBF],
-                    opcode,
-                );
-                single_bit_operation::res(register_address, 7);
-                self.cycles = 8;
-            }
-            0x86 | 0x8E | 0x96 | 0x9E | 0xA6 | 0xAE | 0xB6 | 0xBE => {
-                let opcode_with_bit_pos: [u8; 8] = [0x86, 0x8E, 0x96, 0x9E, 0xA6, 0xAE, 0xB6, 0xBE];
-                let bit_number = opcode_with_bit_pos
-                    .iter()
-                    .position(|&x| x == opcode)
-                    .unwrap() as u8;
 
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                single_bit_operation::res(&mut value, bit_number);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            // SET
-            0xC0 | 0xC1 | 0xC2 | 0xC3 | 0xC4 | 0xC5 | 0xC7 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xC0, 0xC1, 0xC2, 0xC3, 0xC4, 0xC5, 0xC7],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 0);
-                self.cycles = 8;
-            }
-            0xC8 | 0xC9 | 0xCA | 0xCB | 0xCC | 0xCD | 0xCF => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xC8, 0xC9, 0xCA, 0xCB, 0xCC, 0xCD, 0xCF],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 1);
-                self.cycles = 8;
-            }
-            0xD0 | 0xD1 | 0xD2 | 0xD3 | 0xD4 | 0xD5 | 0xD7 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xD0, 0xD1, 0xD2, 0xD3, 0xD4, 0xD5, 0xD7],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 2);
-                self.cycles = 8;
-            }
-            0xD8 | 0xD9 | 0xDA | 0xDB | 0xDC | 0xDD | 0xDF => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xD8, 0xD9, 0xDA, 0xDB, 0xDC, 0xDD, 0xDF],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 3);
-                self.cycles = 8;
-            }
-            0xE0 | 0xE1 | 0xE2 | 0xE3 | 0xE4 | 0xE5 | 0xE7 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xE0, 0xE1, 0xE2, 0xE3, 0xE4, 0xE5, 0xE7],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 4);
-                self.cycles = 8;
-            }
-            0xE8 | 0xE9 | 0xEA | 0xEB | 0xEC | 0xED | 0xEF => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xE8, 0xE9, 0xEA, 0xEB, 0xEC, 0xED, 0xEF],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 5);
-                self.cycles = 8;
-            }
-            0xF0 | 0xF1 | 0xF2 | 0xF3 | 0xF4 | 0xF5 | 0xF7 => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xF0, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF7],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 6);
-                self.cycles = 8;
-            }
-            0xF8 | 0xF9 | 0xFA | 0xFB | 0xFC | 0xFD | 0xFF => {
-                let register_address = self.register.get_reg_address_from_opcode_range(
-                    &[0xF8, 0xF9, 0xFA, 0xFB, 0xFC, 0xFD, 0xFF],
-                    opcode,
-                );
-                single_bit_operation::set(register_address, 7);
-                self.cycles = 8;
-            }
-            0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => {
-                let opcode_with_bit_pos: [u8; 8] = [0xC6, 0xCE, 0xD6, 0xDE, 0xE6, 0xEE, 0xF6, 0xFE];
-                let bit_number = opcode_with_bit_pos
-                    .iter()
-                    .position(|&x| x == opcode)
-                    .unwrap() as u8;
+    pub(crate) fn adc(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::adc(&mut self.register.flag, &mut self.register.a, value);
+    }
 
-                let address = self.register.get_hl();
-                let mut value = self.iommu.borrow_mut().read_byte(address);
-                single_bit_operation::set(&mut value, bit_number);
-                self.iommu.borrow_mut().write_byte(address, value);
-                self.cycles = 16;
-            }
-            _ => panic!("single bit opcode [{:#02x?}] not supported!", opcode),
-        }
+    pub(crate) fn sub(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::sub(&mut self.register.flag, &mut self.register.a, value, 0);
     }
-    fn jump_instruction_dispatcher(&mut self, opcode: u8) {
-        match opcode {
-            //JR
-            0x18 | 0x20 | 0x28 | 0x30 | 0x38 => {
-                let mut conditional_relative_jump: HashMap<u8, bool> = HashMap::new();
-                conditional_relative_jump.insert(0x18, true); //Always jump
-                conditional_relative_jump.insert(0x20, !self.register.flag.z); //JR NZ
-                conditional_relative_jump.insert(0x28, self.register.flag.z); //JR Z
-                conditional_relative_jump.insert(0x30, !self.register.flag.c); //JR NC
-                conditional_relative_jump.insert(0x38, self.register.flag.c); //JR C
-
-                if *conditional_relative_jump.get(&opcode).unwrap() {
-                    let offset = self.fetch_byte() as i8;
-                    jump::relative_jump(&mut self.register.pc, offset);
-                    self.cycles = 12;
-                } else {
-                    self.register.pc += 1;
-                    self.cycles = 8;
-                }
-            }
-            //JP
-            0xC2 | 0xC3 | 0xCA | 0xD2 | 0xDA => {
-                let mut conditional_jump: HashMap<u8, bool> = HashMap::new();
-                conditional_jump.insert(0xC2, !self.register.flag.z); //JP NZ
-                conditional_jump.insert(0xC3, true); //Always jump
-                conditional_jump.insert(0xCA, self.register.flag.z); //JP Z
-                conditional_jump.insert(0xD2, !self.register.flag.c); //JP NC
-                conditional_jump.insert(0xDA, self.register.flag.c); //JP C
-
-                if *conditional_jump.get(&opcode).unwrap() {
-                    let address = self.fetch_word();
-                    jump::jump_to(&mut self.register.pc, address);
-                    self.cycles = 16;
-                } else {
-                    self.register.pc += 2;
-                    self.cycles = 12;
-                }
-            }
-            //JP HL
-            0xE9 => {
-                let address = self.register.get_hl();
-                jump::jump_to(&mut self.register.pc, address);
-                self.cycles = 4;
-            }
-            // CALL
-            0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => {
-                let mut conditional_call: HashMap<u8, bool> = HashMap::new();
-                conditional_call.insert(0xC4, !self.register.flag.z); //CALL NZ
-                conditional_call.insert(0xCC, self.register.flag.z); //CALL Z
-                conditional_call.insert(0xCD, true); //Always call
-                conditional_call.insert(0xD4, !self.register.flag.c); //CALL NC
-                conditional_call.insert(0xDC, self.register.flag.c); //CALL C
-
-                if *conditional_call.get(&opcode).unwrap() {
-                    let address = self.fetch_word();
-                    //Because fetch_word() was called
-                    self.register.pc -= 2;
-                    jump::call(
-                        &mut self.register.pc,
-                        address,
-                        &mut self.iommu.borrow_mut(),
-                        &mut self.register.sp,
-                    );
-                    self.cycles = 24;
-                } else {
-                    self.register.pc += 2;
-                    self.cycles = 12;
-                }
-            }
-            // RET
-            0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 => {
-                let mut conditional_ret: HashMap<u8, bool> = HashMap::new();
-                conditional_ret.insert(0xC0, !self.register.flag.z); //RET NZ
-                conditional_ret.insert(0xC8, self.register.flag.z); //RET Z
-                conditional_ret.insert(0xC9, true); //Always RET
-                conditional_ret.insert(0xD0, !self.register.flag.c); //RET NC
-                conditional_ret.insert(0xD8, self.register.flag.c); //RET C
-
-                if *conditional_ret.get(&opcode).unwrap() {
-                    jump::ret(
-                        &mut self.register.pc,
-                        &mut self.iommu.borrow_mut(),
-                        &mut self.register.sp,
-                    );
-                    if opcode == 0xC9 {
-                        self.cycles = 16;
-                    } else {
-                        self.cycles = 20;
-                    }
-                } else {
-                    self.cycles = 8;
-                }
-            }
-            //RETI
-            0xD9 => {
-                jump::ret(
-                    &mut self.register.pc,
-                    &mut self.iommu.borrow_mut(),
-                    &mut self.register.sp,
-                );
-                // return and enable interrupts (IME=1)
-                self.control.ime = true;
-                self.cycles = 16;
-            }
-            //RST
-            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => {
-                let mut reset_map: HashMap<u8, usize> = HashMap::new();
-                reset_map.insert(0xC7, 0);
-                reset_map.insert(0xCF, 1);
-                reset_map.insert(0xD7, 2);
-                reset_map.insert(0xDF, 3);
-                reset_map.insert(0xE7, 4);
-                reset_map.insert(0xEF, 5);
-                reset_map.insert(0xF7, 6);
-                reset_map.insert(0xFF, 7);
-
-                let reset_index = *reset_map.get(&opcode).unwrap();
-                jump::rst(
-                    reset_index,
-                    &mut self.register.pc,
-                    &mut self.iommu.borrow_mut(),
-                    &mut self.register.sp,
-                );
-                self.cycles = 16;
-            }
-            _ => panic!("Jump opcode [{:#02x?}] not supported!", opcode),
+
+    pub(crate) fn sbc(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::sbc(&mut self.register.flag, &mut self.register.a, value);
+    }
+
+    pub(crate) fn and(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::and(&mut self.register.flag, &mut self.register.a, value);
+    }
+
+    pub(crate) fn xor(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::xor(&mut self.register.flag, &mut self.register.a, value);
+    }
+
+    pub(crate) fn or(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::or(&mut self.register.flag, &mut self.register.a, value);
+    }
+
+    pub(crate) fn cp(&mut self, opcode: u8) {
+        let value = self.alu_operand(opcode);
+        arithmetic_logic::cp(&mut self.register.flag, &mut self.register.a, value);
+    }
+
+    pub(crate) fn inc(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::middle(opcode), arithmetic_logic::inc);
+    }
+
+    pub(crate) fn dec(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::middle(opcode), arithmetic_logic::dec);
+    }
+
+    pub(crate) fn daa(&mut self, _opcode: u8) {
+        arithmetic_logic::daa(&mut self.register.flag, &mut self.register.a);
+    }
+
+    pub(crate) fn cpl(&mut self, _opcode: u8) {
+        arithmetic_logic::cpl(&mut self.register.flag, &mut self.register.a);
+    }
+
+    // ------------------------ 16-BIT ARITHMETIC ------------------------
+    pub(crate) fn inc_16(&mut self, opcode: u8) {
+        let pair = opcode_field::pair(opcode);
+        let [mut high, mut low] = self.register.get_r16(pair).to_be_bytes();
+        arithmetic_logic::inc_16(&mut high, &mut low);
+        self.register.set_r16(pair, u16::from_be_bytes([high, low]));
+    }
+
+    pub(crate) fn dec_16(&mut self, opcode: u8) {
+        let pair = opcode_field::pair(opcode);
+        let [mut high, mut low] = self.register.get_r16(pair).to_be_bytes();
+        arithmetic_logic::dec_16(&mut high, &mut low);
+        self.register.set_r16(pair, u16::from_be_bytes([high, low]));
+    }
+
+    pub(crate) fn add_hl(&mut self, opcode: u8) {
+        let value = self.register.get_r16(opcode_field::pair(opcode));
+        arithmetic_logic::add_hl(
+            &mut self.register.flag,
+            &mut self.register.h,
+            &mut self.register.l,
+            value,
+        );
+    }
+
+    pub(crate) fn add_sp(&mut self, _opcode: u8) {
+        let offset = self.fetch_byte() as i8;
+        arithmetic_logic::add_sp(&mut self.register.flag, &mut self.register.sp, offset);
+    }
+
+    pub(crate) fn ld_hl_sp(&mut self, _opcode: u8) {
+        let offset = self.fetch_byte() as i8;
+        arithmetic_logic::ld_hl(
+            &mut self.register.flag,
+            &mut self.register.h,
+            &mut self.register.l,
+            self.register.sp,
+            offset,
+        );
+    }
+
+    // ------------------------ LOAD ------------------------
+    /// LD r,r and LD r,(HL) / LD (HL),r, 0x76 is HALT
+    pub(crate) fn ld_r8(&mut self, opcode: u8) {
+        let value = self.read_r8(opcode_field::low(opcode));
+        self.write_r8(opcode_field::middle(opcode), value);
+    }
+
+    pub(crate) fn ld_r8_d8(&mut self, opcode: u8) {
+        let value = self.fetch_byte();
+        self.write_r8(opcode_field::middle(opcode), value);
+    }
+
+    pub(crate) fn ld_r16_d16(&mut self, opcode: u8) {
+        let value = self.fetch_word();
+        self.register.set_r16(opcode_field::pair(opcode), value);
+    }
+
+    pub(crate) fn ld_indirect_a(&mut self, opcode: u8) {
+        let address = self.indirect_address(opcode);
+        self.iommu.borrow_mut().write_byte(address, self.register.a);
+    }
+
+    pub(crate) fn ld_a_indirect(&mut self, opcode: u8) {
+        let address = self.indirect_address(opcode);
+        let value = self.iommu.borrow_mut().read_byte(address);
+        load::ld(&mut self.register.a, value);
+    }
+
+    pub(crate) fn ld_a16_sp(&mut self, _opcode: u8) {
+        let address = self.fetch_word();
+        self.iommu
+            .borrow_mut()
+            .write_word(address, self.register.sp);
+    }
+
+    pub(crate) fn ld_a16_a(&mut self, _opcode: u8) {
+        let address = self.fetch_word();
+        self.iommu.borrow_mut().write_byte(address, self.register.a);
+    }
+
+    pub(crate) fn ld_a_a16(&mut self, _opcode: u8) {
+        let address = self.fetch_word();
+        self.register.a = self.iommu.borrow_mut().read_byte(address);
+    }
+
+    /// write to io-port n (memory FF00+n)
+    pub(crate) fn ldh_a8_a(&mut self, _opcode: u8) {
+        let port = self.fetch_byte();
+        let port_address = load::calculate_address_for_io_port(port);
+        self.iommu
+            .borrow_mut()
+            .write_byte(port_address, self.register.a);
+    }
+
+    /// read from io-port n (memory FF00+n)
+    pub(crate) fn ldh_a_a8(&mut self, _opcode: u8) {
+        let port = self.fetch_byte();
+        let port_address = load::calculate_address_for_io_port(port);
+        self.register.a = self.iommu.borrow_mut().read_byte(port_address);
+    }
+
+    /// write to io-port C
+    pub(crate) fn ldh_c_a(&mut self, _opcode: u8) {
+        let port_address = load::calculate_address_for_io_port(self.register.c);
+        self.iommu
+            .borrow_mut()
+            .write_byte(port_address, self.register.a);
+    }
+
+    /// read from io-port C
+    pub(crate) fn ldh_a_c(&mut self, _opcode: u8) {
+        let port_address = load::calculate_address_for_io_port(self.register.c);
+        self.register.a = self.iommu.borrow_mut().read_byte(port_address);
+    }
+
+    pub(crate) fn ld_sp_hl(&mut self, _opcode: u8) {
+        self.register.sp = self.register.get_hl();
+    }
+
+    pub(crate) fn push(&mut self, opcode: u8) {
+        let value = self.register.get_r16_stack(opcode_field::pair(opcode));
+        load::push(&mut self.iommu.borrow_mut(), &mut self.register.sp, value);
+    }
+
+    pub(crate) fn pop(&mut self, opcode: u8) {
+        let value = load::pop(&mut self.iommu.borrow_mut(), &mut self.register.sp);
+        self.register
+            .set_r16_stack(opcode_field::pair(opcode), value);
+    }
+
+    // ------------------------ ROTATE AND SHIFT ------------------------
+    pub(crate) fn rlca(&mut self, _opcode: u8) {
+        rotate_and_shift::rlca(&mut self.register.flag, &mut self.register.a);
+    }
+
+    pub(crate) fn rla(&mut self, _opcode: u8) {
+        rotate_and_shift::rla(&mut self.register.flag, &mut self.register.a);
+    }
+
+    pub(crate) fn rrca(&mut self, _opcode: u8) {
+        rotate_and_shift::rrca(&mut self.register.flag, &mut self.register.a);
+    }
+
+    pub(crate) fn rra(&mut self, _opcode: u8) {
+        rotate_and_shift::rra(&mut self.register.flag, &mut self.register.a);
+    }
+
+    pub(crate) fn rlc(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::rlc);
+    }
+
+    pub(crate) fn rrc(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::rrc);
+    }
+
+    pub(crate) fn rl(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::rl);
+    }
+
+    pub(crate) fn rr(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::rr);
+    }
+
+    pub(crate) fn sla(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::sla);
+    }
+
+    pub(crate) fn sra(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::sra);
+    }
+
+    pub(crate) fn swap(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::swap);
+    }
+
+    pub(crate) fn srl(&mut self, opcode: u8) {
+        self.modify_r8(opcode_field::low(opcode), rotate_and_shift::srl);
+    }
+
+    // ------------------------ SINGLE BIT ------------------------
+    pub(crate) fn bit(&mut self, opcode: u8) {
+        let value = self.read_r8(opcode_field::low(opcode));
+        single_bit_operation::bit(&mut self.register.flag, value, opcode_field::middle(opcode));
+    }
+
+    pub(crate) fn res(&mut self, opcode: u8) {
+        let index = opcode_field::low(opcode);
+        let mut value = self.read_r8(index);
+        single_bit_operation::res(&mut value, opcode_field::middle(opcode));
+        self.write_r8(index, value);
+    }
+
+    pub(crate) fn set(&mut self, opcode: u8) {
+        let index = opcode_field::low(opcode);
+        let mut value = self.read_r8(index);
+        single_bit_operation::set(&mut value, opcode_field::middle(opcode));
+        self.write_r8(index, value);
+    }
+
+    // ------------------------ JUMP ------------------------
+    /// # is_condition_met
+    /// Condition encoded in bits 4-3 of conditional JR, JP, CALL and RET opcodes
+    ///
+    /// 0 - NZ, 1 - Z, 2 - NC, 3 - C
+    fn is_condition_met(&self, opcode: u8) -> bool {
+        match opcode.rotate_right(3) & 0x03 {
+            0 => !self.register.flag.z,
+            1 => self.register.flag.z,
+            2 => !self.register.flag.c,
+            _ => self.register.flag.c,
         }
     }
 
-    fn cpu_control_instruction_dispatcher(&mut self, opcode: u8) {
-        match opcode {
-            0x00 => {
-                //NOP
-                self.cycles = 4;
-            }
-            0x10 => {
-                //STOP
-                self.cycles = 4;
-            }
-            0x37 => {
-                cpu_control::scf(&mut self.register.flag);
-                self.cycles = 4;
-            }
-            0x3F => {
-                cpu_control::ccf(&mut self.register.flag);
-                self.cycles = 4;
-            }
-            0x76 => {
-                cpu_control::halt(&mut self.control);
-                self.cycles = 4;
-            }
-            0xF3 => {
-                cpu_control::di(&mut self.control);
-                self.cycles = 4;
-            }
-            0xFB => {
-                cpu_control::ei(&mut self.control);
-                self.cycles = 4;
-            }
-            _ => panic!("CPU control opcode [{:#02x?}] not supported!", opcode),
+    fn call_fetched_address(&mut self) {
+        let address = self.fetch_word();
+        //Because fetch_word() was called
+        self.register.pc = self.register.pc.wrapping_sub(2);
+        jump::call(
+            &mut self.register.pc,
+            address,
+            &mut self.iommu.borrow_mut(),
+            &mut self.register.sp,
+        );
+    }
+
+    fn return_from_call(&mut self) {
+        jump::ret(
+            &mut self.register.pc,
+            &mut self.iommu.borrow_mut(),
+            &mut self.register.sp,
+        );
+    }
+
+    pub(crate) fn jr(&mut self, _opcode: u8) {
+        let offset = self.fetch_byte() as i8;
+        jump::relative_jump(&mut self.register.pc, offset);
+    }
+
+    /// Base cycles come from the decode table, conditional variants add the cost of the taken branch
+    pub(crate) fn jr_cc(&mut self, opcode: u8) {
+        if self.is_condition_met(opcode) {
+            self.jr(opcode);
+            self.cycles += 4;
+        } else {
+            self.fetch_byte();
         }
     }
 
-    fn execute_cbprefixed_instruction(&mut self, opcode: u8) {
-        if instructions::is_supported(opcode, &single_bit_operation::SINGLE_BIT_OPERATION_OPCODES) {
-            self.single_bit_operation_dispatcher(opcode);
-        } else if instructions::is_supported(
-            opcode,
-            &rotate_and_shift::ROTATE_SHIFT_OPERATION_OPCODES,
-        ) {
-            self.rotate_and_shift_operation_dispatcher(opcode);
+    pub(crate) fn jp(&mut self, _opcode: u8) {
+        let address = self.fetch_word();
+        jump::jump_to(&mut self.register.pc, address);
+    }
+
+    pub(crate) fn jp_cc(&mut self, opcode: u8) {
+        if self.is_condition_met(opcode) {
+            self.jp(opcode);
+            self.cycles += 4;
         } else {
-            panic!("CBPrefixed Instruction [{:#02x?}] not supported!", opcode);
+            self.fetch_word();
         }
     }
 
-    fn execute(&mut self, opcode: u8) {
-        if instructions::is_supported(opcode, &arithmetic_logic::ARITHMETIC_LOGIC_OPCODES) {
-            self.arithmetic_logic_instruction_dispatcher(opcode);
-        } else if instructions::is_supported(opcode, &load::LOAD_OPCODES) {
-            self.load_instruction_dispatcher(opcode);
-        } else if instructions::is_supported(
-            opcode,
-            &rotate_and_shift::ACC_ROTATE_SHIFT_OPERATION_OPCODES,
-        ) {
-            self.accumulator_rotate_and_shift_operation_for_dispatcher(opcode);
-        } else if instructions::is_supported(opcode, &jump::JUMP_OPCODES) {
-            self.jump_instruction_dispatcher(opcode);
-        } else if instructions::is_supported(opcode, &cpu_control::CPU_CONTROL_OPCODES) {
-            self.cpu_control_instruction_dispatcher(opcode);
+    pub(crate) fn jp_hl(&mut self, _opcode: u8) {
+        let address = self.register.get_hl();
+        jump::jump_to(&mut self.register.pc, address);
+    }
+
+    pub(crate) fn call(&mut self, _opcode: u8) {
+        self.call_fetched_address();
+    }
+
+    pub(crate) fn call_cc(&mut self, opcode: u8) {
+        if self.is_condition_met(opcode) {
+            self.call_fetched_address();
+            self.cycles += 12;
         } else {
-            panic!("Instruction [{:#02x?}] not supported!", opcode);
+            self.fetch_word();
+        }
+    }
+
+    pub(crate) fn ret(&mut self, _opcode: u8) {
+        self.return_from_call();
+    }
+
+    pub(crate) fn ret_cc(&mut self, opcode: u8) {
+        if self.is_condition_met(opcode) {
+            self.return_from_call();
+            self.cycles += 12;
+        }
+    }
+
+    /// return and enable interrupts (IME=1)
+    pub(crate) fn reti(&mut self, _opcode: u8) {
+        self.return_from_call();
+        self.control.ime = true;
+    }
+
+    pub(crate) fn rst(&mut self, opcode: u8) {
+        jump::rst(
+            opcode_field::middle(opcode) as usize,
+            &mut self.register.pc,
+            &mut self.iommu.borrow_mut(),
+            &mut self.register.sp,
+        );
+    }
+
+    // ------------------------ CPU CONTROL ------------------------
+    pub(crate) fn nop(&mut self, _opcode: u8) {}
+
+    /// STOP is followed by a padding byte, CGB speed switch takes 2050 M-cycles
+    pub(crate) fn stop(&mut self, _opcode: u8) {
+        self.fetch_byte();
+        if self.iommu.borrow_mut().speed_switch() {
+            self.cycles += 2050 * 4;
         }
     }
 
+    pub(crate) fn halt(&mut self, _opcode: u8) {
+        let is_interrupt_pending = self.pending_interrupts() != 0;
+        cpu_control::halt(&mut self.control, is_interrupt_pending);
+    }
+
+    pub(crate) fn scf(&mut self, _opcode: u8) {
+        cpu_control::scf(&mut self.register.flag);
+    }
+
+    pub(crate) fn ccf(&mut self, _opcode: u8) {
+        cpu_control::ccf(&mut self.register.flag);
+    }
+
+    pub(crate) fn di(&mut self, _opcode: u8) {
+        cpu_control::di(&mut self.control);
+    }
+
+    pub(crate) fn ei(&mut self, _opcode: u8) {
+        cpu_control::ei(&mut self.control);
+    }
+
+    /// # execute_cbprefixed_instruction
+    /// Handler of the 0xCB prefix, the instruction is decoded from the next byte
+    pub(crate) fn execute_cbprefixed_instruction(&mut self, _prefix: u8) {
+        let opcode = self.fetch_byte();
+        let instruction = &CB_OPCODE_TABLE[opcode as usize];
+        self.cycles = instruction.cycles;
+        (instruction.handler)(self, opcode);
+    }
+
+    /// # illegal_instruction
+    /// 0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD hang the CPU
+    pub(crate) fn illegal_instruction(&mut self, opcode: u8) {
+        let address = self.register.pc.wrapping_sub(1);
+        self.locked_instruction = Some((address, opcode));
+    }
+
+    fn execute(&mut self, opcode: u8) {
+        let instruction = &OPCODE_TABLE[opcode as usize];
+        self.cycles = instruction.cycles;
+        (instruction.handler)(self, opcode);
+    }
+
     // ------------------------ DEBUG ------------------------
+    /// # debug_registers
+    pub fn debug_registers(&self) -> CpuRegisters {
+        CpuRegisters {
+            a: self.register.a,
+            f: FlagsRegister::into(self.register.flag),
+            b: self.register.b,
+            c: self.register.c,
+            d: self.register.d,
+            e: self.register.e,
+            h: self.register.h,
+            l: self.register.l,
+            sp: self.register.sp,
+            pc: self.register.pc,
+            ime: self.control.ime,
+            halted: self.control.halted,
+        }
+    }
+
+    /// # set_debug_registers
+    /// IME and HALT are not changed
+    pub fn set_debug_registers(&mut self, registers: &CpuRegisters) {
+        self.register.a = registers.a;
+        self.register.flag = FlagsRegister::from(registers.f);
+        self.register.b = registers.b;
+        self.register.c = registers.c;
+        self.register.d = registers.d;
+        self.register.e = registers.e;
+        self.register.h = registers.h;
+        self.register.l = registers.l;
+        self.register.sp = registers.sp;
+        self.register.pc = registers.pc;
+    }
+
     /// # debug_dump_regs
     /// Returns logfile in following format:
     ///
     /// `A:00 F:11 B:22 C:33 D:44 E:55 H:66 L:77 SP:8888 PC:9999 PCMEM:AA,BB,CC,DD`
     pub fn debug_dump_regs(&self) -> String {
-        let mem_byte_0 = self.iommu.borrow_mut().read_byte(self.register.pc);
-        let mem_byte_1 = self
-            .iommu
-            .borrow_mut()
-            .read_byte(self.register.pc.wrapping_add(1));
-        let mem_byte_2 = self
-            .iommu
-            .borrow_mut()
-            .read_byte(self.register.pc.wrapping_add(2));
-        let mem_byte_3 = self
-            .iommu
-            .borrow_mut()
-            .read_byte(self.register.pc.wrapping_add(3));
+        let iommu = self.iommu.borrow();
+        let mem_byte_0 = iommu.peek_byte(self.register.pc);
+        let mem_byte_1 = iommu.peek_byte(self.register.pc.wrapping_add(1));
+        let mem_byte_2 = iommu.peek_byte(self.register.pc.wrapping_add(2));
+        let mem_byte_3 = iommu.peek_byte(self.register.pc.wrapping_add(3));
         let flag_value: u8 = FlagsRegister::into(self.register.flag);
         format!(
             "A:{:02x} F:{:02x} B:{:02x} C:{:02x} D:{:02x} E:{:02x} H:{:02x} L:{:02x} SP:{:04x} PC:{:04x} PCMEM:{:02x},{:02x},{:02x},{:02x}\n",
@@ -1551,3 +794,585 @@ impl Cpu {
         )
     }
 }
+
+impl Snapshot for Cpu {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.register.save_state(writer);
+        self.control.save_state(writer);
+        writer.write_u32(self.cycles);
+        writer.write_bool(self.locked_instruction.is_some());
+        let (address, opcode) = self.locked_instruction.unwrap_or_default();
+        writer.write_u16(address);
+        writer.write_u8(opcode);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        self.register.load_state(reader)?;
+        self.control.load_state(reader)?;
+        self.cycles = reader.read_u32()?;
+        let is_locked = reader.read_bool()?;
+        let address = reader.read_u16()?;
+        let opcode = reader.read_u8()?;
+        self.locked_instruction = is_locked.then_some((address, opcode));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+    use crate::constants::gb_memory_map::address::io_hardware_register;
+    use crate::debugger::WatchKind;
+    use crate::peripheral::{
+        cartridge::Cartridge, joypad::JoypadInput, ppu::PictureProcessingUnit,
+    };
+
+    const PROGRAM_START: u16 = 0xC000;
+
+    fn cpu_with_program(program: &[u8]) -> Cpu {
+        cpu_with_cartridge(Cartridge::default(), program)
+    }
+
+    /// ROM only cartridge with INC E, RETI at the interrupt vectors, E counts the serviced interrupts
+    fn cpu_with_isr_rom(name: &str, program: &[u8]) -> Cpu {
+        let mut rom = vec![0x00; 0x8000];
+        for vector in [
+            isr_adress::V_BLANK,
+            isr_adress::LCD_STATUS,
+            isr_adress::TIMER,
+            isr_adress::SERIAL_LINK,
+            isr_adress::JOYPAD,
+        ] {
+            rom[vector as usize..vector as usize + 2].copy_from_slice(&[0x1C, 0xD9]);
+        }
+        rom[0x14D] = rom[0x134..0x14D].iter().fold(0u8, |checksum, byte| {
+            checksum.wrapping_sub(*byte).wrapping_sub(1)
+        });
+        let rom_path = std::env::temp_dir().join(format!("r_gb_emu_{}.gb", name));
+        std::fs::write(&rom_path, &rom).unwrap();
+        let mut cartridge = Cartridge::default();
+        cartridge.load(&rom_path).unwrap();
+        let _ = std::fs::remove_file(&rom_path);
+        cpu_with_cartridge(cartridge, program)
+    }
+
+    fn cpu_with_cartridge(cartridge: Cartridge, program: &[u8]) -> Cpu {
+        let cartridge = Rc::new(RefCell::new(cartridge));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let iommu = Rc::new(RefCell::new(Iommu::new(cartridge, ppu, joypad)));
+        for (offset, byte) in program.iter().enumerate() {
+            iommu
+                .borrow_mut()
+                .write_byte(PROGRAM_START + offset as u16, *byte);
+        }
+        let mut cpu = Cpu::new(iommu);
+        cpu.register.pc = PROGRAM_START;
+        cpu.register.sp = *address::HIGH_RAM.end();
+        cpu
+    }
+
+    #[test]
+    fn halt_stops_fetching_test() {
+        // HALT, INC A
+        let mut cpu = cpu_with_program(&[0x76, 0x3C]);
+        cpu.control.ime = false;
+
+        cpu.process().unwrap();
+        assert!(cpu.control.halted);
+        assert_eq!(PROGRAM_START + 1, cpu.register.pc);
+
+        for _ in 0..10 {
+            assert_eq!(4, cpu.process().unwrap());
+        }
+        assert_eq!(PROGRAM_START + 1, cpu.register.pc);
+        assert_eq!(0, cpu.register.a);
+    }
+
+    #[test]
+    fn halt_wake_up_without_ime_test() {
+        // HALT, INC A
+        let mut cpu = cpu_with_program(&[0x76, 0x3C]);
+        cpu.control.ime = false;
+        cpu.process().unwrap();
+        assert!(cpu.control.halted);
+
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+
+        // Interrupt is not serviced, execution continues after HALT
+        cpu.process().unwrap();
+        assert!(!cpu.control.halted);
+        assert_eq!(1, cpu.register.a);
+        assert_eq!(PROGRAM_START + 2, cpu.register.pc);
+        assert_eq!(
+            0x04,
+            cpu.iommu.borrow_mut().read_byte(address::INTF_REGISTER) & 0x1F
+        );
+    }
+
+    #[test]
+    fn halt_bug_test() {
+        // HALT, INC A, NOP
+        let mut cpu = cpu_with_program(&[0x76, 0x3C, 0x00]);
+        cpu.control.ime = false;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+
+        cpu.process().unwrap();
+        assert!(!cpu.control.halted);
+
+        // INC A is executed twice
+        cpu.process().unwrap();
+        assert_eq!(PROGRAM_START + 1, cpu.register.pc);
+        cpu.process().unwrap();
+        assert_eq!(PROGRAM_START + 2, cpu.register.pc);
+        assert_eq!(2, cpu.register.a);
+    }
+
+    #[test]
+    fn halt_bug_two_byte_instruction_test() {
+        // halt_bug.gb sequence: IME=0, IE & IF pending, HALT, LD A,0x14
+        let mut cpu = cpu_with_program(&[0x76, 0x3E, 0x14, 0x00]);
+        cpu.control.ime = false;
+        cpu.register.d = 0;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+
+        cpu.process().unwrap();
+        assert!(!cpu.control.halted);
+
+        // The opcode byte is read again as the operand: LD A,0x3E
+        assert_eq!(8, cpu.process().unwrap());
+        assert_eq!(0x3E, cpu.register.a);
+        assert_eq!(PROGRAM_START + 2, cpu.register.pc);
+
+        // The real operand runs as INC D
+        cpu.process().unwrap();
+        assert_eq!(1, cpu.register.d);
+        assert_eq!(PROGRAM_START + 3, cpu.register.pc);
+        assert!(!cpu.control.ime);
+    }
+
+    #[test]
+    fn ei_delay_test() {
+        // EI, INC A, INC A
+        let mut cpu = cpu_with_program(&[0xFB, 0x3C, 0x3C]);
+        cpu.control.ime = false;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+
+        cpu.process().unwrap();
+        assert!(!cpu.control.ime);
+
+        // Instruction after EI is executed before the interrupt
+        cpu.process().unwrap();
+        assert!(cpu.control.ime);
+        assert_eq!(1, cpu.register.a);
+
+        assert_eq!(20, cpu.interrupt_handling());
+        assert!(!cpu.control.ime);
+        assert_eq!(isr_adress::TIMER, cpu.register.pc);
+        assert_eq!(
+            0x00,
+            cpu.iommu.borrow_mut().read_byte(address::INTF_REGISTER) & 0x1F
+        );
+        assert_eq!(
+            PROGRAM_START + 2,
+            cpu.iommu.borrow_mut().read_word(cpu.register.sp)
+        );
+    }
+
+    #[test]
+    fn ei_di_sequence_test() {
+        // EI, DI, INC A
+        let mut cpu = cpu_with_program(&[0xFB, 0xF3, 0x3C]);
+        cpu.control.ime = false;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+
+        for _ in 0..3 {
+            cpu.process().unwrap();
+        }
+        assert!(!cpu.control.ime);
+        assert_eq!(PROGRAM_START + 3, cpu.register.pc);
+    }
+
+    #[test]
+    fn interrupt_priority_test() {
+        let mut cpu = cpu_with_program(&[0x00]);
+        // V-Blank requested but not enabled, timer requested and enabled
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x05);
+
+        cpu.interrupt_handling();
+        assert_eq!(isr_adress::TIMER, cpu.register.pc);
+        assert_eq!(
+            0x01,
+            cpu.iommu.borrow_mut().read_byte(address::INTF_REGISTER) & 0x1F
+        );
+    }
+
+    #[test]
+    fn ie_written_during_push_test() {
+        let mut cpu = cpu_with_program(&[0x00]);
+        // The high byte of PC (0xC0) is pushed to IE and disables the timer interrupt
+        cpu.register.sp = 0x0000;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+
+        assert_eq!(20, cpu.interrupt_handling());
+        assert_eq!(0x0000, cpu.register.pc);
+        assert_eq!(
+            0x04,
+            cpu.iommu.borrow_mut().read_byte(address::INTF_REGISTER) & 0x1F
+        );
+    }
+
+    fn stacked_word(cpu: &Cpu, address: u16) -> u16 {
+        cpu.iommu.borrow_mut().read_word(address)
+    }
+
+    #[test]
+    fn interrupts_ei_sequence_test() {
+        // 02-interrupts "EI": EI, LD BC,0, PUSH BC, POP BC, INC B, LD A,4, LDH (IF),A, DEC B, JR $
+        let mut cpu = cpu_with_isr_rom(
+            "interrupts_ei",
+            &[
+                0xFB, 0x01, 0x00, 0x00, 0xC5, 0xC1, 0x04, 0x3E, 0x04, 0xE0, 0x0F, 0x05, 0x18, 0xFE,
+            ],
+        );
+        cpu.control.ime = false;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        for _ in 0..7 {
+            cpu.process().unwrap();
+        }
+        assert_eq!(PROGRAM_START + 11, cpu.register.pc);
+
+        // Interrupt is taken right after the IF write: dispatch and INC E of the handler
+        assert_eq!(20 + 4, cpu.process().unwrap());
+        assert_eq!(isr_adress::TIMER + 1, cpu.register.pc);
+        assert_eq!(PROGRAM_START + 11, stacked_word(&cpu, cpu.register.sp));
+
+        // RETI, DEC B
+        cpu.process().unwrap();
+        assert_eq!(PROGRAM_START + 11, cpu.register.pc);
+        assert!(cpu.control.ime);
+        cpu.process().unwrap();
+        assert_eq!(0, cpu.register.b);
+        assert_eq!(1, cpu.register.e);
+        assert_eq!(
+            0x00,
+            cpu.iommu.borrow_mut().read_byte(address::INTF_REGISTER) & 0x1F
+        );
+    }
+
+    #[test]
+    fn interrupts_di_sequence_test() {
+        // 02-interrupts "DI": DI, LD BC,0, PUSH BC, POP BC, LD A,4, LDH (IF),A, JR $
+        let mut cpu = cpu_with_isr_rom(
+            "interrupts_di",
+            &[
+                0xF3, 0x01, 0x00, 0x00, 0xC5, 0xC1, 0x3E, 0x04, 0xE0, 0x0F, 0x18, 0xFE,
+            ],
+        );
+        cpu.control.ime = true;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        for _ in 0..10 {
+            cpu.process().unwrap();
+        }
+
+        // Nothing was pushed over the zero left by PUSH BC
+        assert_eq!(PROGRAM_START + 10, cpu.register.pc);
+        assert_eq!(0x0000, stacked_word(&cpu, cpu.register.sp.wrapping_sub(2)));
+        assert_eq!(0, cpu.register.e);
+        assert_eq!(
+            0x04,
+            cpu.iommu.borrow_mut().read_byte(address::INTF_REGISTER) & 0x1F
+        );
+    }
+
+    #[test]
+    fn interrupts_halt_timer_test() {
+        // 02-interrupts "HALT": timer interrupt wakes up HALT with IME=1. EI, HALT, INC A, JR $
+        let mut cpu = cpu_with_isr_rom("interrupts_halt", &[0xFB, 0x76, 0x3C, 0x18, 0xFE]);
+        cpu.control.ime = false;
+        {
+            let mut iommu = cpu.iommu.borrow_mut();
+            iommu.write_byte(address::INTE_REGISTER, 0x04);
+            iommu.write_byte(address::INTF_REGISTER, 0x00);
+            iommu.write_byte(io_hardware_register::TIMER_TIMA, 0xFE);
+            // 262144 Hz
+            iommu.write_byte(io_hardware_register::TIMER_TAC, 0x05);
+        }
+        cpu.process().unwrap();
+        cpu.process().unwrap();
+        assert!(cpu.control.halted);
+
+        let mut steps = 0;
+        while cpu.register.e == 0 {
+            cpu.process().unwrap();
+            steps += 1;
+            assert!(steps < 100, "Timer interrupt did not wake up HALT");
+        }
+        assert!(!cpu.control.halted);
+        assert_eq!(PROGRAM_START + 2, stacked_word(&cpu, cpu.register.sp));
+        assert_eq!(0, cpu.register.a);
+    }
+
+    #[test]
+    fn interrupt_polling_not_watched_test() {
+        // NOP, NOP, HALT, NOP
+        let mut cpu = cpu_with_isr_rom("interrupt_polling", &[0x00, 0x00, 0x76, 0x00]);
+        cpu.control.ime = true;
+        {
+            let mut iommu = cpu.iommu.borrow_mut();
+            iommu.write_byte(address::INTE_REGISTER, 0x04);
+            iommu.write_byte(address::INTF_REGISTER, 0x00);
+            iommu.set_watchpoint(address::INTE_REGISTER, WatchKind::Read);
+            iommu.set_watchpoint(address::INTF_REGISTER, WatchKind::ReadWrite);
+        }
+        for _ in 0..5 {
+            cpu.process().unwrap();
+        }
+        assert!(cpu.control.halted);
+        assert_eq!(None, cpu.iommu.borrow().take_watchpoint_hit());
+
+        // Wake up, dispatch and acknowledge in IF are not program accesses either
+        cpu.iommu
+            .borrow_mut()
+            .poke_byte(address::INTF_REGISTER, 0x04);
+        cpu.process().unwrap();
+        assert_eq!(1, cpu.register.e);
+        assert_eq!(
+            0x00,
+            cpu.iommu.borrow().peek_byte(address::INTF_REGISTER) & 0x1F
+        );
+        assert_eq!(None, cpu.iommu.borrow().take_watchpoint_hit());
+    }
+
+    #[test]
+    fn ei_ei_sequence_test() {
+        // mooneye ei_sequence: EI, EI, NOP behaves as EI, NOP. The interrupt is taken before the NOP
+        let mut cpu = cpu_with_isr_rom("ei_sequence", &[0xFB, 0xFB, 0x00, 0x18, 0xFE]);
+        cpu.control.ime = false;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+
+        cpu.process().unwrap();
+        assert!(!cpu.control.ime);
+        cpu.process().unwrap();
+        assert!(cpu.control.ime);
+        assert_eq!(0, cpu.register.e);
+
+        cpu.process().unwrap();
+        assert_eq!(1, cpu.register.e);
+        assert_eq!(PROGRAM_START + 2, stacked_word(&cpu, cpu.register.sp));
+    }
+
+    #[test]
+    fn ie_push_rounds_test() {
+        // mooneye ie_push round 2: the low byte push writes IE, the vector is already chosen
+        let mut cpu = cpu_with_isr_rom("ie_push_low", &[0x00]);
+        cpu.register.sp = 0x0001;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x04);
+        assert_eq!(20, cpu.interrupt_handling());
+        assert_eq!(isr_adress::TIMER, cpu.register.pc);
+        assert_eq!(
+            0x00,
+            cpu.iommu.borrow_mut().read_byte(address::INTE_REGISTER)
+        );
+
+        // Round 3: the high byte push (0xC8) enables the serial interrupt only, it is taken instead
+        let mut cpu = cpu_with_isr_rom("ie_push_high", &[0x00]);
+        cpu.register.pc = 0xC808;
+        cpu.register.sp = 0x0000;
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTE_REGISTER, 0x04);
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(address::INTF_REGISTER, 0x0C);
+        assert_eq!(20, cpu.interrupt_handling());
+        assert_eq!(isr_adress::SERIAL_LINK, cpu.register.pc);
+        assert_eq!(
+            0x04,
+            cpu.iommu.borrow_mut().read_byte(address::INTF_REGISTER) & 0x1F
+        );
+    }
+
+    #[test]
+    fn illegal_opcode_locks_cpu_test() {
+        // INC A, ILLEGAL, INC A
+        let mut cpu = cpu_with_program(&[0x3C, 0xD3, 0x3C]);
+        cpu.control.ime = false;
+
+        assert!(cpu.process().is_ok());
+        match cpu.process() {
+            Err(EmulatorError::CpuLocked { opcode, address }) => {
+                assert_eq!(0xD3, opcode);
+                assert_eq!(PROGRAM_START + 1, address);
+            }
+            _ => panic!("CPU should be locked"),
+        }
+        assert!(cpu.is_locked());
+
+        // CPU does not fetch anymore
+        assert!(cpu.process().is_err());
+        assert_eq!(1, cpu.register.a);
+        assert_eq!(PROGRAM_START + 2, cpu.register.pc);
+    }
+
+    #[test]
+    fn stop_speed_switch_test() {
+        // STOP, NOP
+        let mut cpu = cpu_with_program(&[0x10, 0x00]);
+        cpu.iommu.borrow_mut().init(HardwareModel::Cgb, true);
+        cpu.init(HardwareModel::Cgb, true);
+        assert_eq!(0x11, cpu.register.a);
+        cpu.register.pc = PROGRAM_START;
+
+        cpu.iommu
+            .borrow_mut()
+            .write_byte(io_hardware_register::KEY1, 0x01);
+        let cycles = cpu.process().unwrap();
+
+        assert_eq!(4 + 2050 * 4, cycles);
+        assert!(cpu.iommu.borrow().is_double_speed());
+        assert_eq!(
+            0xFE,
+            cpu.iommu.borrow().read_byte(io_hardware_register::KEY1)
+        );
+
+        // Not armed, STOP does not switch back
+        cpu.register.pc = PROGRAM_START;
+        cpu.process().unwrap();
+        assert!(cpu.iommu.borrow().is_double_speed());
+    }
+
+    #[test]
+    fn stop_skips_padding_byte_test() {
+        // STOP, INC A (padding), INC B
+        let mut cpu = cpu_with_program(&[0x10, 0x3C, 0x04]);
+
+        cpu.process().unwrap();
+        assert_eq!(PROGRAM_START + 2, cpu.register.pc);
+
+        cpu.process().unwrap();
+        assert_eq!(0x00, cpu.register.a);
+        assert_eq!(0x01, cpu.register.b);
+    }
+
+    #[test]
+    fn hardware_model_init_test() {
+        let mut cpu = cpu_with_program(&[0x00]);
+
+        cpu.init(HardwareModel::Dmg0, false);
+        assert_eq!(
+            [0x01, 0xFF, 0x13],
+            [cpu.register.a, cpu.register.b, cpu.register.c]
+        );
+        cpu.init(HardwareModel::Mgb, false);
+        assert_eq!(0xFF, cpu.register.a);
+        cpu.init(HardwareModel::Sgb2, false);
+        assert_eq!([0xFF, 0x14], [cpu.register.a, cpu.register.c]);
+        cpu.init(HardwareModel::Cgb, false);
+        assert_eq!([0x11, 0x00], [cpu.register.a, cpu.register.d]);
+        cpu.init(HardwareModel::Agb, true);
+        assert_eq!(
+            [0x11, 0x01, 0xFF],
+            [cpu.register.a, cpu.register.b, cpu.register.d]
+        );
+        assert_eq!(0x00, u8::from(cpu.register.flag));
+        assert_eq!(address::cartridge_header::ENTRY_POINT, cpu.register.pc);
+    }
+
+    #[test]
+    fn operand_decoded_from_opcode_bits_test() {
+        let mut cpu = cpu_with_program(&[
+            0x01, 0x00, 0xC1, // LD BC,0xC100
+            0x3E, 0x5A, // LD A,0x5A
+            0x02, // LD (BC),A
+            0x3E, 0x00, // LD A,0x00
+            0x0A, // LD A,(BC)
+            0x26, 0x81, // LD H,0x81
+            0x1E, 0xFF, // LD E,0xFF
+            0xCB, 0x14, // RL H
+            0xCB, 0x10, // RL B
+            0xCB, 0xB8, // RES 7,B
+            0xCB, 0xBB, // RES 7,E
+        ]);
+        for _ in 0..5 {
+            cpu.process().unwrap();
+        }
+        assert_eq!(0x5A, cpu.iommu.borrow_mut().read_byte(0xC100));
+        assert_eq!(0x5A, cpu.register.a);
+        assert_eq!(0xC1, cpu.register.b);
+
+        for _ in 0..4 {
+            cpu.process().unwrap();
+        }
+        assert_eq!(0x02, cpu.register.h);
+        assert_eq!(0x83, cpu.register.b);
+
+        cpu.process().unwrap();
+        cpu.process().unwrap();
+        assert_eq!(0x03, cpu.register.b);
+        assert_eq!(0x7F, cpu.register.e);
+    }
+
+    #[test]
+    fn branch_not_taken_wraps_pc_test() {
+        // JR NZ,e8 / JP NZ,a16 / CALL NZ,a16 with the operand ending at 0xFFFF
+        for (start, opcode, cycles) in [(0xFFFE, 0x20, 8), (0xFFFD, 0xC2, 12), (0xFFFD, 0xC4, 12)] {
+            let mut cpu = cpu_with_program(&[]);
+            cpu.iommu.borrow_mut().write_byte(start, opcode);
+            cpu.register.pc = start;
+            cpu.register.flag.z = true;
+
+            assert_eq!(cycles, cpu.process().unwrap());
+            assert_eq!(0x0000, cpu.register.pc);
+        }
+    }
+}
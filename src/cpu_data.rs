@@ -1,13 +1,19 @@
+use crate::save_state::{Snapshot, StateReader, StateWriter};
+
 pub struct ControlFlags {
     pub ime: bool,
+    pub ime_scheduled: bool,
     pub halted: bool,
+    pub halt_bug: bool,
 }
 
 impl Default for ControlFlags {
     fn default() -> Self {
         Self {
             ime: true,
+            ime_scheduled: false,
             halted: false,
+            halt_bug: false,
         }
     }
 }
@@ -106,83 +112,98 @@ impl Registers {
         self.flag = FlagsRegister::from(raw_flag_value);
     }
 
-    pub fn get_reg_value_from_opcode_range(&self, opcode_array: &[u8], opcode: u8) -> u8 {
-        assert!(opcode_array.len() == 7);
-        let mut reg_id: usize = 0xFF;
-        for (id, element) in opcode_array.iter().enumerate() {
-            if opcode == *element {
-                reg_id = id;
-            }
-        }
-        match reg_id {
-            0 => self.b,
-            1 => self.c,
-            2 => self.d,
-            3 => self.e,
-            4 => self.h,
-            5 => self.l,
-            6 => self.a,
-            _ => panic!("opcode does not exist in looking array"),
+    /// # get_r16
+    /// Register pair encoded in bits 5-4 of the opcode: 0 - BC, 1 - DE, 2 - HL, 3 - SP
+    pub fn get_r16(&self, pair: u8) -> u16 {
+        match pair & 0x03 {
+            0 => self.get_bc(),
+            1 => self.get_de(),
+            2 => self.get_hl(),
+            _ => self.sp,
         }
     }
 
-    pub fn get_reg_address_from_opcode_range(
-        &mut self,
-        opcode_array: &[u8],
-        opcode: u8,
-    ) -> &mut u8 {
-        assert!(opcode_array.len() == 7);
-        let mut reg_id: usize = 0xFF;
-        for (id, element) in opcode_array.iter().enumerate() {
-            if opcode == *element {
-                reg_id = id;
-            }
-        }
-        match reg_id {
-            0 => &mut self.b,
-            1 => &mut self.c,
-            2 => &mut self.d,
-            3 => &mut self.e,
-            4 => &mut self.h,
-            5 => &mut self.l,
-            6 => &mut self.a,
-            _ => panic!("opcode does not exist in looking array"),
+    /// # set_r16
+    pub fn set_r16(&mut self, pair: u8, value: u16) {
+        match pair & 0x03 {
+            0 => self.set_bc(value),
+            1 => self.set_de(value),
+            2 => self.set_hl(value),
+            _ => self.sp = value,
         }
     }
 
-    pub fn get_reg16_value_from_opcode_array(&self, opcode_array: &[u8], opcode: u8) -> u16 {
-        assert!(opcode_array.len() < 5);
-        let mut reg_id: usize = 0xFF;
-        for (id, element) in opcode_array.iter().enumerate() {
-            if opcode == *element {
-                reg_id = id;
-            }
+    /// # get_r16_stack
+    /// PUSH and POP encode AF instead of SP
+    pub fn get_r16_stack(&self, pair: u8) -> u16 {
+        match pair & 0x03 {
+            3 => self.get_af(),
+            pair => self.get_r16(pair),
         }
+    }
 
-        match reg_id {
-            0 => self.get_bc(),
-            1 => self.get_de(),
-            2 => self.get_hl(),
-            3 => self.sp,
-            _ => panic!("opcode does not exist in looking array"),
+    /// # set_r16_stack
+    pub fn set_r16_stack(&mut self, pair: u8, value: u16) {
+        match pair & 0x03 {
+            3 => self.set_af(value),
+            pair => self.set_r16(pair, value),
         }
     }
 }
 
+impl Snapshot for ControlFlags {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.ime);
+        writer.write_bool(self.ime_scheduled);
+        writer.write_bool(self.halted);
+        writer.write_bool(self.halt_bug);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        self.ime = reader.read_bool()?;
+        self.ime_scheduled = reader.read_bool()?;
+        self.halted = reader.read_bool()?;
+        self.halt_bug = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+impl Snapshot for Registers {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.get_af());
+        writer.write_u16(self.get_bc());
+        writer.write_u16(self.get_de());
+        writer.write_u16(self.get_hl());
+        writer.write_u16(self.pc);
+        writer.write_u16(self.sp);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        self.set_af(reader.read_u16()?);
+        self.set_bc(reader.read_u16()?);
+        self.set_de(reader.read_u16()?);
+        self.set_hl(reader.read_u16()?);
+        self.pc = reader.read_u16()?;
+        self.sp = reader.read_u16()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod uint_test {
     use super::*;
 
     #[test]
     fn register_test() {
-        let mut register = Registers::default();
-
-        register.b = 0x33;
-        register.c = 0x34;
-        register.d = 0x35;
-        register.e = 0x36;
-        register.h = 0x37;
-        register.l = 0x38;
+        let mut register = Registers {
+            b: 0x33,
+            c: 0x34,
+            d: 0x35,
+            e: 0x36,
+            h: 0x37,
+            l: 0x38,
+            ..Default::default()
+        };
 
         assert_eq!(0x3334, register.get_bc());
         assert_eq!(0x3536, register.get_de());
@@ -198,9 +219,11 @@ mod uint_test {
     }
     #[test]
     fn af_test() {
-        let mut register = Registers::default();
+        let mut register = Registers {
+            a: 0x01,
+            ..Default::default()
+        };
 
-        register.a = 0x01;
         assert_eq!(0x100, register.get_af());
         register.flag.z = true;
         assert_eq!(0x180, register.get_af());
@@ -217,44 +240,22 @@ mod uint_test {
     }
 
     #[test]
-    fn get_reg_value_and_address_test() {
+    fn register_pair_from_opcode_test() {
         let mut register = Registers::default();
-        let exp_reg_val: [u8; 7] = [10, 11, 12, 13, 14, 15, 16];
 
-        //SET REGS
-        for opcode in exp_reg_val.iter() {
-            let reg =
-                register.get_reg_address_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], *opcode);
-            *reg = *opcode;
+        for (pair, value) in [0x0102, 0x0304, 0x0506, 0x0708].into_iter().enumerate() {
+            register.set_r16(pair as u8, value);
         }
-
-        assert_eq!(
-            register.get_reg_value_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], 10),
-            register.b
-        );
-        assert_eq!(
-            register.get_reg_value_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], 11),
-            register.c
-        );
-        assert_eq!(
-            register.get_reg_value_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], 12),
-            register.d
-        );
-        assert_eq!(
-            register.get_reg_value_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], 13),
-            register.e
-        );
-        assert_eq!(
-            register.get_reg_value_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], 14),
-            register.h
-        );
-        assert_eq!(
-            register.get_reg_value_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], 15),
-            register.l
-        );
-        assert_eq!(
-            register.get_reg_value_from_opcode_range(&[10, 11, 12, 13, 14, 15, 16], 16),
-            register.a
-        );
+        assert_eq!(0x0102, register.get_bc());
+        assert_eq!(0x0304, register.get_de());
+        assert_eq!(0x0506, register.get_hl());
+        assert_eq!(0x0708, register.sp);
+        assert_eq!(0x0506, register.get_r16(2));
+
+        register.set_r16_stack(3, 0x12F0);
+        assert_eq!(0x12, register.a);
+        assert_eq!(0x12F0, register.get_r16_stack(3));
+        assert_eq!(0x0708, register.get_r16(3));
+        assert_eq!(0x0102, register.get_r16_stack(0));
     }
 }
@@ -0,0 +1,517 @@
+use crate::constants::gb_memory_map::address::io_hardware_register;
+use crate::emulator_constants::clock;
+use crate::error::EmulatorError;
+use crate::GameBoyEmulator;
+use std::collections::BTreeSet;
+use std::fmt;
+
+const VBLANK_FIRST_LINE: u8 = 144;
+const DEFAULT_MEMORY_DUMP_LENGTH: u16 = 0x40;
+const DEFAULT_DISASSEMBLY_LENGTH: u16 = 10;
+
+/// # WatchKind
+/// CPU access which stops the emulation at a watched address or calls a memory hook
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+    // Write of a different value than the one read back before
+    Change,
+}
+
+impl WatchKind {
+    pub fn matches(&self, access: &MemoryAccess) -> bool {
+        match self {
+            WatchKind::Read => !access.is_write,
+            WatchKind::Write => access.is_write,
+            WatchKind::ReadWrite => true,
+            WatchKind::Change => access.is_write && access.value != access.old_value,
+        }
+    }
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchKind::Read => write!(f, "r"),
+            WatchKind::Write => write!(f, "w"),
+            WatchKind::ReadWrite => write!(f, "rw"),
+            WatchKind::Change => write!(f, "c"),
+        }
+    }
+}
+
+/// # MemoryAccess
+/// Value is the one read or written by the CPU, old value the content before the access
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct MemoryAccess {
+    pub address: u16,
+    pub value: u8,
+    pub old_value: u8,
+    pub is_write: bool,
+}
+
+/// # HookAction
+/// Break stops the debugger like a watchpoint
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum HookAction {
+    Continue,
+    Break,
+}
+
+/// # HookId
+/// Returned by `add_memory_hook`, used to remove the hook
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct HookId(pub(crate) usize);
+
+/// # CpuRegisters
+/// Copy of the CPU state for the debug tools
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    pub halted: bool,
+}
+
+impl fmt::Display for CpuRegisters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |bit: u8, name: char| {
+            if self.f.rotate_right(bit as u32) & 1 == 1 {
+                name
+            } else {
+                '-'
+            }
+        };
+        write!(
+            f,
+            "AF={:02X}{:02X} BC={:02X}{:02X} DE={:02X}{:02X} HL={:02X}{:02X} SP={:04X} PC={:04X} [{}{}{}{}] IME={} HALT={}",
+            self.a,
+            self.f,
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+            self.sp,
+            self.pc,
+            flag(7, 'Z'),
+            flag(6, 'N'),
+            flag(5, 'H'),
+            flag(4, 'C'),
+            self.ime as u8,
+            self.halted as u8
+        )
+    }
+}
+
+/// # RunTarget
+/// Frame counts 70224 cycles, VBlank stops when LY reaches 144
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RunTarget {
+    Steps(u32),
+    Frame,
+    VBlank,
+}
+
+/// # StopReason
+#[derive(Debug)]
+pub enum StopReason {
+    TargetReached,
+    Breakpoint(u16),
+    Watchpoint(MemoryAccess),
+    CpuLocked(EmulatorError),
+    // One frame passed without VBlank, the LCD is off
+    VBlankNotReached,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::TargetReached => write!(f, "Stopped"),
+            StopReason::Breakpoint(address) => write!(f, "Breakpoint at {:04X}", address),
+            StopReason::Watchpoint(hit) if hit.is_write => write!(
+                f,
+                "Watchpoint: write {:04X} = {:02X} (was {:02X})",
+                hit.address, hit.value, hit.old_value
+            ),
+            StopReason::Watchpoint(hit) => write!(
+                f,
+                "Watchpoint: read {:04X} = {:02X}",
+                hit.address, hit.value
+            ),
+            StopReason::CpuLocked(error) => write!(f, "{}", error),
+            StopReason::VBlankNotReached => write!(f, "No VBlank for a whole frame, LCD is off"),
+        }
+    }
+}
+
+/// # DebugCommand
+/// Addresses are hexadecimal (`C000`, `0xC000` or `$C000`), counts are decimal
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DebugCommand {
+    Step(u32),
+    Continue,
+    Frame,
+    VBlank,
+    Break(u16),
+    Delete(u16),
+    Watch(u16, WatchKind),
+    Unwatch(u16),
+    List,
+    Registers,
+    Memory { address: u16, length: u16 },
+    Disassemble { address: Option<u16>, count: u16 },
+    Help,
+    Quit,
+}
+
+const HELP: &str = "\
+s, step [n]            execute n instructions (1)
+c, continue            run until a breakpoint or a watchpoint
+f, frame               run one frame
+v, vblank              run until the next VBlank
+b, break <addr>        breakpoint on PC
+del, delete <addr>     remove the breakpoint
+w, watch <addr> [r|w|rw|c]  watchpoint on a memory access or change (rw)
+uw, unwatch <addr>     remove the watchpoint
+l, list                breakpoints and watchpoints
+r, regs                CPU registers
+x, mem <addr> [n]      dump n bytes of memory (64)
+d, disasm [addr] [n]   disassemble n instructions from addr (PC, 10)
+h, help                this help
+q, quit                exit
+Empty line repeats the last command";
+
+fn parse_address(token: Option<&str>) -> Result<u16, String> {
+    let token = token.ok_or_else(|| String::from("Address is missing"))?;
+    let digits = token
+        .trim_start_matches("0x")
+        .trim_start_matches("0X")
+        .trim_start_matches('$');
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Bad address: {}", token))
+}
+
+fn parse_count<T: std::str::FromStr>(token: Option<&str>, default: T) -> Result<T, String> {
+    match token {
+        Some(token) => token
+            .parse::<T>()
+            .map_err(|_| format!("Bad count: {}", token)),
+        None => Ok(default),
+    }
+}
+
+impl std::str::FromStr for DebugCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut tokens = line.split_whitespace();
+        let name = tokens.next().unwrap_or_default().to_lowercase();
+        let command = match name.as_str() {
+            "s" | "step" => DebugCommand::Step(parse_count(tokens.next(), 1)?),
+            "c" | "continue" => DebugCommand::Continue,
+            "f" | "frame" => DebugCommand::Frame,
+            "v" | "vblank" => DebugCommand::VBlank,
+            "b" | "break" => DebugCommand::Break(parse_address(tokens.next())?),
+            "del" | "delete" => DebugCommand::Delete(parse_address(tokens.next())?),
+            "w" | "watch" => {
+                let address = parse_address(tokens.next())?;
+                let kind = match tokens.next() {
+                    Some("r") => WatchKind::Read,
+                    Some("w") => WatchKind::Write,
+                    Some("rw") | None => WatchKind::ReadWrite,
+                    Some("c") => WatchKind::Change,
+                    Some(kind) => return Err(format!("Bad watchpoint kind: {}", kind)),
+                };
+                DebugCommand::Watch(address, kind)
+            }
+            "uw" | "unwatch" => DebugCommand::Unwatch(parse_address(tokens.next())?),
+            "l" | "list" => DebugCommand::List,
+            "r" | "regs" => DebugCommand::Registers,
+            "x" | "mem" => DebugCommand::Memory {
+                address: parse_address(tokens.next())?,
+                length: parse_count(tokens.next(), DEFAULT_MEMORY_DUMP_LENGTH)?,
+            },
+            "d" | "disasm" => DebugCommand::Disassemble {
+                address: tokens
+                    .next()
+                    .map(|token| parse_address(Some(token)))
+                    .transpose()?,
+                count: parse_count(tokens.next(), DEFAULT_DISASSEMBLY_LENGTH)?,
+            },
+            "h" | "help" | "?" => DebugCommand::Help,
+            "q" | "quit" => DebugCommand::Quit,
+            _ => return Err(format!("Unknown command: {}, type help", line.trim())),
+        };
+        Ok(command)
+    }
+}
+
+/// # Debugger
+/// Command line debugger on top of `GameBoyEmulator::emulate_step`.
+/// Breakpoints are checked before every instruction, watchpoints on every CPU memory access.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// # run
+    /// Emulates until the target, a breakpoint, a watchpoint or a CPU lock-up
+    pub fn run(&mut self, gameboy: &mut GameBoyEmulator, target: RunTarget) -> StopReason {
+        let mut steps: u32 = 0;
+        let mut frame_cycles: u32 = 0;
+        let mut last_ly = gameboy.peek_byte(io_hardware_register::LY);
+        // Hit of the debugger's own commands
+        let _ = gameboy.take_watchpoint_hit();
+
+        loop {
+            let cycles = match gameboy.emulate_step() {
+                Ok(cycles) => cycles,
+                Err(error) => return StopReason::CpuLocked(error),
+            };
+            steps += 1;
+            frame_cycles += if gameboy.is_double_speed() {
+                cycles / 2
+            } else {
+                cycles
+            };
+
+            if let Some(hit) = gameboy.take_watchpoint_hit() {
+                return StopReason::Watchpoint(hit);
+            }
+            let pc = gameboy.cpu_registers().pc;
+            if self.breakpoints.contains(&pc) {
+                return StopReason::Breakpoint(pc);
+            }
+
+            match target {
+                RunTarget::Steps(number_of_steps) if steps >= number_of_steps => {
+                    return StopReason::TargetReached
+                }
+                RunTarget::Frame if frame_cycles >= clock::CYCLES_PER_FRAME => {
+                    return StopReason::TargetReached
+                }
+                RunTarget::VBlank => {
+                    let ly = gameboy.peek_byte(io_hardware_register::LY);
+                    if ly == VBLANK_FIRST_LINE && last_ly != VBLANK_FIRST_LINE {
+                        return StopReason::TargetReached;
+                    }
+                    if frame_cycles > clock::CYCLES_PER_FRAME {
+                        return StopReason::VBlankNotReached;
+                    }
+                    last_ly = ly;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// # stop_report
+    /// Stop reason followed by the next instruction and the registers
+    pub fn stop_report(&self, gameboy: &GameBoyEmulator, stop_reason: &StopReason) -> String {
+        let registers = gameboy.cpu_registers();
+        let (instruction, _) = gameboy.disassemble(registers.pc);
+        let mut report = String::new();
+        if !matches!(stop_reason, StopReason::TargetReached) {
+            report.push_str(&format!("{}\n", stop_reason));
+        }
+        report.push_str(&format!(
+            "{:04X}: {}\n{}",
+            registers.pc, instruction, registers
+        ));
+        report
+    }
+
+    /// # execute
+    /// Runs the command and returns the text to show. `Continue` blocks until something stops
+    /// the emulation, interactive frontends can loop `run` with `RunTarget::Frame` instead.
+    pub fn execute(&mut self, gameboy: &mut GameBoyEmulator, command: &DebugCommand) -> String {
+        match *command {
+            DebugCommand::Step(number_of_steps) => {
+                let stop_reason = self.run(gameboy, RunTarget::Steps(number_of_steps.max(1)));
+                self.stop_report(gameboy, &stop_reason)
+            }
+            DebugCommand::Continue => loop {
+                let stop_reason = self.run(gameboy, RunTarget::Frame);
+                if !matches!(stop_reason, StopReason::TargetReached) {
+                    return self.stop_report(gameboy, &stop_reason);
+                }
+            },
+            DebugCommand::Frame => {
+                let stop_reason = self.run(gameboy, RunTarget::Frame);
+                self.stop_report(gameboy, &stop_reason)
+            }
+            DebugCommand::VBlank => {
+                let stop_reason = self.run(gameboy, RunTarget::VBlank);
+                self.stop_report(gameboy, &stop_reason)
+            }
+            DebugCommand::Break(address) => {
+                self.add_breakpoint(address);
+                format!("Breakpoint at {:04X}", address)
+            }
+            DebugCommand::Delete(address) => {
+                if self.remove_breakpoint(address) {
+                    format!("Breakpoint at {:04X} removed", address)
+                } else {
+                    format!("No breakpoint at {:04X}", address)
+                }
+            }
+            DebugCommand::Watch(address, kind) => {
+                gameboy.set_watchpoint(address, kind);
+                format!("Watchpoint ({}) at {:04X}", kind, address)
+            }
+            DebugCommand::Unwatch(address) => {
+                if gameboy.remove_watchpoint(address) {
+                    format!("Watchpoint at {:04X} removed", address)
+                } else {
+                    format!("No watchpoint at {:04X}", address)
+                }
+            }
+            DebugCommand::List => self.list(gameboy),
+            DebugCommand::Registers => gameboy.cpu_registers().to_string(),
+            DebugCommand::Memory { address, length } => Self::memory_dump(gameboy, address, length),
+            DebugCommand::Disassemble { address, count } => {
+                self.disassembly(gameboy, address, count)
+            }
+            DebugCommand::Help => String::from(HELP),
+            DebugCommand::Quit => String::new(),
+        }
+    }
+
+    fn list(&self, gameboy: &GameBoyEmulator) -> String {
+        let mut lines: Vec<String> = self
+            .breakpoints
+            .iter()
+            .map(|address| format!("break {:04X}", address))
+            .collect();
+        lines.extend(
+            gameboy
+                .watchpoints()
+                .iter()
+                .map(|(address, kind)| format!("watch {:04X} {}", address, kind)),
+        );
+        if lines.is_empty() {
+            return String::from("No breakpoints or watchpoints");
+        }
+        lines.join("\n")
+    }
+
+    fn memory_dump(gameboy: &GameBoyEmulator, address: u16, length: u16) -> String {
+        const BYTES_PER_ROW: u16 = 16;
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        while offset < length.max(1) {
+            let row_address = address.wrapping_add(offset);
+            let row_length = BYTES_PER_ROW.min(length.max(1) - offset);
+            let bytes: Vec<String> = (0..row_length)
+                .map(|index| format!("{:02X}", gameboy.peek_byte(row_address.wrapping_add(index))))
+                .collect();
+            lines.push(format!("{:04X}: {}", row_address, bytes.join(" ")));
+            offset += row_length;
+        }
+        lines.join("\n")
+    }
+
+    fn disassembly(&self, gameboy: &GameBoyEmulator, address: Option<u16>, count: u16) -> String {
+        let pc = gameboy.cpu_registers().pc;
+        let mut address = address.unwrap_or(pc);
+        let mut lines = Vec::new();
+        for _ in 0..count.max(1) {
+            let (instruction, length) = gameboy.disassemble(address);
+            let pc_marker = if address == pc { "=>" } else { "  " };
+            let breakpoint_marker = if self.breakpoints.contains(&address) {
+                '*'
+            } else {
+                ' '
+            };
+            lines.push(format!(
+                "{}{}{:04X}: {}",
+                pc_marker, breakpoint_marker, address, instruction
+            ));
+            address = address.wrapping_add(length);
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn parse_command_test() {
+        assert_eq!(Ok(DebugCommand::Step(1)), "s".parse());
+        assert_eq!(Ok(DebugCommand::Step(20)), "step 20".parse());
+        assert_eq!(Ok(DebugCommand::Break(0x0150)), "b 0x150".parse());
+        assert_eq!(Ok(DebugCommand::Delete(0xC000)), "delete $C000".parse());
+        assert_eq!(
+            Ok(DebugCommand::Watch(0xFF01, WatchKind::Write)),
+            "w ff01 w".parse()
+        );
+        assert_eq!(
+            Ok(DebugCommand::Watch(0xFF01, WatchKind::ReadWrite)),
+            "watch FF01".parse()
+        );
+        assert_eq!(
+            Ok(DebugCommand::Watch(0xC0A0, WatchKind::Change)),
+            "w c0a0 c".parse()
+        );
+        assert_eq!(
+            Ok(DebugCommand::Memory {
+                address: 0xC000,
+                length: 16
+            }),
+            "x c000 16".parse()
+        );
+        assert_eq!(
+            Ok(DebugCommand::Disassemble {
+                address: None,
+                count: DEFAULT_DISASSEMBLY_LENGTH
+            }),
+            "d".parse()
+        );
+        assert!("b".parse::<DebugCommand>().is_err());
+        assert!("b xyz".parse::<DebugCommand>().is_err());
+        assert!("w c000 x".parse::<DebugCommand>().is_err());
+        assert!("jump".parse::<DebugCommand>().is_err());
+    }
+
+    #[test]
+    fn cpu_registers_format_test() {
+        let registers = CpuRegisters {
+            a: 0x01,
+            f: 0xB0,
+            pc: 0x0100,
+            sp: 0xFFFE,
+            ime: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "AF=01B0 BC=0000 DE=0000 HL=0000 SP=FFFE PC=0100 [Z-HC] IME=1 HALT=0",
+            registers.to_string()
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use crate::instructions::opcode_table::{CB_OPCODE_TABLE, OPCODE_TABLE};
+use crate::iommu::Iommu;
+
+const PREFIX_CB: u8 = 0xCB;
+const STOP: u8 = 0x10;
+
+/// # disassemble
+/// Decodes the instruction at the address with its operands.
+///
+/// Returns the text and the length of the instruction in bytes, e.g. (`LD A,(HL+)`, 1), (`JR NZ,$+5`, 2)
+///
+/// Relative jumps are shown from the address of the instruction (`$`)
+pub fn disassemble(iommu: &Iommu, address: u16) -> (String, u16) {
+    let opcode = iommu.peek_byte(address);
+
+    if opcode == PREFIX_CB {
+        let cb_opcode = iommu.peek_byte(address.wrapping_add(1));
+        return (CB_OPCODE_TABLE[cb_opcode as usize].mnemonic.to_string(), 2);
+    }
+    // STOP is followed by a padding byte
+    if opcode == STOP {
+        return (String::from("STOP 0"), 2);
+    }
+
+    let mnemonic = OPCODE_TABLE[opcode as usize].mnemonic;
+    let byte_operand = iommu.peek_byte(address.wrapping_add(1));
+    let word_operand =
+        (iommu.peek_byte(address.wrapping_add(2)) as u16).rotate_left(8) | byte_operand as u16;
+
+    if mnemonic == "ILLEGAL" {
+        return (format!("ILLEGAL ${:02X}", opcode), 1);
+    }
+    if mnemonic.contains("d16") {
+        return (
+            mnemonic.replace("d16", &format!("${:04X}", word_operand)),
+            3,
+        );
+    }
+    if mnemonic.contains("a16") {
+        return (
+            mnemonic.replace("a16", &format!("${:04X}", word_operand)),
+            3,
+        );
+    }
+    if mnemonic.contains("d8") {
+        return (mnemonic.replace("d8", &format!("${:02X}", byte_operand)), 2);
+    }
+    if mnemonic.contains("a8") {
+        return (
+            mnemonic.replace("a8", &format!("$FF{:02X}", byte_operand)),
+            2,
+        );
+    }
+    if mnemonic.starts_with("JR") {
+        // Offset is counted from the next instruction
+        let offset = byte_operand as i8 as i16 + 2;
+        return (mnemonic.replace("r8", &format_signed("$", offset)), 2);
+    }
+    if mnemonic.contains("SP+r8") {
+        let offset = byte_operand as i8 as i16;
+        return (mnemonic.replace("SP+r8", &format_signed("SP", offset)), 2);
+    }
+    if mnemonic.contains("r8") {
+        let offset = byte_operand as i8;
+        return (mnemonic.replace("r8", &offset.to_string()), 2);
+    }
+    (mnemonic.to_string(), 1)
+}
+
+fn format_signed(base: &str, value: i16) -> String {
+    if value < 0 {
+        format!("{}{}", base, value)
+    } else {
+        format!("{}+{}", base, value)
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+    use crate::constants::gb_memory_map::address;
+    use crate::peripheral::{
+        cartridge::Cartridge, joypad::JoypadInput, ppu::PictureProcessingUnit,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    const CODE_ADDRESS: u16 = *address::WORKING_RAM_BANK_0.start();
+
+    fn iommu_with_code(code: &[u8]) -> Iommu {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge, ppu, joypad);
+        for (offset, byte) in code.iter().enumerate() {
+            iommu.write_byte(CODE_ADDRESS + offset as u16, *byte);
+        }
+        iommu
+    }
+
+    #[test]
+    fn disassemble_without_operands_test() {
+        let iommu = iommu_with_code(&[0x2A, 0x00, 0xE9]);
+        assert_eq!(
+            (String::from("LD A,(HL+)"), 1),
+            disassemble(&iommu, CODE_ADDRESS)
+        );
+        assert_eq!(
+            (String::from("NOP"), 1),
+            disassemble(&iommu, CODE_ADDRESS + 1)
+        );
+        assert_eq!(
+            (String::from("JP HL"), 1),
+            disassemble(&iommu, CODE_ADDRESS + 2)
+        );
+    }
+
+    #[test]
+    fn disassemble_stop_test() {
+        let iommu = iommu_with_code(&[0x10, 0x00, 0x00]);
+        assert_eq!(
+            (String::from("STOP 0"), 2),
+            disassemble(&iommu, CODE_ADDRESS)
+        );
+        assert_eq!(
+            (String::from("NOP"), 1),
+            disassemble(&iommu, CODE_ADDRESS + 2)
+        );
+    }
+
+    #[test]
+    fn disassemble_immediate_operands_test() {
+        let iommu = iommu_with_code(&[
+            0x3E, 0x12, // LD A,$12
+            0x21, 0x34, 0x12, // LD HL,$1234
+            0xEA, 0x00, 0xC0, // LD ($C000),A
+            0xE0, 0x44, // LDH ($FF44),A
+        ]);
+        assert_eq!(
+            (String::from("LD A,$12"), 2),
+            disassemble(&iommu, CODE_ADDRESS)
+        );
+        assert_eq!(
+            (String::from("LD HL,$1234"), 3),
+            disassemble(&iommu, CODE_ADDRESS + 2)
+        );
+        assert_eq!(
+            (String::from("LD ($C000),A"), 3),
+            disassemble(&iommu, CODE_ADDRESS + 5)
+        );
+        assert_eq!(
+            (String::from("LDH ($FF44),A"), 2),
+            disassemble(&iommu, CODE_ADDRESS + 8)
+        );
+    }
+
+    #[test]
+    fn disassemble_signed_operands_test() {
+        let iommu = iommu_with_code(&[
+            0x20, 0x03, // JR NZ,$+5
+            0x18, 0xFE, // JR $+0
+            0xE8, 0xFE, // ADD SP,-2
+            0xF8, 0x05, // LD HL,SP+5
+            0x38, 0xF0, // JR C,$-14
+        ]);
+        assert_eq!(
+            (String::from("JR NZ,$+5"), 2),
+            disassemble(&iommu, CODE_ADDRESS)
+        );
+        assert_eq!(
+            (String::from("JR $+0"), 2),
+            disassemble(&iommu, CODE_ADDRESS + 2)
+        );
+        assert_eq!(
+            (String::from("ADD SP,-2"), 2),
+            disassemble(&iommu, CODE_ADDRESS + 4)
+        );
+        assert_eq!(
+            (String::from("LD HL,SP+5"), 2),
+            disassemble(&iommu, CODE_ADDRESS + 6)
+        );
+        assert_eq!(
+            (String::from("JR C,$-14"), 2),
+            disassemble(&iommu, CODE_ADDRESS + 8)
+        );
+    }
+
+    #[test]
+    fn disassemble_cb_prefixed_and_illegal_test() {
+        let iommu = iommu_with_code(&[0xCB, 0x7E, 0xCB, 0x37, 0xD3]);
+        assert_eq!(
+            (String::from("BIT 7,(HL)"), 2),
+            disassemble(&iommu, CODE_ADDRESS)
+        );
+        assert_eq!(
+            (String::from("SWAP A"), 2),
+            disassemble(&iommu, CODE_ADDRESS + 2)
+        );
+        assert_eq!(
+            (String::from("ILLEGAL $D3"), 1),
+            disassemble(&iommu, CODE_ADDRESS + 4)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum GameBoyKeys {
     Right,
     Left,
@@ -10,6 +10,71 @@ pub enum GameBoyKeys {
     Start,
 }
 
+impl std::str::FromStr for GameBoyKeys {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "right" => Ok(GameBoyKeys::Right),
+            "left" => Ok(GameBoyKeys::Left),
+            "up" => Ok(GameBoyKeys::Up),
+            "down" => Ok(GameBoyKeys::Down),
+            "a" => Ok(GameBoyKeys::A),
+            "b" => Ok(GameBoyKeys::B),
+            "select" => Ok(GameBoyKeys::Select),
+            "start" => Ok(GameBoyKeys::Start),
+            _ => Err(format!("Unknown Game Boy button: {}", name)),
+        }
+    }
+}
+
+/// # PpuRenderer
+/// Scanline draws a whole line at once, PixelFifo runs the fetcher dot by dot
+/// and keeps mid-scanline register changes (raster effects).
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PpuRenderer {
+    Scanline,
+    PixelFifo,
+}
+
+/// # HardwareModel
+/// Console to behave like. Games and test ROMs detect it from the post-boot registers,
+/// e.g. A=0x11 on CGB, A=0xFF on MGB/SGB2 and B bit 0 on AGB.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum HardwareModel {
+    Dmg0,
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb,
+    Agb,
+}
+
+impl HardwareModel {
+    /// CGB and AGB run cartridges with CGB support in the CGB mode
+    pub fn is_cgb(&self) -> bool {
+        matches!(self, HardwareModel::Cgb | HardwareModel::Agb)
+    }
+}
+
+impl std::str::FromStr for HardwareModel {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "dmg0" => Ok(HardwareModel::Dmg0),
+            "dmg" => Ok(HardwareModel::Dmg),
+            "mgb" => Ok(HardwareModel::Mgb),
+            "sgb" => Ok(HardwareModel::Sgb),
+            "sgb2" => Ok(HardwareModel::Sgb2),
+            "cgb" => Ok(HardwareModel::Cgb),
+            "agb" => Ok(HardwareModel::Agb),
+            _ => Err(format!("Unknown hardware model: {}", name)),
+        }
+    }
+}
+
 pub mod resolution {
     pub const SCREEN_W: usize = 160;
     pub const SCREEN_H: usize = 144;
@@ -18,4 +83,15 @@ pub mod resolution {
 pub mod clock {
     pub const CPU_CLOCK_FREQUENCY: u32 = 4194304;
     pub const CYCLE_SPEED: u32 = CPU_CLOCK_FREQUENCY / 4; // 1_048_576 = 1MHz
+                                                          // 154 scanlines * 456 dots
+    pub const CYCLES_PER_FRAME: u32 = 456 * 154;
+    // 70224 / 4194304 Hz = 16,742706 ms <-- 59.73 fps
+    pub const FRAME_DURATION_NS: u64 =
+        CYCLES_PER_FRAME as u64 * 1_000_000_000 / CPU_CLOCK_FREQUENCY as u64;
+}
+
+pub mod audio {
+    pub const SAMPLE_RATE: u32 = 44100;
+    // Stereo samples kept when the frontend does not drain them
+    pub const SAMPLE_BUFFER_CAPACITY: usize = SAMPLE_RATE as usize / 4;
 }
@@ -0,0 +1,69 @@
+use std::fmt;
+
+/// # EmulatorError
+/// Errors reported by the emulator core instead of aborting the process
+#[derive(Debug)]
+pub enum EmulatorError {
+    /// File system error while reading the ROM or the save data
+    Io(std::io::Error),
+    /// File is too small to contain the cartridge header or the ROM size it declares
+    InvalidRomSize(usize),
+    /// Header checksum (0x014D) does not match the header content
+    InvalidHeaderChecksum,
+    /// Cartridge type code at 0x0147 is not supported
+    UnsupportedCartridgeType(u8),
+    /// ROM size code at 0x0148 is not supported
+    UnsupportedRomSizeCode(u8),
+    /// RAM size code at 0x0149 is not supported
+    UnsupportedRamSizeCode(u8),
+    /// Boot ROM is neither DMG/MGB (256 bytes) nor CGB (2304 bytes)
+    InvalidBootRomSize(usize),
+    /// Illegal opcode was executed, the CPU hangs until reset
+    CpuLocked { opcode: u8, address: u16 },
+}
+
+pub type Result<T> = std::result::Result<T, EmulatorError>;
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::Io(error) => write!(f, "I/O error: {}", error),
+            EmulatorError::InvalidRomSize(size) => {
+                write!(f, "ROM file is too small: {} bytes", size)
+            }
+            EmulatorError::InvalidHeaderChecksum => write!(f, "ROM header checksum is not valid"),
+            EmulatorError::UnsupportedCartridgeType(code) => {
+                write!(f, "Unsupported cartridge type: [0x{:02x}]", code)
+            }
+            EmulatorError::UnsupportedRomSizeCode(code) => {
+                write!(f, "Unsupported ROM size code: [0x{:02x}]", code)
+            }
+            EmulatorError::UnsupportedRamSizeCode(code) => {
+                write!(f, "Unsupported RAM size code: [0x{:02x}]", code)
+            }
+            EmulatorError::InvalidBootRomSize(size) => {
+                write!(f, "Unsupported boot ROM size: {} bytes", size)
+            }
+            EmulatorError::CpuLocked { opcode, address } => write!(
+                f,
+                "CPU locked up on illegal opcode [0x{:02x}] at [{:#06x}]",
+                opcode, address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmulatorError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for EmulatorError {
+    fn from(error: std::io::Error) -> Self {
+        EmulatorError::Io(error)
+    }
+}
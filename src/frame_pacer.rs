@@ -0,0 +1,91 @@
+use crate::emulator_constants::clock;
+use std::{thread, time};
+
+/// # FramePacer
+/// Keeps the frontend at the Game Boy refresh rate (~59.73 fps).
+///
+/// Frame deadlines are scheduled from the start time, not from the end of the previous frame,
+/// so a late frame does not slow the whole emulation down.
+pub struct FramePacer {
+    frame_duration: time::Duration,
+    next_frame_deadline: time::Instant,
+    last_frame_time: time::Instant,
+    fps: f32,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new(time::Duration::from_nanos(clock::FRAME_DURATION_NS))
+    }
+}
+
+impl FramePacer {
+    pub fn new(frame_duration: time::Duration) -> Self {
+        let now = time::Instant::now();
+        Self {
+            frame_duration,
+            next_frame_deadline: now + frame_duration,
+            last_frame_time: now,
+            fps: 0.0,
+        }
+    }
+
+    /// # wait_for_next_frame
+    /// Sleeps until the deadline of the current frame. When the emulation fell behind
+    /// by more than one frame the schedule starts over instead of running in bursts.
+    pub fn wait_for_next_frame(&mut self) {
+        let now = time::Instant::now();
+        if now < self.next_frame_deadline {
+            thread::sleep(self.next_frame_deadline - now);
+            self.next_frame_deadline += self.frame_duration;
+        } else if now - self.next_frame_deadline > self.frame_duration {
+            self.next_frame_deadline = now + self.frame_duration;
+        } else {
+            self.next_frame_deadline += self.frame_duration;
+        }
+
+        let now = time::Instant::now();
+        let elapsed = now - self.last_frame_time;
+        self.last_frame_time = now;
+        if !elapsed.is_zero() {
+            self.fps = 1.0 / elapsed.as_secs_f32();
+        }
+    }
+
+    /// # fps
+    /// Frame rate measured between the last two frames
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn frame_pacer_keeps_frame_duration_test() {
+        let frame_duration = time::Duration::from_millis(5);
+        let mut pacer = FramePacer::new(frame_duration);
+        let start_time = time::Instant::now();
+
+        for _ in 0..4 {
+            pacer.wait_for_next_frame();
+        }
+
+        assert!(start_time.elapsed() >= frame_duration * 3);
+        assert!(pacer.fps() > 0.0);
+    }
+
+    #[test]
+    fn frame_pacer_skips_lost_frames_test() {
+        let frame_duration = time::Duration::from_millis(1);
+        let mut pacer = FramePacer::new(frame_duration);
+
+        thread::sleep(frame_duration * 10);
+        pacer.wait_for_next_frame();
+
+        // Schedule starts over from now, the lost frames are not caught up
+        assert!(pacer.next_frame_deadline > time::Instant::now());
+    }
+}
@@ -0,0 +1,489 @@
+use crate::debugger::{CpuRegisters, Debugger, RunTarget, StopReason, WatchKind};
+use crate::GameBoyEmulator;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const CTRL_C: u8 = 0x03;
+const NUMBER_OF_REGISTERS: usize = 6;
+
+type FrameCallback = Box<dyn FnMut(&mut GameBoyEmulator)>;
+
+/// SM83 has no GDB architecture, the registers are described for the client:
+/// AF, BC, DE, HL, SP and PC, 16 bit little endian
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.r_gb_emu.sm83">
+    <reg name="af" bitsize="16" type="int"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="int"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+enum PacketAction {
+    Reply(String),
+    Step,
+    Continue,
+    Detach,
+    Kill,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// # read_packet
+/// `$data#checksum`, acks and Ctrl-C outside of a packet are skipped. None when the client is gone.
+fn read_packet(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    loop {
+        loop {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(b'$') => break,
+                Some(_) => continue,
+            }
+        }
+        let mut data = Vec::new();
+        loop {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(b'#') => break,
+                Some(byte) => data.push(byte),
+            }
+        }
+        let mut checksum_digits = String::new();
+        for _ in 0..2 {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(byte) => checksum_digits.push(byte as char),
+            }
+        }
+
+        if u8::from_str_radix(&checksum_digits, 16).ok() == Some(checksum(&data)) {
+            stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        // Client sends it again
+        stream.write_all(b"-")?;
+    }
+}
+
+/// The client acknowledges with `+`, TCP does not lose data so the ack is not awaited
+fn send_packet(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+    stream.write_all(packet.as_bytes())
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn register_values(registers: &CpuRegisters) -> [u16; NUMBER_OF_REGISTERS] {
+    let pair = |high: u8, low: u8| (high as u16).rotate_left(8) | low as u16;
+    [
+        pair(registers.a, registers.f),
+        pair(registers.b, registers.c),
+        pair(registers.d, registers.e),
+        pair(registers.h, registers.l),
+        registers.sp,
+        registers.pc,
+    ]
+}
+
+fn set_register_value(registers: &mut CpuRegisters, register_id: usize, value: u16) {
+    let high = value.rotate_right(8) as u8;
+    let low = value as u8;
+    match register_id {
+        // Lower 4 bits of F do not exist
+        0 => (registers.a, registers.f) = (high, low & 0xF0),
+        1 => (registers.b, registers.c) = (high, low),
+        2 => (registers.d, registers.e) = (high, low),
+        3 => (registers.h, registers.l) = (high, low),
+        4 => registers.sp = value,
+        _ => registers.pc = value,
+    }
+}
+
+fn register_to_hex(value: u16) -> String {
+    format!("{:02x}{:02x}", value as u8, value.rotate_right(8) as u8)
+}
+
+fn register_from_hex(text: &str) -> Option<u16> {
+    if text.len() != 4 {
+        return None;
+    }
+    let low = parse_hex(&text[0..2])?;
+    let high = parse_hex(&text[2..4])?;
+    Some((high as u16).rotate_left(8) | low as u16)
+}
+
+/// Parses `addr,length` of the memory and breakpoint packets
+fn parse_address_length(text: &str) -> Option<(u16, u16)> {
+    let (address, length) = text.split_once(',')?;
+    let address = parse_hex(address)?;
+    let length = parse_hex(length)?;
+    if address.checked_add(length)? > 0x10000 {
+        return None;
+    }
+    Some((u16::try_from(address).ok()?, u16::try_from(length).ok()?))
+}
+
+/// # GdbStub
+/// GDB remote serial protocol target over TCP. Breakpoints and stepping go through `Debugger`,
+/// watchpoints and memory through the IOMMU, Ctrl-C in the client stops a running target.
+#[derive(Default)]
+pub struct GdbStub {
+    debugger: Debugger,
+    frame_callback: Option<FrameCallback>,
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # set_frame_callback
+    /// Called after every emulated frame while the target runs, e.g. to show the screen
+    pub fn set_frame_callback(&mut self, callback: impl FnMut(&mut GameBoyEmulator) + 'static) {
+        self.frame_callback = Some(Box::new(callback));
+    }
+
+    /// # listen
+    /// Waits for one client on 127.0.0.1:port and serves it until it detaches or kills the target
+    pub fn listen(&mut self, gameboy: &mut GameBoyEmulator, port: u16) -> io::Result<()> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let (stream, _) = listener.accept()?;
+        self.serve(gameboy, stream)
+    }
+
+    /// # serve
+    /// The target is stopped until the client continues or steps it
+    pub fn serve(
+        &mut self,
+        gameboy: &mut GameBoyEmulator,
+        mut stream: TcpStream,
+    ) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        while let Some(packet) = read_packet(&mut stream)? {
+            match self.handle_packet(gameboy, &packet) {
+                PacketAction::Reply(reply) => send_packet(&mut stream, &reply)?,
+                PacketAction::Step => {
+                    let stop_reason = self.debugger.run(gameboy, RunTarget::Steps(1));
+                    send_packet(&mut stream, &Self::stop_reply(gameboy, &stop_reason))?;
+                }
+                PacketAction::Continue => match self.continue_until_stop(gameboy, &mut stream)? {
+                    Some(reply) => send_packet(&mut stream, &reply)?,
+                    None => return Ok(()),
+                },
+                PacketAction::Detach => {
+                    send_packet(&mut stream, "OK")?;
+                    return Ok(());
+                }
+                PacketAction::Kill => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop reply, None when the client disconnected meanwhile
+    fn continue_until_stop(
+        &mut self,
+        gameboy: &mut GameBoyEmulator,
+        stream: &mut TcpStream,
+    ) -> io::Result<Option<String>> {
+        stream.set_nonblocking(true)?;
+        let reply = loop {
+            let stop_reason = self.debugger.run(gameboy, RunTarget::Frame);
+            if let Some(callback) = self.frame_callback.as_mut() {
+                callback(gameboy);
+            }
+            if !matches!(stop_reason, StopReason::TargetReached) {
+                break Some(Self::stop_reply(gameboy, &stop_reason));
+            }
+
+            let mut byte = [0];
+            match stream.read(&mut byte) {
+                Ok(0) => break None,
+                Ok(_) if byte[0] == CTRL_C => break Some(format!("S{:02x}", SIGINT)),
+                Ok(_) => (),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => (),
+                Err(error) => return Err(error),
+            }
+        };
+        stream.set_nonblocking(false)?;
+        Ok(reply)
+    }
+
+    fn stop_reply(gameboy: &GameBoyEmulator, stop_reason: &StopReason) -> String {
+        match stop_reason {
+            StopReason::Watchpoint(hit) => {
+                let kind = gameboy
+                    .watchpoints()
+                    .iter()
+                    .find(|(address, _)| *address == hit.address)
+                    .map_or(WatchKind::ReadWrite, |(_, kind)| *kind);
+                let name = match kind {
+                    WatchKind::Write | WatchKind::Change => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::ReadWrite => "awatch",
+                };
+                format!("T{:02x}{}:{:04x};", SIGTRAP, name, hit.address)
+            }
+            StopReason::CpuLocked(_) => format!("S{:02x}", SIGILL),
+            _ => format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    fn handle_packet(&mut self, gameboy: &mut GameBoyEmulator, packet: &str) -> PacketAction {
+        // The arguments are sliced at byte offsets, which are character offsets only in ASCII
+        if !packet.is_ascii() {
+            return PacketAction::Reply(String::from("E01"));
+        }
+        let Some(command) = packet.chars().next() else {
+            return PacketAction::Reply(String::new());
+        };
+        let arguments = &packet[1..];
+        let reply = match command {
+            '?' => Some(format!("S{:02x}", SIGTRAP)),
+            'g' => Some(
+                register_values(&gameboy.cpu_registers())
+                    .iter()
+                    .map(|value| register_to_hex(*value))
+                    .collect(),
+            ),
+            'G' => self.write_registers(gameboy, arguments),
+            'p' => parse_hex(arguments)
+                .filter(|register_id| (*register_id as usize) < NUMBER_OF_REGISTERS)
+                .map(|register_id| {
+                    register_to_hex(register_values(&gameboy.cpu_registers())[register_id as usize])
+                }),
+            'P' => self.write_register(gameboy, arguments),
+            'm' => parse_address_length(arguments).map(|(address, length)| {
+                (0..length)
+                    .map(|offset| format!("{:02x}", gameboy.peek_byte(address + offset)))
+                    .collect()
+            }),
+            'M' => self.write_memory(gameboy, arguments),
+            's' => return PacketAction::Step,
+            'c' => return PacketAction::Continue,
+            'Z' | 'z' => self.breakpoint_packet(gameboy, command == 'Z', arguments),
+            'D' => return PacketAction::Detach,
+            'k' => return PacketAction::Kill,
+            'H' => Some(String::from("OK")),
+            'q' => Some(Self::query(arguments)),
+            // Not supported packets are answered with an empty reply
+            _ => Some(String::new()),
+        };
+        PacketAction::Reply(reply.unwrap_or_else(|| String::from("E01")))
+    }
+
+    fn write_registers(
+        &mut self,
+        gameboy: &mut GameBoyEmulator,
+        arguments: &str,
+    ) -> Option<String> {
+        if arguments.len() != NUMBER_OF_REGISTERS * 4 {
+            return None;
+        }
+        let mut registers = gameboy.cpu_registers();
+        for register_id in 0..NUMBER_OF_REGISTERS {
+            let value = register_from_hex(&arguments[register_id * 4..register_id * 4 + 4])?;
+            set_register_value(&mut registers, register_id, value);
+        }
+        gameboy.set_cpu_registers(&registers);
+        Some(String::from("OK"))
+    }
+
+    fn write_register(&mut self, gameboy: &mut GameBoyEmulator, arguments: &str) -> Option<String> {
+        let (register_id, value) = arguments.split_once('=')?;
+        let register_id = parse_hex(register_id)? as usize;
+        if register_id >= NUMBER_OF_REGISTERS {
+            return None;
+        }
+        let mut registers = gameboy.cpu_registers();
+        set_register_value(&mut registers, register_id, register_from_hex(value)?);
+        gameboy.set_cpu_registers(&registers);
+        Some(String::from("OK"))
+    }
+
+    fn write_memory(&mut self, gameboy: &mut GameBoyEmulator, arguments: &str) -> Option<String> {
+        let (address_length, data) = arguments.split_once(':')?;
+        let (address, length) = parse_address_length(address_length)?;
+        if data.len() != length as usize * 2 {
+            return None;
+        }
+        for offset in 0..length {
+            let index = offset as usize * 2;
+            let value = parse_hex(&data[index..index + 2])? as u8;
+            gameboy.poke_byte(address + offset, value);
+        }
+        Some(String::from("OK"))
+    }
+
+    /// `Z0`/`Z1` breakpoint, `Z2` write, `Z3` read and `Z4` access watchpoint
+    fn breakpoint_packet(
+        &mut self,
+        gameboy: &mut GameBoyEmulator,
+        insert: bool,
+        arguments: &str,
+    ) -> Option<String> {
+        let (kind, address_length) = arguments.split_once(',')?;
+        let (address, length) = parse_address_length(address_length)?;
+        let watch_kind = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.add_breakpoint(address);
+                } else {
+                    self.debugger.remove_breakpoint(address);
+                }
+                return Some(String::from("OK"));
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::ReadWrite,
+            _ => return Some(String::new()),
+        };
+        for offset in 0..length.max(1) {
+            if insert {
+                gameboy.set_watchpoint(address + offset, watch_kind);
+            } else {
+                gameboy.remove_watchpoint(address + offset);
+            }
+        }
+        Some(String::from("OK"))
+    }
+
+    fn query(arguments: &str) -> String {
+        if arguments.starts_with("Supported") {
+            return String::from("PacketSize=1000;qXfer:features:read+");
+        }
+        if arguments == "Attached" {
+            return String::from("1");
+        }
+        if let Some(range) = arguments.strip_prefix("Xfer:features:read:target.xml:") {
+            let Some((offset, length)) = range.split_once(',') else {
+                return String::from("E01");
+            };
+            let (Some(offset), Some(length)) = (parse_hex(offset), parse_hex(length)) else {
+                return String::from("E01");
+            };
+            let offset = (offset as usize).min(TARGET_XML.len());
+            let end = offset.saturating_add(length as usize).min(TARGET_XML.len());
+            let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            return format!("{}{}", marker, &TARGET_XML[offset..end]);
+        }
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+    use crate::emulator_constants::HardwareModel;
+
+    fn reply(stub: &mut GdbStub, gameboy: &mut GameBoyEmulator, packet: &str) -> String {
+        match stub.handle_packet(gameboy, packet) {
+            PacketAction::Reply(reply) => reply,
+            _ => panic!("Packet {} does not have a direct reply", packet),
+        }
+    }
+
+    #[test]
+    fn checksum_test() {
+        assert_eq!(0x9a, checksum(b"OK"));
+        assert_eq!(0x00, checksum(b""));
+    }
+
+    #[test]
+    fn register_packets_test() {
+        let mut gameboy = GameBoyEmulator::with_hardware_model(HardwareModel::Dmg);
+        let mut stub = GdbStub::new();
+
+        // AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100
+        assert_eq!(
+            "b0011300d8004d01feff0001",
+            reply(&mut stub, &mut gameboy, "g")
+        );
+        assert_eq!("0001", reply(&mut stub, &mut gameboy, "p5"));
+        assert_eq!("E01", reply(&mut stub, &mut gameboy, "p6"));
+
+        assert_eq!(
+            "OK",
+            reply(&mut stub, &mut gameboy, "Gff12341256347856feff50c0")
+        );
+        let registers = gameboy.cpu_registers();
+        assert_eq!([0x12, 0xF0], [registers.a, registers.f]);
+        assert_eq!([0x12, 0x34], [registers.b, registers.c]);
+        assert_eq!(0xC050, registers.pc);
+
+        assert_eq!("OK", reply(&mut stub, &mut gameboy, "P4=00d0"));
+        assert_eq!(0xD000, gameboy.cpu_registers().sp);
+    }
+
+    #[test]
+    fn memory_packets_test() {
+        let mut gameboy = GameBoyEmulator::new();
+        let mut stub = GdbStub::new();
+
+        assert_eq!("OK", reply(&mut stub, &mut gameboy, "Mc000,3:0a0b0c"));
+        assert_eq!("0a0b0c00", reply(&mut stub, &mut gameboy, "mc000,4"));
+        assert_eq!("E01", reply(&mut stub, &mut gameboy, "mfffe,4"));
+        assert_eq!("E01", reply(&mut stub, &mut gameboy, "Mc000,2:0a"));
+
+        assert_eq!("OK", reply(&mut stub, &mut gameboy, "Z2,c000,2"));
+        assert_eq!(2, gameboy.watchpoints().len());
+        assert_eq!("OK", reply(&mut stub, &mut gameboy, "z2,c000,2"));
+        assert_eq!(0, gameboy.watchpoints().len());
+    }
+
+    #[test]
+    fn malformed_packets_test() {
+        let mut gameboy = GameBoyEmulator::new();
+        let mut stub = GdbStub::new();
+
+        // Non-ASCII bytes are replaced by U+FFFD when the packet is read
+        for packet in [
+            "\u{FFFD}",
+            "m\u{FFFD},1",
+            "Mc000,2:a\u{FFFD}",
+            "P0=a\u{FFFD}",
+            "G\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}",
+        ] {
+            assert_eq!("E01", reply(&mut stub, &mut gameboy, packet));
+        }
+
+        assert_eq!("E01", reply(&mut stub, &mut gameboy, "mffffffff,1"));
+        assert_eq!("E01", reply(&mut stub, &mut gameboy, "m1,ffffffff"));
+        assert_eq!("E01", reply(&mut stub, &mut gameboy, "m10000,0"));
+        assert_eq!("E01", reply(&mut stub, &mut gameboy, "Z2,ffffffff,1"));
+        assert_eq!("OK", reply(&mut stub, &mut gameboy, "Mffff,1:1f"));
+        assert_eq!("1f", reply(&mut stub, &mut gameboy, "mffff,1"));
+        assert_eq!(
+            "l",
+            GdbStub::query("Xfer:features:read:target.xml:ffffffff,ffffffff")
+        );
+    }
+
+    #[test]
+    fn query_packets_test() {
+        assert!(GdbStub::query("Supported:multiprocess+").contains("qXfer:features:read+"));
+        assert_eq!("1", GdbStub::query("Attached"));
+        assert!(GdbStub::query("Xfer:features:read:target.xml:0,10").starts_with("m<?xml"));
+        assert!(GdbStub::query("Xfer:features:read:target.xml:0,1000").starts_with('l'));
+        assert_eq!("", GdbStub::query("fThreadInfo"));
+    }
+}
@@ -0,0 +1,395 @@
+use minifb::{Key, KeyRepeat, Window};
+use r_gb_emu::emulator_constants::GameBoyKeys;
+
+/// Keys which can be bound, looked up by their minifb name
+const KEYS: [Key; 106] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::F13,
+    Key::F14,
+    Key::F15,
+    Key::Down,
+    Key::Left,
+    Key::Right,
+    Key::Up,
+    Key::Apostrophe,
+    Key::Backquote,
+    Key::Backslash,
+    Key::Comma,
+    Key::Equal,
+    Key::LeftBracket,
+    Key::Minus,
+    Key::Period,
+    Key::RightBracket,
+    Key::Semicolon,
+    Key::Slash,
+    Key::Backspace,
+    Key::Delete,
+    Key::End,
+    Key::Enter,
+    Key::Escape,
+    Key::Home,
+    Key::Insert,
+    Key::Menu,
+    Key::PageDown,
+    Key::PageUp,
+    Key::Pause,
+    Key::Space,
+    Key::Tab,
+    Key::NumLock,
+    Key::CapsLock,
+    Key::ScrollLock,
+    Key::LeftShift,
+    Key::RightShift,
+    Key::LeftCtrl,
+    Key::RightCtrl,
+    Key::NumPad0,
+    Key::NumPad1,
+    Key::NumPad2,
+    Key::NumPad3,
+    Key::NumPad4,
+    Key::NumPad5,
+    Key::NumPad6,
+    Key::NumPad7,
+    Key::NumPad8,
+    Key::NumPad9,
+    Key::NumPadDot,
+    Key::NumPadSlash,
+    Key::NumPadAsterisk,
+    Key::NumPadMinus,
+    Key::NumPadPlus,
+    Key::NumPadEnter,
+    Key::LeftAlt,
+    Key::RightAlt,
+    Key::LeftSuper,
+    Key::RightSuper,
+];
+
+fn parse_key(name: &str) -> Result<Key, String> {
+    KEYS.iter()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or(format!("Unknown key: {}", name))
+}
+
+/// # HotkeyAction
+/// Emulator actions bound in the [hotkeys] section
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum HotkeyAction {
+    Quit,
+    Pause,
+    // Held down: frames are not paced
+    Turbo,
+    SaveState,
+    LoadState,
+    // Stops `continue` in the debugger
+    Break,
+}
+
+impl std::str::FromStr for HotkeyAction {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "quit" => Ok(HotkeyAction::Quit),
+            "pause" => Ok(HotkeyAction::Pause),
+            "turbo" => Ok(HotkeyAction::Turbo),
+            "save_state" => Ok(HotkeyAction::SaveState),
+            "load_state" => Ok(HotkeyAction::LoadState),
+            "break" => Ok(HotkeyAction::Break),
+            _ => Err(format!("Unknown hotkey action: {}", name)),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Section {
+    None,
+    Buttons,
+    Hotkeys,
+}
+
+/// # InputConfig
+/// Keyboard bindings of the buttons and the emulator actions, any number of keys each.
+///
+/// INI file, the values are also accepted as TOML arrays:
+/// ```ini
+/// [buttons]
+/// a = Z, J
+/// start = ["Enter", "Space"]
+/// [hotkeys]
+/// save_state = F5
+/// ```
+/// An entry replaces the default keys of its button or action, an empty value unbinds it.
+#[derive(Clone, Debug)]
+pub struct InputConfig {
+    buttons: Vec<(GameBoyKeys, Vec<Key>)>,
+    hotkeys: Vec<(HotkeyAction, Vec<Key>)>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            buttons: vec![
+                (GameBoyKeys::Right, vec![Key::Right]),
+                (GameBoyKeys::Left, vec![Key::Left]),
+                (GameBoyKeys::Up, vec![Key::Up]),
+                (GameBoyKeys::Down, vec![Key::Down]),
+                (GameBoyKeys::A, vec![Key::Z]),
+                (GameBoyKeys::B, vec![Key::X]),
+                (GameBoyKeys::Select, vec![Key::Space]),
+                (GameBoyKeys::Start, vec![Key::Enter]),
+            ],
+            hotkeys: vec![
+                (HotkeyAction::Quit, vec![Key::Escape]),
+                (HotkeyAction::Pause, vec![Key::P]),
+                (HotkeyAction::Turbo, vec![Key::Tab]),
+                (HotkeyAction::SaveState, vec![Key::F5]),
+                (HotkeyAction::LoadState, vec![Key::F9]),
+                (HotkeyAction::Break, vec![Key::F12]),
+            ],
+        }
+    }
+}
+
+impl InputConfig {
+    /// # load
+    /// Default bindings changed by the file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        let mut config = Self::default();
+        config
+            .apply(&text)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        Ok(config)
+    }
+
+    /// # apply
+    /// Entries of the config file text
+    pub fn apply(&mut self, text: &str) -> Result<(), String> {
+        let mut section = Section::None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(['#', ';']).next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let result = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(name) => match name.trim().to_lowercase().as_str() {
+                    "buttons" => {
+                        section = Section::Buttons;
+                        Ok(())
+                    }
+                    "hotkeys" => {
+                        section = Section::Hotkeys;
+                        Ok(())
+                    }
+                    _ => Err(format!("Unknown section: {}", name)),
+                },
+                None => self.apply_entry(section, line),
+            };
+            result.map_err(|error| format!("line {}: {}", index + 1, error))?;
+        }
+        Ok(())
+    }
+
+    /// # apply_override
+    /// Command line entry `name=keys`, the button or action name decides the section
+    pub fn apply_override(&mut self, entry: &str) -> Result<(), String> {
+        let name = entry.split('=').next().unwrap_or("").trim();
+        let section = if name.parse::<GameBoyKeys>().is_ok() {
+            Section::Buttons
+        } else {
+            Section::Hotkeys
+        };
+        self.apply_entry(section, entry)
+    }
+
+    fn apply_entry(&mut self, section: Section, entry: &str) -> Result<(), String> {
+        let Some((name, value)) = entry.split_once('=') else {
+            return Err(format!("Expected name = keys: {}", entry));
+        };
+        let value = value.trim();
+        let value = value
+            .strip_prefix('[')
+            .and_then(|v| v.strip_suffix(']'))
+            .unwrap_or(value);
+        let keys = value
+            .split(',')
+            .map(|key| key.trim().trim_matches('"').trim_matches('\''))
+            .filter(|key| !key.is_empty())
+            .map(parse_key)
+            .collect::<Result<Vec<Key>, String>>()?;
+
+        let name = name.trim().trim_matches('"');
+        match section {
+            Section::Buttons => {
+                let button = name.parse::<GameBoyKeys>()?;
+                Self::bind(&mut self.buttons, button, keys);
+            }
+            Section::Hotkeys => {
+                let action = name.parse::<HotkeyAction>()?;
+                Self::bind(&mut self.hotkeys, action, keys);
+            }
+            Section::None => return Err(format!("Entry outside of a section: {}", entry)),
+        }
+        Ok(())
+    }
+
+    fn bind<T: PartialEq>(bindings: &mut Vec<(T, Vec<Key>)>, target: T, keys: Vec<Key>) {
+        match bindings.iter_mut().find(|(bound, _)| *bound == target) {
+            Some((_, bound_keys)) => *bound_keys = keys,
+            None => bindings.push((target, keys)),
+        }
+    }
+
+    /// # buttons
+    /// Buttons with their keys
+    pub fn buttons(&self) -> &[(GameBoyKeys, Vec<Key>)] {
+        &self.buttons
+    }
+
+    fn action_keys(&self, action: HotkeyAction) -> &[Key] {
+        self.hotkeys
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// # is_action_down
+    /// Any key of the action is held
+    pub fn is_action_down(&self, window: &Window, action: HotkeyAction) -> bool {
+        self.action_keys(action)
+            .iter()
+            .any(|key| window.is_key_down(*key))
+    }
+
+    /// # is_action_pressed
+    /// Any key of the action went down since the last window update
+    pub fn is_action_pressed(&self, window: &Window, action: HotkeyAction) -> bool {
+        self.action_keys(action)
+            .iter()
+            .any(|key| window.is_key_pressed(*key, KeyRepeat::No))
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn parse_key_test() {
+        assert_eq!(Ok(Key::Z), parse_key("z"));
+        assert_eq!(Ok(Key::NumPadEnter), parse_key("NumPadEnter"));
+        assert_eq!(Ok(Key::Key1), parse_key("key1"));
+        assert!(parse_key("Unknown").is_err());
+        assert!(parse_key("Count").is_err());
+    }
+
+    #[test]
+    fn config_file_test() {
+        let mut config = InputConfig::default();
+        let text = "# Layout for the left hand\n\
+                    [buttons]\n\
+                    a = J, K ; two keys\n\
+                    Start = [\"Enter\", \"Space\"]\n\
+                    select =\n\
+                    [Hotkeys]\n\
+                    save_state = F2\n";
+        assert!(config.apply(text).is_ok());
+
+        let keys_of = |button| {
+            config
+                .buttons()
+                .iter()
+                .find(|(bound, _)| *bound == button)
+                .unwrap()
+                .1
+                .clone()
+        };
+        assert_eq!(vec![Key::J, Key::K], keys_of(GameBoyKeys::A));
+        assert_eq!(vec![Key::Enter, Key::Space], keys_of(GameBoyKeys::Start));
+        assert!(keys_of(GameBoyKeys::Select).is_empty());
+        // Not in the file
+        assert_eq!(vec![Key::X], keys_of(GameBoyKeys::B));
+        assert_eq!([Key::F2], config.action_keys(HotkeyAction::SaveState));
+        assert_eq!([Key::F9], config.action_keys(HotkeyAction::LoadState));
+    }
+
+    #[test]
+    fn config_error_test() {
+        let mut config = InputConfig::default();
+        assert_eq!(
+            Err(String::from("line 1: Entry outside of a section: a = Z")),
+            config.apply("a = Z")
+        );
+        assert_eq!(
+            Err(String::from("line 2: Unknown key: Zed")),
+            config.apply("[buttons]\na = Zed")
+        );
+        assert_eq!(
+            Err(String::from("line 1: Unknown section: keys")),
+            config.apply("[keys]")
+        );
+        assert!(config.apply("[hotkeys]\nrewind = R").is_err());
+    }
+
+    #[test]
+    fn override_test() {
+        let mut config = InputConfig::default();
+        assert!(config.apply_override("up=W,NumPad8").is_ok());
+        assert!(config.apply_override("quit=Q").is_ok());
+        assert!(config.apply_override("fast=F").is_err());
+
+        assert!(config
+            .buttons()
+            .contains(&(GameBoyKeys::Up, vec![Key::W, Key::NumPad8])));
+        assert_eq!([Key::Q], config.action_keys(HotkeyAction::Quit));
+    }
+}
@@ -2,16 +2,6 @@
 
 use crate::cpu_data::FlagsRegister;
 
-pub static ARITHMETIC_LOGIC_OPCODES: [u8; 104] = [
-    0x03, 0x04, 0x05, 0x09, 0x0b, 0x0c, 0x0d, 0x13, 0x14, 0x15, 0x19, 0x1b, 0x1c, 0x1d, 0x23, 0x24,
-    0x25, 0x27, 0x29, 0x2b, 0x2c, 0x2d, 0x2f, 0x33, 0x34, 0x35, 0x39, 0x3b, 0x3c, 0x3d, 0x80, 0x81,
-    0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x8b, 0x8c, 0x8d, 0x8e, 0x8f, 0x90, 0x91,
-    0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0x9b, 0x9c, 0x9d, 0x9e, 0x9f, 0xa0, 0xa1,
-    0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xab, 0xac, 0xad, 0xae, 0xaf, 0xb0, 0xb1,
-    0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xbb, 0xbc, 0xbd, 0xbe, 0xbf, 0xc6, 0xce,
-    0xd6, 0xde, 0xe6, 0xe8, 0xee, 0xf6, 0xf8, 0xfe,
-];
-
 fn half_carry_on_addition(a: u8, b: u8) -> bool {
     (((a & 0xF) + (b & 0xF)) & 0x10) == 0x10
 }
@@ -318,55 +308,63 @@ mod ut {
 
     #[test]
     fn add_half_carry_flag_test() {
-        let mut register = Registers::default();
-        register.a = 0x6C;
+        let mut register = Registers {
+            a: 0x6C,
+            ..Default::default()
+        };
         register.flag.n = true;
 
         add(&mut register.flag, &mut register.a, 0x2E, 0);
 
         assert_eq!(0x9A, register.a);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn add_carry_half_carry_and_zero_flag_test() {
-        let mut register = Registers::default();
-        register.a = 0xFF;
+        let mut register = Registers {
+            a: 0xFF,
+            ..Default::default()
+        };
         register.flag.n = true;
 
         add(&mut register.flag, &mut register.a, 1, 0);
 
         assert_eq!(0x00, register.a);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == true);
-        assert!(register.flag.c == true);
-        assert!(register.flag.n == false);
+        assert!(register.flag.h);
+        assert!(register.flag.z);
+        assert!(register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn adc_carry_flag_set_test() {
-        let mut register = Registers::default();
-        register.a = 0x3D;
+        let mut register = Registers {
+            a: 0x3D,
+            ..Default::default()
+        };
         register.flag.n = true;
         register.flag.c = true;
 
         adc(&mut register.flag, &mut register.a, 0x42);
 
         assert_eq!(0x80, register.a);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn add_hl_half_carry_test() {
-        let mut register = Registers::default();
-        register.b = 0x4C;
-        register.c = 0x00;
+        let mut register = Registers {
+            b: 0x4C,
+            c: 0x00,
+            ..Default::default()
+        };
 
         register.h = 0x4C;
         register.l = 0x00;
@@ -383,16 +381,18 @@ mod ut {
         );
 
         assert_eq!(0x9800, register.get_hl());
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn add_sp_with_carry_test() {
-        let mut register = Registers::default();
-        register.sp = 0x81;
+        let mut register = Registers {
+            sp: 0x81,
+            ..Default::default()
+        };
 
         register.flag.n = true;
         register.flag.z = true;
@@ -400,52 +400,58 @@ mod ut {
         add_sp(&mut register.flag, &mut register.sp, -1);
 
         assert_eq!(0x80, register.sp);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == true);
-        assert!(register.flag.n == false);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn sub_the_same_value_test() {
-        let mut register = Registers::default();
-        register.a = 0x3E;
+        let mut register = Registers {
+            a: 0x3E,
+            ..Default::default()
+        };
 
         sub(&mut register.flag, &mut register.a, 0x3E, 0);
 
         assert_eq!(0, register.a);
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == true);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == true);
+        assert!(!register.flag.h);
+        assert!(register.flag.z);
+        assert!(!register.flag.c);
+        assert!(register.flag.n);
     }
     #[test]
     fn sub_overflow_test() {
-        let mut register = Registers::default();
-        register.a = 16;
+        let mut register = Registers {
+            a: 16,
+            ..Default::default()
+        };
 
         sub(&mut register.flag, &mut register.a, 18, 0);
 
         assert_eq!(254, register.a);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == true);
-        assert!(register.flag.n == true);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(register.flag.c);
+        assert!(register.flag.n);
     }
 
     #[test]
     fn sbc_carry_flag_set_test() {
-        let mut register = Registers::default();
-        register.a = 77;
+        let mut register = Registers {
+            a: 77,
+            ..Default::default()
+        };
         register.flag.c = true;
 
         sbc(&mut register.flag, &mut register.a, 7);
 
         assert_eq!(69, register.a);
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == true);
+        assert!(!register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(register.flag.n);
     }
 
     #[test]
@@ -458,10 +464,10 @@ mod ut {
         and(&mut register.flag, &mut register.a, 0x0F);
 
         assert_eq!(0xC, register.a);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
     #[test]
     fn xor_test() {
@@ -474,10 +480,10 @@ mod ut {
         xor(&mut register.flag, &mut register.a, 0xAC);
 
         assert_eq!(0x50, register.a);
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(!register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
     #[test]
     fn or_test() {
@@ -490,24 +496,26 @@ mod ut {
         or(&mut register.flag, &mut register.a, 0xA6);
 
         assert_eq!(0xAF, register.a);
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(!register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn cp_test() {
-        let mut register = Registers::default();
-        register.a = 0x3E;
+        let mut register = Registers {
+            a: 0x3E,
+            ..Default::default()
+        };
 
         cp(&mut register.flag, &mut register.a, 0x3E);
 
         assert_eq!(0x3E, register.a);
-        assert!(register.flag.h == false); // false for z80 for 8080 true
-        assert!(register.flag.z == true);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == true);
+        assert!(!register.flag.h); // false for z80 for 8080 true
+        assert!(register.flag.z);
+        assert!(!register.flag.c);
+        assert!(register.flag.n);
     }
 
     #[test]
@@ -519,36 +527,37 @@ mod ut {
         inc(&mut register.flag, &mut register.b);
 
         assert_eq!(0, register.b);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == true);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(register.flag.h);
+        assert!(register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
 
         let mut val: u8 = 99;
 
         inc(&mut register.flag, &mut val);
 
         assert_eq!(100, val);
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(!register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn inc16_test() {
-        let mut register = Registers::default();
-
-        register.b = 0x01;
-        register.c = 0x10;
+        let mut register = Registers {
+            b: 0x01,
+            c: 0x10,
+            ..Default::default()
+        };
 
         inc_16(&mut register.b, &mut register.c);
 
         assert_eq!(0x111, register.get_bc());
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(!register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
 
         //INC sp
         register.sp = 0xAABB;
@@ -572,49 +581,51 @@ mod ut {
         dec(&mut register.flag, &mut register.b);
 
         assert_eq!(255, register.b);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == true);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(register.flag.n);
 
         let mut val: u8 = 99;
 
         dec(&mut register.flag, &mut val);
 
         assert_eq!(98, val);
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == true);
+        assert!(!register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(register.flag.n);
     }
 
     #[test]
     fn dec16_test() {
-        let mut register = Registers::default();
-
-        register.b = 0x01;
-        register.c = 0x10;
+        let mut register = Registers {
+            b: 0x01,
+            c: 0x10,
+            ..Default::default()
+        };
 
         dec_16(&mut register.b, &mut register.c);
 
         assert_eq!(0x10F, register.get_bc());
-        assert!(register.flag.h == false);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == false);
+        assert!(!register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(!register.flag.n);
     }
 
     #[test]
     fn daa_test() {
-        let mut register = Registers::default();
-
         //Performing decimal addition
         //    85  1000 0101   0x85
         // +  36  0011 0110   0x36
         // = 121 BCD
 
         //1) First add 0x85 + 0x36
-        register.a = 0x85;
+        let mut register = Registers {
+            a: 0x85,
+            ..Default::default()
+        };
 
         add(&mut register.flag, &mut register.a, 0x36, 0);
 
@@ -625,30 +636,32 @@ mod ut {
 
         assert_eq!(0x21, register.a);
         //1 carry 21 = 121 BCD
-        assert!(register.flag.c == true);
+        assert!(register.flag.c);
     }
 
     #[test]
     fn cpl_test() {
-        let mut register = Registers::default();
-
-        register.a = 0xFF;
+        let mut register = Registers {
+            a: 0xFF,
+            ..Default::default()
+        };
 
         cpl(&mut register.flag, &mut register.a);
 
         assert_eq!(0, register.a);
-        assert!(register.flag.h == true);
-        assert!(register.flag.z == false);
-        assert!(register.flag.c == false);
-        assert!(register.flag.n == true);
+        assert!(register.flag.h);
+        assert!(!register.flag.z);
+        assert!(!register.flag.c);
+        assert!(register.flag.n);
     }
 
     #[test]
     fn ld_hl_test() {
-        let mut register = Registers::default();
-
-        register.h = 0x01;
-        register.l = 0;
+        let mut register = Registers {
+            h: 0x01,
+            l: 0,
+            ..Default::default()
+        };
 
         register.sp = 30;
 
@@ -1,11 +1,7 @@
 use super::load::pop;
 use super::load::push;
-use crate::iommu::IOMMU;
+use crate::iommu::Iommu;
 
-pub static JUMP_OPCODES: [u8; 30] = [
-    0x18, 0x20, 0x28, 0x30, 0x38, 0xC0, 0xC2, 0xC3, 0xC4, 0xC7, 0xC8, 0xC9, 0xCA, 0xCC, 0xCD, 0xCF,
-    0xD0, 0xD2, 0xD4, 0xD7, 0xD8, 0xD9, 0xDA, 0xDC, 0xDF, 0xE7, 0xE9, 0xEF, 0xF7, 0xFF,
-];
 pub static RESET_VECTOR_ADDRESS: [u16; 8] = [
     0x0000, 0x0008, 0x0010, 0x0018, 0x0020, 0x0028, 0x0030, 0x0038,
 ];
@@ -25,22 +21,22 @@ pub fn relative_jump(program_counter: &mut u16, address_offset: i8) {
 
 /// # call
 /// call to nn, SP=SP-2, (SP)=PC, PC=nn
-pub fn call(program_counter: &mut u16, address: u16, stack: &mut IOMMU, reg_sp: &mut u16) {
-    let next_pc = *program_counter + 2;
+pub fn call(program_counter: &mut u16, address: u16, stack: &mut Iommu, reg_sp: &mut u16) {
+    let next_pc = program_counter.wrapping_add(2);
     push(stack, reg_sp, next_pc);
     jump_to(program_counter, address);
 }
 
 /// # ret
 /// return, PC=(SP), SP=SP+2
-pub fn ret(program_counter: &mut u16, stack: &mut IOMMU, reg_sp: &mut u16) {
+pub fn ret(program_counter: &mut u16, stack: &mut Iommu, reg_sp: &mut u16) {
     let old_pc = pop(stack, reg_sp);
     *program_counter = old_pc;
 }
 
 /// # rst
 /// reset, call to 0x0000, 0x0008, 0x0010, 0x0018, 0x0020, 0x0028, 0x0030, 0x0038
-pub fn rst(rst_index: usize, program_counter: &mut u16, stack: &mut IOMMU, reg_sp: &mut u16) {
+pub fn rst(rst_index: usize, program_counter: &mut u16, stack: &mut Iommu, reg_sp: &mut u16) {
     push(stack, reg_sp, *program_counter);
     jump_to(program_counter, RESET_VECTOR_ADDRESS[rst_index]);
 }
@@ -56,8 +52,10 @@ mod ut {
 
     #[test]
     fn jump_to_test() {
-        let mut register = Registers::default();
-        register.pc = 0xAAAA;
+        let mut register = Registers {
+            pc: 0xAAAA,
+            ..Default::default()
+        };
 
         jump_to(&mut register.pc, 0xBBCC);
 
@@ -66,8 +64,10 @@ mod ut {
 
     #[test]
     fn relative_jump_test() {
-        let mut register = Registers::default();
-        register.pc = 1234;
+        let mut register = Registers {
+            pc: 1234,
+            ..Default::default()
+        };
 
         relative_jump(&mut register.pc, -100);
         assert_eq!(1134, register.pc);
@@ -86,7 +86,7 @@ mod ut {
         let cartridge = Rc::new(RefCell::new(Cartridge::default()));
         let joypad = Rc::new(RefCell::new(JoypadInput::default()));
         let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
-        let mut iommu = IOMMU::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
 
         call(&mut register.pc, 500, &mut iommu, &mut register.sp);
         assert_eq!(500, register.pc);
@@ -97,18 +97,20 @@ mod ut {
 
     #[test]
     fn rst_test() {
-        let mut register = Registers::default();
-        register.pc = 0xAAAA;
-        register.sp = 0xFFFE;
+        let mut register = Registers {
+            pc: 0xAAAA,
+            sp: 0xFFFE,
+            ..Default::default()
+        };
 
         let cartridge = Rc::new(RefCell::new(Cartridge::default()));
         let joypad = Rc::new(RefCell::new(JoypadInput::default()));
         let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
-        let mut iommu = IOMMU::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
 
-        for index in 0..RESET_VECTOR_ADDRESS.len() {
+        for (index, address) in RESET_VECTOR_ADDRESS.iter().enumerate() {
             rst(index, &mut register.pc, &mut iommu, &mut register.sp);
-            assert_eq!(RESET_VECTOR_ADDRESS[index], register.pc);
+            assert_eq!(*address, register.pc);
         }
     }
 }
@@ -1,17 +1,17 @@
+use crate::cpu::Cpu;
+
 pub mod arithmetic_logic;
 pub mod cpu_control;
 pub mod jump;
 pub mod load;
+pub mod opcode_table;
 pub mod rotate_and_shift;
 pub mod single_bit_operation;
 
-pub fn is_supported(looking_opcode: u8, opcode_array: &[u8]) -> bool {
-    let result = opcode_array
-        .iter()
-        .position(|&element| looking_opcode == element);
-
-    if result.is_some() {
-        return true;
-    }
-    false
+/// # Instruction
+/// Decode table entry: handler, mnemonic and base cycles of the opcode
+pub struct Instruction {
+    pub mnemonic: &'static str,
+    pub cycles: u32,
+    pub handler: fn(&mut Cpu, u8),
 }
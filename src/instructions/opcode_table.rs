@@ -0,0 +1,569 @@
+use super::Instruction;
+use crate::cpu::Cpu;
+
+type Handler = fn(&mut Cpu, u8);
+
+const fn instruction(mnemonic: &'static str, cycles: u32, handler: Handler) -> Instruction {
+    Instruction {
+        mnemonic,
+        cycles,
+        handler,
+    }
+}
+
+/// # OPCODE_TABLE
+/// Base instruction set. Cycles of conditional instructions are given for the not taken branch.
+///
+/// Operands: d8 - immediate byte, d16 - immediate word, a8 - 0xFF00 + byte address,
+/// a16 - word address, r8 - signed offset
+pub static OPCODE_TABLE: [Instruction; 256] = [
+    /* 0x00 */ instruction("NOP", 4, Cpu::nop),
+    /* 0x01 */ instruction("LD BC,d16", 12, Cpu::ld_r16_d16),
+    /* 0x02 */ instruction("LD (BC),A", 8, Cpu::ld_indirect_a),
+    /* 0x03 */ instruction("INC BC", 8, Cpu::inc_16),
+    /* 0x04 */ instruction("INC B", 4, Cpu::inc),
+    /* 0x05 */ instruction("DEC B", 4, Cpu::dec),
+    /* 0x06 */ instruction("LD B,d8", 8, Cpu::ld_r8_d8),
+    /* 0x07 */ instruction("RLCA", 4, Cpu::rlca),
+    /* 0x08 */ instruction("LD (a16),SP", 20, Cpu::ld_a16_sp),
+    /* 0x09 */ instruction("ADD HL,BC", 8, Cpu::add_hl),
+    /* 0x0A */ instruction("LD A,(BC)", 8, Cpu::ld_a_indirect),
+    /* 0x0B */ instruction("DEC BC", 8, Cpu::dec_16),
+    /* 0x0C */ instruction("INC C", 4, Cpu::inc),
+    /* 0x0D */ instruction("DEC C", 4, Cpu::dec),
+    /* 0x0E */ instruction("LD C,d8", 8, Cpu::ld_r8_d8),
+    /* 0x0F */ instruction("RRCA", 4, Cpu::rrca),
+    /* 0x10 */ instruction("STOP", 4, Cpu::stop),
+    /* 0x11 */ instruction("LD DE,d16", 12, Cpu::ld_r16_d16),
+    /* 0x12 */ instruction("LD (DE),A", 8, Cpu::ld_indirect_a),
+    /* 0x13 */ instruction("INC DE", 8, Cpu::inc_16),
+    /* 0x14 */ instruction("INC D", 4, Cpu::inc),
+    /* 0x15 */ instruction("DEC D", 4, Cpu::dec),
+    /* 0x16 */ instruction("LD D,d8", 8, Cpu::ld_r8_d8),
+    /* 0x17 */ instruction("RLA", 4, Cpu::rla),
+    /* 0x18 */ instruction("JR r8", 12, Cpu::jr),
+    /* 0x19 */ instruction("ADD HL,DE", 8, Cpu::add_hl),
+    /* 0x1A */ instruction("LD A,(DE)", 8, Cpu::ld_a_indirect),
+    /* 0x1B */ instruction("DEC DE", 8, Cpu::dec_16),
+    /* 0x1C */ instruction("INC E", 4, Cpu::inc),
+    /* 0x1D */ instruction("DEC E", 4, Cpu::dec),
+    /* 0x1E */ instruction("LD E,d8", 8, Cpu::ld_r8_d8),
+    /* 0x1F */ instruction("RRA", 4, Cpu::rra),
+    /* 0x20 */ instruction("JR NZ,r8", 8, Cpu::jr_cc),
+    /* 0x21 */ instruction("LD HL,d16", 12, Cpu::ld_r16_d16),
+    /* 0x22 */ instruction("LD (HL+),A", 8, Cpu::ld_indirect_a),
+    /* 0x23 */ instruction("INC HL", 8, Cpu::inc_16),
+    /* 0x24 */ instruction("INC H", 4, Cpu::inc),
+    /* 0x25 */ instruction("DEC H", 4, Cpu::dec),
+    /* 0x26 */ instruction("LD H,d8", 8, Cpu::ld_r8_d8),
+    /* 0x27 */ instruction("DAA", 4, Cpu::daa),
+    /* 0x28 */ instruction("JR Z,r8", 8, Cpu::jr_cc),
+    /* 0x29 */ instruction("ADD HL,HL", 8, Cpu::add_hl),
+    /* 0x2A */ instruction("LD A,(HL+)", 8, Cpu::ld_a_indirect),
+    /* 0x2B */ instruction("DEC HL", 8, Cpu::dec_16),
+    /* 0x2C */ instruction("INC L", 4, Cpu::inc),
+    /* 0x2D */ instruction("DEC L", 4, Cpu::dec),
+    /* 0x2E */ instruction("LD L,d8", 8, Cpu::ld_r8_d8),
+    /* 0x2F */ instruction("CPL", 4, Cpu::cpl),
+    /* 0x30 */ instruction("JR NC,r8", 8, Cpu::jr_cc),
+    /* 0x31 */ instruction("LD SP,d16", 12, Cpu::ld_r16_d16),
+    /* 0x32 */ instruction("LD (HL-),A", 8, Cpu::ld_indirect_a),
+    /* 0x33 */ instruction("INC SP", 8, Cpu::inc_16),
+    /* 0x34 */ instruction("INC (HL)", 12, Cpu::inc),
+    /* 0x35 */ instruction("DEC (HL)", 12, Cpu::dec),
+    /* 0x36 */ instruction("LD (HL),d8", 12, Cpu::ld_r8_d8),
+    /* 0x37 */ instruction("SCF", 4, Cpu::scf),
+    /* 0x38 */ instruction("JR C,r8", 8, Cpu::jr_cc),
+    /* 0x39 */ instruction("ADD HL,SP", 8, Cpu::add_hl),
+    /* 0x3A */ instruction("LD A,(HL-)", 8, Cpu::ld_a_indirect),
+    /* 0x3B */ instruction("DEC SP", 8, Cpu::dec_16),
+    /* 0x3C */ instruction("INC A", 4, Cpu::inc),
+    /* 0x3D */ instruction("DEC A", 4, Cpu::dec),
+    /* 0x3E */ instruction("LD A,d8", 8, Cpu::ld_r8_d8),
+    /* 0x3F */ instruction("CCF", 4, Cpu::ccf),
+    /* 0x40 */ instruction("LD B,B", 4, Cpu::ld_r8),
+    /* 0x41 */ instruction("LD B,C", 4, Cpu::ld_r8),
+    /* 0x42 */ instruction("LD B,D", 4, Cpu::ld_r8),
+    /* 0x43 */ instruction("LD B,E", 4, Cpu::ld_r8),
+    /* 0x44 */ instruction("LD B,H", 4, Cpu::ld_r8),
+    /* 0x45 */ instruction("LD B,L", 4, Cpu::ld_r8),
+    /* 0x46 */ instruction("LD B,(HL)", 8, Cpu::ld_r8),
+    /* 0x47 */ instruction("LD B,A", 4, Cpu::ld_r8),
+    /* 0x48 */ instruction("LD C,B", 4, Cpu::ld_r8),
+    /* 0x49 */ instruction("LD C,C", 4, Cpu::ld_r8),
+    /* 0x4A */ instruction("LD C,D", 4, Cpu::ld_r8),
+    /* 0x4B */ instruction("LD C,E", 4, Cpu::ld_r8),
+    /* 0x4C */ instruction("LD C,H", 4, Cpu::ld_r8),
+    /* 0x4D */ instruction("LD C,L", 4, Cpu::ld_r8),
+    /* 0x4E */ instruction("LD C,(HL)", 8, Cpu::ld_r8),
+    /* 0x4F */ instruction("LD C,A", 4, Cpu::ld_r8),
+    /* 0x50 */ instruction("LD D,B", 4, Cpu::ld_r8),
+    /* 0x51 */ instruction("LD D,C", 4, Cpu::ld_r8),
+    /* 0x52 */ instruction("LD D,D", 4, Cpu::ld_r8),
+    /* 0x53 */ instruction("LD D,E", 4, Cpu::ld_r8),
+    /* 0x54 */ instruction("LD D,H", 4, Cpu::ld_r8),
+    /* 0x55 */ instruction("LD D,L", 4, Cpu::ld_r8),
+    /* 0x56 */ instruction("LD D,(HL)", 8, Cpu::ld_r8),
+    /* 0x57 */ instruction("LD D,A", 4, Cpu::ld_r8),
+    /* 0x58 */ instruction("LD E,B", 4, Cpu::ld_r8),
+    /* 0x59 */ instruction("LD E,C", 4, Cpu::ld_r8),
+    /* 0x5A */ instruction("LD E,D", 4, Cpu::ld_r8),
+    /* 0x5B */ instruction("LD E,E", 4, Cpu::ld_r8),
+    /* 0x5C */ instruction("LD E,H", 4, Cpu::ld_r8),
+    /* 0x5D */ instruction("LD E,L", 4, Cpu::ld_r8),
+    /* 0x5E */ instruction("LD E,(HL)", 8, Cpu::ld_r8),
+    /* 0x5F */ instruction("LD E,A", 4, Cpu::ld_r8),
+    /* 0x60 */ instruction("LD H,B", 4, Cpu::ld_r8),
+    /* 0x61 */ instruction("LD H,C", 4, Cpu::ld_r8),
+    /* 0x62 */ instruction("LD H,D", 4, Cpu::ld_r8),
+    /* 0x63 */ instruction("LD H,E", 4, Cpu::ld_r8),
+    /* 0x64 */ instruction("LD H,H", 4, Cpu::ld_r8),
+    /* 0x65 */ instruction("LD H,L", 4, Cpu::ld_r8),
+    /* 0x66 */ instruction("LD H,(HL)", 8, Cpu::ld_r8),
+    /* 0x67 */ instruction("LD H,A", 4, Cpu::ld_r8),
+    /* 0x68 */ instruction("LD L,B", 4, Cpu::ld_r8),
+    /* 0x69 */ instruction("LD L,C", 4, Cpu::ld_r8),
+    /* 0x6A */ instruction("LD L,D", 4, Cpu::ld_r8),
+    /* 0x6B */ instruction("LD L,E", 4, Cpu::ld_r8),
+    /* 0x6C */ instruction("LD L,H", 4, Cpu::ld_r8),
+    /* 0x6D */ instruction("LD L,L", 4, Cpu::ld_r8),
+    /* 0x6E */ instruction("LD L,(HL)", 8, Cpu::ld_r8),
+    /* 0x6F */ instruction("LD L,A", 4, Cpu::ld_r8),
+    /* 0x70 */ instruction("LD (HL),B", 8, Cpu::ld_r8),
+    /* 0x71 */ instruction("LD (HL),C", 8, Cpu::ld_r8),
+    /* 0x72 */ instruction("LD (HL),D", 8, Cpu::ld_r8),
+    /* 0x73 */ instruction("LD (HL),E", 8, Cpu::ld_r8),
+    /* 0x74 */ instruction("LD (HL),H", 8, Cpu::ld_r8),
+    /* 0x75 */ instruction("LD (HL),L", 8, Cpu::ld_r8),
+    /* 0x76 */ instruction("HALT", 4, Cpu::halt),
+    /* 0x77 */ instruction("LD (HL),A", 8, Cpu::ld_r8),
+    /* 0x78 */ instruction("LD A,B", 4, Cpu::ld_r8),
+    /* 0x79 */ instruction("LD A,C", 4, Cpu::ld_r8),
+    /* 0x7A */ instruction("LD A,D", 4, Cpu::ld_r8),
+    /* 0x7B */ instruction("LD A,E", 4, Cpu::ld_r8),
+    /* 0x7C */ instruction("LD A,H", 4, Cpu::ld_r8),
+    /* 0x7D */ instruction("LD A,L", 4, Cpu::ld_r8),
+    /* 0x7E */ instruction("LD A,(HL)", 8, Cpu::ld_r8),
+    /* 0x7F */ instruction("LD A,A", 4, Cpu::ld_r8),
+    /* 0x80 */ instruction("ADD A,B", 4, Cpu::add),
+    /* 0x81 */ instruction("ADD A,C", 4, Cpu::add),
+    /* 0x82 */ instruction("ADD A,D", 4, Cpu::add),
+    /* 0x83 */ instruction("ADD A,E", 4, Cpu::add),
+    /* 0x84 */ instruction("ADD A,H", 4, Cpu::add),
+    /* 0x85 */ instruction("ADD A,L", 4, Cpu::add),
+    /* 0x86 */ instruction("ADD A,(HL)", 8, Cpu::add),
+    /* 0x87 */ instruction("ADD A,A", 4, Cpu::add),
+    /* 0x88 */ instruction("ADC A,B", 4, Cpu::adc),
+    /* 0x89 */ instruction("ADC A,C", 4, Cpu::adc),
+    /* 0x8A */ instruction("ADC A,D", 4, Cpu::adc),
+    /* 0x8B */ instruction("ADC A,E", 4, Cpu::adc),
+    /* 0x8C */ instruction("ADC A,H", 4, Cpu::adc),
+    /* 0x8D */ instruction("ADC A,L", 4, Cpu::adc),
+    /* 0x8E */ instruction("ADC A,(HL)", 8, Cpu::adc),
+    /* 0x8F */ instruction("ADC A,A", 4, Cpu::adc),
+    /* 0x90 */ instruction("SUB B", 4, Cpu::sub),
+    /* 0x91 */ instruction("SUB C", 4, Cpu::sub),
+    /* 0x92 */ instruction("SUB D", 4, Cpu::sub),
+    /* 0x93 */ instruction("SUB E", 4, Cpu::sub),
+    /* 0x94 */ instruction("SUB H", 4, Cpu::sub),
+    /* 0x95 */ instruction("SUB L", 4, Cpu::sub),
+    /* 0x96 */ instruction("SUB (HL)", 8, Cpu::sub),
+    /* 0x97 */ instruction("SUB A", 4, Cpu::sub),
+    /* 0x98 */ instruction("SBC A,B", 4, Cpu::sbc),
+    /* 0x99 */ instruction("SBC A,C", 4, Cpu::sbc),
+    /* 0x9A */ instruction("SBC A,D", 4, Cpu::sbc),
+    /* 0x9B */ instruction("SBC A,E", 4, Cpu::sbc),
+    /* 0x9C */ instruction("SBC A,H", 4, Cpu::sbc),
+    /* 0x9D */ instruction("SBC A,L", 4, Cpu::sbc),
+    /* 0x9E */ instruction("SBC A,(HL)", 8, Cpu::sbc),
+    /* 0x9F */ instruction("SBC A,A", 4, Cpu::sbc),
+    /* 0xA0 */ instruction("AND B", 4, Cpu::and),
+    /* 0xA1 */ instruction("AND C", 4, Cpu::and),
+    /* 0xA2 */ instruction("AND D", 4, Cpu::and),
+    /* 0xA3 */ instruction("AND E", 4, Cpu::and),
+    /* 0xA4 */ instruction("AND H", 4, Cpu::and),
+    /* 0xA5 */ instruction("AND L", 4, Cpu::and),
+    /* 0xA6 */ instruction("AND (HL)", 8, Cpu::and),
+    /* 0xA7 */ instruction("AND A", 4, Cpu::and),
+    /* 0xA8 */ instruction("XOR B", 4, Cpu::xor),
+    /* 0xA9 */ instruction("XOR C", 4, Cpu::xor),
+    /* 0xAA */ instruction("XOR D", 4, Cpu::xor),
+    /* 0xAB */ instruction("XOR E", 4, Cpu::xor),
+    /* 0xAC */ instruction("XOR H", 4, Cpu::xor),
+    /* 0xAD */ instruction("XOR L", 4, Cpu::xor),
+    /* 0xAE */ instruction("XOR (HL)", 8, Cpu::xor),
+    /* 0xAF */ instruction("XOR A", 4, Cpu::xor),
+    /* 0xB0 */ instruction("OR B", 4, Cpu::or),
+    /* 0xB1 */ instruction("OR C", 4, Cpu::or),
+    /* 0xB2 */ instruction("OR D", 4, Cpu::or),
+    /* 0xB3 */ instruction("OR E", 4, Cpu::or),
+    /* 0xB4 */ instruction("OR H", 4, Cpu::or),
+    /* 0xB5 */ instruction("OR L", 4, Cpu::or),
+    /* 0xB6 */ instruction("OR (HL)", 8, Cpu::or),
+    /* 0xB7 */ instruction("OR A", 4, Cpu::or),
+    /* 0xB8 */ instruction("CP B", 4, Cpu::cp),
+    /* 0xB9 */ instruction("CP C", 4, Cpu::cp),
+    /* 0xBA */ instruction("CP D", 4, Cpu::cp),
+    /* 0xBB */ instruction("CP E", 4, Cpu::cp),
+    /* 0xBC */ instruction("CP H", 4, Cpu::cp),
+    /* 0xBD */ instruction("CP L", 4, Cpu::cp),
+    /* 0xBE */ instruction("CP (HL)", 8, Cpu::cp),
+    /* 0xBF */ instruction("CP A", 4, Cpu::cp),
+    /* 0xC0 */ instruction("RET NZ", 8, Cpu::ret_cc),
+    /* 0xC1 */ instruction("POP BC", 12, Cpu::pop),
+    /* 0xC2 */ instruction("JP NZ,a16", 12, Cpu::jp_cc),
+    /* 0xC3 */ instruction("JP a16", 16, Cpu::jp),
+    /* 0xC4 */ instruction("CALL NZ,a16", 12, Cpu::call_cc),
+    /* 0xC5 */ instruction("PUSH BC", 16, Cpu::push),
+    /* 0xC6 */ instruction("ADD A,d8", 8, Cpu::add),
+    /* 0xC7 */ instruction("RST 00H", 16, Cpu::rst),
+    /* 0xC8 */ instruction("RET Z", 8, Cpu::ret_cc),
+    /* 0xC9 */ instruction("RET", 16, Cpu::ret),
+    /* 0xCA */ instruction("JP Z,a16", 12, Cpu::jp_cc),
+    /* 0xCB */ instruction("PREFIX CB", 4, Cpu::execute_cbprefixed_instruction),
+    /* 0xCC */ instruction("CALL Z,a16", 12, Cpu::call_cc),
+    /* 0xCD */ instruction("CALL a16", 24, Cpu::call),
+    /* 0xCE */ instruction("ADC A,d8", 8, Cpu::adc),
+    /* 0xCF */ instruction("RST 08H", 16, Cpu::rst),
+    /* 0xD0 */ instruction("RET NC", 8, Cpu::ret_cc),
+    /* 0xD1 */ instruction("POP DE", 12, Cpu::pop),
+    /* 0xD2 */ instruction("JP NC,a16", 12, Cpu::jp_cc),
+    /* 0xD3 */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xD4 */ instruction("CALL NC,a16", 12, Cpu::call_cc),
+    /* 0xD5 */ instruction("PUSH DE", 16, Cpu::push),
+    /* 0xD6 */ instruction("SUB d8", 8, Cpu::sub),
+    /* 0xD7 */ instruction("RST 10H", 16, Cpu::rst),
+    /* 0xD8 */ instruction("RET C", 8, Cpu::ret_cc),
+    /* 0xD9 */ instruction("RETI", 16, Cpu::reti),
+    /* 0xDA */ instruction("JP C,a16", 12, Cpu::jp_cc),
+    /* 0xDB */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xDC */ instruction("CALL C,a16", 12, Cpu::call_cc),
+    /* 0xDD */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xDE */ instruction("SBC A,d8", 8, Cpu::sbc),
+    /* 0xDF */ instruction("RST 18H", 16, Cpu::rst),
+    /* 0xE0 */ instruction("LDH (a8),A", 12, Cpu::ldh_a8_a),
+    /* 0xE1 */ instruction("POP HL", 12, Cpu::pop),
+    /* 0xE2 */ instruction("LD (C),A", 8, Cpu::ldh_c_a),
+    /* 0xE3 */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xE4 */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xE5 */ instruction("PUSH HL", 16, Cpu::push),
+    /* 0xE6 */ instruction("AND d8", 8, Cpu::and),
+    /* 0xE7 */ instruction("RST 20H", 16, Cpu::rst),
+    /* 0xE8 */ instruction("ADD SP,r8", 16, Cpu::add_sp),
+    /* 0xE9 */ instruction("JP HL", 4, Cpu::jp_hl),
+    /* 0xEA */ instruction("LD (a16),A", 16, Cpu::ld_a16_a),
+    /* 0xEB */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xEC */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xED */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xEE */ instruction("XOR d8", 8, Cpu::xor),
+    /* 0xEF */ instruction("RST 28H", 16, Cpu::rst),
+    /* 0xF0 */ instruction("LDH A,(a8)", 12, Cpu::ldh_a_a8),
+    /* 0xF1 */ instruction("POP AF", 12, Cpu::pop),
+    /* 0xF2 */ instruction("LD A,(C)", 8, Cpu::ldh_a_c),
+    /* 0xF3 */ instruction("DI", 4, Cpu::di),
+    /* 0xF4 */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xF5 */ instruction("PUSH AF", 16, Cpu::push),
+    /* 0xF6 */ instruction("OR d8", 8, Cpu::or),
+    /* 0xF7 */ instruction("RST 30H", 16, Cpu::rst),
+    /* 0xF8 */ instruction("LD HL,SP+r8", 12, Cpu::ld_hl_sp),
+    /* 0xF9 */ instruction("LD SP,HL", 8, Cpu::ld_sp_hl),
+    /* 0xFA */ instruction("LD A,(a16)", 16, Cpu::ld_a_a16),
+    /* 0xFB */ instruction("EI", 4, Cpu::ei),
+    /* 0xFC */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xFD */ instruction("ILLEGAL", 4, Cpu::illegal_instruction),
+    /* 0xFE */ instruction("CP d8", 8, Cpu::cp),
+    /* 0xFF */ instruction("RST 38H", 16, Cpu::rst),
+];
+
+/// # CB_OPCODE_TABLE
+/// Instructions prefixed with 0xCB. Cycles include the prefix fetch.
+pub static CB_OPCODE_TABLE: [Instruction; 256] = [
+    /* 0x00 */ instruction("RLC B", 8, Cpu::rlc),
+    /* 0x01 */ instruction("RLC C", 8, Cpu::rlc),
+    /* 0x02 */ instruction("RLC D", 8, Cpu::rlc),
+    /* 0x03 */ instruction("RLC E", 8, Cpu::rlc),
+    /* 0x04 */ instruction("RLC H", 8, Cpu::rlc),
+    /* 0x05 */ instruction("RLC L", 8, Cpu::rlc),
+    /* 0x06 */ instruction("RLC (HL)", 16, Cpu::rlc),
+    /* 0x07 */ instruction("RLC A", 8, Cpu::rlc),
+    /* 0x08 */ instruction("RRC B", 8, Cpu::rrc),
+    /* 0x09 */ instruction("RRC C", 8, Cpu::rrc),
+    /* 0x0A */ instruction("RRC D", 8, Cpu::rrc),
+    /* 0x0B */ instruction("RRC E", 8, Cpu::rrc),
+    /* 0x0C */ instruction("RRC H", 8, Cpu::rrc),
+    /* 0x0D */ instruction("RRC L", 8, Cpu::rrc),
+    /* 0x0E */ instruction("RRC (HL)", 16, Cpu::rrc),
+    /* 0x0F */ instruction("RRC A", 8, Cpu::rrc),
+    /* 0x10 */ instruction("RL B", 8, Cpu::rl),
+    /* 0x11 */ instruction("RL C", 8, Cpu::rl),
+    /* 0x12 */ instruction("RL D", 8, Cpu::rl),
+    /* 0x13 */ instruction("RL E", 8, Cpu::rl),
+    /* 0x14 */ instruction("RL H", 8, Cpu::rl),
+    /* 0x15 */ instruction("RL L", 8, Cpu::rl),
+    /* 0x16 */ instruction("RL (HL)", 16, Cpu::rl),
+    /* 0x17 */ instruction("RL A", 8, Cpu::rl),
+    /* 0x18 */ instruction("RR B", 8, Cpu::rr),
+    /* 0x19 */ instruction("RR C", 8, Cpu::rr),
+    /* 0x1A */ instruction("RR D", 8, Cpu::rr),
+    /* 0x1B */ instruction("RR E", 8, Cpu::rr),
+    /* 0x1C */ instruction("RR H", 8, Cpu::rr),
+    /* 0x1D */ instruction("RR L", 8, Cpu::rr),
+    /* 0x1E */ instruction("RR (HL)", 16, Cpu::rr),
+    /* 0x1F */ instruction("RR A", 8, Cpu::rr),
+    /* 0x20 */ instruction("SLA B", 8, Cpu::sla),
+    /* 0x21 */ instruction("SLA C", 8, Cpu::sla),
+    /* 0x22 */ instruction("SLA D", 8, Cpu::sla),
+    /* 0x23 */ instruction("SLA E", 8, Cpu::sla),
+    /* 0x24 */ instruction("SLA H", 8, Cpu::sla),
+    /* 0x25 */ instruction("SLA L", 8, Cpu::sla),
+    /* 0x26 */ instruction("SLA (HL)", 16, Cpu::sla),
+    /* 0x27 */ instruction("SLA A", 8, Cpu::sla),
+    /* 0x28 */ instruction("SRA B", 8, Cpu::sra),
+    /* 0x29 */ instruction("SRA C", 8, Cpu::sra),
+    /* 0x2A */ instruction("SRA D", 8, Cpu::sra),
+    /* 0x2B */ instruction("SRA E", 8, Cpu::sra),
+    /* 0x2C */ instruction("SRA H", 8, Cpu::sra),
+    /* 0x2D */ instruction("SRA L", 8, Cpu::sra),
+    /* 0x2E */ instruction("SRA (HL)", 16, Cpu::sra),
+    /* 0x2F */ instruction("SRA A", 8, Cpu::sra),
+    /* 0x30 */ instruction("SWAP B", 8, Cpu::swap),
+    /* 0x31 */ instruction("SWAP C", 8, Cpu::swap),
+    /* 0x32 */ instruction("SWAP D", 8, Cpu::swap),
+    /* 0x33 */ instruction("SWAP E", 8, Cpu::swap),
+    /* 0x34 */ instruction("SWAP H", 8, Cpu::swap),
+    /* 0x35 */ instruction("SWAP L", 8, Cpu::swap),
+    /* 0x36 */ instruction("SWAP (HL)", 16, Cpu::swap),
+    /* 0x37 */ instruction("SWAP A", 8, Cpu::swap),
+    /* 0x38 */ instruction("SRL B", 8, Cpu::srl),
+    /* 0x39 */ instruction("SRL C", 8, Cpu::srl),
+    /* 0x3A */ instruction("SRL D", 8, Cpu::srl),
+    /* 0x3B */ instruction("SRL E", 8, Cpu::srl),
+    /* 0x3C */ instruction("SRL H", 8, Cpu::srl),
+    /* 0x3D */ instruction("SRL L", 8, Cpu::srl),
+    /* 0x3E */ instruction("SRL (HL)", 16, Cpu::srl),
+    /* 0x3F */ instruction("SRL A", 8, Cpu::srl),
+    /* 0x40 */ instruction("BIT 0,B", 8, Cpu::bit),
+    /* 0x41 */ instruction("BIT 0,C", 8, Cpu::bit),
+    /* 0x42 */ instruction("BIT 0,D", 8, Cpu::bit),
+    /* 0x43 */ instruction("BIT 0,E", 8, Cpu::bit),
+    /* 0x44 */ instruction("BIT 0,H", 8, Cpu::bit),
+    /* 0x45 */ instruction("BIT 0,L", 8, Cpu::bit),
+    /* 0x46 */ instruction("BIT 0,(HL)", 12, Cpu::bit),
+    /* 0x47 */ instruction("BIT 0,A", 8, Cpu::bit),
+    /* 0x48 */ instruction("BIT 1,B", 8, Cpu::bit),
+    /* 0x49 */ instruction("BIT 1,C", 8, Cpu::bit),
+    /* 0x4A */ instruction("BIT 1,D", 8, Cpu::bit),
+    /* 0x4B */ instruction("BIT 1,E", 8, Cpu::bit),
+    /* 0x4C */ instruction("BIT 1,H", 8, Cpu::bit),
+    /* 0x4D */ instruction("BIT 1,L", 8, Cpu::bit),
+    /* 0x4E */ instruction("BIT 1,(HL)", 12, Cpu::bit),
+    /* 0x4F */ instruction("BIT 1,A", 8, Cpu::bit),
+    /* 0x50 */ instruction("BIT 2,B", 8, Cpu::bit),
+    /* 0x51 */ instruction("BIT 2,C", 8, Cpu::bit),
+    /* 0x52 */ instruction("BIT 2,D", 8, Cpu::bit),
+    /* 0x53 */ instruction("BIT 2,E", 8, Cpu::bit),
+    /* 0x54 */ instruction("BIT 2,H", 8, Cpu::bit),
+    /* 0x55 */ instruction("BIT 2,L", 8, Cpu::bit),
+    /* 0x56 */ instruction("BIT 2,(HL)", 12, Cpu::bit),
+    /* 0x57 */ instruction("BIT 2,A", 8, Cpu::bit),
+    /* 0x58 */ instruction("BIT 3,B", 8, Cpu::bit),
+    /* 0x59 */ instruction("BIT 3,C", 8, Cpu::bit),
+    /* 0x5A */ instruction("BIT 3,D", 8, Cpu::bit),
+    /* 0x5B */ instruction("BIT 3,E", 8, Cpu::bit),
+    /* 0x5C */ instruction("BIT 3,H", 8, Cpu::bit),
+    /* 0x5D */ instruction("BIT 3,L", 8, Cpu::bit),
+    /* 0x5E */ instruction("BIT 3,(HL)", 12, Cpu::bit),
+    /* 0x5F */ instruction("BIT 3,A", 8, Cpu::bit),
+    /* 0x60 */ instruction("BIT 4,B", 8, Cpu::bit),
+    /* 0x61 */ instruction("BIT 4,C", 8, Cpu::bit),
+    /* 0x62 */ instruction("BIT 4,D", 8, Cpu::bit),
+    /* 0x63 */ instruction("BIT 4,E", 8, Cpu::bit),
+    /* 0x64 */ instruction("BIT 4,H", 8, Cpu::bit),
+    /* 0x65 */ instruction("BIT 4,L", 8, Cpu::bit),
+    /* 0x66 */ instruction("BIT 4,(HL)", 12, Cpu::bit),
+    /* 0x67 */ instruction("BIT 4,A", 8, Cpu::bit),
+    /* 0x68 */ instruction("BIT 5,B", 8, Cpu::bit),
+    /* 0x69 */ instruction("BIT 5,C", 8, Cpu::bit),
+    /* 0x6A */ instruction("BIT 5,D", 8, Cpu::bit),
+    /* 0x6B */ instruction("BIT 5,E", 8, Cpu::bit),
+    /* 0x6C */ instruction("BIT 5,H", 8, Cpu::bit),
+    /* 0x6D */ instruction("BIT 5,L", 8, Cpu::bit),
+    /* 0x6E */ instruction("BIT 5,(HL)", 12, Cpu::bit),
+    /* 0x6F */ instruction("BIT 5,A", 8, Cpu::bit),
+    /* 0x70 */ instruction("BIT 6,B", 8, Cpu::bit),
+    /* 0x71 */ instruction("BIT 6,C", 8, Cpu::bit),
+    /* 0x72 */ instruction("BIT 6,D", 8, Cpu::bit),
+    /* 0x73 */ instruction("BIT 6,E", 8, Cpu::bit),
+    /* 0x74 */ instruction("BIT 6,H", 8, Cpu::bit),
+    /* 0x75 */ instruction("BIT 6,L", 8, Cpu::bit),
+    /* 0x76 */ instruction("BIT 6,(HL)", 12, Cpu::bit),
+    /* 0x77 */ instruction("BIT 6,A", 8, Cpu::bit),
+    /* 0x78 */ instruction("BIT 7,B", 8, Cpu::bit),
+    /* 0x79 */ instruction("BIT 7,C", 8, Cpu::bit),
+    /* 0x7A */ instruction("BIT 7,D", 8, Cpu::bit),
+    /* 0x7B */ instruction("BIT 7,E", 8, Cpu::bit),
+    /* 0x7C */ instruction("BIT 7,H", 8, Cpu::bit),
+    /* 0x7D */ instruction("BIT 7,L", 8, Cpu::bit),
+    /* 0x7E */ instruction("BIT 7,(HL)", 12, Cpu::bit),
+    /* 0x7F */ instruction("BIT 7,A", 8, Cpu::bit),
+    /* 0x80 */ instruction("RES 0,B", 8, Cpu::res),
+    /* 0x81 */ instruction("RES 0,C", 8, Cpu::res),
+    /* 0x82 */ instruction("RES 0,D", 8, Cpu::res),
+    /* 0x83 */ instruction("RES 0,E", 8, Cpu::res),
+    /* 0x84 */ instruction("RES 0,H", 8, Cpu::res),
+    /* 0x85 */ instruction("RES 0,L", 8, Cpu::res),
+    /* 0x86 */ instruction("RES 0,(HL)", 16, Cpu::res),
+    /* 0x87 */ instruction("RES 0,A", 8, Cpu::res),
+    /* 0x88 */ instruction("RES 1,B", 8, Cpu::res),
+    /* 0x89 */ instruction("RES 1,C", 8, Cpu::res),
+    /* 0x8A */ instruction("RES 1,D", 8, Cpu::res),
+    /* 0x8B */ instruction("RES 1,E", 8, Cpu::res),
+    /* 0x8C */ instruction("RES 1,H", 8, Cpu::res),
+    /* 0x8D */ instruction("RES 1,L", 8, Cpu::res),
+    /* 0x8E */ instruction("RES 1,(HL)", 16, Cpu::res),
+    /* 0x8F */ instruction("RES 1,A", 8, Cpu::res),
+    /* 0x90 */ instruction("RES 2,B", 8, Cpu::res),
+    /* 0x91 */ instruction("RES 2,C", 8, Cpu::res),
+    /* 0x92 */ instruction("RES 2,D", 8, Cpu::res),
+    /* 0x93 */ instruction("RES 2,E", 8, Cpu::res),
+    /* 0x94 */ instruction("RES 2,H", 8, Cpu::res),
+    /* 0x95 */ instruction("RES 2,L", 8, Cpu::res),
+    /* 0x96 */ instruction("RES 2,(HL)", 16, Cpu::res),
+    /* 0x97 */ instruction("RES 2,A", 8, Cpu::res),
+    /* 0x98 */ instruction("RES 3,B", 8, Cpu::res),
+    /* 0x99 */ instruction("RES 3,C", 8, Cpu::res),
+    /* 0x9A */ instruction("RES 3,D", 8, Cpu::res),
+    /* 0x9B */ instruction("RES 3,E", 8, Cpu::res),
+    /* 0x9C */ instruction("RES 3,H", 8, Cpu::res),
+    /* 0x9D */ instruction("RES 3,L", 8, Cpu::res),
+    /* 0x9E */ instruction("RES 3,(HL)", 16, Cpu::res),
+    /* 0x9F */ instruction("RES 3,A", 8, Cpu::res),
+    /* 0xA0 */ instruction("RES 4,B", 8, Cpu::res),
+    /* 0xA1 */ instruction("RES 4,C", 8, Cpu::res),
+    /* 0xA2 */ instruction("RES 4,D", 8, Cpu::res),
+    /* 0xA3 */ instruction("RES 4,E", 8, Cpu::res),
+    /* 0xA4 */ instruction("RES 4,H", 8, Cpu::res),
+    /* 0xA5 */ instruction("RES 4,L", 8, Cpu::res),
+    /* 0xA6 */ instruction("RES 4,(HL)", 16, Cpu::res),
+    /* 0xA7 */ instruction("RES 4,A", 8, Cpu::res),
+    /* 0xA8 */ instruction("RES 5,B", 8, Cpu::res),
+    /* 0xA9 */ instruction("RES 5,C", 8, Cpu::res),
+    /* 0xAA */ instruction("RES 5,D", 8, Cpu::res),
+    /* 0xAB */ instruction("RES 5,E", 8, Cpu::res),
+    /* 0xAC */ instruction("RES 5,H", 8, Cpu::res),
+    /* 0xAD */ instruction("RES 5,L", 8, Cpu::res),
+    /* 0xAE */ instruction("RES 5,(HL)", 16, Cpu::res),
+    /* 0xAF */ instruction("RES 5,A", 8, Cpu::res),
+    /* 0xB0 */ instruction("RES 6,B", 8, Cpu::res),
+    /* 0xB1 */ instruction("RES 6,C", 8, Cpu::res),
+    /* 0xB2 */ instruction("RES 6,D", 8, Cpu::res),
+    /* 0xB3 */ instruction("RES 6,E", 8, Cpu::res),
+    /* 0xB4 */ instruction("RES 6,H", 8, Cpu::res),
+    /* 0xB5 */ instruction("RES 6,L", 8, Cpu::res),
+    /* 0xB6 */ instruction("RES 6,(HL)", 16, Cpu::res),
+    /* 0xB7 */ instruction("RES 6,A", 8, Cpu::res),
+    /* 0xB8 */ instruction("RES 7,B", 8, Cpu::res),
+    /* 0xB9 */ instruction("RES 7,C", 8, Cpu::res),
+    /* 0xBA */ instruction("RES 7,D", 8, Cpu::res),
+    /* 0xBB */ instruction("RES 7,E", 8, Cpu::res),
+    /* 0xBC */ instruction("RES 7,H", 8, Cpu::res),
+    /* 0xBD */ instruction("RES 7,L", 8, Cpu::res),
+    /* 0xBE */ instruction("RES 7,(HL)", 16, Cpu::res),
+    /* 0xBF */ instruction("RES 7,A", 8, Cpu::res),
+    /* 0xC0 */ instruction("SET 0,B", 8, Cpu::set),
+    /* 0xC1 */ instruction("SET 0,C", 8, Cpu::set),
+    /* 0xC2 */ instruction("SET 0,D", 8, Cpu::set),
+    /* 0xC3 */ instruction("SET 0,E", 8, Cpu::set),
+    /* 0xC4 */ instruction("SET 0,H", 8, Cpu::set),
+    /* 0xC5 */ instruction("SET 0,L", 8, Cpu::set),
+    /* 0xC6 */ instruction("SET 0,(HL)", 16, Cpu::set),
+    /* 0xC7 */ instruction("SET 0,A", 8, Cpu::set),
+    /* 0xC8 */ instruction("SET 1,B", 8, Cpu::set),
+    /* 0xC9 */ instruction("SET 1,C", 8, Cpu::set),
+    /* 0xCA */ instruction("SET 1,D", 8, Cpu::set),
+    /* 0xCB */ instruction("SET 1,E", 8, Cpu::set),
+    /* 0xCC */ instruction("SET 1,H", 8, Cpu::set),
+    /* 0xCD */ instruction("SET 1,L", 8, Cpu::set),
+    /* 0xCE */ instruction("SET 1,(HL)", 16, Cpu::set),
+    /* 0xCF */ instruction("SET 1,A", 8, Cpu::set),
+    /* 0xD0 */ instruction("SET 2,B", 8, Cpu::set),
+    /* 0xD1 */ instruction("SET 2,C", 8, Cpu::set),
+    /* 0xD2 */ instruction("SET 2,D", 8, Cpu::set),
+    /* 0xD3 */ instruction("SET 2,E", 8, Cpu::set),
+    /* 0xD4 */ instruction("SET 2,H", 8, Cpu::set),
+    /* 0xD5 */ instruction("SET 2,L", 8, Cpu::set),
+    /* 0xD6 */ instruction("SET 2,(HL)", 16, Cpu::set),
+    /* 0xD7 */ instruction("SET 2,A", 8, Cpu::set),
+    /* 0xD8 */ instruction("SET 3,B", 8, Cpu::set),
+    /* 0xD9 */ instruction("SET 3,C", 8, Cpu::set),
+    /* 0xDA */ instruction("SET 3,D", 8, Cpu::set),
+    /* 0xDB */ instruction("SET 3,E", 8, Cpu::set),
+    /* 0xDC */ instruction("SET 3,H", 8, Cpu::set),
+    /* 0xDD */ instruction("SET 3,L", 8, Cpu::set),
+    /* 0xDE */ instruction("SET 3,(HL)", 16, Cpu::set),
+    /* 0xDF */ instruction("SET 3,A", 8, Cpu::set),
+    /* 0xE0 */ instruction("SET 4,B", 8, Cpu::set),
+    /* 0xE1 */ instruction("SET 4,C", 8, Cpu::set),
+    /* 0xE2 */ instruction("SET 4,D", 8, Cpu::set),
+    /* 0xE3 */ instruction("SET 4,E", 8, Cpu::set),
+    /* 0xE4 */ instruction("SET 4,H", 8, Cpu::set),
+    /* 0xE5 */ instruction("SET 4,L", 8, Cpu::set),
+    /* 0xE6 */ instruction("SET 4,(HL)", 16, Cpu::set),
+    /* 0xE7 */ instruction("SET 4,A", 8, Cpu::set),
+    /* 0xE8 */ instruction("SET 5,B", 8, Cpu::set),
+    /* 0xE9 */ instruction("SET 5,C", 8, Cpu::set),
+    /* 0xEA */ instruction("SET 5,D", 8, Cpu::set),
+    /* 0xEB */ instruction("SET 5,E", 8, Cpu::set),
+    /* 0xEC */ instruction("SET 5,H", 8, Cpu::set),
+    /* 0xED */ instruction("SET 5,L", 8, Cpu::set),
+    /* 0xEE */ instruction("SET 5,(HL)", 16, Cpu::set),
+    /* 0xEF */ instruction("SET 5,A", 8, Cpu::set),
+    /* 0xF0 */ instruction("SET 6,B", 8, Cpu::set),
+    /* 0xF1 */ instruction("SET 6,C", 8, Cpu::set),
+    /* 0xF2 */ instruction("SET 6,D", 8, Cpu::set),
+    /* 0xF3 */ instruction("SET 6,E", 8, Cpu::set),
+    /* 0xF4 */ instruction("SET 6,H", 8, Cpu::set),
+    /* 0xF5 */ instruction("SET 6,L", 8, Cpu::set),
+    /* 0xF6 */ instruction("SET 6,(HL)", 16, Cpu::set),
+    /* 0xF7 */ instruction("SET 6,A", 8, Cpu::set),
+    /* 0xF8 */ instruction("SET 7,B", 8, Cpu::set),
+    /* 0xF9 */ instruction("SET 7,C", 8, Cpu::set),
+    /* 0xFA */ instruction("SET 7,D", 8, Cpu::set),
+    /* 0xFB */ instruction("SET 7,E", 8, Cpu::set),
+    /* 0xFC */ instruction("SET 7,H", 8, Cpu::set),
+    /* 0xFD */ instruction("SET 7,L", 8, Cpu::set),
+    /* 0xFE */ instruction("SET 7,(HL)", 16, Cpu::set),
+    /* 0xFF */ instruction("SET 7,A", 8, Cpu::set),
+];
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn opcode_table_metadata_test() {
+        assert_eq!("NOP", OPCODE_TABLE[0x00].mnemonic);
+        assert_eq!("LD A,(HL+)", OPCODE_TABLE[0x2A].mnemonic);
+        assert_eq!("JR NZ,r8", OPCODE_TABLE[0x20].mnemonic);
+        assert_eq!(8, OPCODE_TABLE[0x20].cycles);
+        assert_eq!("CALL a16", OPCODE_TABLE[0xCD].mnemonic);
+        assert_eq!(24, OPCODE_TABLE[0xCD].cycles);
+        assert_eq!("RST 38H", OPCODE_TABLE[0xFF].mnemonic);
+        assert_eq!("ILLEGAL", OPCODE_TABLE[0xD3].mnemonic);
+
+        assert_eq!("SWAP A", CB_OPCODE_TABLE[0x37].mnemonic);
+        assert_eq!("BIT 7,(HL)", CB_OPCODE_TABLE[0x7E].mnemonic);
+        assert_eq!(12, CB_OPCODE_TABLE[0x7E].cycles);
+        assert_eq!("SET 0,(HL)", CB_OPCODE_TABLE[0xC6].mnemonic);
+        assert_eq!(16, CB_OPCODE_TABLE[0xC6].cycles);
+    }
+
+    #[test]
+    fn every_opcode_is_decoded_test() {
+        for opcode in 0..=0xFF_usize {
+            assert!(!OPCODE_TABLE[opcode].mnemonic.is_empty());
+            assert!(OPCODE_TABLE[opcode].cycles >= 4);
+            assert!(CB_OPCODE_TABLE[opcode].cycles >= 8);
+        }
+    }
+}
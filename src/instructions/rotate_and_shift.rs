@@ -1,13 +1,5 @@
 use crate::cpu_data::FlagsRegister;
 
-pub static ACC_ROTATE_SHIFT_OPERATION_OPCODES: [u8; 4] = [0x07, 0x17, 0x0F, 0x1F];
-pub static ROTATE_SHIFT_OPERATION_OPCODES: [u8; 64] = [
-    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x09, 0x0A, 0x08, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
-    0x11, 0x12, 0x13, 0x14, 0x10, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F,
-    0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2A, 0x2B, 0x2C, 0x2D, 0x2E, 0x2F,
-    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
-];
-
 /// # rlc
 /// RLC (rotate left register) - bit rotate register left (not through the carry flag)
 pub fn rlc(flag: &mut FlagsRegister, register_or_value: &mut u8) {
@@ -113,7 +105,7 @@ pub fn sra(flag: &mut FlagsRegister, register_or_value: &mut u8) {
 /// # swap
 /// SWAP (swap nibbles) - switch upper and lower nibble of a specific register
 pub fn swap(flag: &mut FlagsRegister, register_or_value: &mut u8) {
-    *register_or_value = (*register_or_value >> 4) | (*register_or_value << 4);
+    *register_or_value = register_or_value.rotate_left(4);
     flag.n = false;
     flag.h = false;
     flag.z = *register_or_value == 0;
@@ -138,8 +130,10 @@ mod ut {
 
     #[test]
     fn rlca_test() {
-        let mut register = Registers::default();
-        register.a = 0xF2;
+        let mut register = Registers {
+            a: 0xF2,
+            ..Default::default()
+        };
         register.flag.z = true;
         register.flag.n = true;
         register.flag.h = true;
@@ -147,16 +141,18 @@ mod ut {
         rlca(&mut register.flag, &mut register.a);
 
         assert_eq!(0xE5, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == true);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(register.flag.c);
     }
 
     #[test]
     fn rla_test() {
-        let mut register = Registers::default();
-        register.a = 0xB5;
+        let mut register = Registers {
+            a: 0xB5,
+            ..Default::default()
+        };
         register.flag.z = true;
         register.flag.n = true;
         register.flag.h = true;
@@ -165,16 +161,18 @@ mod ut {
         rla(&mut register.flag, &mut register.a);
 
         assert_eq!(0x6A, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == true);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(register.flag.c);
     }
 
     #[test]
     fn rrca_test() {
-        let mut register = Registers::default();
-        register.a = 0xF2;
+        let mut register = Registers {
+            a: 0xF2,
+            ..Default::default()
+        };
         register.flag.z = true;
         register.flag.n = true;
         register.flag.h = true;
@@ -183,16 +181,18 @@ mod ut {
         rrca(&mut register.flag, &mut register.a);
 
         assert_eq!(0x79, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == false);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(!register.flag.c);
     }
 
     #[test]
     fn rra_test() {
-        let mut register = Registers::default();
-        register.a = 0x6A;
+        let mut register = Registers {
+            a: 0x6A,
+            ..Default::default()
+        };
         register.flag.z = true;
         register.flag.n = true;
         register.flag.h = true;
@@ -201,70 +201,78 @@ mod ut {
         rra(&mut register.flag, &mut register.a);
 
         assert_eq!(0xB5, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == false);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(!register.flag.c);
     }
 
     #[test]
     fn sla_test() {
-        let mut register = Registers::default();
-        register.a = 0x99;
+        let mut register = Registers {
+            a: 0x99,
+            ..Default::default()
+        };
         register.flag.n = true;
         register.flag.h = true;
 
         sla(&mut register.flag, &mut register.a);
 
         assert_eq!(0x32, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == true);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(register.flag.c);
     }
 
     #[test]
     fn sra_test() {
-        let mut register = Registers::default();
-        register.a = 0xC1;
+        let mut register = Registers {
+            a: 0xC1,
+            ..Default::default()
+        };
         register.flag.n = true;
         register.flag.h = true;
 
         sra(&mut register.flag, &mut register.a);
 
         assert_eq!(0xE0, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == true);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(register.flag.c);
     }
 
     #[test]
     fn swap_test() {
-        let mut register = Registers::default();
-        register.a = 0xF1;
+        let mut register = Registers {
+            a: 0xF1,
+            ..Default::default()
+        };
 
         swap(&mut register.flag, &mut register.a);
 
         assert_eq!(0x1F, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == false);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(!register.flag.c);
     }
     #[test]
     fn srl_test() {
-        let mut register = Registers::default();
-        register.a = 0xC1;
+        let mut register = Registers {
+            a: 0xC1,
+            ..Default::default()
+        };
         register.flag.n = true;
         register.flag.h = true;
 
         srl(&mut register.flag, &mut register.a);
 
         assert_eq!(0x60, register.a);
-        assert!(register.flag.z == false);
-        assert!(register.flag.n == false);
-        assert!(register.flag.h == false);
-        assert!(register.flag.c == true);
+        assert!(!register.flag.z);
+        assert!(!register.flag.n);
+        assert!(!register.flag.h);
+        assert!(register.flag.c);
     }
 }
@@ -1,87 +1,528 @@
 use super::constants::gb_memory_map::address::io_hardware_register;
 use super::constants::gb_memory_map::{address, memory};
+use crate::debugger::{HookAction, HookId, MemoryAccess, WatchKind};
+use crate::emulator_constants::HardwareModel;
 use crate::peripheral::{
-    cartridge::Cartridge, interrupt_controller::InterruptController, joypad::JoypadInput,
-    ppu::PictureProcessingUnit, serial::SerialDataTransfer, timer::Timer, HardwareAccessible,
-    IoWorkingCycle,
+    apu::AudioProcessingUnit, cartridge::Cartridge, interrupt_controller::InterruptController,
+    joypad::JoypadInput, ppu::PictureProcessingUnit, serial::SerialDataTransfer, timer::Timer,
+    HardwareAccessible, IoWorkingCycle,
 };
-use std::{cell::RefCell, rc::Rc};
+use crate::save_state::{self, Snapshot, StateReader, StateWriter};
+use std::{
+    cell::{Cell, RefCell},
+    ops::RangeInclusive,
+    rc::Rc,
+};
+
+/// # KEY1
+/// CGB speed switch: Bit7 current speed (0=Normal, 1=Double), Bit0 switch armed
+#[derive(Default)]
+struct SpeedSwitch {
+    double_speed: bool,
+    armed: bool,
+}
+
+/// # VRAM DMA
+/// CGB transfer of 16 byte blocks to VRAM. General purpose DMA copies everything at once,
+/// HBlank DMA copies one block at the beginning of every HBlank. The CPU is stalled meanwhile.
+#[derive(Default)]
+struct VramDma {
+    source: u16,
+    destination: u16,
+    // Number of blocks left minus 1, 0x7F when the transfer is done
+    remaining_blocks: u8,
+    hblank_active: bool,
+}
+
+impl VramDma {
+    const BLOCK_SIZE: u16 = 0x10;
+    // 8 M-cycles per block in normal speed
+    const BLOCK_CYCLES: u32 = 32;
+
+    fn new() -> Self {
+        Self {
+            remaining_blocks: 0x7F,
+            ..Default::default()
+        }
+    }
+
+    /// HDMA5: Bit7 0=Active, 1=Done or terminated, Bit6-0 remaining blocks minus 1
+    fn read_status(&self) -> u8 {
+        if self.hblank_active {
+            return self.remaining_blocks;
+        }
+        0x80 | self.remaining_blocks
+    }
+}
+
+/// # OAM DMA
+/// Copies 160 bytes to OAM in the background, one byte per M-cycle after one M-cycle of start-up.
+/// Meanwhile the CPU bus below 0xFF00 is taken: reads return 0xFF and writes are ignored,
+/// only HRAM and the I/O registers are left to the CPU.
+struct OamDma {
+    register: u8,
+    source: u16,
+    // Next byte to copy
+    index: u16,
+    active: bool,
+    // Write to 0xFF46 waiting for the start, the running transfer continues until then
+    pending_source: Option<u16>,
+    remaining_cycles: u32,
+}
+
+impl OamDma {
+    const M_CYCLE: u32 = 4;
+
+    fn new() -> Self {
+        Self {
+            register: 0xFF,
+            source: 0,
+            index: 0,
+            active: false,
+            pending_source: None,
+            remaining_cycles: 0,
+        }
+    }
+
+    fn start(&mut self, hi_source_address: u8) {
+        self.register = hi_source_address;
+        self.pending_source = Some((hi_source_address as u16).rotate_left(8));
+    }
+
+    fn is_bus_locked(&self, address: u16) -> bool {
+        self.active && address <= *address::NOT_USABLE.end()
+    }
+}
+
+impl SpeedSwitch {
+    fn read(&self) -> u8 {
+        let mut out_value = 0x7E;
+        if self.double_speed {
+            out_value |= 1_u8.rotate_left(7);
+        }
+        if self.armed {
+            out_value |= 1;
+        }
+        out_value
+    }
+}
+
+pub type MemoryHookCallback = Box<dyn FnMut(&MemoryAccess) -> HookAction>;
+
+struct MemoryHook {
+    id: HookId,
+    range: RangeInclusive<u16>,
+    kind: WatchKind,
+    callback: MemoryHookCallback,
+}
+
 /// # I/O Memory Management
 /// Input–output memory management unit
-pub struct IOMMU {
+pub struct Iommu {
     cartridge_rom: Rc<RefCell<Cartridge>>,
-    wram: [u8; memory::WRAM_SIZE],
+    boot_rom: Vec<u8>,
+    boot_rom_mapped: bool,
+    wram: [u8; memory::CGB_WRAM_SIZE],
     hram: [u8; memory::HIGH_RAM_SIZE],
+    cgb_mode: bool,
+    wram_bank: usize,
+    speed_switch: SpeedSwitch,
+    vram_dma: VramDma,
+    dma_stall_cycles: u32,
+    oam_dma: OamDma,
     isr_controller: InterruptController,
     pub serial: SerialDataTransfer,
     timer: Timer,
+    pub apu: AudioProcessingUnit,
     ppu: Rc<RefCell<PictureProcessingUnit>>,
     joypad: Rc<RefCell<JoypadInput>>,
+    watchpoints: Vec<(u16, WatchKind)>,
+    // First CPU access which hit a watchpoint, reads take `&self`
+    watchpoint_hit: Cell<Option<MemoryAccess>>,
+    memory_hooks: RefCell<Vec<MemoryHook>>,
+    next_hook_id: usize,
+    // Any watchpoint or hook, the only check done on the CPU access without them
+    instrumented: bool,
 }
 
-impl IOMMU {
+impl Iommu {
     pub fn new(
         cartridge: Rc<RefCell<Cartridge>>,
         ppu: Rc<RefCell<PictureProcessingUnit>>,
         input_controller: Rc<RefCell<JoypadInput>>,
     ) -> Self {
-        IOMMU {
+        Iommu {
             cartridge_rom: cartridge,
-            wram: [memory::DEFAULT_INIT_VALUE; memory::WRAM_SIZE],
+            boot_rom: Vec::new(),
+            boot_rom_mapped: false,
+            wram: [memory::DEFAULT_INIT_VALUE; memory::CGB_WRAM_SIZE],
             hram: [memory::DEFAULT_INIT_VALUE; memory::HIGH_RAM_SIZE],
+            cgb_mode: false,
+            wram_bank: 1,
+            speed_switch: SpeedSwitch::default(),
+            vram_dma: VramDma::new(),
+            dma_stall_cycles: 0,
+            oam_dma: OamDma::new(),
             isr_controller: InterruptController::default(),
             serial: SerialDataTransfer::default(),
             timer: Timer::default(),
+            apu: AudioProcessingUnit::default(),
             ppu,
             joypad: input_controller,
+            watchpoints: Vec::new(),
+            watchpoint_hit: Cell::new(None),
+            memory_hooks: RefCell::new(Vec::new()),
+            next_hook_id: 0,
+            instrumented: false,
         }
     }
-    pub fn init(&mut self) {
+    /// # set_boot_rom
+    /// Boot ROM is mapped over the cartridge at every `init` until 0xFF50 is written
+    pub fn set_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.boot_rom = boot_rom;
+    }
+
+    pub fn boot_rom_size(&self) -> usize {
+        self.boot_rom.len()
+    }
+
+    /// # init
+    /// Register values after the boot ROM of the model, CGB mode is chosen from the cartridge header.
+    /// With a boot ROM only the power on state is set, the boot ROM does the rest.
+    pub fn init(&mut self, model: HardwareModel, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.wram_bank = 1;
+        self.speed_switch = SpeedSwitch::default();
+        self.vram_dma = VramDma::new();
+        self.dma_stall_cycles = 0;
+        self.oam_dma = OamDma::new();
+        self.ppu.borrow_mut().set_oam_dma_active(false);
+        self.ppu.borrow_mut().set_cgb_mode(cgb_mode);
+        self.serial.set_cgb_hardware(model.is_cgb());
+
+        self.boot_rom_mapped = !self.boot_rom.is_empty();
+        if self.boot_rom_mapped {
+            self.write_byte(io_hardware_register::LCD_CONTROL, 0);
+            self.write_byte(io_hardware_register::LCD_STATUS, 0);
+            self.write_byte(io_hardware_register::NR52, 0);
+            self.write_byte(io_hardware_register::TIMER_DIV, 0);
+            self.write_byte(io_hardware_register::TIMER_TAC, 0);
+            self.write_byte(address::INTF_REGISTER, 0);
+            self.write_byte(address::INTE_REGISTER, 0);
+            return;
+        }
+
         self.write_byte(io_hardware_register::JOYPAD_INPUT, 0xCF);
         self.write_byte(io_hardware_register::SERIAL_DATA, 0);
-        self.write_byte(io_hardware_register::SERIAL_CONTROL, 0x7E);
-        self.write_byte(io_hardware_register::TIMER_DIV, 0x18);
+        // Unused bit 0 (clock speed) reads as 1 on CGB hardware
+        let serial_control = if model.is_cgb() { 0x7F } else { 0x7E };
+        self.write_byte(io_hardware_register::SERIAL_CONTROL, serial_control);
+        // SGB and CGB values depend on the boot ROM run time, the DMG one is used
+        let div = if model == HardwareModel::Dmg0 {
+            0x18
+        } else {
+            0xAB
+        };
+        self.timer.set_div(div);
         self.write_byte(io_hardware_register::TIMER_TIMA, 0);
         self.write_byte(io_hardware_register::TIMER_TMA, 0);
         self.write_byte(io_hardware_register::TIMER_TAC, 0xF8);
         self.write_byte(address::INTF_REGISTER, 0xE1);
-        // self.write_byte(0xFF10, 0x80);
-        // self.write_byte(0xFF11, 0xBF);
-        // self.write_byte(0xFF12, 0xF3);
-        // self.write_byte(0xFF13, 0xFF);
-        // self.write_byte(0xFF14, 0xBF);
-        // self.write_byte(0xFF16, 0x3F);
-        // self.write_byte(0xFF17, 0);
-        // self.write_byte(0xFF18, 0xFF);
-        // self.write_byte(0xFF19, 0xBF);
-        // self.write_byte(0xFF1A, 0x7F);
-        // self.write_byte(0xFF1B, 0xFF);
-        // self.write_byte(0xFF1C, 0x9F);
-        // self.write_byte(0xFF1D, 0xFF);
-        // self.write_byte(0xFF1E, 0xBF);
-        // self.write_byte(0xFF20, 0xFF);
-        // self.write_byte(0xFF21, 0);
-        // self.write_byte(0xFF22, 0);
-        // self.write_byte(0xFF23, 0xBF);
-        // self.write_byte(0xFF24, 0x77);
-        // self.write_byte(0xFF25, 0xF3);
-        // self.write_byte(0xFF26, 0xF1);
+        // APU has to be powered on before the sound registers are writable
+        self.write_byte(io_hardware_register::NR52, 0xF1);
+        self.write_byte(io_hardware_register::NR10, 0x80);
+        self.write_byte(io_hardware_register::NR11, 0xBF);
+        self.write_byte(io_hardware_register::NR12, 0xF3);
+        self.write_byte(io_hardware_register::NR13, 0xFF);
+        self.write_byte(io_hardware_register::NR14, 0xBF);
+        self.write_byte(io_hardware_register::NR21, 0x3F);
+        self.write_byte(io_hardware_register::NR22, 0);
+        self.write_byte(io_hardware_register::NR23, 0xFF);
+        self.write_byte(io_hardware_register::NR24, 0xBF);
+        self.write_byte(io_hardware_register::NR30, 0x7F);
+        self.write_byte(io_hardware_register::NR31, 0xFF);
+        self.write_byte(io_hardware_register::NR32, 0x9F);
+        self.write_byte(io_hardware_register::NR33, 0xFF);
+        self.write_byte(io_hardware_register::NR34, 0xBF);
+        self.write_byte(io_hardware_register::NR41, 0xFF);
+        self.write_byte(io_hardware_register::NR42, 0);
+        self.write_byte(io_hardware_register::NR43, 0);
+        self.write_byte(io_hardware_register::NR44, 0xBF);
+        self.write_byte(io_hardware_register::NR50, 0x77);
+        self.write_byte(io_hardware_register::NR51, 0xF3);
         self.write_byte(io_hardware_register::LCD_CONTROL, 0x91);
         self.write_byte(io_hardware_register::LCD_STATUS, 0x81);
         self.write_byte(io_hardware_register::SCY, 0);
         self.write_byte(io_hardware_register::SCX, 0);
         //self.write_byte(io_hardware_register::LY, 0x91);
         self.write_byte(io_hardware_register::LYC, 0);
-        self.write_byte(io_hardware_register::OAM_DMA, 0xFF);
+        // Only the register value, no transfer is started
+        self.oam_dma.register = if model.is_cgb() { 0x00 } else { 0xFF };
         self.write_byte(io_hardware_register::BGP, 0xFC);
         self.write_byte(io_hardware_register::OBP0, 0xFF);
         self.write_byte(io_hardware_register::OBP1, 0xFF);
         self.write_byte(io_hardware_register::WY, 0);
         self.write_byte(io_hardware_register::WX, 0);
         self.write_byte(address::INTE_REGISTER, 0);
+
+        if cgb_mode {
+            // Boot ROM leaves all background palettes white
+            self.write_byte(io_hardware_register::BCPS, 0x80);
+            for _ in 0..memory::COLOR_PALETTE_RAM_SIZE {
+                self.write_byte(io_hardware_register::BCPD, 0xFF);
+            }
+            self.write_byte(io_hardware_register::BCPS, 0);
+        }
+    }
+
+    /// # speed_switch
+    /// STOP switches between normal and double speed when KEY1 was armed (CGB only)
+    pub fn speed_switch(&mut self) -> bool {
+        if !self.cgb_mode || !self.speed_switch.armed {
+            return false;
+        }
+        self.speed_switch.armed = false;
+        self.speed_switch.double_speed = !self.speed_switch.double_speed;
+        true
+    }
+
+    pub fn is_double_speed(&self) -> bool {
+        self.speed_switch.double_speed
+    }
+
+    /// # take_dma_stall_cycles
+    /// CPU cycles lost to the VRAM DMA since the last call
+    pub fn take_dma_stall_cycles(&mut self) -> u32 {
+        std::mem::take(&mut self.dma_stall_cycles)
+    }
+
+    fn vram_dma_write(&mut self, address: u16, data: u8) {
+        let dma = &mut self.vram_dma;
+        match address {
+            io_hardware_register::HDMA1 => {
+                dma.source = (dma.source & 0x00FF) | (data as u16).rotate_left(8);
+            }
+            // Lower 4 bits are ignored
+            io_hardware_register::HDMA2 => {
+                dma.source = (dma.source & 0xFF00) | (data & 0xF0) as u16
+            }
+            // Destination is always in VRAM
+            io_hardware_register::HDMA3 => {
+                dma.destination =
+                    (dma.destination & 0x00FF) | ((data & 0x1F) as u16).rotate_left(8);
+            }
+            io_hardware_register::HDMA4 => {
+                dma.destination = (dma.destination & 0xFF00) | (data & 0xF0) as u16;
+            }
+            _ => {
+                // Writing Bit7=0 during the HBlank DMA terminates it
+                if dma.hblank_active && (data & 0x80) == 0 {
+                    dma.hblank_active = false;
+                    return;
+                }
+                dma.remaining_blocks = data & 0x7F;
+                if (data & 0x80) == 0x80 {
+                    dma.hblank_active = true;
+                } else {
+                    // General purpose DMA
+                    for _ in 0..=dma.remaining_blocks {
+                        self.vram_dma_block_transfer();
+                    }
+                }
+            }
+        }
+    }
+
+    fn vram_dma_block_transfer(&mut self) {
+        let source = self.vram_dma.source;
+        let destination = *address::VIDEO_RAM.start() | (self.vram_dma.destination & 0x1FF0);
+        for offset in 0..VramDma::BLOCK_SIZE {
+            let source_byte = self.bus_read_byte(source.wrapping_add(offset));
+            self.bus_write_byte(destination + offset, source_byte);
+        }
+        self.vram_dma.source = source.wrapping_add(VramDma::BLOCK_SIZE);
+        self.vram_dma.destination = self.vram_dma.destination.wrapping_add(VramDma::BLOCK_SIZE);
+
+        if self.vram_dma.remaining_blocks == 0 {
+            self.vram_dma.remaining_blocks = 0x7F;
+            self.vram_dma.hblank_active = false;
+        } else {
+            self.vram_dma.remaining_blocks -= 1;
+        }
+
+        // The block takes the same time in both speeds, twice as many CPU cycles in double speed
+        self.dma_stall_cycles += if self.speed_switch.double_speed {
+            VramDma::BLOCK_CYCLES * 2
+        } else {
+            VramDma::BLOCK_CYCLES
+        };
+    }
+
+    fn is_boot_rom_address(&self, address: u16) -> bool {
+        self.boot_rom_mapped
+            && (address::BOOT_ROM.contains(&address)
+                || (self.boot_rom.len() == memory::CGB_BOOT_ROM_SIZE
+                    && address::CGB_BOOT_ROM_HIGH.contains(&address)))
+    }
+
+    /// # set_dmg_compatibility_mode
+    /// CGB boot ROM switches to DMG mode for cartridges without CGB support
+    fn set_dmg_compatibility_mode(&mut self) {
+        self.cgb_mode = false;
+        self.wram_bank = 1;
+        self.ppu.borrow_mut().set_cgb_mode(false);
     }
+
+    /// Bank 1-7 mapped at 0xD000, bank 0 is selected as 1
+    fn wram_bank_n_index(&self, address: u16) -> usize {
+        self.wram_bank * memory::WRAM_BANK_SIZE + (address as usize & memory::WRAM_ADDRESS_MASK)
+    }
+    /// # read_byte
+    /// CPU view of the memory map, the bus is not available during OAM DMA
     pub fn read_byte(&self, address: u16) -> u8 {
+        let value = if self.oam_dma.is_bus_locked(address) {
+            0xFF
+        } else {
+            self.bus_read_byte(address)
+        };
+        if self.instrumented {
+            self.access_check(MemoryAccess {
+                address,
+                value,
+                old_value: value,
+                is_write: false,
+            });
+        }
+        value
+    }
+
+    /// # write_byte
+    /// CPU view of the memory map, writes are lost during OAM DMA
+    pub fn write_byte(&mut self, address: u16, data: u8) {
+        if self.instrumented {
+            self.access_check(MemoryAccess {
+                address,
+                value: data,
+                old_value: self.bus_read_byte(address),
+                is_write: true,
+            });
+        }
+        if self.oam_dma.is_bus_locked(address) {
+            return;
+        }
+        self.bus_write_byte(address, data)
+    }
+
+    /// # peek_byte
+    /// Memory content for the debug tools, no bus conflicts and no watchpoints
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        self.bus_read_byte(address)
+    }
+
+    /// # poke_byte
+    /// Memory write for the debug tools, no bus conflicts and no watchpoints
+    pub fn poke_byte(&mut self, address: u16, data: u8) {
+        self.bus_write_byte(address, data)
+    }
+
+    /// # pending_interrupts
+    /// Requested and enabled interrupts (IE & IF), polled inside the CPU so no watchpoints are checked
+    pub fn pending_interrupts(&self) -> u8 {
+        let intf = self.bus_read_byte(address::INTF_REGISTER);
+        let inte = self.bus_read_byte(address::INTE_REGISTER);
+        inte & intf & 0x1F
+    }
+
+    /// # acknowledge_interrupt
+    /// Clears the serviced request in IF during interrupt dispatch
+    pub fn acknowledge_interrupt(&mut self, interrupt: u8) {
+        let intf = self.bus_read_byte(address::INTF_REGISTER);
+        self.bus_write_byte(address::INTF_REGISTER, intf & !interrupt);
+    }
+
+    /// # set_watchpoint
+    /// The next CPU access of the address is reported by `take_watchpoint_hit`
+    pub fn set_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        self.remove_watchpoint(address);
+        self.watchpoints.push((address, kind));
+        self.instrumented = true;
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        let number_of_watchpoints = self.watchpoints.len();
+        self.watchpoints
+            .retain(|(watch_address, _)| *watch_address != address);
+        self.update_instrumented();
+        self.watchpoints.len() != number_of_watchpoints
+    }
+
+    pub fn watchpoints(&self) -> &[(u16, WatchKind)] {
+        &self.watchpoints
+    }
+
+    pub fn take_watchpoint_hit(&self) -> Option<MemoryAccess> {
+        self.watchpoint_hit.take()
+    }
+
+    /// # add_memory_hook
+    /// Callback on every matching CPU access in the range, `HookAction::Break` is reported as a watchpoint hit
+    pub fn add_memory_hook(
+        &mut self,
+        range: RangeInclusive<u16>,
+        kind: WatchKind,
+        callback: MemoryHookCallback,
+    ) -> HookId {
+        let id = HookId(self.next_hook_id);
+        self.next_hook_id += 1;
+        self.memory_hooks.get_mut().push(MemoryHook {
+            id,
+            range,
+            kind,
+            callback,
+        });
+        self.instrumented = true;
+        id
+    }
+
+    pub fn remove_memory_hook(&mut self, id: HookId) -> bool {
+        let memory_hooks = self.memory_hooks.get_mut();
+        let number_of_hooks = memory_hooks.len();
+        memory_hooks.retain(|hook| hook.id != id);
+        let is_removed = memory_hooks.len() != number_of_hooks;
+        self.update_instrumented();
+        is_removed
+    }
+
+    fn update_instrumented(&mut self) {
+        self.instrumented = !self.watchpoints.is_empty() || !self.memory_hooks.get_mut().is_empty();
+    }
+
+    fn access_check(&self, access: MemoryAccess) {
+        let mut is_hit = self
+            .watchpoints
+            .iter()
+            .any(|(watch_address, kind)| *watch_address == access.address && kind.matches(&access));
+
+        for hook in self.memory_hooks.borrow_mut().iter_mut() {
+            if hook.range.contains(&access.address)
+                && hook.kind.matches(&access)
+                && (hook.callback)(&access) == HookAction::Break
+            {
+                is_hit = true;
+            }
+        }
+
+        if is_hit && self.watchpoint_hit.get().is_none() {
+            self.watchpoint_hit.set(Some(access));
+        }
+    }
+
+    fn bus_read_byte(&self, address: u16) -> u8 {
         match address {
+            boot_rom_address if self.is_boot_rom_address(boot_rom_address) => {
+                self.boot_rom[boot_rom_address as usize]
+            }
+
             rom_bank_0_address if address::CARTRIDGE_ROM_BANK_0.contains(&rom_bank_0_address) => {
                 self.cartridge_rom
                     .borrow_mut()
@@ -110,7 +551,7 @@ impl IOMMU {
                 if address::WORKING_RAM_BANK_1_7.contains(&wram_address_bank_1_7)
                     | address::ECHO_RAM_BANK_1_7.contains(&wram_address_bank_1_7) =>
             {
-                self.wram[wram_address_bank_1_7 as usize & memory::WRAM_ADDRESS_MASK | 0x1000]
+                self.wram[self.wram_bank_n_index(wram_address_bank_1_7)]
             }
 
             voam_address if address::OAM.contains(&voam_address) => self
@@ -140,15 +581,39 @@ impl IOMMU {
                 self.timer.read_byte_from_hardware_register(timer_address)
             }
 
+            sound_address
+                if address::HARDWARE_IO_SOUND.contains(&sound_address)
+                    | address::WAVE_PATTERN_RAM.contains(&sound_address) =>
+            {
+                self.apu.read_byte_from_hardware_register(sound_address)
+            }
+
             graphics_address
                 if address::HARDWARE_IO_GRAPHICS_1.contains(&graphics_address)
-                    | address::HARDWARE_IO_GRAPHICS_2.contains(&graphics_address) =>
+                    | address::HARDWARE_IO_GRAPHICS_2.contains(&graphics_address)
+                    | address::HARDWARE_IO_COLOR_PALETTES.contains(&graphics_address)
+                    | (graphics_address == io_hardware_register::VBK) =>
             {
                 self.ppu
                     .borrow_mut()
                     .read_byte_from_hardware_register(graphics_address)
             }
 
+            io_hardware_register::OAM_DMA => self.oam_dma.register,
+
+            io_hardware_register::KEY1 if self.cgb_mode => self.speed_switch.read(),
+
+            // HDMA1-4 are write only
+            io_hardware_register::HDMA5 if self.cgb_mode => self.vram_dma.read_status(),
+            vram_dma_address
+                if self.cgb_mode && address::HARDWARE_IO_VRAM_DMA.contains(&vram_dma_address) =>
+            {
+                0xFF
+            }
+
+            // Only bits 0-2 are used, the others read as 1
+            io_hardware_register::SVBK if self.cgb_mode => 0xF8 | self.wram_bank as u8,
+
             address::INTF_REGISTER | address::INTE_REGISTER => self
                 .isr_controller
                 .read_byte_from_hardware_register(address),
@@ -160,7 +625,7 @@ impl IOMMU {
         }
     }
 
-    pub fn write_byte(&mut self, address: u16, data: u8) {
+    fn bus_write_byte(&mut self, address: u16, data: u8) {
         match address {
             rom_bank_0_address if address::CARTRIDGE_ROM_BANK_0.contains(&rom_bank_0_address) => {
                 self.cartridge_rom
@@ -190,8 +655,8 @@ impl IOMMU {
                 if address::WORKING_RAM_BANK_1_7.contains(&wram_address_bank_1_7)
                     | address::ECHO_RAM_BANK_1_7.contains(&wram_address_bank_1_7) =>
             {
-                self.wram[wram_address_bank_1_7 as usize & memory::WRAM_ADDRESS_MASK | 0x1000] =
-                    data;
+                let wram_index = self.wram_bank_n_index(wram_address_bank_1_7);
+                self.wram[wram_index] = data;
             }
 
             voam_address if address::OAM.contains(&voam_address) => self
@@ -219,18 +684,50 @@ impl IOMMU {
                 self.timer
                     .write_byte_to_hardware_register(timer_address, data);
             }
-            //FIXME
-            io_hardware_register::OAM_DMA => self.oam_dma_transfer(data),
+
+            sound_address
+                if address::HARDWARE_IO_SOUND.contains(&sound_address)
+                    | address::WAVE_PATTERN_RAM.contains(&sound_address) =>
+            {
+                self.apu
+                    .write_byte_to_hardware_register(sound_address, data);
+            }
+            io_hardware_register::OAM_DMA => self.oam_dma.start(data),
 
             graphics_address
                 if address::HARDWARE_IO_GRAPHICS_1.contains(&graphics_address)
-                    | address::HARDWARE_IO_GRAPHICS_2.contains(&graphics_address) =>
+                    | address::HARDWARE_IO_GRAPHICS_2.contains(&graphics_address)
+                    | address::HARDWARE_IO_COLOR_PALETTES.contains(&graphics_address)
+                    | (graphics_address == io_hardware_register::VBK) =>
             {
                 self.ppu
                     .borrow_mut()
                     .write_byte_to_hardware_register(graphics_address, data)
             }
 
+            io_hardware_register::KEY1 if self.cgb_mode => {
+                self.speed_switch.armed = (data & 0x01) == 1
+            }
+
+            // Locked once the boot ROM is gone
+            io_hardware_register::KEY0
+                if self.cgb_mode && self.boot_rom_mapped && (data & 0x04) == 0x04 =>
+            {
+                self.set_dmg_compatibility_mode()
+            }
+
+            io_hardware_register::BOOT_ROM_DISABLE if data != 0 => self.boot_rom_mapped = false,
+
+            io_hardware_register::SVBK if self.cgb_mode => {
+                self.wram_bank = (data & 0x07).max(1) as usize;
+            }
+
+            vram_dma_address
+                if self.cgb_mode && address::HARDWARE_IO_VRAM_DMA.contains(&vram_dma_address) =>
+            {
+                self.vram_dma_write(vram_dma_address, data)
+            }
+
             address::INTF_REGISTER | address::INTE_REGISTER => self
                 .isr_controller
                 .write_byte_to_hardware_register(address, data),
@@ -244,28 +741,48 @@ impl IOMMU {
         }
     }
 
+    /// # process
+    /// Requests are latched in IF until the CPU services them or the software clears them
+    ///
+    /// In double speed mode the CPU and the timer run twice as fast, the PPU, APU and RTC keep their clock
     pub fn process(&mut self, cycles: u32) {
-        self.ppu.borrow_mut().next_to(cycles);
+        let normal_speed_cycles = if self.speed_switch.double_speed {
+            cycles / 2
+        } else {
+            cycles
+        };
+        // OAM DMA runs at the CPU speed
+        self.oam_dma_handling(cycles);
+        self.cartridge_rom.borrow_mut().next_to(normal_speed_cycles);
+        self.ppu.borrow_mut().next_to(normal_speed_cycles);
+
+        let is_hblank_started = std::mem::take(&mut self.ppu.borrow_mut().hblank_dma_req);
+        if is_hblank_started && self.vram_dma.hblank_active {
+            self.vram_dma_block_transfer();
+        }
 
         //  * 0 V-Blank
-        self.isr_controller.intf.v_blank = self.ppu.borrow_mut().vblank_interrupt_req;
+        self.isr_controller.intf.v_blank |= self.ppu.borrow_mut().vblank_interrupt_req;
         self.ppu.borrow_mut().vblank_interrupt_req = false;
 
         //  * 1 LCD
-        self.isr_controller.intf.lcd = self.ppu.borrow_mut().lcd_interrupt_req;
+        self.isr_controller.intf.lcd |= self.ppu.borrow_mut().lcd_interrupt_req;
         self.ppu.borrow_mut().lcd_interrupt_req = false;
 
+        self.apu.next_to(normal_speed_cycles);
+
         //  * 2 Timer
         self.timer.next_to(cycles);
-        self.isr_controller.intf.timer = self.timer.interrupt_req;
+        self.isr_controller.intf.timer |= self.timer.interrupt_req;
         self.timer.interrupt_req = false;
 
         //  * 3 Serial Link
-        self.isr_controller.intf.serial_link = self.serial.interrupt_req;
+        self.serial.next_to(cycles);
+        self.isr_controller.intf.serial_link |= self.serial.interrupt_req;
         self.serial.interrupt_req = false;
 
         //  * 4 Joypad
-        self.isr_controller.intf.joypad = self.joypad.borrow_mut().interrupt_req;
+        self.isr_controller.intf.joypad |= self.joypad.borrow_mut().interrupt_req;
         self.joypad.borrow_mut().interrupt_req = false;
     }
 
@@ -282,13 +799,112 @@ impl IOMMU {
         self.write_byte(address + 1, high_byte_val);
     }
 
-    fn oam_dma_transfer(&mut self, hi_source_address: u8) {
-        let base_source_address = (hi_source_address as u16).rotate_left(8);
-        let base_destination_address = *address::OAM.start();
-        for i in 0..memory::VOAM_SIZE as u16 {
-            let source_byte = self.read_byte(base_source_address + i);
-            self.write_byte(base_destination_address + i, source_byte);
+    fn oam_dma_handling(&mut self, cycles: u32) {
+        if !self.oam_dma.active && self.oam_dma.pending_source.is_none() {
+            return;
         }
+        self.oam_dma.remaining_cycles += cycles;
+        while self.oam_dma.remaining_cycles >= OamDma::M_CYCLE {
+            self.oam_dma.remaining_cycles -= OamDma::M_CYCLE;
+            self.oam_dma_m_cycle();
+        }
+        let is_active = self.oam_dma.active;
+        self.ppu.borrow_mut().set_oam_dma_active(is_active);
+    }
+
+    fn oam_dma_m_cycle(&mut self) {
+        if self.oam_dma.active {
+            let index = self.oam_dma.index;
+            // Source above 0xDFFF is mapped to the WRAM echo
+            let source_address = match self.oam_dma.source + index {
+                echo_address if echo_address >= *address::ECHO_RAM_BANK_0.start() => {
+                    echo_address - 0x2000
+                }
+                source_address => source_address,
+            };
+            let source_byte = self.bus_read_byte(source_address);
+            self.bus_write_byte(*address::OAM.start() + index, source_byte);
+            self.oam_dma.index += 1;
+            self.oam_dma.active = self.oam_dma.index < memory::VOAM_SIZE as u16;
+        }
+
+        // Start-up takes one M-cycle, a restart keeps the bus locked by the old transfer meanwhile
+        if let Some(source) = self.oam_dma.pending_source.take() {
+            self.oam_dma.source = source;
+            self.oam_dma.index = 0;
+            self.oam_dma.active = true;
+        }
+    }
+}
+
+/// Cartridge, PPU and Joypad are shared with the emulator and stored separately
+impl Snapshot for Iommu {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.wram);
+        writer.write_bytes(&self.hram);
+        writer.write_bool(self.boot_rom_mapped);
+        writer.write_bool(self.cgb_mode);
+        writer.write_u8(self.wram_bank as u8);
+        writer.write_u8(self.speed_switch.read());
+        writer.write_u16(self.vram_dma.source);
+        writer.write_u16(self.vram_dma.destination);
+        writer.write_u8(self.vram_dma.read_status());
+        writer.write_u32(self.dma_stall_cycles);
+        writer.write_u8(self.oam_dma.register);
+        writer.write_u16(self.oam_dma.source);
+        writer.write_u16(self.oam_dma.index);
+        writer.write_bool(self.oam_dma.active);
+        writer.write_bool(self.oam_dma.pending_source.is_some());
+        writer.write_u16(self.oam_dma.pending_source.unwrap_or_default());
+        writer.write_u32(self.oam_dma.remaining_cycles);
+        self.isr_controller.save_state(writer);
+        self.serial.save_state(writer);
+        self.timer.save_state(writer);
+        self.apu.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        reader.read_bytes(&mut self.wram)?;
+        reader.read_bytes(&mut self.hram)?;
+        self.boot_rom_mapped = reader.read_bool()?;
+        if self.boot_rom_mapped && self.boot_rom.is_empty() {
+            return Err(save_state::invalid_data_error(
+                "Save state was made during the boot ROM, load the boot ROM first",
+            ));
+        }
+        self.cgb_mode = reader.read_bool()?;
+        self.wram_bank = (reader.read_u8()? & 0x07).max(1) as usize;
+        let key1 = reader.read_u8()?;
+        self.speed_switch = SpeedSwitch {
+            double_speed: (key1.rotate_right(7) & 1) == 1,
+            armed: (key1 & 0x01) == 1,
+        };
+        let source = reader.read_u16()?;
+        let destination = reader.read_u16()?;
+        let vram_dma_status = reader.read_u8()?;
+        self.vram_dma = VramDma {
+            source,
+            destination,
+            remaining_blocks: vram_dma_status & 0x7F,
+            hblank_active: (vram_dma_status & 0x80) == 0,
+        };
+        self.dma_stall_cycles = reader.read_u32()?;
+        self.oam_dma.register = reader.read_u8()?;
+        self.oam_dma.source = reader.read_u16()?;
+        self.oam_dma.index = reader.read_u16()?.min(memory::VOAM_SIZE as u16);
+        self.oam_dma.active = reader.read_bool()?;
+        let is_pending = reader.read_bool()?;
+        let pending_source = reader.read_u16()?;
+        self.oam_dma.pending_source = is_pending.then_some(pending_source);
+        self.oam_dma.remaining_cycles = reader.read_u32()? % OamDma::M_CYCLE;
+        self.ppu
+            .borrow_mut()
+            .set_oam_dma_active(self.oam_dma.active);
+        self.isr_controller.load_state(reader)?;
+        self.serial.load_state(reader)?;
+        self.timer.load_state(reader)?;
+        self.apu.load_state(reader)?;
+        Ok(())
     }
 }
 
@@ -301,7 +917,7 @@ mod ut {
         let cartridge = Rc::new(RefCell::new(Cartridge::default()));
         let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
         let joypad = Rc::new(RefCell::new(JoypadInput::default()));
-        let mut iommu = IOMMU::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
 
         iommu.write_byte(*address::HIGH_RAM.start(), 0xCD);
         iommu.write_byte(*address::HIGH_RAM.start() + 1, 0xAB);
@@ -315,7 +931,7 @@ mod ut {
         let cartridge = Rc::new(RefCell::new(Cartridge::default()));
         let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
         let joypad = Rc::new(RefCell::new(JoypadInput::default()));
-        let mut iommu = IOMMU::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
 
         // [0xFEA0 - 0xFEFF] Not Usable
         iommu.write_byte(*address::NOT_USABLE.start(), EXP_STORED_VALUE);
@@ -337,7 +953,7 @@ mod ut {
         let cartridge = Rc::new(RefCell::new(Cartridge::default()));
         let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
         let joypad = Rc::new(RefCell::new(JoypadInput::default()));
-        let mut iommu = IOMMU::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
         iommu.write_byte(address::io_hardware_register::SERIAL_DATA, 0xAA);
 
         assert_eq!(
@@ -345,4 +961,352 @@ mod ut {
             iommu.read_byte(address::io_hardware_register::SERIAL_DATA)
         );
     }
+
+    #[test]
+    fn hardware_model_init_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+
+        iommu.init(HardwareModel::Dmg0, false);
+        assert_eq!(0x18, iommu.read_byte(io_hardware_register::TIMER_DIV));
+        assert_eq!(0xFF, iommu.read_byte(io_hardware_register::OAM_DMA));
+
+        iommu.init(HardwareModel::Dmg, false);
+        assert_eq!(0xAB, iommu.read_byte(io_hardware_register::TIMER_DIV));
+        assert_eq!(0x7E, iommu.read_byte(io_hardware_register::SERIAL_CONTROL));
+
+        iommu.init(HardwareModel::Cgb, false);
+        assert_eq!(0x7F, iommu.read_byte(io_hardware_register::SERIAL_CONTROL));
+        assert_eq!(0x00, iommu.read_byte(io_hardware_register::OAM_DMA));
+        assert!(!ppu.borrow().is_cgb_mode());
+    }
+
+    #[test]
+    fn boot_rom_mapping_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+
+        iommu.set_boot_rom(vec![0xAA; memory::DMG_BOOT_ROM_SIZE]);
+        iommu.init(HardwareModel::Dmg, false);
+        assert_eq!(0xAA, iommu.read_byte(0x0000));
+        assert_eq!(0xAA, iommu.read_byte(0x00FF));
+        assert_eq!(0, iommu.read_byte(io_hardware_register::LCD_CONTROL));
+
+        iommu.write_byte(io_hardware_register::BOOT_ROM_DISABLE, 0x00);
+        assert_eq!(0xAA, iommu.read_byte(0x0000));
+        iommu.write_byte(io_hardware_register::BOOT_ROM_DISABLE, 0x01);
+        assert!(!iommu.is_boot_rom_address(0x0000));
+    }
+
+    #[test]
+    fn cgb_boot_rom_mapping_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+
+        iommu.set_boot_rom(vec![0xBB; memory::CGB_BOOT_ROM_SIZE]);
+        iommu.init(HardwareModel::Cgb, true);
+        assert_eq!(0xBB, iommu.read_byte(0x0000));
+        assert_eq!(0xBB, iommu.read_byte(0x0200));
+        assert_eq!(0xBB, iommu.read_byte(0x08FF));
+        // Cartridge header
+        assert!(!iommu.is_boot_rom_address(0x0100));
+
+        // DMG cartridge
+        iommu.write_byte(io_hardware_register::KEY0, 0x04);
+        assert!(!ppu.borrow().is_cgb_mode());
+        assert_eq!(0, iommu.read_byte(io_hardware_register::KEY1));
+    }
+
+    #[test]
+    fn cgb_wram_bank_switch_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        iommu.init(HardwareModel::Cgb, true);
+        let bank_n_address = *address::WORKING_RAM_BANK_1_7.start();
+
+        for bank in 1..=7 {
+            iommu.write_byte(io_hardware_register::SVBK, bank);
+            iommu.write_byte(bank_n_address, bank * 0x10);
+        }
+        for bank in 1..=7 {
+            iommu.write_byte(io_hardware_register::SVBK, bank);
+            assert_eq!(bank * 0x10, iommu.read_byte(bank_n_address));
+            assert_eq!(
+                bank * 0x10,
+                iommu.read_byte(*address::ECHO_RAM_BANK_1_7.start())
+            );
+        }
+
+        // Bank 0 selects bank 1
+        iommu.write_byte(io_hardware_register::SVBK, 0);
+        assert_eq!(0xF9, iommu.read_byte(io_hardware_register::SVBK));
+        assert_eq!(0x10, iommu.read_byte(bank_n_address));
+
+        // DMG has a fixed bank 1
+        iommu.init(HardwareModel::Dmg, false);
+        iommu.write_byte(io_hardware_register::SVBK, 7);
+        assert_eq!(0x10, iommu.read_byte(bank_n_address));
+    }
+
+    #[test]
+    fn cgb_double_speed_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        iommu.init(HardwareModel::Cgb, true);
+
+        assert!(!iommu.speed_switch());
+        assert_eq!(0x7E, iommu.read_byte(io_hardware_register::KEY1));
+
+        iommu.write_byte(io_hardware_register::KEY1, 0x01);
+        assert_eq!(0x7F, iommu.read_byte(io_hardware_register::KEY1));
+        assert!(iommu.speed_switch());
+        assert!(iommu.is_double_speed());
+        assert_eq!(0xFE, iommu.read_byte(io_hardware_register::KEY1));
+
+        // PPU gets half of the CPU cycles: one scanline takes 912 CPU cycles
+        iommu.write_byte(io_hardware_register::LY, 0);
+        for _ in 0..(912 / 4) {
+            iommu.process(4);
+        }
+        assert_eq!(1, iommu.read_byte(io_hardware_register::LY));
+    }
+
+    #[test]
+    fn oam_dma_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        iommu.init(HardwareModel::Dmg, false);
+        let oam = *address::OAM.start();
+
+        for i in 0..memory::VOAM_SIZE as u16 {
+            iommu.write_byte(0xC100 + i, i as u8 + 1);
+        }
+        iommu.write_byte(*address::HIGH_RAM.start(), 0x42);
+        iommu.write_byte(io_hardware_register::OAM_DMA, 0xC1);
+        assert_eq!(0xC1, iommu.read_byte(io_hardware_register::OAM_DMA));
+
+        // Bus is still free until the end of the start-up M-cycle
+        assert_eq!(0x01, iommu.read_byte(0xC100));
+        iommu.process(4);
+        assert_eq!(0xFF, iommu.read_byte(0xC100));
+
+        iommu.process(4);
+        assert_eq!(0xFF, iommu.read_byte(0xC100));
+        assert_eq!(0xFF, iommu.read_byte(oam));
+        iommu.write_byte(0xC100, 0x00);
+        // HRAM and I/O registers stay available
+        assert_eq!(0x42, iommu.read_byte(*address::HIGH_RAM.start()));
+        assert_eq!(0xC1, iommu.read_byte(io_hardware_register::OAM_DMA));
+
+        for _ in 0..(memory::VOAM_SIZE - 2) {
+            iommu.process(4);
+        }
+        assert_eq!(0xFF, iommu.read_byte(0xC100));
+        iommu.process(4);
+        assert_eq!(0x01, iommu.read_byte(0xC100));
+        for i in 0..memory::VOAM_SIZE as u16 {
+            assert_eq!(i as u8 + 1, iommu.read_byte(oam + i));
+        }
+    }
+
+    #[test]
+    fn oam_dma_restart_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        iommu.init(HardwareModel::Dmg, false);
+        let oam = *address::OAM.start();
+
+        for i in 0..memory::VOAM_SIZE as u16 {
+            iommu.write_byte(0xC000 + i, 0x11);
+            iommu.write_byte(0xC100 + i, 0x22);
+        }
+        iommu.write_byte(io_hardware_register::OAM_DMA, 0xC0);
+        for _ in 0..(1 + 80) {
+            iommu.process(4);
+        }
+        iommu.write_byte(io_hardware_register::OAM_DMA, 0xC1);
+        // The old transfer keeps the bus during the start-up of the new one
+        iommu.process(4);
+        assert_eq!(0xFF, iommu.read_byte(0xC000));
+
+        for _ in 0..(memory::VOAM_SIZE - 1) {
+            iommu.process(4);
+        }
+        assert_eq!(0xFF, iommu.read_byte(0xC000));
+        iommu.process(4);
+        for i in 0..memory::VOAM_SIZE as u16 {
+            assert_eq!(0x22, iommu.read_byte(oam + i));
+        }
+    }
+
+    fn setup_vram_dma(iommu: &mut Iommu, source: u16, destination: u16) {
+        iommu.write_byte(io_hardware_register::HDMA1, source.rotate_right(8) as u8);
+        iommu.write_byte(io_hardware_register::HDMA2, source as u8);
+        iommu.write_byte(
+            io_hardware_register::HDMA3,
+            destination.rotate_right(8) as u8,
+        );
+        iommu.write_byte(io_hardware_register::HDMA4, destination as u8);
+    }
+
+    #[test]
+    fn general_purpose_vram_dma_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        iommu.init(HardwareModel::Cgb, true);
+        // LCD off, VRAM is always accessible
+        iommu.write_byte(io_hardware_register::LCD_CONTROL, 0x00);
+
+        for i in 0..0x30 {
+            iommu.write_byte(0xC000 + i, i as u8 + 1);
+        }
+        // Lower 4 bits of the addresses are ignored
+        setup_vram_dma(&mut iommu, 0xC00F, 0x810F);
+        iommu.write_byte(io_hardware_register::HDMA5, 0x02);
+
+        for i in 0..0x30 {
+            assert_eq!(i as u8 + 1, iommu.read_byte(0x8100 + i));
+        }
+        assert_eq!(0xFF, iommu.read_byte(io_hardware_register::HDMA5));
+        assert_eq!(3 * 32, iommu.take_dma_stall_cycles());
+        assert_eq!(0, iommu.take_dma_stall_cycles());
+    }
+
+    #[test]
+    fn hblank_vram_dma_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        iommu.init(HardwareModel::Cgb, true);
+
+        for i in 0..0x40 {
+            iommu.write_byte(0xC000 + i, 0xA0 + i as u8);
+        }
+        iommu.write_byte(io_hardware_register::LY, 0);
+        setup_vram_dma(&mut iommu, 0xC000, 0x9000);
+        iommu.write_byte(io_hardware_register::HDMA5, 0x83);
+        assert_eq!(0x03, iommu.read_byte(io_hardware_register::HDMA5));
+        assert_eq!(0, iommu.take_dma_stall_cycles());
+
+        // One block per HBlank
+        for _ in 0..(456 / 4) {
+            iommu.process(4);
+        }
+        assert_eq!(0x02, iommu.read_byte(io_hardware_register::HDMA5));
+        assert_eq!(32, iommu.take_dma_stall_cycles());
+        for _ in 0..(456 / 4) {
+            iommu.process(4);
+        }
+        assert_eq!(0x01, iommu.read_byte(io_hardware_register::HDMA5));
+
+        // Terminated with the remaining length still readable
+        iommu.write_byte(io_hardware_register::HDMA5, 0x00);
+        assert_eq!(0x81, iommu.read_byte(io_hardware_register::HDMA5));
+        for _ in 0..(456 / 4) {
+            iommu.process(4);
+        }
+        assert_eq!(0x81, iommu.read_byte(io_hardware_register::HDMA5));
+
+        iommu.write_byte(io_hardware_register::LCD_CONTROL, 0x00);
+        for i in 0..0x20 {
+            assert_eq!(0xA0 + i as u8, iommu.read_byte(0x9000 + i));
+        }
+        assert_eq!(0x00, iommu.read_byte(0x9020));
+    }
+
+    #[test]
+    fn memory_hook_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let hook_log = log.clone();
+        let id = iommu.add_memory_hook(
+            0xC000..=0xC0FF,
+            WatchKind::Write,
+            Box::new(move |access| {
+                hook_log.borrow_mut().push(*access);
+                HookAction::Continue
+            }),
+        );
+        iommu.write_byte(0xC010, 0x12);
+        iommu.write_byte(0xC010, 0x34);
+        iommu.write_byte(0xC100, 0x56);
+        let _ = iommu.read_byte(0xC010);
+
+        assert_eq!(2, log.borrow().len());
+        assert_eq!(
+            MemoryAccess {
+                address: 0xC010,
+                value: 0x34,
+                old_value: 0x12,
+                is_write: true
+            },
+            log.borrow()[1]
+        );
+        assert_eq!(None, iommu.take_watchpoint_hit());
+
+        assert!(iommu.remove_memory_hook(id));
+        assert!(!iommu.remove_memory_hook(id));
+        assert!(!iommu.instrumented);
+        iommu.write_byte(0xC010, 0x78);
+        assert_eq!(2, log.borrow().len());
+
+        iommu.add_memory_hook(
+            0xFF80..=0xFFFE,
+            WatchKind::Read,
+            Box::new(|access| {
+                if access.value == 0xAA {
+                    HookAction::Break
+                } else {
+                    HookAction::Continue
+                }
+            }),
+        );
+        iommu.write_byte(0xFF90, 0xAA);
+        let _ = iommu.read_byte(0xFF80);
+        assert_eq!(None, iommu.take_watchpoint_hit());
+        let _ = iommu.read_byte(0xFF90);
+        assert_eq!(
+            Some(0xFF90),
+            iommu.take_watchpoint_hit().map(|hit| hit.address)
+        );
+    }
+
+    #[test]
+    fn change_watchpoint_test() {
+        let cartridge = Rc::new(RefCell::new(Cartridge::default()));
+        let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
+        let joypad = Rc::new(RefCell::new(JoypadInput::default()));
+        let mut iommu = Iommu::new(cartridge.clone(), ppu.clone(), joypad.clone());
+
+        iommu.write_byte(0xC0A0, 0x05);
+        iommu.set_watchpoint(0xC0A0, WatchKind::Change);
+        iommu.write_byte(0xC0A0, 0x05);
+        assert_eq!(None, iommu.take_watchpoint_hit());
+
+        iommu.write_byte(0xC0A0, 0x06);
+        let hit = iommu.take_watchpoint_hit().unwrap();
+        assert_eq!([0x06, 0x05], [hit.value, hit.old_value]);
+        assert!(hit.is_write);
+    }
 }
@@ -1,27 +1,52 @@
 mod constants;
 mod cpu;
 mod cpu_data;
+pub mod debugger;
+mod disassembler;
 pub mod emulator_constants;
+pub mod error;
+pub mod frame_pacer;
+pub mod gdb_stub;
 mod instructions;
 mod iommu;
+pub mod link;
 mod peripheral;
+mod png;
+pub mod printer;
+mod save_state;
+pub mod serial_device;
 
+use constants::gb_memory_map::memory;
 use cpu::Cpu;
-use emulator_constants::GameBoyKeys;
-use iommu::IOMMU;
-use peripheral::{cartridge::Cartridge, joypad::JoypadInput, ppu::PictureProcessingUnit};
+use debugger::{CpuRegisters, HookAction, HookId, MemoryAccess, WatchKind};
+use emulator_constants::{clock, GameBoyKeys, HardwareModel, PpuRenderer};
+use error::{EmulatorError, Result};
+use iommu::Iommu;
+use link::{LinkMessage, TcpLink};
+use peripheral::{
+    cartridge::Cartridge, joypad::JoypadInput, ppu::PictureProcessingUnit,
+    serial::SerialDataTransfer,
+};
+use save_state::{Snapshot, StateReader, StateWriter};
+use serial_device::{Disconnected, SerialDevice};
 use std::cell::RefCell;
-use std::io::Write;
+use std::ops::RangeInclusive;
 use std::path::Path;
 use std::rc::Rc;
-use std::{thread, time};
 
 pub struct GameBoyEmulator {
     cartridge: Rc<RefCell<Cartridge>>,
     ppu: Rc<RefCell<PictureProcessingUnit>>,
     pub joypad: Rc<RefCell<JoypadInput>>,
-    iommu: Rc<RefCell<IOMMU>>,
+    iommu: Rc<RefCell<Iommu>>,
     cpu: Cpu,
+    // Chosen from the cartridge and the boot ROM when not given
+    hardware_model: Option<HardwareModel>,
+    rumble_callback: Option<Box<dyn FnMut(bool)>>,
+    link: Option<TcpLink>,
+    link_poll_cycles: u32,
+    frames_since_battery_save: u32,
+    frame_cycles_overrun: u32,
 }
 
 impl Default for GameBoyEmulator {
@@ -32,33 +57,150 @@ impl Default for GameBoyEmulator {
 
 impl GameBoyEmulator {
     pub fn new() -> Self {
+        Self::create(None)
+    }
+
+    /// # with_hardware_model
+    /// Emulates the given console instead of choosing DMG or CGB from the cartridge header
+    pub fn with_hardware_model(model: HardwareModel) -> Self {
+        Self::create(Some(model))
+    }
+
+    fn create(hardware_model: Option<HardwareModel>) -> Self {
         let cartridge = Rc::new(RefCell::new(Cartridge::default()));
         let ppu = Rc::new(RefCell::new(PictureProcessingUnit::new()));
         let joypad = Rc::new(RefCell::new(JoypadInput::default()));
-        let iommu = Rc::new(RefCell::new(IOMMU::new(
+        let iommu = Rc::new(RefCell::new(Iommu::new(
             cartridge.clone(),
             ppu.clone(),
             joypad.clone(),
         )));
 
-        iommu.borrow_mut().init();
+        let cpu = Cpu::new(iommu.clone());
 
-        let mut cpu = Cpu::new(iommu.clone());
-        cpu.init();
-
-        Self {
+        let mut emulator = Self {
             cartridge,
             ppu,
             joypad,
             iommu,
             cpu,
-        }
+            hardware_model,
+            rumble_callback: None,
+            link: None,
+            link_poll_cycles: 0,
+            frames_since_battery_save: 0,
+            frame_cycles_overrun: 0,
+        };
+        emulator.power_on();
+        emulator
     }
 
     /// # load_cartridge
-    pub fn load_cartridge(&mut self, cartridge_path: &str) -> std::io::Result<()> {
+    /// Reports missing files, broken headers and unsupported cartridges.
+    /// The machine starts in CGB mode when the cartridge supports it.
+    pub fn load_cartridge(&mut self, cartridge_path: &str) -> Result<()> {
         let path = Path::new(cartridge_path);
         self.cartridge.borrow_mut().load(path)?;
+        self.power_on();
+        Ok(())
+    }
+
+    /// # load_boot_rom
+    /// DMG/MGB (256 bytes) or CGB (2304 bytes) boot ROM, the machine restarts from 0x0000.
+    /// Without it the emulation starts at 0x0100 with the post-boot register values.
+    pub fn load_boot_rom(&mut self, boot_rom_path: &str) -> Result<()> {
+        let boot_rom = std::fs::read(boot_rom_path)?;
+        if boot_rom.len() != memory::DMG_BOOT_ROM_SIZE
+            && boot_rom.len() != memory::CGB_BOOT_ROM_SIZE
+        {
+            return Err(EmulatorError::InvalidBootRomSize(boot_rom.len()));
+        }
+        self.iommu.borrow_mut().set_boot_rom(boot_rom);
+        self.power_on();
+        Ok(())
+    }
+
+    /// # hardware_model
+    /// Emulated console, DMG or CGB from the boot ROM and the cartridge when not chosen
+    pub fn hardware_model(&self) -> HardwareModel {
+        if let Some(model) = self.hardware_model {
+            return model;
+        }
+        let is_cgb_detected = match self.iommu.borrow().boot_rom_size() {
+            0 => self.cartridge.borrow().is_cgb_supported(),
+            boot_rom_size => boot_rom_size == memory::CGB_BOOT_ROM_SIZE,
+        };
+        if is_cgb_detected {
+            HardwareModel::Cgb
+        } else {
+            HardwareModel::Dmg
+        }
+    }
+
+    fn power_on(&mut self) {
+        let model = self.hardware_model();
+        let boot_rom_size = self.iommu.borrow().boot_rom_size();
+        // CGB boot ROM switches to the DMG compatibility mode itself
+        let cgb_mode =
+            model.is_cgb() && (boot_rom_size != 0 || self.cartridge.borrow().is_cgb_supported());
+        self.iommu.borrow_mut().init(model, cgb_mode);
+        if boot_rom_size == 0 {
+            self.cpu.init(model, cgb_mode);
+        } else {
+            self.cpu.init_for_boot_rom();
+        }
+    }
+
+    /// # is_cgb_mode
+    pub fn is_cgb_mode(&self) -> bool {
+        self.ppu.borrow().is_cgb_mode()
+    }
+
+    /// # save_battery_ram
+    /// Flushes battery-backed cartridge RAM to `<rom>.sav`
+    pub fn save_battery_ram(&mut self) -> Result<()> {
+        self.frames_since_battery_save = 0;
+        self.cartridge.borrow_mut().save_battery_ram()?;
+        Ok(())
+    }
+
+    /// # save_state
+    /// Snapshot of the whole machine as a versioned binary blob.
+    /// The ROM is not a part of the snapshot, the same cartridge has to be loaded to restore it.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        self.cpu.save_state(&mut writer);
+        self.iommu.borrow().save_state(&mut writer);
+        self.ppu.borrow().save_state(&mut writer);
+        self.joypad.borrow().save_state(&mut writer);
+        self.cartridge.borrow().save_state(&mut writer);
+        writer.into_inner()
+    }
+
+    /// # load_state
+    /// Restores the snapshot made by `save_state`. On error the machine is left untouched.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<()> {
+        let backup_state = self.save_state();
+        if let Err(error) = self.restore_state(state) {
+            // Backup made from the running machine is always valid
+            let _ = self.restore_state(&backup_state);
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> std::io::Result<()> {
+        let mut reader = StateReader::new(state)?;
+        self.cpu.load_state(&mut reader)?;
+        self.iommu.borrow_mut().load_state(&mut reader)?;
+        self.ppu.borrow_mut().load_state(&mut reader)?;
+        self.joypad.borrow_mut().load_state(&mut reader)?;
+        self.cartridge.borrow_mut().load_state(&mut reader)?;
+        if !reader.is_finished() {
+            return Err(save_state::invalid_data_error(
+                "Save state has unexpected trailing data",
+            ));
+        }
         Ok(())
     }
 
@@ -73,24 +215,161 @@ impl GameBoyEmulator {
     }
 
     /// # emulate_step
-    /// One cpu step
-    pub fn emulate_step(&mut self) -> u32 {
+    /// One cpu step. Returns the number of cycles or `EmulatorError::CpuLocked` after an illegal opcode
+    pub fn emulate_step(&mut self) -> Result<u32> {
         // 0,000000238 * cycle
-        self.cpu.process()
+        let cycles = self.cpu.process();
+        self.rumble_state_handling();
+        if let Ok(cycles) = &cycles {
+            self.link_handling(*cycles);
+        }
+        cycles
     }
 
-    /// # emulate_frame
-    /// A frame consists of 154 scanlines. A dot = 4194304 Hhz
-    /// Frame 1/4194304 (0,000000238) * 456 * 154 = 0,016742706 = 16,74 ms <--60 fps
-    pub fn emulate_frame(&mut self, frame_buffer: &mut [u32]) {
-        let start_time_of_emulation_frame = time::Instant::now();
-        const NUMBER_OF_CYCLES_PER_FRAME: u32 = 456 * 154;
-        let mut sum_of_processed_cycles: u32 = 0;
+    /// # is_cpu_locked
+    pub fn is_cpu_locked(&self) -> bool {
+        self.cpu.is_locked()
+    }
+
+    /// # set_rumble_callback
+    /// Callback is called with the new motor state every time a rumble cartridge turns it on or off
+    pub fn set_rumble_callback(&mut self, callback: impl FnMut(bool) + 'static) {
+        self.rumble_callback = Some(Box::new(callback));
+    }
 
-        while sum_of_processed_cycles < NUMBER_OF_CYCLES_PER_FRAME {
-            sum_of_processed_cycles += self.emulate_step();
+    fn rumble_state_handling(&mut self) {
+        let mut cartridge = self.cartridge.borrow_mut();
+        if !cartridge.rumble_change_req {
+            return;
         }
+        cartridge.rumble_change_req = false;
+        if let Some(callback) = self.rumble_callback.as_mut() {
+            callback(cartridge.is_rumble_on());
+        }
+    }
 
+    /// # connect_link
+    /// Link cable to another emulator, the transfers started with the internal clock wait for its reply
+    pub fn connect_link(&mut self, link: TcpLink) {
+        self.link = Some(link);
+        self.link_poll_cycles = 0;
+        self.iommu.borrow_mut().serial.set_link_connected(true);
+    }
+
+    /// # disconnect_link
+    /// Transfers receive 0xFF again, also done when the peer goes away
+    pub fn disconnect_link(&mut self) {
+        if self.link.take().is_some() {
+            self.iommu.borrow_mut().serial.set_link_connected(false);
+        }
+    }
+
+    pub fn is_link_connected(&self) -> bool {
+        self.link.is_some()
+    }
+
+    /// # connect_serial_device
+    /// Device on the link cable (printer, text capture...) instead of another emulator
+    pub fn connect_serial_device(&mut self, device: impl SerialDevice + 'static) {
+        self.disconnect_link();
+        self.iommu.borrow_mut().serial.set_device(Box::new(device));
+    }
+
+    /// # disconnect_serial_device
+    /// Returns the device, transfers receive 0xFF again
+    pub fn disconnect_serial_device(&mut self) -> Box<dyn SerialDevice> {
+        self.iommu
+            .borrow_mut()
+            .serial
+            .set_device(Box::new(Disconnected))
+    }
+
+    fn link_handling(&mut self, cycles: u32) {
+        // Once per bit period at the normal clock
+        const LINK_POLL_CYCLES: u32 = 512;
+        let Some(link) = self.link.as_mut() else {
+            return;
+        };
+        let mut iommu = self.iommu.borrow_mut();
+        let mut result = match iommu.serial.take_link_request() {
+            Some(data) => link.send(LinkMessage::Data(data)),
+            None => Ok(()),
+        };
+        self.link_poll_cycles += cycles;
+        if result.is_ok() && self.link_poll_cycles >= LINK_POLL_CYCLES {
+            self.link_poll_cycles = 0;
+            result = Self::link_receive(link, &mut iommu.serial);
+        }
+        drop(iommu);
+        if result.is_err() {
+            self.disconnect_link();
+        }
+    }
+
+    fn link_receive(link: &mut TcpLink, serial: &mut SerialDataTransfer) -> std::io::Result<()> {
+        while let Some(message) = link.receive()? {
+            match message {
+                LinkMessage::Data(data) => {
+                    let reply = serial.external_clock_transfer(data);
+                    link.send(LinkMessage::Reply(reply))?;
+                }
+                LinkMessage::Reply(data) => serial.set_link_reply(data),
+            }
+        }
+        Ok(())
+    }
+
+    /// # serial_link_exchange
+    /// Transfer started with the internal clock goes to the peer's serial unit right away
+    pub(crate) fn serial_link_exchange(&mut self, peer: &mut GameBoyEmulator) {
+        let mut iommu = self.iommu.borrow_mut();
+        if let Some(data) = iommu.serial.take_link_request() {
+            let reply = peer.iommu.borrow_mut().serial.external_clock_transfer(data);
+            iommu.serial.set_link_reply(reply);
+        }
+    }
+
+    pub(crate) fn set_serial_link_connected(&mut self, is_link_connected: bool) {
+        self.iommu
+            .borrow_mut()
+            .serial
+            .set_link_connected(is_link_connected);
+    }
+
+    /// # set_ppu_renderer
+    /// Scanline renderer is the default, the pixel FIFO one is slower but keeps raster effects
+    pub fn set_ppu_renderer(&mut self, renderer: PpuRenderer) {
+        self.ppu.borrow_mut().set_renderer(renderer);
+    }
+
+    /// # run_frame
+    /// Emulates one video frame (70224 cycles) as fast as possible and copies it to the frame buffer.
+    /// Cycles of the last instruction that cross the frame boundary are taken from the next frame,
+    /// so the frames keep the exact hardware length on average.
+    ///
+    /// No pacing is done here, see `frame_pacer::FramePacer` for the real time speed.
+    pub fn run_frame(&mut self, frame_buffer: &mut [u32]) -> Result<()> {
+        let mut sum_of_processed_cycles: u32 = self.frame_cycles_overrun;
+
+        while sum_of_processed_cycles < clock::CYCLES_PER_FRAME {
+            let cycles = self.emulate_step()?;
+            // The frame length does not change in double speed mode
+            sum_of_processed_cycles += if self.is_double_speed() {
+                cycles / 2
+            } else {
+                cycles
+            };
+        }
+        self.frame_cycles_overrun = sum_of_processed_cycles - clock::CYCLES_PER_FRAME;
+
+        self.copy_frame_buffer(frame_buffer);
+        self.battery_save_handling();
+        Ok(())
+    }
+
+    /// # copy_frame_buffer
+    /// Last picture of the PPU as 0xAARRGGBB pixels
+    pub fn copy_frame_buffer(&self, frame_buffer: &mut [u32]) {
         let mut frame_pixel_id: usize = 0;
 
         for ppu_pixel in self.ppu.borrow_mut().out_frame_buffer.iter() {
@@ -103,16 +382,24 @@ impl GameBoyEmulator {
                 frame_pixel_id += 1;
             }
         }
-        let end_of_processed_time = start_time_of_emulation_frame.elapsed();
+    }
 
-        if end_of_processed_time < time::Duration::from_micros(16743) {
-            let sleeping_time = time::Duration::from_micros(16743) - end_of_processed_time;
-            thread::sleep(sleeping_time);
-        }
+    /// # is_double_speed
+    /// CGB double speed, the CPU cycles of `emulate_step` count half
+    pub fn is_double_speed(&self) -> bool {
+        self.iommu.borrow().is_double_speed()
+    }
 
-        let fps = 1.0 / start_time_of_emulation_frame.elapsed().as_secs_f32();
-        print!("\rFPS:{:?} ", fps);
-        let _ = std::io::stdout().flush();
+    /// # battery_save_handling
+    /// Periodic flush of the changed save RAM, so a crash does not lose the game progress
+    fn battery_save_handling(&mut self) {
+        const NUMBER_OF_FRAMES_BETWEEN_SAVES: u32 = 60 * 5;
+        self.frames_since_battery_save += 1;
+        if self.frames_since_battery_save >= NUMBER_OF_FRAMES_BETWEEN_SAVES
+            && self.cartridge.borrow().is_save_required()
+        {
+            let _ = self.save_battery_ram();
+        }
     }
 
     pub fn button_pressed(&mut self, key: GameBoyKeys) {
@@ -129,11 +416,80 @@ impl GameBoyEmulator {
         self.cpu.debug_dump_regs().to_uppercase()
     }
 
-    pub fn serial_out(&self) -> String {
-        let mut out = String::new();
-        for i in self.iommu.borrow_mut().serial.test_out_data.iter() {
-            out.push(*i);
-        }
-        out
+    /// # disassemble
+    /// Instruction at the address and its length in bytes, e.g. (`JR NZ,$+5`, 2)
+    pub fn disassemble(&self, address: u16) -> (String, u16) {
+        disassembler::disassemble(&self.iommu.borrow(), address)
+    }
+
+    /// # cpu_registers
+    pub fn cpu_registers(&self) -> CpuRegisters {
+        self.cpu.debug_registers()
+    }
+
+    pub fn set_cpu_registers(&mut self, registers: &CpuRegisters) {
+        self.cpu.set_debug_registers(registers);
+    }
+
+    /// # peek_byte
+    /// Memory as seen by the CPU, without bus conflicts and watchpoints
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        self.iommu.borrow().peek_byte(address)
+    }
+
+    /// # poke_byte
+    /// CPU write without bus conflicts and watchpoints, ROM writes reach the MBC
+    pub fn poke_byte(&mut self, address: u16, value: u8) {
+        self.iommu.borrow_mut().poke_byte(address, value);
+    }
+
+    /// # set_watchpoint
+    /// CPU access of the address is reported by `take_watchpoint_hit`
+    pub fn set_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        self.iommu.borrow_mut().set_watchpoint(address, kind);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        self.iommu.borrow_mut().remove_watchpoint(address)
+    }
+
+    pub fn watchpoints(&self) -> Vec<(u16, WatchKind)> {
+        self.iommu.borrow().watchpoints().to_vec()
+    }
+
+    /// # add_memory_hook
+    /// Calls back on every matching CPU access to the range, e.g. to log the writes of a register.
+    /// A hook returning `HookAction::Break` stops the debugger like a watchpoint.
+    pub fn add_memory_hook(
+        &mut self,
+        range: RangeInclusive<u16>,
+        kind: WatchKind,
+        callback: impl FnMut(&MemoryAccess) -> HookAction + 'static,
+    ) -> HookId {
+        self.iommu
+            .borrow_mut()
+            .add_memory_hook(range, kind, Box::new(callback))
+    }
+
+    pub fn remove_memory_hook(&mut self, id: HookId) -> bool {
+        self.iommu.borrow_mut().remove_memory_hook(id)
+    }
+
+    /// # take_watchpoint_hit
+    /// First watchpoint or breaking hook hit since the last call
+    pub fn take_watchpoint_hit(&mut self) -> Option<MemoryAccess> {
+        self.iommu.borrow().take_watchpoint_hit()
+    }
+
+    /// # take_audio_samples
+    /// Stereo samples (left, right) generated since the last call at `audio::SAMPLE_RATE`
+    pub fn take_audio_samples(&mut self) -> Vec<(f32, f32)> {
+        self.iommu.borrow_mut().apu.take_samples()
+    }
+}
+
+impl Drop for GameBoyEmulator {
+    fn drop(&mut self) {
+        let _ = self.save_battery_ram();
     }
 }
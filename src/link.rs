@@ -0,0 +1,238 @@
+use crate::emulator_constants::clock;
+use crate::error;
+use crate::GameBoyEmulator;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+mod message_kind {
+    pub const DATA: u8 = 0x01;
+    pub const REPLY: u8 = 0x02;
+}
+
+const MESSAGE_SIZE: usize = 2;
+
+/// # LinkMessage
+/// Data is the byte of an internal clock transfer, Reply the byte the peer shifted out for it
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum LinkMessage {
+    Data(u8),
+    Reply(u8),
+}
+
+/// # TcpLink
+/// Link cable to another emulator over TCP. Every transfer started with the internal clock
+/// sends its byte, the peer answers with the content of its serial data register.
+pub struct TcpLink {
+    stream: TcpStream,
+    received: Vec<u8>,
+}
+
+impl TcpLink {
+    /// # listen
+    /// Waits for the peer on 127.0.0.1:port
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let (stream, _) = listener.accept()?;
+        Self::from_stream(stream)
+    }
+
+    /// # connect
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::from_stream(TcpStream::connect(address)?)
+    }
+
+    /// # from_stream
+    /// Stream is switched to the non-blocking mode, the emulation only polls it
+    pub fn from_stream(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            received: Vec::with_capacity(MESSAGE_SIZE),
+        })
+    }
+
+    pub(crate) fn send(&mut self, message: LinkMessage) -> io::Result<()> {
+        let bytes = match message {
+            LinkMessage::Data(data) => [message_kind::DATA, data],
+            LinkMessage::Reply(data) => [message_kind::REPLY, data],
+        };
+        let mut written = 0;
+        while written < bytes.len() {
+            match self.stream.write(&bytes[written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(length) => written += length,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => std::thread::yield_now(),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    /// # receive
+    /// None when no whole message arrived yet, error when the peer is gone
+    pub(crate) fn receive(&mut self) -> io::Result<Option<LinkMessage>> {
+        while self.received.len() < MESSAGE_SIZE {
+            let mut buffer = [0; MESSAGE_SIZE];
+            let missing = MESSAGE_SIZE - self.received.len();
+            match self.stream.read(&mut buffer[..missing]) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(length) => self.received.extend_from_slice(&buffer[..length]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(error) => return Err(error),
+            }
+        }
+        let message = match self.received[0] {
+            message_kind::DATA => LinkMessage::Data(self.received[1]),
+            message_kind::REPLY => LinkMessage::Reply(self.received[1]),
+            _ => return Err(io::ErrorKind::InvalidData.into()),
+        };
+        self.received.clear();
+        Ok(Some(message))
+    }
+}
+
+/// # LinkedPair
+/// Two emulators with a link cable between their serial units, stepped in lockstep in one thread.
+/// The emulator behind in time runs the next instruction and the transfers are exchanged
+/// right away, so the runs are deterministic.
+pub struct LinkedPair {
+    first: GameBoyEmulator,
+    second: GameBoyEmulator,
+    // Normal speed cycles, the double speed mode does not change the time
+    first_cycles: u64,
+    second_cycles: u64,
+}
+
+impl LinkedPair {
+    /// # new
+    /// TCP links of the emulators are disconnected, the cable replaces their serial devices
+    pub fn new(mut first: GameBoyEmulator, mut second: GameBoyEmulator) -> Self {
+        for gameboy in [&mut first, &mut second] {
+            gameboy.disconnect_link();
+            gameboy.set_serial_link_connected(true);
+        }
+        Self {
+            first,
+            second,
+            first_cycles: 0,
+            second_cycles: 0,
+        }
+    }
+
+    pub fn first(&self) -> &GameBoyEmulator {
+        &self.first
+    }
+
+    pub fn first_mut(&mut self) -> &mut GameBoyEmulator {
+        &mut self.first
+    }
+
+    pub fn second(&self) -> &GameBoyEmulator {
+        &self.second
+    }
+
+    pub fn second_mut(&mut self) -> &mut GameBoyEmulator {
+        &mut self.second
+    }
+
+    /// # into_inner
+    /// Unplugs the cable
+    pub fn into_inner(mut self) -> (GameBoyEmulator, GameBoyEmulator) {
+        self.first.set_serial_link_connected(false);
+        self.second.set_serial_link_connected(false);
+        (self.first, self.second)
+    }
+
+    /// # emulate_step
+    /// One instruction of the emulator behind, returns its cycles
+    pub fn emulate_step(&mut self) -> error::Result<u32> {
+        let (gameboy, gameboy_cycles) = if self.first_cycles <= self.second_cycles {
+            (&mut self.first, &mut self.first_cycles)
+        } else {
+            (&mut self.second, &mut self.second_cycles)
+        };
+        let cycles = gameboy.emulate_step()?;
+        *gameboy_cycles += if gameboy.is_double_speed() {
+            cycles / 2
+        } else {
+            cycles
+        } as u64;
+
+        self.first.serial_link_exchange(&mut self.second);
+        self.second.serial_link_exchange(&mut self.first);
+        Ok(cycles)
+    }
+
+    /// # run_frame
+    /// Both emulators run one video frame, see `GameBoyEmulator::copy_frame_buffer` for the screens
+    pub fn run_frame(&mut self) -> error::Result<()> {
+        let frame_end = self.first_cycles.min(self.second_cycles) + clock::CYCLES_PER_FRAME as u64;
+        while self.first_cycles < frame_end || self.second_cycles < frame_end {
+            self.emulate_step()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn linked_pair_lockstep_test() {
+        let mut first = GameBoyEmulator::new();
+        let mut second = GameBoyEmulator::new();
+        // Zeroed WRAM runs NOPs until JR $
+        for gameboy in [&mut first, &mut second] {
+            gameboy.poke_byte(0xC000 + 50, 0x18);
+            gameboy.poke_byte(0xC000 + 51, 0xFE);
+            let mut registers = gameboy.cpu_registers();
+            registers.pc = 0xC000;
+            gameboy.set_cpu_registers(&registers);
+        }
+        let mut linked_pair = LinkedPair::new(first, second);
+
+        for _ in 0..100 {
+            linked_pair.emulate_step().unwrap();
+            assert!(linked_pair.first_cycles.abs_diff(linked_pair.second_cycles) <= 4);
+        }
+        assert_eq!(0xC000 + 50, linked_pair.first().cpu_registers().pc);
+        assert_eq!(0xC000 + 50, linked_pair.second().cpu_registers().pc);
+
+        linked_pair.run_frame().unwrap();
+        assert!(linked_pair.first_cycles >= clock::CYCLES_PER_FRAME as u64);
+        assert!(linked_pair.second_cycles >= clock::CYCLES_PER_FRAME as u64);
+    }
+
+    #[test]
+    fn message_exchange_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut first = TcpLink::connect(listener.local_addr().unwrap()).unwrap();
+        let mut second = TcpLink::from_stream(listener.accept().unwrap().0).unwrap();
+
+        assert_eq!(None, second.receive().unwrap());
+        first.send(LinkMessage::Data(0x12)).unwrap();
+        first.send(LinkMessage::Reply(0x34)).unwrap();
+
+        let mut messages = Vec::new();
+        while messages.len() < 2 {
+            if let Some(message) = second.receive().unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(
+            vec![LinkMessage::Data(0x12), LinkMessage::Reply(0x34)],
+            messages
+        );
+
+        drop(first);
+        let disconnected = loop {
+            match second.receive() {
+                Ok(None) => continue,
+                result => break result,
+            }
+        };
+        assert!(disconnected.is_err());
+    }
+}
@@ -1,60 +1,396 @@
 //use argparse::ArgumentParser;
-use minifb::{Key, Window};
-use r_gb_emu::emulator_constants::{resolution, GameBoyKeys};
+mod input_config;
+
+use input_config::{HotkeyAction, InputConfig};
+use minifb::Window;
+use r_gb_emu::debugger::{DebugCommand, Debugger, RunTarget, StopReason};
+use r_gb_emu::emulator_constants::{resolution, HardwareModel, PpuRenderer};
+use r_gb_emu::frame_pacer::FramePacer;
+use r_gb_emu::gdb_stub::GdbStub;
+use r_gb_emu::link::TcpLink;
+use r_gb_emu::printer::GameBoyPrinter;
 use r_gb_emu::GameBoyEmulator;
+use std::io::Write;
+use std::process::ExitCode;
 
-fn keyboard_handle_event(window: &Window, gameboy: &mut GameBoyEmulator) {
-    let key_map: [(Key, GameBoyKeys); 8] = [
-        (minifb::Key::Right, GameBoyKeys::Right),
-        (minifb::Key::Up, GameBoyKeys::Up),
-        (minifb::Key::Left, GameBoyKeys::Left),
-        (minifb::Key::Down, GameBoyKeys::Down),
-        (minifb::Key::Z, GameBoyKeys::A),
-        (minifb::Key::X, GameBoyKeys::B),
-        (minifb::Key::Space, GameBoyKeys::Select),
-        (minifb::Key::Enter, GameBoyKeys::Start),
-    ];
-
-    for (frame_work_key, emulator_key) in key_map {
-        if window.is_key_pressed(frame_work_key, minifb::KeyRepeat::Yes) {
-            gameboy.button_pressed(emulator_key);
+fn keyboard_handle_event(window: &Window, gameboy: &mut GameBoyEmulator, input: &InputConfig) {
+    for (emulator_key, frame_work_keys) in input.buttons() {
+        if frame_work_keys.iter().any(|key| window.is_key_down(*key)) {
+            gameboy.button_pressed(*emulator_key);
         } else {
-            gameboy.button_released(emulator_key);
+            gameboy.button_released(*emulator_key);
+        }
+    }
+}
+
+/// # hotkey_handle_event
+/// Pause, save and load state. The state is kept in memory until the emulator quits.
+fn hotkey_handle_event(
+    window: &Window,
+    gameboy: &mut GameBoyEmulator,
+    input: &InputConfig,
+    is_paused: &mut bool,
+    saved_state: &mut Option<Vec<u8>>,
+) {
+    if input.is_action_pressed(window, HotkeyAction::Pause) {
+        *is_paused = !*is_paused;
+        print!("{}", if *is_paused { "\nPaused" } else { "\nResumed" });
+    }
+    if input.is_action_pressed(window, HotkeyAction::SaveState) {
+        *saved_state = Some(gameboy.save_state());
+        print!("\nState saved");
+    }
+    if input.is_action_pressed(window, HotkeyAction::LoadState) {
+        match saved_state.as_ref().map(|state| gameboy.load_state(state)) {
+            Some(Ok(())) => print!("\nState loaded"),
+            Some(Err(error)) => print!("\n * [Error] Cannot load the state: {}", error),
+            None => print!("\nNo state saved yet"),
+        }
+    }
+}
+fn window_refresh(window: &mut Window, gameboy: &mut GameBoyEmulator, frame_buffer: &mut [u32]) {
+    gameboy.copy_frame_buffer(frame_buffer);
+    if let Err(error) =
+        window.update_with_buffer(frame_buffer, resolution::SCREEN_W, resolution::SCREEN_H)
+    {
+        exit_on_window_error(gameboy, error);
+    }
+}
+
+/// # exit_on_window_error
+/// The window cannot be shown or updated, the battery RAM is saved before the emulator exits
+fn exit_on_window_error(gameboy: &mut GameBoyEmulator, error: minifb::Error) -> ! {
+    println!("\n * [Error] Window: {}", error);
+    if let Err(error) = gameboy.save_battery_ram() {
+        println!(" * [Error] Cannot write the save file: {}", error);
+    }
+    print_end_banner();
+    std::process::exit(1);
+}
+
+/// # print_end_banner
+/// Closes the console output on every exit path
+fn print_end_banner() {
+    println!(
+        "\x1b[96m=========================\n      ..::END::..      \n=========================\x1b[0m"
+    );
+}
+
+/// # debugger_continue
+/// Runs in real time with the window until a breakpoint, a watchpoint or the break hotkey (F12)
+fn debugger_continue(
+    debugger: &mut Debugger,
+    gameboy: &mut GameBoyEmulator,
+    window: &mut Window,
+    frame_buffer: &mut [u32],
+    input: &InputConfig,
+) {
+    println!("Running, press the break hotkey in the window to break");
+    let mut frame_pacer = FramePacer::default();
+    loop {
+        let stop_reason = debugger.run(gameboy, RunTarget::Frame);
+        window_refresh(window, gameboy, frame_buffer);
+        keyboard_handle_event(window, gameboy, input);
+
+        if !matches!(stop_reason, StopReason::TargetReached) {
+            println!("{}", debugger.stop_report(gameboy, &stop_reason));
+            return;
+        }
+        if !window.is_open() || input.is_action_down(window, HotkeyAction::Break) {
+            println!("Interrupted");
+            println!("{}", debugger.stop_report(gameboy, &stop_reason));
+            return;
+        }
+        frame_pacer.wait_for_next_frame();
+    }
+}
+
+/// # debugger_repl
+/// Reads the debugger commands from stdin, the window shows the last frame
+fn debugger_repl(
+    gameboy: &mut GameBoyEmulator,
+    window: &mut Window,
+    frame_buffer: &mut [u32],
+    input: &InputConfig,
+) {
+    let mut debugger = Debugger::new();
+    let mut last_line = String::from("step");
+    println!("Debugger, type help for the commands");
+    println!(
+        "{}",
+        debugger.execute(
+            gameboy,
+            &DebugCommand::Disassemble {
+                address: None,
+                count: 1
+            }
+        )
+    );
+
+    loop {
+        print!("(r_gb_emu) ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        // End of the input closes the debugger
+        if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let line = match line.trim() {
+            "" => last_line.clone(),
+            line => line.to_string(),
+        };
+
+        match line.parse::<DebugCommand>() {
+            Ok(DebugCommand::Quit) => break,
+            Ok(DebugCommand::Continue) => {
+                debugger_continue(&mut debugger, gameboy, window, frame_buffer, input)
+            }
+            Ok(command) => println!("{}", debugger.execute(gameboy, &command)),
+            Err(error) => {
+                println!("{}", error);
+                continue;
+            }
+        }
+        last_line = line;
+        window_refresh(window, gameboy, frame_buffer);
+    }
+}
+
+/// # gdb_serve
+/// The window shows the frames while the GDB client keeps the target running
+fn gdb_serve(
+    gameboy: &mut GameBoyEmulator,
+    mut window: Window,
+    mut frame_buffer: Vec<u32>,
+    port: u16,
+    input: InputConfig,
+) -> ExitCode {
+    println!(
+        "Waiting for GDB on 127.0.0.1:{} (target remote :{})",
+        port, port
+    );
+    let mut gdb_stub = GdbStub::new();
+    let mut frame_pacer = FramePacer::default();
+    gdb_stub.set_frame_callback(move |gameboy| {
+        window_refresh(&mut window, gameboy, frame_buffer.as_mut_slice());
+        keyboard_handle_event(&window, gameboy, &input);
+        frame_pacer.wait_for_next_frame();
+    });
+    match gdb_stub.listen(gameboy, port) {
+        Ok(()) => {
+            println!("GDB client disconnected");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            println!(" * [Error] GDB connection: {}", error);
+            ExitCode::FAILURE
         }
     }
 }
+
+struct InputArgs {
+    rom_path: String,
+    boot_rom_path: String,
+    model: String,
+    pixel_fifo: bool,
+    debug: bool,
+    gdb_port: u16,
+    link_listen_port: u16,
+    link_connect_address: String,
+    printer_directory: String,
+    input_config_path: String,
+    key_overrides: Vec<String>,
+}
+
 /// # parse_input_args
 /// Parse following input:
-/// ./r_gb_emu --rom /r_gb_emu/roms/t.gb
-fn parse_input_args() -> String {
-    let mut rom_path = String::new();
+/// ./r_gb_emu --rom /r_gb_emu/roms/t.gb [--boot-rom /r_gb_emu/roms/dmg_boot.bin] [--model cgb] [--pixel-fifo] [--debug] [--gdb 2159]
+/// [--link-listen 5555 | --link-connect 127.0.0.1:5555 | --printer /r_gb_emu/prints]
+/// [--input-config keys.ini] [--bind a=J,K ...]
+fn parse_input_args() -> InputArgs {
+    let mut input_args = InputArgs {
+        rom_path: String::new(),
+        boot_rom_path: String::new(),
+        model: String::new(),
+        pixel_fifo: false,
+        debug: false,
+        gdb_port: 0,
+        link_listen_port: 0,
+        link_connect_address: String::new(),
+        printer_directory: String::new(),
+        input_config_path: String::new(),
+        key_overrides: Vec::new(),
+    };
     {
         let mut arg_parser = argparse::ArgumentParser::new();
         arg_parser.set_description("Gameboy Emulator");
+        arg_parser.refer(&mut input_args.rom_path).add_option(
+            &["--rom"],
+            argparse::Store,
+            "Rom path",
+        );
+        arg_parser.refer(&mut input_args.boot_rom_path).add_option(
+            &["--boot-rom"],
+            argparse::Store,
+            "DMG/MGB/CGB boot ROM path, skipped when not given",
+        );
+        arg_parser.refer(&mut input_args.model).add_option(
+            &["--model"],
+            argparse::Store,
+            "Hardware model: dmg0, dmg, mgb, sgb, sgb2, cgb or agb (from the cartridge by default)",
+        );
+        arg_parser.refer(&mut input_args.pixel_fifo).add_option(
+            &["--pixel-fifo"],
+            argparse::StoreTrue,
+            "Use the pixel FIFO renderer (raster effects)",
+        );
+        arg_parser.refer(&mut input_args.debug).add_option(
+            &["--debug"],
+            argparse::StoreTrue,
+            "Start in the command line debugger",
+        );
+        arg_parser.refer(&mut input_args.gdb_port).add_option(
+            &["--gdb"],
+            argparse::Store,
+            "Wait for a GDB remote client on the local TCP port",
+        );
         arg_parser
-            .refer(&mut rom_path)
-            .add_option(&["--rom"], argparse::Store, "Rom path");
+            .refer(&mut input_args.link_listen_port)
+            .add_option(
+                &["--link-listen"],
+                argparse::Store,
+                "Wait for the link cable peer on the local TCP port",
+            );
+        arg_parser
+            .refer(&mut input_args.link_connect_address)
+            .add_option(
+                &["--link-connect"],
+                argparse::Store,
+                "Connect the link cable to the peer at host:port",
+            );
+        arg_parser
+            .refer(&mut input_args.printer_directory)
+            .add_option(
+                &["--printer"],
+                argparse::Store,
+                "Connect the Game Boy Printer, printouts are written to the directory",
+            );
+        arg_parser
+            .refer(&mut input_args.input_config_path)
+            .add_option(
+                &["--input-config"],
+                argparse::Store,
+                "Key bindings file, r_gb_emu.ini in the working directory is used when it exists",
+            );
+        arg_parser.refer(&mut input_args.key_overrides).add_option(
+            &["--bind"],
+            argparse::Collect,
+            "Binding over the config file, e.g. a=J,K or save_state=F2 (repeatable)",
+        );
         arg_parser.parse_args_or_exit();
     }
-    rom_path
+    input_args
+}
+
+/// # load_input_config
+/// Default bindings, the config file and the command line bindings over it
+fn load_input_config(path: &str, overrides: &[String]) -> Result<InputConfig, String> {
+    const DEFAULT_CONFIG_PATH: &str = "r_gb_emu.ini";
+    let mut input_config = if !path.is_empty() {
+        InputConfig::load(path)?
+    } else if std::path::Path::new(DEFAULT_CONFIG_PATH).exists() {
+        InputConfig::load(DEFAULT_CONFIG_PATH)?
+    } else {
+        InputConfig::default()
+    };
+    for entry in overrides {
+        input_config
+            .apply_override(entry)
+            .map_err(|error| format!("--bind {}: {}", entry, error))?;
+    }
+    Ok(input_config)
 }
 
-fn main() {
-    let rom_path = parse_input_args();
+fn main() -> ExitCode {
+    let input_args = parse_input_args();
+    let rom_path = input_args.rom_path;
 
     println!("\x1b[94m=========================\n..::Gameboy Emulator::..\n=========================\x1b[0m");
 
-    let mut gameboy = GameBoyEmulator::default();
-    let status = gameboy.load_cartridge(&rom_path);
-    if status.is_err() {
+    let input_config =
+        match load_input_config(&input_args.input_config_path, &input_args.key_overrides) {
+            Ok(input_config) => input_config,
+            Err(error) => {
+                println!(" * [Error] Key bindings: {}", error);
+                print_end_banner();
+                return ExitCode::FAILURE;
+            }
+        };
+
+    let mut gameboy = if input_args.model.is_empty() {
+        GameBoyEmulator::default()
+    } else {
+        match input_args.model.parse::<HardwareModel>() {
+            Ok(model) => GameBoyEmulator::with_hardware_model(model),
+            Err(error) => {
+                println!(" * [Error] {}", error);
+                print_end_banner();
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    if input_args.pixel_fifo {
+        gameboy.set_ppu_renderer(PpuRenderer::PixelFifo);
+    }
+    if let Err(error) = gameboy.load_cartridge(&rom_path) {
+        println!(" * [Error] Cannot load the ROM {}: {}", rom_path, error);
+        print_end_banner();
+        return ExitCode::FAILURE;
+    }
+    if !input_args.boot_rom_path.is_empty() {
+        if let Err(error) = gameboy.load_boot_rom(&input_args.boot_rom_path) {
+            println!(
+                " * [Error] Cannot load the boot ROM {}: {}",
+                input_args.boot_rom_path, error
+            );
+            print_end_banner();
+            return ExitCode::FAILURE;
+        }
+    }
+    let link = if input_args.link_listen_port != 0 {
         println!(
-            " * [Error] Cannot open the following ROM path: {} ",
-            rom_path
+            "Waiting for the link cable peer on 127.0.0.1:{}",
+            input_args.link_listen_port
         );
+        Some(TcpLink::listen(input_args.link_listen_port))
+    } else if !input_args.link_connect_address.is_empty() {
+        Some(TcpLink::connect(input_args.link_connect_address.as_str()))
+    } else {
+        None
+    };
+    match link {
+        Some(Ok(link)) => {
+            println!("Link cable connected");
+            gameboy.connect_link(link);
+        }
+        Some(Err(error)) => {
+            println!(" * [Error] Link cable: {}", error);
+            print_end_banner();
+            return ExitCode::FAILURE;
+        }
+        None => (),
+    }
+    if !input_args.printer_directory.is_empty() {
         println!(
-            "\x1b[96m=========================\n      ..::END::..      \n=========================\x1b[0m"
+            "Game Boy Printer connected, printouts go to {}",
+            input_args.printer_directory
         );
-        return;
+        let mut printer = GameBoyPrinter::new(&input_args.printer_directory);
+        printer.set_printout_callback(|printout| match printout {
+            Ok(path) => println!("\nPrinted {}", path.display()),
+            Err(error) => println!("\n * [Error] Cannot write the printout: {}", error),
+        });
+        gameboy.connect_serial_device(printer);
     }
     gameboy.show_cartridge_status();
     println!("\x1b[93mEmulation starts...\x1b[0m");
@@ -68,26 +404,74 @@ fn main() {
     };
     let window_name = String::from("r_gb_emu - ") + &gameboy.get_cartridge_name();
 
-    let mut window = Window::new(
+    let mut window = match Window::new(
         &window_name,
         resolution::SCREEN_W,
         resolution::SCREEN_H,
         window_option,
-    )
-    .unwrap_or_else(|e| {
-        panic!("{}", e);
-    });
+    ) {
+        Ok(window) => window,
+        Err(error) => exit_on_window_error(&mut gameboy, error),
+    };
+
+    let mut exit_code = ExitCode::SUCCESS;
+    if input_args.debug {
+        debugger_repl(
+            &mut gameboy,
+            &mut window,
+            frame_buffer.as_mut_slice(),
+            &input_config,
+        );
+    } else if input_args.gdb_port != 0 {
+        exit_code = gdb_serve(
+            &mut gameboy,
+            window,
+            frame_buffer,
+            input_args.gdb_port,
+            input_config,
+        );
+    } else {
+        let mut frame_pacer = FramePacer::default();
+        let mut is_paused = false;
+        let mut saved_state = None;
+
+        while window.is_open() && !input_config.is_action_down(&window, HotkeyAction::Quit) {
+            if !is_paused {
+                if let Err(error) = gameboy.run_frame(frame_buffer.as_mut_slice()) {
+                    println!("\n * [Error] {}", error);
+                    exit_code = ExitCode::FAILURE;
+                    break;
+                }
+            }
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        gameboy.emulate_frame(frame_buffer.as_mut_slice());
+            if let Err(error) =
+                window.update_with_buffer(&frame_buffer, resolution::SCREEN_W, resolution::SCREEN_H)
+            {
+                exit_on_window_error(&mut gameboy, error);
+            }
 
-        window
-            .update_with_buffer(&frame_buffer, resolution::SCREEN_W, resolution::SCREEN_H)
-            .unwrap();
+            keyboard_handle_event(&window, &mut gameboy, &input_config);
+            hotkey_handle_event(
+                &window,
+                &mut gameboy,
+                &input_config,
+                &mut is_paused,
+                &mut saved_state,
+            );
 
-        keyboard_handle_event(&window, &mut gameboy);
+            if !input_config.is_action_down(&window, HotkeyAction::Turbo) {
+                frame_pacer.wait_for_next_frame();
+            }
+            print!("\rFPS:{:?} ", frame_pacer.fps());
+            let _ = std::io::stdout().flush();
+        }
     }
-    println!(
-        "\n\x1b[96m=========================\n      ..::END::..      \n=========================\x1b[0m"
-    );
+
+    if let Err(error) = gameboy.save_battery_ram() {
+        println!("\n * [Error] Cannot write the save file: {}", error);
+        exit_code = ExitCode::FAILURE;
+    }
+    println!();
+    print_end_banner();
+    exit_code
 }
@@ -0,0 +1,1057 @@
+use self::channel::{NoiseChannel, SquareChannel, WaveChannel};
+use super::{HardwareAccessible, IoWorkingCycle};
+use crate::constants::gb_memory_map::{address, address::io_hardware_register, memory};
+use crate::emulator_constants::{audio, clock};
+use crate::save_state::{Snapshot, StateReader, StateWriter};
+use std::collections::VecDeque;
+
+mod sound_setup {
+    // 512 Hz
+    pub const FRAME_SEQUENCER_CLOCK_DIV: u32 = 8192;
+    pub const NUMBER_OF_REGISTERS: usize = 0x17;
+    pub const WAVE_RAM_SIZE: usize = 0x10;
+
+    /// Bits which are not readable return 1
+    pub const REGISTER_READ_MASK: [u8; NUMBER_OF_REGISTERS] = [
+        0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+        0xFF, 0x3F, 0x00, 0xFF, 0xBF, // ----, NR21-NR24
+        0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+        0xFF, 0xFF, 0x00, 0x00, 0xBF, // ----, NR41-NR44
+        0x00, 0x00, 0x70, // NR50-NR52
+    ];
+}
+
+mod channel {
+    use crate::save_state::{Snapshot, StateReader, StateWriter};
+
+    const DUTY_WAVEFORM: [[u8; 8]; 4] = [
+        [0, 0, 0, 0, 0, 0, 0, 1], // 12.5%
+        [1, 0, 0, 0, 0, 0, 0, 1], // 25%
+        [1, 0, 0, 0, 0, 1, 1, 1], // 50%
+        [0, 1, 1, 1, 1, 1, 1, 0], // 75%
+    ];
+    const NOISE_DIVISOR: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+    /// # Length Counter
+    /// When it reaches zero the channel is turned off. Clocked at 256 Hz.
+    #[derive(Default)]
+    pub struct LengthCounter {
+        pub enabled: bool,
+        counter: u16,
+        max_length: u16,
+    }
+
+    impl LengthCounter {
+        pub fn new(max_length: u16) -> Self {
+            Self {
+                enabled: false,
+                counter: 0,
+                max_length,
+            }
+        }
+
+        pub fn load(&mut self, length_data: u8) {
+            self.counter = self.max_length - length_data as u16;
+        }
+
+        pub fn trigger(&mut self) {
+            if self.counter == 0 {
+                self.counter = self.max_length;
+            }
+        }
+
+        /// Returns true when the channel has to be turned off
+        pub fn clock(&mut self) -> bool {
+            if self.enabled && self.counter > 0 {
+                self.counter -= 1;
+                return self.counter == 0;
+            }
+            false
+        }
+    }
+
+    impl Snapshot for LengthCounter {
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.write_bool(self.enabled);
+            writer.write_u16(self.counter);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.enabled = reader.read_bool()?;
+            self.counter = reader.read_u16()?;
+            Ok(())
+        }
+    }
+
+    /// # Volume Envelope
+    /// NRx2: Bit 7-4 initial volume, Bit 3 direction (1=increase), Bit 2-0 sweep pace.
+    /// Clocked at 64 Hz.
+    #[derive(Default)]
+    pub struct VolumeEnvelope {
+        initial_volume: u8,
+        increase: bool,
+        period: u8,
+        timer: u8,
+        pub volume: u8,
+    }
+
+    impl VolumeEnvelope {
+        pub fn write(&mut self, data: u8) {
+            self.initial_volume = data.rotate_right(4) & 0x0F;
+            self.increase = (data.rotate_right(3) & 1) == 1;
+            self.period = data & 0x07;
+        }
+
+        pub fn trigger(&mut self) {
+            self.volume = self.initial_volume;
+            self.timer = self.period;
+        }
+
+        pub fn clock(&mut self) {
+            if self.period == 0 {
+                return;
+            }
+            self.timer = self.timer.saturating_sub(1);
+            if self.timer == 0 {
+                self.timer = self.period;
+                if self.increase && self.volume < 15 {
+                    self.volume += 1;
+                } else if !self.increase && self.volume > 0 {
+                    self.volume -= 1;
+                }
+            }
+        }
+    }
+
+    impl Snapshot for VolumeEnvelope {
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.write_u8(self.initial_volume);
+            writer.write_bool(self.increase);
+            writer.write_u8(self.period);
+            writer.write_u8(self.timer);
+            writer.write_u8(self.volume);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.initial_volume = reader.read_u8()?;
+            self.increase = reader.read_bool()?;
+            self.period = reader.read_u8()?;
+            self.timer = reader.read_u8()?;
+            self.volume = reader.read_u8()?;
+            Ok(())
+        }
+    }
+
+    /// # Frequency Sweep
+    /// NR10: Bit 6-4 pace, Bit 3 direction (1=decrease), Bit 2-0 individual step.
+    /// Clocked at 128 Hz, channel 1 only.
+    #[derive(Default)]
+    pub struct FrequencySweep {
+        period: u8,
+        negate: bool,
+        shift: u8,
+        timer: u8,
+        enabled: bool,
+        shadow_frequency: u16,
+    }
+
+    impl FrequencySweep {
+        pub fn write(&mut self, data: u8) {
+            self.period = data.rotate_right(4) & 0x07;
+            self.negate = (data.rotate_right(3) & 1) == 1;
+            self.shift = data & 0x07;
+        }
+
+        fn reload_timer(&mut self) {
+            self.timer = if self.period == 0 { 8 } else { self.period };
+        }
+
+        fn calculate_frequency(&self) -> u16 {
+            let delta = self.shadow_frequency >> self.shift;
+            if self.negate {
+                self.shadow_frequency.wrapping_sub(delta)
+            } else {
+                self.shadow_frequency + delta
+            }
+        }
+
+        /// Returns false when the frequency overflows and the channel has to be turned off
+        pub fn trigger(&mut self, frequency: u16) -> bool {
+            self.shadow_frequency = frequency;
+            self.reload_timer();
+            self.enabled = self.period != 0 || self.shift != 0;
+            self.shift == 0 || self.calculate_frequency() <= 0x7FF
+        }
+
+        /// Returns the new frequency or None when the channel has to be turned off
+        pub fn clock(&mut self, frequency: u16) -> Option<u16> {
+            self.timer = self.timer.saturating_sub(1);
+            if self.timer != 0 {
+                return Some(frequency);
+            }
+            self.reload_timer();
+            if !self.enabled || self.period == 0 {
+                return Some(frequency);
+            }
+
+            let new_frequency = self.calculate_frequency();
+            if new_frequency > 0x7FF {
+                return None;
+            }
+            if self.shift == 0 {
+                return Some(frequency);
+            }
+            self.shadow_frequency = new_frequency;
+            if self.calculate_frequency() > 0x7FF {
+                return None;
+            }
+            Some(new_frequency)
+        }
+    }
+
+    impl Snapshot for FrequencySweep {
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.write_u8(self.period);
+            writer.write_bool(self.negate);
+            writer.write_u8(self.shift);
+            writer.write_u8(self.timer);
+            writer.write_bool(self.enabled);
+            writer.write_u16(self.shadow_frequency);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.period = reader.read_u8()?;
+            self.negate = reader.read_bool()?;
+            self.shift = reader.read_u8()?;
+            self.timer = reader.read_u8()?;
+            self.enabled = reader.read_bool()?;
+            self.shadow_frequency = reader.read_u16()?;
+            Ok(())
+        }
+    }
+
+    /// Runs the channel timer for the given cycles and returns the number of timer expirations
+    fn run_timer(timer: &mut u32, period: u32, cycles: u32) -> u32 {
+        let mut expirations = 0;
+        let mut cycles = cycles;
+        while cycles > 0 {
+            if *timer == 0 {
+                *timer = period;
+            }
+            let step = cycles.min(*timer);
+            *timer -= step;
+            cycles -= step;
+            if *timer == 0 {
+                expirations += 1;
+            }
+        }
+        expirations
+    }
+
+    /// # Square Channel (CH1, CH2)
+    pub struct SquareChannel {
+        pub enabled: bool,
+        pub dac_enabled: bool,
+        duty: u8,
+        duty_position: u8,
+        pub frequency: u16,
+        timer: u32,
+        pub length: LengthCounter,
+        pub envelope: VolumeEnvelope,
+        pub sweep: FrequencySweep,
+    }
+
+    impl SquareChannel {
+        pub fn new() -> Self {
+            Self {
+                enabled: false,
+                dac_enabled: false,
+                duty: 0,
+                duty_position: 0,
+                frequency: 0,
+                timer: 0,
+                length: LengthCounter::new(64),
+                envelope: VolumeEnvelope::default(),
+                sweep: FrequencySweep::default(),
+            }
+        }
+
+        /// NRx1: Bit 7-6 wave duty, Bit 5-0 initial length timer
+        pub fn write_length_duty(&mut self, data: u8) {
+            self.duty = data.rotate_right(6) & 0x03;
+            self.length.load(data & 0x3F);
+        }
+
+        /// NRx2
+        pub fn write_envelope(&mut self, data: u8) {
+            self.envelope.write(data);
+            self.dac_enabled = data & 0xF8 != 0;
+            if !self.dac_enabled {
+                self.enabled = false;
+            }
+        }
+
+        pub fn trigger(&mut self, with_sweep: bool) {
+            self.enabled = self.dac_enabled;
+            self.length.trigger();
+            self.envelope.trigger();
+            self.timer = (2048 - self.frequency as u32) * 4;
+            if with_sweep && !self.sweep.trigger(self.frequency) {
+                self.enabled = false;
+            }
+        }
+
+        pub fn clock_sweep(&mut self) {
+            match self.sweep.clock(self.frequency) {
+                Some(frequency) => self.frequency = frequency,
+                None => self.enabled = false,
+            }
+        }
+
+        pub fn step(&mut self, cycles: u32) {
+            let period = (2048 - self.frequency as u32) * 4;
+            let expirations = run_timer(&mut self.timer, period, cycles);
+            self.duty_position = ((self.duty_position as u32 + expirations) % 8) as u8;
+        }
+
+        pub fn output(&self) -> u8 {
+            if !self.enabled {
+                return 0;
+            }
+            DUTY_WAVEFORM[self.duty as usize][self.duty_position as usize] * self.envelope.volume
+        }
+    }
+
+    impl Snapshot for SquareChannel {
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.write_bool(self.enabled);
+            writer.write_bool(self.dac_enabled);
+            writer.write_u8(self.duty);
+            writer.write_u8(self.duty_position);
+            writer.write_u16(self.frequency);
+            writer.write_u32(self.timer);
+            self.length.save_state(writer);
+            self.envelope.save_state(writer);
+            self.sweep.save_state(writer);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.enabled = reader.read_bool()?;
+            self.dac_enabled = reader.read_bool()?;
+            self.duty = reader.read_u8()? & 0x03;
+            self.duty_position = reader.read_u8()? & 0x07;
+            self.frequency = reader.read_u16()? & 0x7FF;
+            self.timer = reader.read_u32()?;
+            self.length.load_state(reader)?;
+            self.envelope.load_state(reader)?;
+            self.sweep.load_state(reader)?;
+            Ok(())
+        }
+    }
+
+    /// # Wave Channel (CH3)
+    /// Plays 32 4-bit samples from the wave pattern RAM
+    pub struct WaveChannel {
+        pub enabled: bool,
+        pub dac_enabled: bool,
+        output_level: u8,
+        pub frequency: u16,
+        timer: u32,
+        position: u8,
+        pub length: LengthCounter,
+    }
+
+    impl WaveChannel {
+        pub fn new() -> Self {
+            Self {
+                enabled: false,
+                dac_enabled: false,
+                output_level: 0,
+                frequency: 0,
+                timer: 0,
+                position: 0,
+                length: LengthCounter::new(256),
+            }
+        }
+
+        /// NR30: Bit 7 DAC on/off
+        pub fn write_dac(&mut self, data: u8) {
+            self.dac_enabled = (data.rotate_right(7) & 1) == 1;
+            if !self.dac_enabled {
+                self.enabled = false;
+            }
+        }
+
+        /// NR32: Bit 6-5 output level (0=Mute, 1=100%, 2=50%, 3=25%)
+        pub fn write_output_level(&mut self, data: u8) {
+            self.output_level = data.rotate_right(5) & 0x03;
+        }
+
+        pub fn trigger(&mut self) {
+            self.enabled = self.dac_enabled;
+            self.length.trigger();
+            self.timer = (2048 - self.frequency as u32) * 2;
+            self.position = 0;
+        }
+
+        pub fn step(&mut self, cycles: u32) {
+            let period = (2048 - self.frequency as u32) * 2;
+            let expirations = run_timer(&mut self.timer, period, cycles);
+            self.position = ((self.position as u32 + expirations) % 32) as u8;
+        }
+
+        pub fn output(&self, wave_ram: &[u8]) -> u8 {
+            if !self.enabled {
+                return 0;
+            }
+            let wave_byte = wave_ram[self.position as usize / 2];
+            let sample = if self.position.is_multiple_of(2) {
+                wave_byte.rotate_right(4) & 0x0F
+            } else {
+                wave_byte & 0x0F
+            };
+            match self.output_level {
+                0 => 0,
+                level => sample >> (level - 1),
+            }
+        }
+    }
+
+    impl Snapshot for WaveChannel {
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.write_bool(self.enabled);
+            writer.write_bool(self.dac_enabled);
+            writer.write_u8(self.output_level);
+            writer.write_u16(self.frequency);
+            writer.write_u32(self.timer);
+            writer.write_u8(self.position);
+            self.length.save_state(writer);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.enabled = reader.read_bool()?;
+            self.dac_enabled = reader.read_bool()?;
+            self.output_level = reader.read_u8()? & 0x03;
+            self.frequency = reader.read_u16()? & 0x7FF;
+            self.timer = reader.read_u32()?;
+            self.position = reader.read_u8()? & 0x1F;
+            self.length.load_state(reader)?;
+            Ok(())
+        }
+    }
+
+    /// # Noise Channel (CH4)
+    /// Pseudo random output from the 15-bit linear feedback shift register
+    pub struct NoiseChannel {
+        pub enabled: bool,
+        pub dac_enabled: bool,
+        clock_shift: u8,
+        short_mode: bool,
+        divisor_code: u8,
+        timer: u32,
+        pub lfsr: u16,
+        pub length: LengthCounter,
+        pub envelope: VolumeEnvelope,
+    }
+
+    impl NoiseChannel {
+        pub fn new() -> Self {
+            Self {
+                enabled: false,
+                dac_enabled: false,
+                clock_shift: 0,
+                short_mode: false,
+                divisor_code: 0,
+                timer: 0,
+                lfsr: 0x7FFF,
+                length: LengthCounter::new(64),
+                envelope: VolumeEnvelope::default(),
+            }
+        }
+
+        /// NR42
+        pub fn write_envelope(&mut self, data: u8) {
+            self.envelope.write(data);
+            self.dac_enabled = data & 0xF8 != 0;
+            if !self.dac_enabled {
+                self.enabled = false;
+            }
+        }
+
+        /// NR43: Bit 7-4 clock shift, Bit 3 LFSR width (1=7 bits), Bit 2-0 clock divider
+        pub fn write_polynomial(&mut self, data: u8) {
+            self.clock_shift = data.rotate_right(4) & 0x0F;
+            self.short_mode = (data.rotate_right(3) & 1) == 1;
+            self.divisor_code = data & 0x07;
+        }
+
+        fn period(&self) -> u32 {
+            NOISE_DIVISOR[self.divisor_code as usize] << self.clock_shift
+        }
+
+        pub fn trigger(&mut self) {
+            self.enabled = self.dac_enabled;
+            self.length.trigger();
+            self.envelope.trigger();
+            self.timer = self.period();
+            self.lfsr = 0x7FFF;
+        }
+
+        fn shift_lfsr(&mut self) {
+            let xor_bit = (self.lfsr & 0x01) ^ ((self.lfsr >> 1) & 0x01);
+            self.lfsr = (self.lfsr >> 1) | (xor_bit << 14);
+            if self.short_mode {
+                self.lfsr &= !(1 << 6);
+                self.lfsr |= xor_bit << 6;
+            }
+        }
+
+        pub fn step(&mut self, cycles: u32) {
+            // Clock shift 14 and 15 stop the LFSR
+            if self.clock_shift >= 14 {
+                return;
+            }
+            let period = self.period();
+            let expirations = run_timer(&mut self.timer, period, cycles);
+            for _ in 0..expirations {
+                self.shift_lfsr();
+            }
+        }
+
+        pub fn output(&self) -> u8 {
+            if !self.enabled {
+                return 0;
+            }
+            (!self.lfsr & 0x01) as u8 * self.envelope.volume
+        }
+    }
+
+    impl Snapshot for NoiseChannel {
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.write_bool(self.enabled);
+            writer.write_bool(self.dac_enabled);
+            writer.write_u8(self.clock_shift);
+            writer.write_bool(self.short_mode);
+            writer.write_u8(self.divisor_code);
+            writer.write_u32(self.timer);
+            writer.write_u16(self.lfsr);
+            self.length.save_state(writer);
+            self.envelope.save_state(writer);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.enabled = reader.read_bool()?;
+            self.dac_enabled = reader.read_bool()?;
+            self.clock_shift = reader.read_u8()? & 0x0F;
+            self.short_mode = reader.read_bool()?;
+            self.divisor_code = reader.read_u8()? & 0x07;
+            self.timer = reader.read_u32()?;
+            self.lfsr = reader.read_u16()?;
+            self.length.load_state(reader)?;
+            self.envelope.load_state(reader)?;
+            Ok(())
+        }
+    }
+}
+
+/// # APU (Audio Processing Unit)
+/// Four sound channels mixed into a stereo output:
+///
+/// - CH1 Square wave with frequency sweep
+/// - CH2 Square wave
+/// - CH3 Programmable wave from the wave pattern RAM
+/// - CH4 Noise
+///
+/// The samples are produced at `audio::SAMPLE_RATE` into a ring buffer which is drained by the frontend.
+pub struct AudioProcessingUnit {
+    //..::Registers::..
+    registers: [u8; sound_setup::NUMBER_OF_REGISTERS],
+    wave_ram: [u8; sound_setup::WAVE_RAM_SIZE],
+    is_power_on: bool,
+    //..::Channels::..
+    channel_1: SquareChannel,
+    channel_2: SquareChannel,
+    channel_3: WaveChannel,
+    channel_4: NoiseChannel,
+    //..::Internal::..
+    frame_sequencer_counter: u32,
+    frame_sequencer_step: u8,
+    internal_sample_counter: u32,
+    //..::Out::..
+    out_sample_buffer: VecDeque<(f32, f32)>,
+}
+
+impl Default for AudioProcessingUnit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioProcessingUnit {
+    pub fn new() -> Self {
+        Self {
+            registers: [0; sound_setup::NUMBER_OF_REGISTERS],
+            wave_ram: [0; sound_setup::WAVE_RAM_SIZE],
+            is_power_on: false,
+            channel_1: SquareChannel::new(),
+            channel_2: SquareChannel::new(),
+            channel_3: WaveChannel::new(),
+            channel_4: NoiseChannel::new(),
+            frame_sequencer_counter: 0,
+            frame_sequencer_step: 0,
+            internal_sample_counter: 0,
+            out_sample_buffer: VecDeque::with_capacity(audio::SAMPLE_BUFFER_CAPACITY),
+        }
+    }
+
+    /// # take_samples
+    /// Drains stereo samples (left, right) in range -1.0..=1.0
+    pub fn take_samples(&mut self) -> Vec<(f32, f32)> {
+        self.out_sample_buffer.drain(..).collect()
+    }
+
+    fn register_index(address: u16) -> usize {
+        (address - *address::HARDWARE_IO_SOUND.start()) as usize
+    }
+
+    fn power_off(&mut self) {
+        self.registers = [0; sound_setup::NUMBER_OF_REGISTERS];
+        self.channel_1 = SquareChannel::new();
+        self.channel_2 = SquareChannel::new();
+        self.channel_3 = WaveChannel::new();
+        self.channel_4 = NoiseChannel::new();
+        self.is_power_on = false;
+    }
+
+    fn power_on(&mut self) {
+        self.is_power_on = true;
+        self.frame_sequencer_step = 0;
+    }
+
+    fn write_frequency_control(&mut self, address: u16, data: u8) {
+        let length_enable = (data.rotate_right(6) & 1) == 1;
+        let trigger = (data.rotate_right(7) & 1) == 1;
+        let frequency_high = ((data & 0x07) as u16).rotate_left(8);
+
+        match address {
+            io_hardware_register::NR14 => {
+                let channel = &mut self.channel_1;
+                channel.frequency = (channel.frequency & 0x00FF) | frequency_high;
+                channel.length.enabled = length_enable;
+                if trigger {
+                    channel.trigger(true);
+                }
+            }
+            io_hardware_register::NR24 => {
+                let channel = &mut self.channel_2;
+                channel.frequency = (channel.frequency & 0x00FF) | frequency_high;
+                channel.length.enabled = length_enable;
+                if trigger {
+                    channel.trigger(false);
+                }
+            }
+            io_hardware_register::NR34 => {
+                let channel = &mut self.channel_3;
+                channel.frequency = (channel.frequency & 0x00FF) | frequency_high;
+                channel.length.enabled = length_enable;
+                if trigger {
+                    channel.trigger();
+                }
+            }
+            io_hardware_register::NR44 => {
+                let channel = &mut self.channel_4;
+                channel.length.enabled = length_enable;
+                if trigger {
+                    channel.trigger();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn write_sound_register(&mut self, address: u16, data: u8) {
+        match address {
+            io_hardware_register::NR10 => self.channel_1.sweep.write(data),
+            io_hardware_register::NR11 => self.channel_1.write_length_duty(data),
+            io_hardware_register::NR12 => self.channel_1.write_envelope(data),
+            io_hardware_register::NR13 => {
+                self.channel_1.frequency = (self.channel_1.frequency & 0x0700) | data as u16
+            }
+            io_hardware_register::NR21 => self.channel_2.write_length_duty(data),
+            io_hardware_register::NR22 => self.channel_2.write_envelope(data),
+            io_hardware_register::NR23 => {
+                self.channel_2.frequency = (self.channel_2.frequency & 0x0700) | data as u16
+            }
+            io_hardware_register::NR30 => self.channel_3.write_dac(data),
+            io_hardware_register::NR31 => self.channel_3.length.load(data),
+            io_hardware_register::NR32 => self.channel_3.write_output_level(data),
+            io_hardware_register::NR33 => {
+                self.channel_3.frequency = (self.channel_3.frequency & 0x0700) | data as u16
+            }
+            io_hardware_register::NR41 => self.channel_4.length.load(data & 0x3F),
+            io_hardware_register::NR42 => self.channel_4.write_envelope(data),
+            io_hardware_register::NR43 => self.channel_4.write_polynomial(data),
+            io_hardware_register::NR14
+            | io_hardware_register::NR24
+            | io_hardware_register::NR34
+            | io_hardware_register::NR44 => self.write_frequency_control(address, data),
+            _ => (),
+        }
+    }
+
+    /// # frame_sequencer_step
+    ///
+    /// Step   Length Ctr  Vol Env     Sweep
+    ///
+    /// 0      Clock       -           -
+    /// 1      -           -           -
+    /// 2      Clock       -           Clock
+    /// 3      -           -           -
+    /// 4      Clock       -           -
+    /// 5      -           -           -
+    /// 6      Clock       -           Clock
+    /// 7      -           Clock       -
+    fn frame_sequencer_clock(&mut self) {
+        let step = self.frame_sequencer_step;
+
+        if step.is_multiple_of(2) {
+            if self.channel_1.length.clock() {
+                self.channel_1.enabled = false;
+            }
+            if self.channel_2.length.clock() {
+                self.channel_2.enabled = false;
+            }
+            if self.channel_3.length.clock() {
+                self.channel_3.enabled = false;
+            }
+            if self.channel_4.length.clock() {
+                self.channel_4.enabled = false;
+            }
+        }
+
+        if step == 2 || step == 6 {
+            self.channel_1.clock_sweep();
+        }
+
+        if step == 7 {
+            self.channel_1.envelope.clock();
+            self.channel_2.envelope.clock();
+            self.channel_4.envelope.clock();
+        }
+
+        self.frame_sequencer_step = (step + 1) % 8;
+    }
+
+    /// Converts the digital 0..15 channel output to the -1.0..1.0 analog value
+    fn dac_output(digital_value: u8, dac_enabled: bool) -> f32 {
+        if !dac_enabled {
+            return 0.0;
+        }
+        digital_value as f32 / 7.5 - 1.0
+    }
+
+    fn mix_sample(&self) -> (f32, f32) {
+        let channels_output = [
+            Self::dac_output(self.channel_1.output(), self.channel_1.dac_enabled),
+            Self::dac_output(self.channel_2.output(), self.channel_2.dac_enabled),
+            Self::dac_output(
+                self.channel_3.output(&self.wave_ram),
+                self.channel_3.dac_enabled,
+            ),
+            Self::dac_output(self.channel_4.output(), self.channel_4.dac_enabled),
+        ];
+
+        let nr50 = self.registers[Self::register_index(io_hardware_register::NR50)];
+        let nr51 = self.registers[Self::register_index(io_hardware_register::NR51)];
+
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (channel_id, output) in channels_output.iter().enumerate() {
+            if (nr51.rotate_right(4 + channel_id as u32) & 1) == 1 {
+                left += output;
+            }
+            if (nr51.rotate_right(channel_id as u32) & 1) == 1 {
+                right += output;
+            }
+        }
+
+        let left_volume = (nr50.rotate_right(4) & 0x07) as f32 + 1.0;
+        let right_volume = (nr50 & 0x07) as f32 + 1.0;
+        (
+            left / 4.0 * left_volume / 8.0,
+            right / 4.0 * right_volume / 8.0,
+        )
+    }
+
+    fn push_sample(&mut self, sample: (f32, f32)) {
+        if self.out_sample_buffer.len() == audio::SAMPLE_BUFFER_CAPACITY {
+            self.out_sample_buffer.pop_front();
+        }
+        self.out_sample_buffer.push_back(sample);
+    }
+}
+
+impl HardwareAccessible for AudioProcessingUnit {
+    fn read_byte_from_hardware_register(&self, address: u16) -> u8 {
+        match address {
+            io_hardware_register::NR52 => {
+                let mut out_value = sound_setup::REGISTER_READ_MASK[Self::register_index(address)];
+                if self.is_power_on {
+                    out_value |= 1_u8.rotate_left(7);
+                }
+                if self.channel_1.enabled {
+                    out_value |= 1;
+                }
+                if self.channel_2.enabled {
+                    out_value |= 1_u8.rotate_left(1);
+                }
+                if self.channel_3.enabled {
+                    out_value |= 1_u8.rotate_left(2);
+                }
+                if self.channel_4.enabled {
+                    out_value |= 1_u8.rotate_left(3);
+                }
+                out_value
+            }
+            sound_address if address::HARDWARE_IO_SOUND.contains(&sound_address) => {
+                let index = Self::register_index(sound_address);
+                self.registers[index] | sound_setup::REGISTER_READ_MASK[index]
+            }
+            wave_address if address::WAVE_PATTERN_RAM.contains(&wave_address) => {
+                self.wave_ram[(wave_address - *address::WAVE_PATTERN_RAM.start()) as usize]
+            }
+            _ => memory::DEFAULT_INIT_VALUE,
+        }
+    }
+
+    fn write_byte_to_hardware_register(&mut self, address: u16, data: u8) {
+        match address {
+            io_hardware_register::NR52 => {
+                let power_on_request = (data.rotate_right(7) & 1) == 1;
+                if !power_on_request && self.is_power_on {
+                    self.power_off();
+                } else if power_on_request && !self.is_power_on {
+                    self.power_on();
+                }
+            }
+            sound_address if address::HARDWARE_IO_SOUND.contains(&sound_address) => {
+                // While the APU is off the registers are read-only
+                if !self.is_power_on {
+                    return;
+                }
+                self.registers[Self::register_index(sound_address)] = data;
+                self.write_sound_register(sound_address, data);
+            }
+            wave_address if address::WAVE_PATTERN_RAM.contains(&wave_address) => {
+                self.wave_ram[(wave_address - *address::WAVE_PATTERN_RAM.start()) as usize] = data;
+            }
+            _ => (),
+        }
+    }
+}
+
+impl IoWorkingCycle for AudioProcessingUnit {
+    fn next_to(&mut self, cycles: u32) {
+        if self.is_power_on {
+            self.frame_sequencer_counter += cycles;
+            while self.frame_sequencer_counter >= sound_setup::FRAME_SEQUENCER_CLOCK_DIV {
+                self.frame_sequencer_counter -= sound_setup::FRAME_SEQUENCER_CLOCK_DIV;
+                self.frame_sequencer_clock();
+            }
+
+            self.channel_1.step(cycles);
+            self.channel_2.step(cycles);
+            self.channel_3.step(cycles);
+            self.channel_4.step(cycles);
+        }
+
+        self.internal_sample_counter += cycles * audio::SAMPLE_RATE;
+        while self.internal_sample_counter >= clock::CPU_CLOCK_FREQUENCY {
+            self.internal_sample_counter -= clock::CPU_CLOCK_FREQUENCY;
+            let sample = if self.is_power_on {
+                self.mix_sample()
+            } else {
+                (0.0, 0.0)
+            };
+            self.push_sample(sample);
+        }
+    }
+}
+
+impl Snapshot for AudioProcessingUnit {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.registers);
+        writer.write_bytes(&self.wave_ram);
+        writer.write_bool(self.is_power_on);
+        self.channel_1.save_state(writer);
+        self.channel_2.save_state(writer);
+        self.channel_3.save_state(writer);
+        self.channel_4.save_state(writer);
+        writer.write_u32(self.frame_sequencer_counter);
+        writer.write_u8(self.frame_sequencer_step);
+        writer.write_u32(self.internal_sample_counter);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        reader.read_bytes(&mut self.registers)?;
+        reader.read_bytes(&mut self.wave_ram)?;
+        self.is_power_on = reader.read_bool()?;
+        self.channel_1.load_state(reader)?;
+        self.channel_2.load_state(reader)?;
+        self.channel_3.load_state(reader)?;
+        self.channel_4.load_state(reader)?;
+        self.frame_sequencer_counter = reader.read_u32()?;
+        self.frame_sequencer_step = reader.read_u8()? & 0x07;
+        self.internal_sample_counter = reader.read_u32()?;
+        self.out_sample_buffer.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    fn powered_on_apu() -> AudioProcessingUnit {
+        let mut apu = AudioProcessingUnit::new();
+        apu.write_byte_to_hardware_register(io_hardware_register::NR52, 0x80);
+        apu
+    }
+
+    #[test]
+    fn register_read_mask_test() {
+        let mut apu = powered_on_apu();
+
+        apu.write_byte_to_hardware_register(io_hardware_register::NR11, 0x80);
+        assert_eq!(
+            0xBF,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR11)
+        );
+        apu.write_byte_to_hardware_register(io_hardware_register::NR13, 0x12);
+        assert_eq!(
+            0xFF,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR13)
+        );
+        apu.write_byte_to_hardware_register(io_hardware_register::NR50, 0x77);
+        assert_eq!(
+            0x77,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR50)
+        );
+        assert_eq!(
+            0xF0,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR52)
+        );
+    }
+
+    #[test]
+    fn power_off_test() {
+        let mut apu = powered_on_apu();
+        apu.write_byte_to_hardware_register(io_hardware_register::NR50, 0x77);
+        apu.write_byte_to_hardware_register(0xFF30, 0xAB);
+
+        apu.write_byte_to_hardware_register(io_hardware_register::NR52, 0x00);
+        assert_eq!(
+            0x70,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR52)
+        );
+        assert_eq!(
+            0x00,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR50)
+        );
+
+        // Registers are read-only, wave RAM is still accessible
+        apu.write_byte_to_hardware_register(io_hardware_register::NR50, 0x77);
+        assert_eq!(
+            0x00,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR50)
+        );
+        assert_eq!(0xAB, apu.read_byte_from_hardware_register(0xFF30));
+    }
+
+    #[test]
+    fn square_channel_length_counter_test() {
+        let mut apu = powered_on_apu();
+
+        // Length = 64 - 62 = 2 frame sequencer length clocks
+        apu.write_byte_to_hardware_register(io_hardware_register::NR21, 62);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR22, 0xF0);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR24, 0xC0);
+        assert_eq!(
+            0xF2,
+            apu.read_byte_from_hardware_register(io_hardware_register::NR52)
+        );
+
+        // Step 0 and 1
+        apu.next_to(sound_setup::FRAME_SEQUENCER_CLOCK_DIV * 2);
+        assert!(apu.channel_2.enabled);
+
+        // Step 2
+        apu.next_to(sound_setup::FRAME_SEQUENCER_CLOCK_DIV);
+        assert!(!apu.channel_2.enabled);
+    }
+
+    #[test]
+    fn dac_off_disables_channel_test() {
+        let mut apu = powered_on_apu();
+        apu.write_byte_to_hardware_register(io_hardware_register::NR42, 0xF0);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR44, 0x80);
+        assert!(apu.channel_4.enabled);
+
+        apu.write_byte_to_hardware_register(io_hardware_register::NR42, 0x00);
+        assert!(!apu.channel_4.enabled);
+
+        // Trigger with DAC off does not enable the channel
+        apu.write_byte_to_hardware_register(io_hardware_register::NR30, 0x00);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR34, 0x80);
+        assert!(!apu.channel_3.enabled);
+    }
+
+    #[test]
+    fn frequency_sweep_overflow_test() {
+        let mut apu = powered_on_apu();
+        apu.write_byte_to_hardware_register(io_hardware_register::NR12, 0xF0);
+        // Sweep pace 1, increase, shift 1
+        apu.write_byte_to_hardware_register(io_hardware_register::NR10, 0x11);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR13, 0x00);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR14, 0x84);
+        assert!(apu.channel_1.enabled);
+
+        // 0x400 + 0x200 = 0x600, next check 0x600 + 0x300 overflows
+        apu.next_to(sound_setup::FRAME_SEQUENCER_CLOCK_DIV * 3);
+        assert!(!apu.channel_1.enabled);
+    }
+
+    #[test]
+    fn noise_lfsr_test() {
+        let mut apu = powered_on_apu();
+        apu.write_byte_to_hardware_register(io_hardware_register::NR42, 0xF0);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR43, 0x00);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR44, 0x80);
+        assert_eq!(0x7FFF, apu.channel_4.lfsr);
+
+        // Divisor 8, shift 0
+        apu.next_to(8);
+        assert_eq!(0x3FFF, apu.channel_4.lfsr);
+    }
+
+    #[test]
+    fn sample_generation_test() {
+        let mut apu = powered_on_apu();
+        apu.write_byte_to_hardware_register(io_hardware_register::NR50, 0x77);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR51, 0x22);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR22, 0xF0);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR21, 0x80);
+        apu.write_byte_to_hardware_register(io_hardware_register::NR24, 0x87);
+
+        // One second
+        for _ in 0..clock::CPU_CLOCK_FREQUENCY / 4 {
+            apu.next_to(4);
+        }
+        let samples = apu.take_samples();
+        assert_eq!(audio::SAMPLE_BUFFER_CAPACITY, samples.len());
+        assert!(samples
+            .iter()
+            .any(|(left, right)| *left > 0.0 && *right > 0.0));
+        assert!(apu.take_samples().is_empty());
+    }
+}
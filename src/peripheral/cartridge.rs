@@ -1,19 +1,22 @@
-use std::fs::File;
-use std::io;
-use std::io::Error;
-use std::io::Read;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::HardwareAccessible;
+use self::real_time_clock::RealTimeClock;
+use super::{HardwareAccessible, IoWorkingCycle};
 
 use crate::constants::gb_memory_map::address;
 use crate::constants::gb_memory_map::memory;
+use crate::error::{EmulatorError, Result};
+use crate::save_state::{invalid_data_error, Snapshot, StateReader, StateWriter};
 
 #[derive(PartialEq)]
 enum CartridgeType {
     RomOnly,
     Mbc1,
     Mbc2,
+    Mbc3,
+    Mbc5,
 }
 #[derive(PartialEq)]
 enum BankMode {
@@ -21,6 +24,234 @@ enum BankMode {
     Ram,
 }
 
+mod real_time_clock {
+    use crate::emulator_constants::clock;
+    use crate::save_state::{Snapshot, StateReader, StateWriter};
+
+    pub const RTC_SECONDS: u8 = 0x08;
+    pub const RTC_MINUTES: u8 = 0x09;
+    pub const RTC_HOURS: u8 = 0x0A;
+    pub const RTC_DAY_LOW: u8 = 0x0B;
+    pub const RTC_DAY_HIGH: u8 = 0x0C;
+    pub const RTC_SAVE_DATA_SIZE: usize = 48;
+    pub const RTC_SAVE_DATA_SIZE_32BIT_TIMESTAMP: usize = 44;
+
+    /// # RTC Registers
+    ///
+    /// * 08h  RTC S   Seconds   0-59 (0-3Bh)
+    /// * 09h  RTC M   Minutes   0-59 (0-3Bh)
+    /// * 0Ah  RTC H   Hours     0-23 (0-17h)
+    /// * 0Bh  RTC DL  Lower 8 bits of Day Counter (0-FFh)
+    /// * 0Ch  RTC DH  Upper 1 bit of Day Counter, Carry Bit, Halt Flag
+    ///   * Bit 0  Most significant bit of Day Counter (Bit 8)
+    ///   * Bit 6  Halt (0=Active, 1=Stop Timer)
+    ///   * Bit 7  Day Counter Carry Bit (1=Counter Overflow)
+    #[derive(Clone, Copy, Default)]
+    pub struct RtcRegisters {
+        pub seconds: u8,
+        pub minutes: u8,
+        pub hours: u8,
+        pub day_low: u8,
+        pub day_high: u8,
+    }
+
+    impl RtcRegisters {
+        pub fn is_halted(&self) -> bool {
+            (self.day_high.rotate_right(6) & 1) == 1
+        }
+
+        fn increment_day(&mut self) {
+            let day = ((self.day_high as u16 & 0x01).rotate_left(8) | self.day_low as u16) + 1;
+            self.day_low = (day & 0x00FF) as u8;
+            self.day_high &= 0xFE;
+            if day > 0x1FF {
+                // Day counter overflow, the carry bit stays set until the game clears it
+                self.day_high |= 1_u8.rotate_left(7);
+            } else {
+                self.day_high |= (day.rotate_right(8) & 0x01) as u8;
+            }
+        }
+
+        pub fn advance_seconds(&mut self, seconds: u64) {
+            let day = (self.day_high as u64 & 0x01).rotate_left(8) | self.day_low as u64;
+            let total_seconds = seconds
+                + self.seconds as u64
+                + self.minutes as u64 * 60
+                + self.hours as u64 * 3600
+                + day * 86400;
+
+            let days = total_seconds / 86400;
+            self.seconds = (total_seconds % 60) as u8;
+            self.minutes = (total_seconds / 60 % 60) as u8;
+            self.hours = (total_seconds / 3600 % 24) as u8;
+            self.day_low = (days & 0xFF) as u8;
+            self.day_high = (self.day_high & 0xC0) | ((days.rotate_right(8) & 0x01) as u8);
+            if days > 0x1FF {
+                self.day_high |= 1_u8.rotate_left(7);
+            }
+        }
+
+        pub fn increment_second(&mut self) {
+            self.seconds = (self.seconds + 1) & 0x3F;
+            if self.seconds != 60 {
+                return;
+            }
+            self.seconds = 0;
+            self.minutes = (self.minutes + 1) & 0x3F;
+            if self.minutes != 60 {
+                return;
+            }
+            self.minutes = 0;
+            self.hours = (self.hours + 1) & 0x1F;
+            if self.hours != 24 {
+                return;
+            }
+            self.hours = 0;
+            self.increment_day();
+        }
+    }
+
+    /// # Real Time Clock
+    /// MBC3 clock counter driven by emulated cycles.
+    /// The game reads the latched copy of the registers, which is refreshed
+    /// by writing 0x00 and then 0x01 to 0x6000-0x7FFF.
+    #[derive(Default)]
+    pub struct RealTimeClock {
+        pub registers: RtcRegisters,
+        pub latched_registers: RtcRegisters,
+        internal_cycle_counter: u32,
+        latch_prepared: bool,
+    }
+
+    impl RealTimeClock {
+        pub fn tick(&mut self, cycles: u32) {
+            if self.registers.is_halted() {
+                return;
+            }
+            self.internal_cycle_counter += cycles;
+            while self.internal_cycle_counter >= clock::CPU_CLOCK_FREQUENCY {
+                self.internal_cycle_counter -= clock::CPU_CLOCK_FREQUENCY;
+                self.registers.increment_second();
+            }
+        }
+
+        pub fn latch_request(&mut self, data: u8) {
+            if self.latch_prepared && data == 0x01 {
+                self.latched_registers = self.registers;
+            }
+            self.latch_prepared = data == 0x00;
+        }
+
+        fn save_registers(registers: &RtcRegisters, writer: &mut StateWriter) {
+            writer.write_u8(registers.seconds);
+            writer.write_u8(registers.minutes);
+            writer.write_u8(registers.hours);
+            writer.write_u8(registers.day_low);
+            writer.write_u8(registers.day_high);
+        }
+
+        fn load_registers(reader: &mut StateReader) -> std::io::Result<RtcRegisters> {
+            Ok(RtcRegisters {
+                seconds: reader.read_u8()?,
+                minutes: reader.read_u8()?,
+                hours: reader.read_u8()?,
+                day_low: reader.read_u8()?,
+                day_high: reader.read_u8()?,
+            })
+        }
+
+        pub fn read(&self, rtc_register: u8) -> u8 {
+            match rtc_register {
+                RTC_SECONDS => self.latched_registers.seconds,
+                RTC_MINUTES => self.latched_registers.minutes,
+                RTC_HOURS => self.latched_registers.hours,
+                RTC_DAY_LOW => self.latched_registers.day_low,
+                RTC_DAY_HIGH => self.latched_registers.day_high | 0x3E,
+                _ => 0xFF,
+            }
+        }
+
+        /// # to_save_data
+        /// RTC footer appended to the RAM dump, the same layout as BGB and VBA-M:
+        /// 5 registers and 5 latched registers as 32-bit little endian values
+        /// followed by a 64-bit little endian UNIX timestamp.
+        pub fn to_save_data(&self, timestamp: u64) -> Vec<u8> {
+            let mut out = Vec::with_capacity(RTC_SAVE_DATA_SIZE);
+            for registers in [self.registers, self.latched_registers] {
+                for value in [
+                    registers.seconds,
+                    registers.minutes,
+                    registers.hours,
+                    registers.day_low,
+                    registers.day_high,
+                ] {
+                    out.extend_from_slice(&(value as u32).to_le_bytes());
+                }
+            }
+            out.extend_from_slice(&timestamp.to_le_bytes());
+            out
+        }
+
+        /// # load_save_data
+        /// Restores the RTC footer and advances the clock by the real time
+        /// which has elapsed since the save was written.
+        pub fn load_save_data(&mut self, data: &[u8], now: u64) {
+            let value = |index: usize| data[index * 4];
+            let to_registers = |base: usize| RtcRegisters {
+                seconds: value(base),
+                minutes: value(base + 1),
+                hours: value(base + 2),
+                day_low: value(base + 3),
+                day_high: value(base + 4),
+            };
+            self.registers = to_registers(0);
+            self.latched_registers = to_registers(5);
+
+            // Older saves keep only a 32-bit timestamp
+            let mut timestamp_bytes = [0_u8; 8];
+            let timestamp_data = &data[40..];
+            timestamp_bytes[..timestamp_data.len()].copy_from_slice(timestamp_data);
+            let timestamp = u64::from_le_bytes(timestamp_bytes);
+
+            if !self.registers.is_halted() && now > timestamp {
+                self.registers.advance_seconds(now - timestamp);
+            }
+        }
+
+        pub fn write(&mut self, rtc_register: u8, data: u8) {
+            match rtc_register {
+                RTC_SECONDS => {
+                    self.registers.seconds = data & 0x3F;
+                    // Writing seconds resets the sub-second divider
+                    self.internal_cycle_counter = 0;
+                }
+                RTC_MINUTES => self.registers.minutes = data & 0x3F,
+                RTC_HOURS => self.registers.hours = data & 0x1F,
+                RTC_DAY_LOW => self.registers.day_low = data,
+                RTC_DAY_HIGH => self.registers.day_high = data & 0xC1,
+                _ => (),
+            }
+        }
+    }
+
+    impl Snapshot for RealTimeClock {
+        fn save_state(&self, writer: &mut StateWriter) {
+            RealTimeClock::save_registers(&self.registers, writer);
+            RealTimeClock::save_registers(&self.latched_registers, writer);
+            writer.write_u32(self.internal_cycle_counter);
+            writer.write_bool(self.latch_prepared);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.registers = RealTimeClock::load_registers(reader)?;
+            self.latched_registers = RealTimeClock::load_registers(reader)?;
+            self.internal_cycle_counter = reader.read_u32()?;
+            self.latch_prepared = reader.read_bool()?;
+            Ok(())
+        }
+    }
+}
+
 struct CartridgeController {
     cart_type: CartridgeType,
     bank_mode: BankMode,
@@ -31,6 +262,12 @@ struct CartridgeController {
     ram_size: usize,
     current_ram_bank: usize,
     number_of_ram_banks: u16,
+    has_rtc: bool,
+    rtc_register_select: Option<u8>,
+    rtc: RealTimeClock,
+    has_rumble: bool,
+    is_rumble_on: bool,
+    has_battery: bool,
 }
 
 impl CartridgeController {
@@ -45,18 +282,35 @@ impl CartridgeController {
             ram_size: 0,
             current_ram_bank: 0,
             number_of_ram_banks: 0,
+            has_rtc: false,
+            rtc_register_select: None,
+            rtc: RealTimeClock::default(),
+            has_rumble: false,
+            is_rumble_on: false,
+            has_battery: false,
         }
     }
 
-    pub fn determine_cartridge_type(&mut self, code: u8) {
+    pub fn determine_cartridge_type(&mut self, code: u8) -> Result<()> {
         self.cart_type = match code {
             0 => CartridgeType::RomOnly,
             1..=3 => CartridgeType::Mbc1,
             5..=6 => CartridgeType::Mbc2,
-            _ => panic!("[CARTRIDGE ERROR] Unsupported Bank mode: [0x{:02x}]", code),
-        }
+            0x0F..=0x13 => CartridgeType::Mbc3,
+            0x19..=0x1E => CartridgeType::Mbc5,
+            _ => return Err(EmulatorError::UnsupportedCartridgeType(code)),
+        };
+        // MBC3+TIMER+BATTERY, MBC3+TIMER+RAM+BATTERY
+        self.has_rtc = matches!(code, 0x0F | 0x10);
+        // MBC5+RUMBLE, MBC5+RUMBLE+RAM, MBC5+RUMBLE+RAM+BATTERY
+        self.has_rumble = matches!(code, 0x1C..=0x1E);
+        self.has_battery = matches!(
+            code,
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E
+        );
+        Ok(())
     }
-    pub fn calculate_rom_size(&mut self, code: u8) {
+    pub fn calculate_rom_size(&mut self, code: u8) -> Result<()> {
         self.number_of_rom_banks = match code {
             0x00 => 2,
             0x01 => 4,
@@ -70,24 +324,26 @@ impl CartridgeController {
             0x52 => 72,
             0x53 => 80,
             0x54 => 96,
-            _ => panic!("[CARTRIDGE ERROR] Unsupported Rom bank: [0x{:02x}]", code),
+            _ => return Err(EmulatorError::UnsupportedRomSizeCode(code)),
         };
         let bank_size: usize = 0x4000; // 16 KiB
         self.rom_size = bank_size * self.number_of_rom_banks as usize;
+        Ok(())
     }
 
-    pub fn calculate_ram_size(&mut self, code: u8) {
+    pub fn calculate_ram_size(&mut self, code: u8) -> Result<()> {
         self.number_of_ram_banks = match code {
             0x00 => 0,
             0x02 => 1,
             0x03 => 4,
             0x04 => 16,
             0x05 => 8,
-            _ => panic!("[CARTRIDGE ERROR] Unsupported Ram bank: [0x{:02x}]", code),
+            _ => return Err(EmulatorError::UnsupportedRamSizeCode(code)),
         };
 
         let bank_size: usize = 0x2000; // 8 KiB
         self.ram_size = bank_size * self.number_of_ram_banks as usize;
+        Ok(())
     }
 }
 
@@ -97,6 +353,9 @@ pub struct Cartridge {
     controller: CartridgeController,
     pub name: String,
     checksum_validation_status: bool,
+    pub rumble_change_req: bool,
+    save_path: Option<PathBuf>,
+    is_ram_dirty: bool,
 }
 
 impl Default for Cartridge {
@@ -107,37 +366,134 @@ impl Default for Cartridge {
             controller: CartridgeController::new(),
             name: String::new(),
             checksum_validation_status: false,
+            rumble_change_req: false,
+            save_path: None,
+            is_ram_dirty: false,
         }
     }
 }
 
 impl Cartridge {
-    pub fn load(&mut self, path: &Path) -> std::io::Result<()> {
-        let mut cartridge_file = File::open(path)?;
-        cartridge_file.read_to_end(&mut self.rom)?;
+    /// # load
+    /// Reads the ROM file and sets up the memory bank controller from the header
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        const HEADER_END: usize = 0x150;
+
+        self.rom = fs::read(path)?;
+        if self.rom.len() < HEADER_END {
+            return Err(EmulatorError::InvalidRomSize(self.rom.len()));
+        }
 
         self.checksum_validation_status = self.is_checksum_valid();
         if !self.checksum_validation_status {
-            let error = Error::new(io::ErrorKind::Other, "Rom checksum is not valid");
-            return Err(error);
+            return Err(EmulatorError::InvalidHeaderChecksum);
         }
 
-        self.name = String::from(path.file_name().unwrap().to_os_string().to_str().unwrap());
+        self.name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
 
-        self.controller
-            .determine_cartridge_type(self.rom[address::cartridge_header::CARTRIDGE_TYPE as usize]);
+        self.controller.determine_cartridge_type(
+            self.rom[address::cartridge_header::CARTRIDGE_TYPE as usize],
+        )?;
 
         self.controller
-            .calculate_rom_size(self.rom[address::cartridge_header::ROM_SIZE as usize]);
+            .calculate_rom_size(self.rom[address::cartridge_header::ROM_SIZE as usize])?;
+        if self.rom.len() < self.controller.rom_size {
+            return Err(EmulatorError::InvalidRomSize(self.rom.len()));
+        }
 
         self.controller
-            .calculate_ram_size(self.rom[address::cartridge_header::RAM_SIZE as usize]);
+            .calculate_ram_size(self.rom[address::cartridge_header::RAM_SIZE as usize])?;
+
+        // MBC2 has 512x4 bits of built-in RAM, the header reports no RAM
+        if self.controller.cart_type == CartridgeType::Mbc2 {
+            self.controller.ram_size = 0x200;
+        }
 
         if self.controller.cart_type != CartridgeType::RomOnly {
-            self.ram.fill(memory::DEFAULT_INIT_VALUE);
-            self.ram.reserve(self.controller.ram_size);
+            self.ram = vec![memory::DEFAULT_INIT_VALUE; self.controller.ram_size];
+        }
+
+        if self.controller.has_battery {
+            let save_path = path.with_extension("sav");
+            if save_path.exists() {
+                self.load_battery_ram(&save_path)?;
+            }
+            self.save_path = Some(save_path);
+        }
+
+        Ok(())
+    }
+
+    /// # is_cgb_supported
+    /// CGB flag (0x0143): 0x80 works on both DMG and CGB, 0xC0 is CGB only
+    pub fn is_cgb_supported(&self) -> bool {
+        let cgb_flag = address::cartridge_header::CGB_FLAG as usize;
+        self.rom
+            .get(cgb_flag)
+            .is_some_and(|flag| (flag & 0x80) == 0x80)
+    }
+
+    /// Header checksum (0x014D) and global checksum (0x014E-0x014F)
+    fn get_rom_checksums(&self) -> [u8; 3] {
+        let mut checksums = [0_u8; 3];
+        let header_checksum = address::cartridge_header::HEADER_CHECKSUM as usize;
+        if let Some(rom_checksums) = self.rom.get(header_checksum..header_checksum + 3) {
+            checksums.copy_from_slice(rom_checksums);
+        }
+        checksums
+    }
+
+    fn unix_timestamp() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// # load_battery_ram
+    /// Loads a raw RAM dump, MBC3+TIMER saves are followed by the RTC footer.
+    fn load_battery_ram(&mut self, save_path: &Path) -> std::io::Result<()> {
+        let save_data = fs::read(save_path)?;
+        let ram_size = self.ram.len().min(save_data.len());
+        self.ram[..ram_size].copy_from_slice(&save_data[..ram_size]);
+
+        let rtc_data = &save_data[ram_size..];
+        if self.controller.has_rtc
+            && (rtc_data.len() == real_time_clock::RTC_SAVE_DATA_SIZE
+                || rtc_data.len() == real_time_clock::RTC_SAVE_DATA_SIZE_32BIT_TIMESTAMP)
+        {
+            self.controller
+                .rtc
+                .load_save_data(rtc_data, Self::unix_timestamp());
+        }
+        Ok(())
+    }
+
+    /// # is_save_required
+    /// RAM has changed since the last flush
+    pub fn is_save_required(&self) -> bool {
+        self.save_path.is_some() && self.is_ram_dirty
+    }
+
+    /// # save_battery_ram
+    /// Flushes battery-backed RAM to the `.sav` file next to the ROM.
+    pub fn save_battery_ram(&mut self) -> std::io::Result<()> {
+        let Some(save_path) = self.save_path.as_ref() else {
+            return Ok(());
+        };
+        if !self.is_ram_dirty && !self.controller.has_rtc {
+            return Ok(());
         }
 
+        let mut save_data = self.ram.clone();
+        if self.controller.has_rtc {
+            save_data.extend(self.controller.rtc.to_save_data(Self::unix_timestamp()));
+        }
+        fs::write(save_path, save_data)?;
+        self.is_ram_dirty = false;
         Ok(())
     }
 
@@ -146,6 +502,10 @@ impl Cartridge {
             CartridgeType::RomOnly => "Rom only",
             CartridgeType::Mbc1 => "MBC1",
             CartridgeType::Mbc2 => "MBC2",
+            CartridgeType::Mbc3 if self.controller.has_rtc => "MBC3+TIMER",
+            CartridgeType::Mbc3 => "MBC3",
+            CartridgeType::Mbc5 if self.controller.has_rumble => "MBC5+RUMBLE",
+            CartridgeType::Mbc5 => "MBC5",
         };
         //println!("-----------------------------");
         println!("Cartridge: {}", self.name);
@@ -160,6 +520,14 @@ impl Cartridge {
             self.controller.ram_size, self.controller.number_of_ram_banks
         );
 
+        if self.is_cgb_supported() {
+            println!(" * Game Boy Color: \x1b[96mSupported\x1b[0m");
+        }
+
+        if let Some(save_path) = self.save_path.as_ref() {
+            println!(" * Battery save: {}", save_path.display());
+        }
+
         if self.checksum_validation_status {
             println!(" * Checksum:\x1b[92m Success\x1b[0m");
         } else {
@@ -230,7 +598,85 @@ impl Cartridge {
         }
     }
 
+    fn mbc3_rom_bank_change(&mut self, data: u8) {
+        let mut reg_data = data & 0x7F;
+        if reg_data == 0 {
+            reg_data = 0x01;
+        }
+        self.controller.current_rom_bank = reg_data as usize;
+    }
+
+    fn mbc3_ram_bank_or_rtc_select(&mut self, data: u8) {
+        match data {
+            0x00..=0x03 => {
+                self.controller.current_ram_bank = data as usize;
+                self.controller.rtc_register_select = None;
+            }
+            0x08..=0x0C if self.controller.has_rtc => {
+                self.controller.rtc_register_select = Some(data);
+            }
+            _ => (),
+        }
+    }
+
+    fn mbc3_bank_handling(&mut self, address: u16, data: u8) {
+        match address {
+            // do RAM and RTC enabling
+            0x0000..=0x1FFF => self.ram_bank_enable_request(address, data),
+            // do ROM bank change
+            0x2000..=0x3FFF => self.mbc3_rom_bank_change(data),
+            // do RAM bank or RTC register select
+            0x4000..=0x5FFF => self.mbc3_ram_bank_or_rtc_select(data),
+            // do latch clock data
+            0x6000..=0x7FFF => self.controller.rtc.latch_request(data),
+            _ => (),
+        }
+    }
+
+    fn mbc5_rom_bank_change_low(&mut self, data: u8) {
+        self.controller.current_rom_bank &= 0x100; // Clear 0-7 bits
+        self.controller.current_rom_bank |= data as usize;
+    }
+
+    fn mbc5_rom_bank_change_high(&mut self, data: u8) {
+        self.controller.current_rom_bank &= 0xFF; // Clear 8 bit
+        self.controller.current_rom_bank |= ((data & 0x01) as usize).rotate_left(8);
+    }
+
+    fn mbc5_ram_bank_change(&mut self, data: u8) {
+        if self.controller.has_rumble {
+            // On rumble carts bit 3 drives the motor instead of selecting a RAM bank
+            let is_rumble_on = (data.rotate_right(3) & 1) == 1;
+            if is_rumble_on != self.controller.is_rumble_on {
+                self.controller.is_rumble_on = is_rumble_on;
+                self.rumble_change_req = true;
+            }
+            self.controller.current_ram_bank = (data & 0x07) as usize;
+        } else {
+            self.controller.current_ram_bank = (data & 0x0F) as usize;
+        }
+    }
+
+    fn mbc5_bank_handling(&mut self, address: u16, data: u8) {
+        match address {
+            // do RAM enabling
+            0x0000..=0x1FFF => self.ram_bank_enable_request(address, data),
+            // do ROM bank change (low 8 bits)
+            0x2000..=0x2FFF => self.mbc5_rom_bank_change_low(data),
+            // do ROM bank change (9th bit)
+            0x3000..=0x3FFF => self.mbc5_rom_bank_change_high(data),
+            // do RAM bank change
+            0x4000..=0x5FFF => self.mbc5_ram_bank_change(data),
+            _ => (),
+        }
+    }
+
     fn bank_handling(&mut self, address: u16, data: u8) {
+        match self.controller.cart_type {
+            CartridgeType::Mbc3 => return self.mbc3_bank_handling(address, data),
+            CartridgeType::Mbc5 => return self.mbc5_bank_handling(address, data),
+            _ => (),
+        }
         match address {
             // do RAM enabling
             0x0000..=0x1FFF => {
@@ -240,9 +686,9 @@ impl Cartridge {
             // do ROM bank change
             0x2000..=0x3FFF => {
                 match self.controller.cart_type {
-                    CartridgeType::RomOnly => (),
                     CartridgeType::Mbc1 => self.mbc1_rom_bank_change_step_1(data),
                     CartridgeType::Mbc2 => self.mbc2_rom_bank_change(data),
+                    _ => (),
                 };
             }
 
@@ -262,46 +708,73 @@ impl Cartridge {
         }
     }
 
+    pub fn is_rumble_on(&self) -> bool {
+        self.controller.is_rumble_on
+    }
+
     fn write_to_ram(&mut self, address: u16, data: u8) {
         if !self.controller.is_ram_enable {
             return;
         }
 
+        if let Some(rtc_register) = self.controller.rtc_register_select {
+            self.controller.rtc.write(rtc_register, data);
+            return;
+        }
+
         let mut new_ram_address = (address - *address::CARTRIDGE_RAM.start()) as usize;
         new_ram_address += self.controller.current_ram_bank * 0x2000;
-        self.ram[new_ram_address] = data;
+        if let Some(ram_byte) = self.ram.get_mut(new_ram_address) {
+            *ram_byte = data;
+            self.is_ram_dirty = true;
+        }
+    }
+}
+
+impl IoWorkingCycle for Cartridge {
+    fn next_to(&mut self, cycles: u32) {
+        if self.controller.has_rtc {
+            self.controller.rtc.tick(cycles);
+        }
     }
 }
 
 impl HardwareAccessible for Cartridge {
     fn read_byte_from_hardware_register(&self, address: u16) -> u8 {
         match address {
-            rom_bank_0_adr if address::CARTRIDGE_ROM_BANK_0.contains(&rom_bank_0_adr) => {
-                self.rom[rom_bank_0_adr as usize]
-            }
+            rom_bank_0_adr if address::CARTRIDGE_ROM_BANK_0.contains(&rom_bank_0_adr) => self
+                .rom
+                .get(rom_bank_0_adr as usize)
+                .copied()
+                .unwrap_or(memory::OPEN_BUS_VALUE),
 
             rom_bank_n_adr if address::CARTRIDGE_ROM_BANK_1_N.contains(&rom_bank_n_adr) => {
                 let mut new_rom_address =
                     (rom_bank_n_adr - *address::CARTRIDGE_ROM_BANK_1_N.start()) as usize;
                 new_rom_address += self.controller.current_rom_bank * 0x4000;
-                self.rom[new_rom_address]
+                // Unconnected bank lines wrap around the ROM size
+                new_rom_address
+                    .checked_rem(self.rom.len())
+                    .map_or(memory::OPEN_BUS_VALUE, |rom_address| self.rom[rom_address])
             }
 
             ram_bank_adr if address::CARTRIDGE_RAM.contains(&ram_bank_adr) => {
-                if self.controller.is_ram_enable {
+                if !self.controller.is_ram_enable {
+                    memory::DEFAULT_INIT_VALUE
+                } else if let Some(rtc_register) = self.controller.rtc_register_select {
+                    self.controller.rtc.read(rtc_register)
+                } else {
                     let mut new_ram_address =
                         (ram_bank_adr - *address::CARTRIDGE_RAM.start()) as usize;
                     new_ram_address += self.controller.current_ram_bank * 0x2000;
-                    self.ram[new_ram_address]
-                } else {
-                    memory::DEFAULT_INIT_VALUE
+                    self.ram
+                        .get(new_ram_address)
+                        .copied()
+                        .unwrap_or(memory::DEFAULT_INIT_VALUE)
                 }
             }
 
-            _ => panic!(
-                "[CARTRIDGE ERROR][Read] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            _ => memory::DEFAULT_INIT_VALUE,
         }
     }
 
@@ -322,10 +795,336 @@ impl HardwareAccessible for Cartridge {
                 self.write_to_ram(ram_address, data);
             }
 
-            _ => panic!(
-                "[CARTRIDGE ERROR][Write] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            _ => (),
+        }
+    }
+}
+
+/// ROM is not a part of the snapshot, only the header checksums are stored
+/// to check that the state is loaded for the same game.
+impl Snapshot for Cartridge {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.get_rom_checksums());
+        writer.write_bool(self.controller.bank_mode == BankMode::Ram);
+        writer.write_u32(self.controller.current_rom_bank as u32);
+        writer.write_bool(self.controller.is_ram_enable);
+        writer.write_u32(self.controller.current_ram_bank as u32);
+        writer.write_u8(self.controller.rtc_register_select.unwrap_or(0));
+        self.controller.rtc.save_state(writer);
+        writer.write_bool(self.controller.is_rumble_on);
+        writer.write_vec(&self.ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        let mut checksums = [0_u8; 3];
+        reader.read_bytes(&mut checksums)?;
+        if checksums != self.get_rom_checksums() {
+            return Err(invalid_data_error("Save state was created for another ROM"));
+        }
+        self.controller.bank_mode = if reader.read_bool()? {
+            BankMode::Ram
+        } else {
+            BankMode::Rom
+        };
+        self.controller.current_rom_bank = reader.read_u32()? as usize;
+        self.controller.is_ram_enable = reader.read_bool()?;
+        self.controller.current_ram_bank = reader.read_u32()? as usize;
+        self.controller.rtc_register_select = match reader.read_u8()? {
+            0 => None,
+            rtc_register => Some(rtc_register),
+        };
+        self.controller.rtc.load_state(reader)?;
+        self.controller.is_rumble_on = reader.read_bool()?;
+
+        let ram = reader.read_vec()?;
+        if ram.len() != self.ram.len() {
+            return Err(invalid_data_error("Save state RAM size does not match"));
         }
+        self.ram = ram;
+        self.is_ram_dirty = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::real_time_clock::*;
+    use super::*;
+    use crate::emulator_constants::clock;
+
+    fn mbc3_cartridge_with_rtc() -> Cartridge {
+        let mut cartridge = Cartridge::default();
+        cartridge.controller.determine_cartridge_type(0x10).unwrap();
+        cartridge.controller.calculate_rom_size(0x05).unwrap();
+        cartridge.controller.calculate_ram_size(0x03).unwrap();
+        cartridge.rom = vec![0; cartridge.controller.rom_size];
+        cartridge.ram = vec![0; cartridge.controller.ram_size];
+        cartridge
+    }
+
+    #[test]
+    fn mbc3_rom_bank_switch_test() {
+        let mut cartridge = mbc3_cartridge_with_rtc();
+        cartridge.rom[0x4000 * 0x25] = 0xAB;
+
+        cartridge.write_byte_to_hardware_register(0x2000, 0x25);
+        assert_eq!(0x25, cartridge.controller.current_rom_bank);
+        assert_eq!(0xAB, cartridge.read_byte_from_hardware_register(0x4000));
+
+        // Bank 0 is mapped as bank 1
+        cartridge.write_byte_to_hardware_register(0x2000, 0x00);
+        assert_eq!(0x01, cartridge.controller.current_rom_bank);
+    }
+
+    #[test]
+    fn mbc3_ram_bank_switch_test() {
+        let mut cartridge = mbc3_cartridge_with_rtc();
+
+        cartridge.write_byte_to_hardware_register(0x0000, 0x0A);
+        cartridge.write_byte_to_hardware_register(0x4000, 0x02);
+        cartridge.write_byte_to_hardware_register(0xA010, 0x55);
+
+        assert_eq!(0x55, cartridge.ram[2 * 0x2000 + 0x10]);
+        assert_eq!(0x55, cartridge.read_byte_from_hardware_register(0xA010));
+
+        cartridge.write_byte_to_hardware_register(0x4000, 0x00);
+        assert_eq!(0x00, cartridge.read_byte_from_hardware_register(0xA010));
+
+        // RAM disabled
+        cartridge.write_byte_to_hardware_register(0x0000, 0x00);
+        assert_eq!(
+            memory::DEFAULT_INIT_VALUE,
+            cartridge.read_byte_from_hardware_register(0xA010)
+        );
+    }
+
+    #[test]
+    fn mbc3_rtc_latch_test() {
+        let mut cartridge = mbc3_cartridge_with_rtc();
+        cartridge.write_byte_to_hardware_register(0x0000, 0x0A);
+
+        // 1 day, 23:59:59 + 1 second
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_SECONDS);
+        cartridge.write_byte_to_hardware_register(0xA000, 59);
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_MINUTES);
+        cartridge.write_byte_to_hardware_register(0xA000, 59);
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_HOURS);
+        cartridge.write_byte_to_hardware_register(0xA000, 23);
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_DAY_LOW);
+        cartridge.write_byte_to_hardware_register(0xA000, 1);
+
+        cartridge.next_to(clock::CPU_CLOCK_FREQUENCY);
+
+        // Not latched yet
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_DAY_LOW);
+        assert_eq!(0, cartridge.read_byte_from_hardware_register(0xA000));
+
+        cartridge.write_byte_to_hardware_register(0x6000, 0x00);
+        cartridge.write_byte_to_hardware_register(0x6000, 0x01);
+
+        assert_eq!(2, cartridge.read_byte_from_hardware_register(0xA000));
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_HOURS);
+        assert_eq!(0, cartridge.read_byte_from_hardware_register(0xA000));
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_MINUTES);
+        assert_eq!(0, cartridge.read_byte_from_hardware_register(0xA000));
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_SECONDS);
+        assert_eq!(0, cartridge.read_byte_from_hardware_register(0xA000));
+    }
+
+    #[test]
+    fn mbc5_rom_and_ram_bank_switch_test() {
+        let mut cartridge = Cartridge::default();
+        cartridge.controller.determine_cartridge_type(0x1B).unwrap();
+        cartridge.controller.calculate_rom_size(0x08).unwrap();
+        cartridge.controller.calculate_ram_size(0x04).unwrap();
+        cartridge.rom = vec![0; cartridge.controller.rom_size];
+        cartridge.ram = vec![0; cartridge.controller.ram_size];
+        cartridge.rom[0x4000 * 0x1A5] = 0xAB;
+
+        cartridge.write_byte_to_hardware_register(0x2000, 0xA5);
+        cartridge.write_byte_to_hardware_register(0x3000, 0x01);
+        assert_eq!(0x1A5, cartridge.controller.current_rom_bank);
+        assert_eq!(0xAB, cartridge.read_byte_from_hardware_register(0x4000));
+
+        // Bank 0 can be mapped to 0x4000-0x7FFF
+        cartridge.write_byte_to_hardware_register(0x2000, 0x00);
+        cartridge.write_byte_to_hardware_register(0x3000, 0x00);
+        assert_eq!(0x00, cartridge.controller.current_rom_bank);
+
+        cartridge.write_byte_to_hardware_register(0x0000, 0x0A);
+        cartridge.write_byte_to_hardware_register(0x4000, 0x0F);
+        cartridge.write_byte_to_hardware_register(0xA000, 0x55);
+        assert_eq!(0x55, cartridge.ram[0x0F * 0x2000]);
+        assert!(!cartridge.rumble_change_req);
+    }
+
+    #[test]
+    fn mbc5_rumble_test() {
+        let mut cartridge = Cartridge::default();
+        cartridge.controller.determine_cartridge_type(0x1E).unwrap();
+        cartridge.controller.calculate_ram_size(0x03).unwrap();
+        cartridge.ram = vec![0; cartridge.controller.ram_size];
+
+        cartridge.write_byte_to_hardware_register(0x4000, 0x0B);
+        assert!(cartridge.rumble_change_req);
+        assert!(cartridge.is_rumble_on());
+        assert_eq!(0x03, cartridge.controller.current_ram_bank);
+
+        cartridge.rumble_change_req = false;
+        cartridge.write_byte_to_hardware_register(0x4000, 0x0A);
+        assert!(!cartridge.rumble_change_req);
+
+        cartridge.write_byte_to_hardware_register(0x4000, 0x02);
+        assert!(cartridge.rumble_change_req);
+        assert!(!cartridge.is_rumble_on());
+    }
+
+    #[test]
+    fn battery_ram_save_and_load_test() {
+        let save_path = std::env::temp_dir().join("r_gb_emu_battery_ram_test.sav");
+        let mut cartridge = mbc3_cartridge_with_rtc();
+        cartridge.save_path = Some(save_path.clone());
+        assert!(cartridge.controller.has_battery);
+
+        cartridge.write_byte_to_hardware_register(0x0000, 0x0A);
+        cartridge.write_byte_to_hardware_register(0x4000, 0x03);
+        cartridge.write_byte_to_hardware_register(0xA123, 0x42);
+        cartridge.write_byte_to_hardware_register(0x4000, RTC_HOURS);
+        cartridge.write_byte_to_hardware_register(0xA000, 12);
+        assert!(cartridge.is_save_required());
+
+        cartridge.save_battery_ram().unwrap();
+        assert!(!cartridge.is_save_required());
+        assert_eq!(
+            cartridge.controller.ram_size + RTC_SAVE_DATA_SIZE,
+            fs::read(&save_path).unwrap().len()
+        );
+
+        let mut loaded_cartridge = mbc3_cartridge_with_rtc();
+        loaded_cartridge.load_battery_ram(&save_path).unwrap();
+        let _ = fs::remove_file(&save_path);
+
+        assert_eq!(0x42, loaded_cartridge.ram[3 * 0x2000 + 0x123]);
+        assert_eq!(12, loaded_cartridge.controller.rtc.registers.hours);
+    }
+
+    #[test]
+    fn rtc_advance_seconds_test() {
+        let mut registers = RtcRegisters {
+            seconds: 30,
+            minutes: 59,
+            hours: 23,
+            day_low: 0xFF,
+            day_high: 0x00,
+        };
+        registers.advance_seconds(30);
+        assert_eq!(0, registers.seconds);
+        assert_eq!(0, registers.minutes);
+        assert_eq!(0, registers.hours);
+        assert_eq!(0x00, registers.day_low);
+        assert_eq!(0x01, registers.day_high);
+
+        registers.advance_seconds(256 * 86400);
+        assert_eq!(0x00, registers.day_low);
+        assert_eq!(0x80, registers.day_high);
+    }
+
+    #[test]
+    fn mbc3_rtc_halt_and_day_carry_test() {
+        let mut rtc = RealTimeClock::default();
+
+        rtc.write(RTC_DAY_HIGH, 0x40);
+        rtc.tick(clock::CPU_CLOCK_FREQUENCY * 2);
+        assert_eq!(0, rtc.registers.seconds);
+
+        // Day 511 23:59:59
+        rtc.write(RTC_DAY_HIGH, 0x01);
+        rtc.write(RTC_DAY_LOW, 0xFF);
+        rtc.write(RTC_HOURS, 23);
+        rtc.write(RTC_MINUTES, 59);
+        rtc.write(RTC_SECONDS, 59);
+        rtc.tick(clock::CPU_CLOCK_FREQUENCY);
+
+        assert_eq!(0, rtc.registers.day_low);
+        assert_eq!(0x80, rtc.registers.day_high);
+    }
+
+    #[test]
+    fn load_errors_test() {
+        let mut cartridge = Cartridge::default();
+        assert!(matches!(
+            cartridge.load(Path::new("roms/does_not_exist.gb")),
+            Err(EmulatorError::Io(_))
+        ));
+
+        let rom_path = std::env::temp_dir().join("r_gb_emu_too_small_rom.gb");
+        fs::write(&rom_path, [0_u8; 0x100]).unwrap();
+        assert!(matches!(
+            cartridge.load(&rom_path),
+            Err(EmulatorError::InvalidRomSize(0x100))
+        ));
+        let _ = fs::remove_file(&rom_path);
+
+        // Valid header which declares 64 KiB, the file has only 32 KiB
+        let mut rom = vec![0_u8; 0x8000];
+        rom[address::cartridge_header::ROM_SIZE as usize] = 0x01;
+        rom[0x14D] = rom[0x134..0x14D].iter().fold(0u8, |checksum, byte| {
+            checksum.wrapping_sub(*byte).wrapping_sub(1)
+        });
+        let rom_path = std::env::temp_dir().join("r_gb_emu_truncated_rom.gb");
+        fs::write(&rom_path, &rom).unwrap();
+        assert!(matches!(
+            cartridge.load(&rom_path),
+            Err(EmulatorError::InvalidRomSize(0x8000))
+        ));
+        let _ = fs::remove_file(&rom_path);
+
+        let mut controller = CartridgeController::new();
+        assert!(matches!(
+            controller.determine_cartridge_type(0xFC),
+            Err(EmulatorError::UnsupportedCartridgeType(0xFC))
+        ));
+        assert!(matches!(
+            controller.calculate_rom_size(0x09),
+            Err(EmulatorError::UnsupportedRomSizeCode(0x09))
+        ));
+        assert!(matches!(
+            controller.calculate_ram_size(0x01),
+            Err(EmulatorError::UnsupportedRamSizeCode(0x01))
+        ));
+    }
+
+    #[test]
+    fn read_without_rom_test() {
+        let mut cartridge = Cartridge::default();
+        assert_eq!(
+            memory::OPEN_BUS_VALUE,
+            cartridge.read_byte_from_hardware_register(0x0100)
+        );
+        assert_eq!(
+            memory::OPEN_BUS_VALUE,
+            cartridge.read_byte_from_hardware_register(0x4000)
+        );
+
+        cartridge.rom = vec![0; 0x150];
+        assert_eq!(
+            memory::OPEN_BUS_VALUE,
+            cartridge.read_byte_from_hardware_register(0x0150)
+        );
+    }
+
+    #[test]
+    fn cgb_flag_test() {
+        let mut cartridge = Cartridge {
+            rom: vec![0; 0x150],
+            ..Default::default()
+        };
+        assert!(!cartridge.is_cgb_supported());
+
+        cartridge.rom[address::cartridge_header::CGB_FLAG as usize] = 0x80;
+        assert!(cartridge.is_cgb_supported());
+
+        cartridge.rom[address::cartridge_header::CGB_FLAG as usize] = 0xC0;
+        assert!(cartridge.is_cgb_supported());
     }
 }
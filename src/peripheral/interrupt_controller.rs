@@ -1,5 +1,6 @@
 use super::HardwareAccessible;
-use crate::constants::gb_memory_map::address;
+use crate::constants::gb_memory_map::{address, memory};
+use crate::save_state::{Snapshot, StateReader, StateWriter};
 
 /// # InterruptRegister
 ///
@@ -65,21 +66,31 @@ impl HardwareAccessible for InterruptController {
         match address {
             address::INTF_REGISTER => InterruptRegister::into(self.intf),
             address::INTE_REGISTER => InterruptRegister::into(self.inte),
-            _ => panic!("[ISR ERROR][Read] Unsupported address: [{:#06x?}]", address),
+            _ => memory::DEFAULT_INIT_VALUE,
         }
     }
     fn write_byte_to_hardware_register(&mut self, address: u16, data: u8) {
         match address {
             address::INTF_REGISTER => self.intf = InterruptRegister::from(data),
             address::INTE_REGISTER => self.inte = InterruptRegister::from(data),
-            _ => panic!(
-                "[ISR ERROR][Write] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            _ => (),
         }
     }
 }
 
+impl Snapshot for InterruptController {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(InterruptRegister::into(self.intf));
+        writer.write_u8(InterruptRegister::into(self.inte));
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        self.intf = InterruptRegister::from(reader.read_u8()?);
+        self.inte = InterruptRegister::from(reader.read_u8()?);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod ut {
 
@@ -94,11 +105,11 @@ mod ut {
             0x19,
             isr.read_byte_from_hardware_register(address::INTF_REGISTER)
         );
-        assert!(isr.intf.joypad == true);
-        assert!(isr.intf.serial_link == true);
-        assert!(isr.intf.timer == false);
-        assert!(isr.intf.lcd == false);
-        assert!(isr.intf.v_blank == true);
+        assert!(isr.intf.joypad);
+        assert!(isr.intf.serial_link);
+        assert!(!isr.intf.timer);
+        assert!(!isr.intf.lcd);
+        assert!(isr.intf.v_blank);
 
         //IE
         isr.inte.lcd = true;
@@ -113,10 +124,10 @@ mod ut {
             0x1F,
             isr.read_byte_from_hardware_register(address::INTE_REGISTER)
         );
-        assert!(isr.inte.joypad == true);
-        assert!(isr.inte.serial_link == true);
-        assert!(isr.inte.timer == true);
-        assert!(isr.inte.lcd == true);
-        assert!(isr.inte.v_blank == true);
+        assert!(isr.inte.joypad);
+        assert!(isr.inte.serial_link);
+        assert!(isr.inte.timer);
+        assert!(isr.inte.lcd);
+        assert!(isr.inte.v_blank);
     }
 }
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
 use super::HardwareAccessible;
-use crate::constants::gb_memory_map::address::io_hardware_register;
+use crate::constants::gb_memory_map::{address::io_hardware_register, memory};
 use crate::emulator_constants::GameBoyKeys;
+use crate::save_state::{Snapshot, StateReader, StateWriter};
 
 mod joypad_state_register {
     pub const ALL_KEYS_NOT_PRESSED: u8 = 0xFF;
@@ -92,23 +93,37 @@ impl HardwareAccessible for JoypadInput {
                     self.select
                 }
             }
-            _ => panic!(
-                "[JOYPAD ERROR][Read] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            _ => memory::DEFAULT_INIT_VALUE,
         }
     }
 
     fn write_byte_to_hardware_register(&mut self, address: u16, data: u8) {
-        match address {
-            io_hardware_register::JOYPAD_INPUT => self.select = 0xCF | (data & 0x30),
-            _ => panic!(
-                "[JOYPAD ERROR][Write] Unsupported address: [{:#06x?}]",
-                address
-            ),
+        if address == io_hardware_register::JOYPAD_INPUT {
+            self.select = 0xCF | (data & 0x30);
         }
     }
 }
+impl Snapshot for JoypadInput {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.key_data_register[&joypad_state_register::D_PAD_MODE_REQUEST]);
+        writer.write_u8(self.key_data_register[&joypad_state_register::BUTTONS_MODE_REQUEST]);
+        writer.write_u8(self.select);
+        writer.write_bool(self.interrupt_req);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        self.key_data_register
+            .insert(joypad_state_register::D_PAD_MODE_REQUEST, reader.read_u8()?);
+        self.key_data_register.insert(
+            joypad_state_register::BUTTONS_MODE_REQUEST,
+            reader.read_u8()?,
+        );
+        self.select = reader.read_u8()?;
+        self.interrupt_req = reader.read_bool()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod ut {
     use super::*;
@@ -137,7 +152,7 @@ mod ut {
                 joypad.read_byte_from_hardware_register(io_hardware_register::JOYPAD_INPUT)
             );
 
-            assert!(joypad.interrupt_req == true);
+            assert!(joypad.interrupt_req);
 
             joypad.key_released(key.0);
 
@@ -175,7 +190,7 @@ mod ut {
                 joypad.read_byte_from_hardware_register(io_hardware_register::JOYPAD_INPUT)
             );
 
-            assert!(joypad.interrupt_req == true);
+            assert!(joypad.interrupt_req);
 
             joypad.key_released(key.0);
 
@@ -1,9 +1,12 @@
 use self::fsm::PpuState;
-use self::lcd_monochrome::{Color, PaletteRegister, PalleteMode, Pixel2bpp};
+use self::lcd_color::ColorPaletteRam;
+use self::lcd_monochrome::{Color, PaletteRegister, Pixel2bpp};
+use self::pixel_fifo::{BgPixel, FetcherStep, ObjPixel, PixelFifo};
 use self::sprite::{Attribute, Sprite};
 use super::{HardwareAccessible, IoWorkingCycle};
 use crate::constants::gb_memory_map::{address, address::io_hardware_register, memory};
-use crate::emulator_constants::resolution;
+use crate::emulator_constants::{resolution, PpuRenderer};
+use crate::save_state::{invalid_data_error, Snapshot, StateReader, StateWriter};
 
 mod graphics {
     pub const MAX_NUMBER_OF_SPRITES: u16 = 40;
@@ -160,12 +163,6 @@ impl std::convert::From<LcdStatusRegister> for u8 {
 }
 
 mod lcd_monochrome {
-    #[derive(Clone, Copy, PartialEq)]
-    pub enum PalleteMode {
-        BgPallete,
-        ObjPallete,
-    }
-
     #[derive(Clone, Copy, PartialEq, Debug)]
     pub enum Color {
         White = 0xff,
@@ -183,12 +180,11 @@ mod lcd_monochrome {
     #[derive(Clone, Copy)]
     pub struct PaletteRegister {
         pub data: u8,
-        mode: PalleteMode,
     }
 
     impl PaletteRegister {
-        pub fn new(mode: PalleteMode) -> Self {
-            Self { data: 0, mode }
+        pub fn new() -> Self {
+            Self { data: 0 }
         }
         pub fn get_color(&self, color_id: u8) -> Color {
             let color_value = self.data.rotate_right(2 * color_id as u32) & 0x03;
@@ -199,9 +195,6 @@ mod lcd_monochrome {
                 _ => Color::Black,
             }
         }
-        pub fn is_transparent(&self, color: Color) -> bool {
-            self.mode == PalleteMode::ObjPallete && color == Color::White
-        }
     }
 
     #[derive(Clone, Copy)]
@@ -226,6 +219,71 @@ mod lcd_monochrome {
     }
 }
 
+mod lcd_color {
+    use crate::constants::gb_memory_map::memory;
+
+    /// # ColorPaletteRam
+    /// CGB palette memory: 8 palettes x 4 colors x 2 bytes (RGB555, little endian).
+    /// Accessed through the specification register (index and auto increment) and the data register.
+    #[derive(Clone, Copy)]
+    pub struct ColorPaletteRam {
+        pub data: [u8; memory::COLOR_PALETTE_RAM_SIZE],
+        pub index: u8,
+        pub auto_increment: bool,
+    }
+
+    impl ColorPaletteRam {
+        pub fn new() -> Self {
+            Self {
+                data: [memory::DEFAULT_INIT_VALUE; memory::COLOR_PALETTE_RAM_SIZE],
+                index: 0,
+                auto_increment: false,
+            }
+        }
+
+        pub fn read_specification(&self) -> u8 {
+            // Bit 6 is not used and reads as 1
+            let mut out_value = 0x40 | self.index;
+            if self.auto_increment {
+                out_value |= 1_u8.rotate_left(7);
+            }
+            out_value
+        }
+
+        pub fn write_specification(&mut self, data: u8) {
+            self.index = data & 0x3F;
+            self.auto_increment = (data.rotate_right(7) & 1) == 1;
+        }
+
+        pub fn read_data(&self) -> u8 {
+            self.data[self.index as usize]
+        }
+
+        pub fn write_data(&mut self, data: u8) {
+            self.data[self.index as usize] = data;
+            if self.auto_increment {
+                self.index = (self.index + 1) & 0x3F;
+            }
+        }
+
+        /// # get_rgb
+        /// 15-bit color scaled to 8 bits per channel
+        pub fn get_rgb(&self, palette: u8, color_id: u8) -> [u8; 3] {
+            let offset = (palette as usize & 0x07) * 8 + color_id as usize * 2;
+            let color = u16::from_le_bytes([self.data[offset], self.data[offset + 1]]);
+            let scale = |channel: u16| {
+                let channel = (channel & 0x1F) as u8;
+                channel.rotate_left(3) | channel.rotate_right(2) & 0x07
+            };
+            [
+                scale(color),
+                scale(color.rotate_right(5)),
+                scale(color.rotate_right(10)),
+            ]
+        }
+    }
+}
+
 mod fsm {
     #[derive(Clone, Copy, PartialEq, Debug)]
     pub enum PpuState {
@@ -239,6 +297,15 @@ mod fsm {
         pub fn new() -> Self {
             Self::OamScanMode2
         }
+        pub fn from_mode(mode: u8) -> Option<Self> {
+            match mode {
+                2 => Some(Self::OamScanMode2),
+                3 => Some(Self::DrawingPixelsMode3),
+                0 => Some(Self::HBlankMode0),
+                1 => Some(Self::VBlankMode1),
+                _ => None,
+            }
+        }
         pub fn next(self) -> Self {
             match self {
                 Self::OamScanMode2 => Self::DrawingPixelsMode3,
@@ -251,21 +318,51 @@ mod fsm {
 }
 
 mod sprite {
+    /// # Attribute
+    /// OAM byte 3. On CGB the BG map attributes (VRAM bank 1) use the same layout,
+    /// there Bit7 is BG-to-OAM priority and Bit4 is not used.
     pub struct Attribute {
         pub priority: bool, // Bit7 OBJ-to-BG Priority (0=OBJ Above BG, 1=OBJ Behind BG color 1-3)
         pub yflip: bool,    // Bit6 Y flip          (0=Normal, 1=Vertically mirrored)
         pub xflip: bool,    // Bit5 X flip          (0=Normal, 1=Horizontally mirrored)
         pub dmg_palette: bool, // Bit4 Palette number  **Non CGB Mode Only** (0=OBP0, 1=OBP1)
+        pub vram_bank: bool, // Bit3 Tile VRAM bank  **CGB Mode Only** (0=Bank 0, 1=Bank 1)
+        pub cgb_palette: u8, // Bit2-0 Palette number **CGB Mode Only** (OBP0-7)
     }
 
     impl From<u8> for Attribute {
         fn from(value: u8) -> Self {
             Self {
-                priority: (value.rotate_left(7) & 1) == 1,
-                yflip: (value.rotate_left(6) & 1) == 1,
-                xflip: (value.rotate_left(5) & 1) == 1,
-                dmg_palette: (value.rotate_left(4) & 1) == 1,
+                priority: (value.rotate_right(7) & 1) == 1,
+                yflip: (value.rotate_right(6) & 1) == 1,
+                xflip: (value.rotate_right(5) & 1) == 1,
+                dmg_palette: (value.rotate_right(4) & 1) == 1,
+                vram_bank: (value.rotate_right(3) & 1) == 1,
+                cgb_palette: value & 0x07,
+            }
+        }
+    }
+
+    impl From<&Attribute> for u8 {
+        fn from(attribute: &Attribute) -> Self {
+            let mut out_value: u8 = 0;
+            if attribute.priority {
+                out_value |= 1_u8.rotate_left(7);
             }
+            if attribute.yflip {
+                out_value |= 1_u8.rotate_left(6);
+            }
+            if attribute.xflip {
+                out_value |= 1_u8.rotate_left(5);
+            }
+            if attribute.dmg_palette {
+                out_value |= 1_u8.rotate_left(4);
+            }
+            if attribute.vram_bank {
+                out_value |= 1_u8.rotate_left(3);
+            }
+            out_value |= attribute.cgb_palette & 0x07;
+            out_value
         }
     }
 
@@ -276,14 +373,301 @@ mod sprite {
         pub x_position: u8,       // Byte 1 — X Position
         pub y_position: u8,       // Byte 0 — Y Position
     }
+
+    impl Sprite {
+        /// # screen_x
+        /// Positions are stored shifted by 8 (X) and 16 (Y), sprites can start left of the screen
+        pub fn screen_x(&self) -> i16 {
+            if self.x_position >= 248 {
+                return self.x_position as i16 - 256;
+            }
+            self.x_position as i16
+        }
+
+        /// # screen_y
+        pub fn screen_y(&self) -> i16 {
+            if self.y_position >= 240 {
+                return self.y_position as i16 - 256;
+            }
+            self.y_position as i16
+        }
+    }
+}
+
+mod pixel_fifo {
+    use super::sprite::Attribute;
+    use crate::emulator_constants::resolution;
+    use crate::save_state::{invalid_data_error, Snapshot, StateReader, StateWriter};
+    use std::collections::VecDeque;
+
+    pub const FIFO_SIZE: usize = 8;
+    // The first tile is fetched twice at the beginning of Mode 3
+    pub const STARTUP_DOTS: u8 = 6;
+    pub const SPRITE_FETCH_DOTS: u8 = 6;
+
+    /// # FetcherStep
+    /// Every step except Push takes 2 dots. Push waits until the background FIFO is empty.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum FetcherStep {
+        GetTile = 0,
+        GetTileDataLow = 1,
+        GetTileDataHigh = 2,
+        Push = 3,
+    }
+
+    impl FetcherStep {
+        pub fn from_step(step: u8) -> Option<Self> {
+            match step {
+                0 => Some(Self::GetTile),
+                1 => Some(Self::GetTileDataLow),
+                2 => Some(Self::GetTileDataHigh),
+                3 => Some(Self::Push),
+                _ => None,
+            }
+        }
+        pub fn next(self) -> Self {
+            match self {
+                Self::GetTile => Self::GetTileDataLow,
+                Self::GetTileDataLow => Self::GetTileDataHigh,
+                Self::GetTileDataHigh => Self::Push,
+                Self::Push => Self::GetTile,
+            }
+        }
+    }
+
+    /// # BgPixel
+    /// Palette and priority are used only in CGB mode
+    #[derive(Clone, Copy, Default, PartialEq, Debug)]
+    pub struct BgPixel {
+        pub color_id: u8,
+        pub cgb_palette: u8,
+        pub bg_priority: bool,
+    }
+
+    #[derive(Clone, Copy, Default, PartialEq, Debug)]
+    pub struct ObjPixel {
+        pub color_id: u8,
+        pub obp1_palette: bool,
+        pub cgb_palette: u8,
+        pub bg_priority: bool,
+        // Position in the OAM scan, on CGB the lower one is drawn on top
+        pub sprite_id: u8,
+    }
+
+    /// # PixelFifo
+    /// State of the dot based renderer: background/window fetcher, both FIFOs and
+    /// the sprite fetch which stalls the pixel output.
+    pub struct PixelFifo {
+        pub bg_fifo: VecDeque<BgPixel>,
+        pub obj_fifo: VecDeque<ObjPixel>,
+        pub fetcher_step: FetcherStep,
+        pub fetcher_dots: u8,
+        pub fetcher_x: u8,
+        pub tile_number: u8,
+        pub tile_attributes: u8,
+        pub tile_data_low: u8,
+        pub tile_data_high: u8,
+        pub lx: u8,
+        pub discard_pixels: u8,
+        pub startup_dots: u8,
+        pub fetching_window: bool,
+        pub window_y_triggered: bool,
+        pub window_drawn_on_line: bool,
+        pub sprite_pending: Option<u8>,
+        pub sprite_fetch_dots: u8,
+        pub fetched_sprites: u16,
+    }
+
+    impl PixelFifo {
+        pub fn new() -> Self {
+            Self {
+                bg_fifo: VecDeque::with_capacity(FIFO_SIZE),
+                obj_fifo: VecDeque::with_capacity(FIFO_SIZE),
+                fetcher_step: FetcherStep::GetTile,
+                fetcher_dots: 0,
+                fetcher_x: 0,
+                tile_number: 0,
+                tile_attributes: 0,
+                tile_data_low: 0,
+                tile_data_high: 0,
+                lx: 0,
+                discard_pixels: 0,
+                startup_dots: 0,
+                fetching_window: false,
+                window_y_triggered: false,
+                window_drawn_on_line: false,
+                sprite_pending: None,
+                sprite_fetch_dots: 0,
+                fetched_sprites: 0,
+            }
+        }
+
+        /// # start_scanline
+        /// Mode 3 begins, the fine scroll SCX % 8 is dropped from the first tile
+        pub fn start_scanline(&mut self, scx: u8) {
+            self.bg_fifo.clear();
+            self.obj_fifo.clear();
+            self.reset_fetcher();
+            self.lx = 0;
+            self.discard_pixels = scx % 8;
+            self.startup_dots = STARTUP_DOTS;
+            self.fetching_window = false;
+            self.window_drawn_on_line = false;
+            self.sprite_pending = None;
+            self.sprite_fetch_dots = 0;
+            self.fetched_sprites = 0;
+        }
+
+        pub fn reset_fetcher(&mut self) {
+            self.fetcher_step = FetcherStep::GetTile;
+            self.fetcher_dots = 0;
+            self.fetcher_x = 0;
+        }
+
+        /// # push_tile_row
+        /// 8 pixels of the fetched row, the leftmost pixel first.
+        /// Tile attributes are always 0 in DMG mode.
+        pub fn push_tile_row(&mut self) {
+            let attribute = Attribute::from(self.tile_attributes);
+            for pixel_col in 0..8 {
+                let bit = if attribute.xflip {
+                    pixel_col
+                } else {
+                    7 - pixel_col
+                };
+                let low = self.tile_data_low.rotate_right(bit) & 1;
+                let high = self.tile_data_high.rotate_right(bit) & 1;
+                self.bg_fifo.push_back(BgPixel {
+                    color_id: high.rotate_left(1) | low,
+                    cgb_palette: attribute.cgb_palette,
+                    bg_priority: attribute.priority,
+                });
+            }
+        }
+
+        /// # merge_sprite_row
+        /// Sprite pixels are mixed into the OBJ FIFO. A pixel already there wins unless it is transparent,
+        /// this gives the DMG priority: lower X first, then lower OAM index.
+        /// On CGB only the OAM index counts.
+        pub fn merge_sprite_row(&mut self, row: [ObjPixel; 8], first_pixel: usize, cgb_mode: bool) {
+            while self.obj_fifo.len() < FIFO_SIZE {
+                self.obj_fifo.push_back(ObjPixel::default());
+            }
+            for (slot, pixel) in row.iter().skip(first_pixel).enumerate() {
+                let fifo_pixel = self.obj_fifo[slot];
+                let is_on_top =
+                    cgb_mode && pixel.color_id != 0 && pixel.sprite_id < fifo_pixel.sprite_id;
+                if fifo_pixel.color_id == 0 || is_on_top {
+                    self.obj_fifo[slot] = *pixel;
+                }
+            }
+        }
+    }
+
+    impl Snapshot for PixelFifo {
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.write_u8(self.bg_fifo.len() as u8);
+            for pixel in self.bg_fifo.iter() {
+                writer.write_u8(pixel.color_id);
+                writer.write_u8(pixel.cgb_palette);
+                writer.write_bool(pixel.bg_priority);
+            }
+            writer.write_u8(self.obj_fifo.len() as u8);
+            for pixel in self.obj_fifo.iter() {
+                writer.write_u8(pixel.color_id);
+                writer.write_bool(pixel.obp1_palette);
+                writer.write_u8(pixel.cgb_palette);
+                writer.write_bool(pixel.bg_priority);
+                writer.write_u8(pixel.sprite_id);
+            }
+            writer.write_u8(self.fetcher_step as u8);
+            writer.write_u8(self.fetcher_dots);
+            writer.write_u8(self.fetcher_x);
+            writer.write_u8(self.tile_number);
+            writer.write_u8(self.tile_attributes);
+            writer.write_u8(self.tile_data_low);
+            writer.write_u8(self.tile_data_high);
+            writer.write_u8(self.lx);
+            writer.write_u8(self.discard_pixels);
+            writer.write_u8(self.startup_dots);
+            writer.write_bool(self.fetching_window);
+            writer.write_bool(self.window_y_triggered);
+            writer.write_bool(self.window_drawn_on_line);
+            writer.write_bool(self.sprite_pending.is_some());
+            writer.write_u8(self.sprite_pending.unwrap_or(0));
+            writer.write_u8(self.sprite_fetch_dots);
+            writer.write_u16(self.fetched_sprites);
+        }
+
+        fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+            self.bg_fifo.clear();
+            let bg_fifo_len = reader.read_u8()? as usize;
+            if bg_fifo_len > FIFO_SIZE {
+                return Err(invalid_data_error("Invalid BG FIFO length"));
+            }
+            for _ in 0..bg_fifo_len {
+                self.bg_fifo.push_back(BgPixel {
+                    color_id: reader.read_u8()?,
+                    cgb_palette: reader.read_u8()?,
+                    bg_priority: reader.read_bool()?,
+                });
+            }
+            self.obj_fifo.clear();
+            let obj_fifo_len = reader.read_u8()? as usize;
+            if obj_fifo_len > FIFO_SIZE {
+                return Err(invalid_data_error("Invalid OBJ FIFO length"));
+            }
+            for _ in 0..obj_fifo_len {
+                self.obj_fifo.push_back(ObjPixel {
+                    color_id: reader.read_u8()?,
+                    obp1_palette: reader.read_bool()?,
+                    cgb_palette: reader.read_u8()?,
+                    bg_priority: reader.read_bool()?,
+                    sprite_id: reader.read_u8()?,
+                });
+            }
+            self.fetcher_step = FetcherStep::from_step(reader.read_u8()?)
+                .ok_or_else(|| invalid_data_error("Invalid pixel fetcher step"))?;
+            self.fetcher_dots = reader.read_u8()?;
+            self.fetcher_x = reader.read_u8()?;
+            self.tile_number = reader.read_u8()?;
+            self.tile_attributes = reader.read_u8()?;
+            self.tile_data_low = reader.read_u8()?;
+            self.tile_data_high = reader.read_u8()?;
+            self.lx = reader.read_u8()?;
+            self.discard_pixels = reader.read_u8()?;
+            self.startup_dots = reader.read_u8()?;
+            self.fetching_window = reader.read_bool()?;
+            self.window_y_triggered = reader.read_bool()?;
+            self.window_drawn_on_line = reader.read_bool()?;
+            let is_sprite_pending = reader.read_bool()?;
+            let sprite_pending = reader.read_u8()?;
+            self.sprite_pending = is_sprite_pending.then_some(sprite_pending);
+            self.sprite_fetch_dots = reader.read_u8()?;
+            self.fetched_sprites = reader.read_u16()?;
+
+            // Counters out of range would keep the PPU in Mode 3 past the end of the scanline
+            let is_in_range = self.lx as usize <= resolution::SCREEN_W
+                && (self.discard_pixels as usize) < FIFO_SIZE
+                && self.startup_dots <= STARTUP_DOTS
+                && self.fetcher_dots < 2
+                && self.sprite_fetch_dots < SPRITE_FETCH_DOTS;
+            if !is_in_range {
+                return Err(invalid_data_error("Invalid pixel FIFO counters"));
+            }
+            Ok(())
+        }
+    }
 }
 
 /// # PPU (Picture Processing Unit)
 /// On Gameboy Classic there's only one way to initialize VRAM - manually copy data with CPU instructions. This is done in bootstrap ROM process:
 pub struct PictureProcessingUnit {
     //..::Memory::..
-    vram: [u8; memory::VRAM_SIZE],
+    vram: [[u8; memory::VRAM_SIZE]; memory::CGB_VRAM_BANKS],
     voam: [u8; memory::VOAM_SIZE],
+    bg_palette_ram: ColorPaletteRam,
+    obj_palette_ram: ColorPaletteRam,
     //..::Registers::..
     lcd_control_register: LcdControlRegister,
     lcd_stat_register: LcdStatusRegister,
@@ -296,13 +680,21 @@ pub struct PictureProcessingUnit {
     obp1_register: PaletteRegister,
     wy_register: u8,
     wx_register: u8,
+    vram_bank: usize,
     pub vblank_interrupt_req: bool,
     pub lcd_interrupt_req: bool,
+    // Start of HBlank on a visible line, triggers the CGB HBlank DMA
+    pub hblank_dma_req: bool,
     //..::Internal::..
+    cgb_mode: bool,
+    oam_dma_active: bool,
     ppu_fsm: PpuState,
     internal_scan_line_counter: u32,
     internal_window_line_counter: u8,
     sprite_buffer: Vec<Sprite>,
+    bg_scanline: [BgPixel; resolution::SCREEN_W],
+    renderer: PpuRenderer,
+    pixel_fifo: PixelFifo,
     //..::Out::..
     pub out_frame_buffer: [[[u8; 3]; resolution::SCREEN_W]; resolution::SCREEN_H],
 }
@@ -311,8 +703,10 @@ impl PictureProcessingUnit {
     pub fn new() -> Self {
         PictureProcessingUnit {
             //..::Memory::..
-            vram: [memory::DEFAULT_INIT_VALUE; memory::VRAM_SIZE],
+            vram: [[memory::DEFAULT_INIT_VALUE; memory::VRAM_SIZE]; memory::CGB_VRAM_BANKS],
             voam: [memory::DEFAULT_INIT_VALUE; memory::VOAM_SIZE],
+            bg_palette_ram: ColorPaletteRam::new(),
+            obj_palette_ram: ColorPaletteRam::new(),
             //..::Registers::..
             lcd_control_register: LcdControlRegister::default(),
             lcd_stat_register: LcdStatusRegister::default(),
@@ -320,23 +714,54 @@ impl PictureProcessingUnit {
             scx_register: 0,
             ly_register: 0,
             lyc_register: 0,
-            bgp_register: PaletteRegister::new(PalleteMode::BgPallete),
-            obp0_register: PaletteRegister::new(PalleteMode::ObjPallete),
-            obp1_register: PaletteRegister::new(PalleteMode::ObjPallete),
+            bgp_register: PaletteRegister::new(),
+            obp0_register: PaletteRegister::new(),
+            obp1_register: PaletteRegister::new(),
             wy_register: 0,
             wx_register: 0,
+            vram_bank: 0,
             vblank_interrupt_req: false,
             lcd_interrupt_req: false,
+            hblank_dma_req: false,
             //..::Internal::..
+            cgb_mode: false,
+            oam_dma_active: false,
             ppu_fsm: PpuState::new(),
             internal_scan_line_counter: 0,
             internal_window_line_counter: 0,
             sprite_buffer: Vec::new(),
+            bg_scanline: [BgPixel::default(); resolution::SCREEN_W],
+            renderer: PpuRenderer::Scanline,
+            pixel_fifo: PixelFifo::new(),
             //..::Out::..
             out_frame_buffer: [[[0xFF; 3]; resolution::SCREEN_W]; resolution::SCREEN_H],
         }
     }
 
+    /// # set_cgb_mode
+    /// Enables VRAM bank 1, tile attributes and the color palettes
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.vram_bank = 0;
+    }
+
+    pub fn is_cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    /// # set_oam_dma_active
+    /// OAM is owned by the DMA during the transfer, the OAM scan sees only 0xFF
+    pub fn set_oam_dma_active(&mut self, active: bool) {
+        self.oam_dma_active = active;
+    }
+
+    fn read_oam(&self, address: u16) -> u8 {
+        if self.oam_dma_active {
+            return 0xFF;
+        }
+        self.voam[(address - *address::OAM.start()) as usize]
+    }
+
     fn get_tile_data_address(&self, tile_number: u8) -> u16 {
         let base_title_address = self.lcd_control_register.get_tile_data_base_address();
 
@@ -354,18 +779,16 @@ impl PictureProcessingUnit {
         let oam_base_address = *address::OAM.start();
         // sprite occupies 4 bytes in the sprite attributes table
         let sprite_index = sprite_id * 4;
-        let y = self.read_byte_from_hardware_register(oam_base_address + sprite_index) as i16 - 16;
-        let x =
-            self.read_byte_from_hardware_register(oam_base_address + sprite_index + 1) as i16 - 8;
-        let tile_index = self.read_byte_from_hardware_register(oam_base_address + sprite_index + 2)
+        let y = self.read_oam(oam_base_address + sprite_index) as i16 - 16;
+        let x = self.read_oam(oam_base_address + sprite_index + 1) as i16 - 8;
+        let tile_index = self.read_oam(oam_base_address + sprite_index + 2)
             & if self.lcd_control_register.obj_size {
                 0xFE
             } else {
                 0xFF
             };
 
-        let raw_attribute =
-            self.read_byte_from_hardware_register(oam_base_address + sprite_index + 3);
+        let raw_attribute = self.read_oam(oam_base_address + sprite_index + 3);
 
         Sprite {
             attribute: Attribute::from(raw_attribute),
@@ -389,21 +812,23 @@ impl PictureProcessingUnit {
             let sprite = self.get_sprite_from_oam(sprite_id);
 
             // The sprite is not on the screen at this line.
-            if line < sprite.y_position || line >= sprite.y_position + sprite_high_size {
+            let line = line as i16;
+            if line < sprite.screen_y() || line >= sprite.screen_y() + sprite_high_size as i16 {
                 continue;
             }
             self.sprite_buffer.push(sprite);
         }
     }
 
-    fn get_tile_pixel(&mut self, cursor_x: u8, cursor_y: u8, tile_map_address: u16) -> Pixel2bpp {
+    fn get_tile_pixel(&self, cursor_x: u8, cursor_y: u8, tile_map_address: u16) -> BgPixel {
         // 32x32 grid of 8x8 pixel tiles
         let tile_grid_map_row_num = cursor_y / 8;
         let tile_grid_map_col_num = cursor_x / 8;
         let tile_coordinates = tile_grid_map_row_num as u16 * 32 + tile_grid_map_col_num as u16;
 
-        let tile_number =
-            self.read_byte_from_hardware_register(tile_map_address + tile_coordinates);
+        let tile_number = self.read_vram(0, tile_map_address + tile_coordinates);
+        let attribute =
+            Attribute::from(self.get_tile_attributes(tile_map_address + tile_coordinates));
 
         let tile_data_address = self.get_tile_data_address(tile_number);
 
@@ -415,20 +840,30 @@ impl PictureProcessingUnit {
         // [Row0][Row1][Row2][Row3][Row4][Row5][Row6][Row7]
         //  7C 7C 00 C6 C6 00 00 FE C6 C6 00 C6 C6 00 00 00 <-One title
 
-        //Pixel coordinates in the local 8x8 tile.
-        let pixel_row_num = cursor_y % 8;
-        let pixel_col_num = cursor_x % 8;
+        //Pixel coordinates in the local 8x8 tile. Flips exist only in CGB mode.
+        let mut pixel_row_num = cursor_y % 8;
+        let mut pixel_col_num = cursor_x % 8;
+        if attribute.yflip {
+            pixel_row_num = 7 - pixel_row_num;
+        }
+        if attribute.xflip {
+            pixel_col_num = 7 - pixel_col_num;
+        }
 
         // multiply by 2 because every row of 8 pixels is 2 bytes of data.
         let tile_pixel_row_index = tile_data_address + (pixel_row_num as u16 * 2);
+        let vram_bank = attribute.vram_bank as usize;
 
-        let low_byte = self.read_byte_from_hardware_register(tile_pixel_row_index);
-        let high_byte = self.read_byte_from_hardware_register(tile_pixel_row_index + 1);
-
-        Pixel2bpp {
-            low_byte,
-            high_byte,
+        let mut pixel = Pixel2bpp {
+            low_byte: self.read_vram(vram_bank, tile_pixel_row_index),
+            high_byte: self.read_vram(vram_bank, tile_pixel_row_index + 1),
             pixel_bit_activation: pixel_col_num,
+        };
+
+        BgPixel {
+            color_id: pixel.get_color_id(),
+            cgb_palette: attribute.cgb_palette,
+            bg_priority: attribute.priority,
         }
     }
 
@@ -443,16 +878,11 @@ impl PictureProcessingUnit {
 
         for screen_col in 0..resolution::SCREEN_W as u8 {
             let bg_cursor_x = screen_col.wrapping_add(self.scx_register);
-            let mut pixel = self.get_tile_pixel(bg_cursor_x, bg_cursor_y, tile_map_address);
-            let color_id = pixel.get_color_id();
+            let pixel = self.get_tile_pixel(bg_cursor_x, bg_cursor_y, tile_map_address);
 
-            let color = self.bgp_register.get_color(color_id);
+            self.bg_scanline[screen_col as usize] = pixel;
             self.out_frame_buffer[self.ly_register as usize][screen_col as usize] =
-                [color.rgb().0, color.rgb().1, color.rgb().2];
-
-            // self.out_frame_buffer[self.ly_register as usize]
-            //     [self.scx_register as usize + screen_col as usize] =
-            //     [color.rgb().0, color.rgb().1, color.rgb().2];
+                self.get_bg_pixel_rgb(pixel);
         }
     }
 
@@ -472,13 +902,12 @@ impl PictureProcessingUnit {
                 continue;
             }
 
-            let mut pixel = self.get_tile_pixel(win_cursor_x as u8, win_cursor_y, tile_map_address);
-            let color_id = pixel.get_color_id();
+            let pixel = self.get_tile_pixel(win_cursor_x as u8, win_cursor_y, tile_map_address);
 
-            let color = self.bgp_register.get_color(color_id);
             //Todo screen_col or win_cursor_x
+            self.bg_scanline[screen_col as usize] = pixel;
             self.out_frame_buffer[self.ly_register as usize][screen_col as usize] =
-                [color.rgb().0, color.rgb().1, color.rgb().2];
+                self.get_bg_pixel_rgb(pixel);
         }
 
         self.internal_window_line_counter += 1;
@@ -488,38 +917,25 @@ impl PictureProcessingUnit {
         let sprite_high = self.lcd_control_register.get_sprite_high_size();
         let line = self.ly_register;
 
-        for sprite in self.sprite_buffer.iter() {
+        // Sprites with lower OAM index are drawn on top, so they are drawn last
+        for (sprite_id, sprite) in self.sprite_buffer.iter().enumerate().rev() {
             let sprite_y = if sprite.attribute.yflip {
-                (sprite_high as i16 - 1 - (line as i16 - sprite.y_position as i16)) as u16
+                (sprite_high as i16 - 1 - (line as i16 - sprite.screen_y())) as u16
             } else {
-                (line as i16 - sprite.y_position as i16) as u16
+                (line as i16 - sprite.screen_y()) as u16
             };
 
             let sprite_data_address =
                 *address::VIDEO_RAM.start() + (sprite.tile_index as u16 * 16) + (sprite_y * 2);
+            let vram_bank = (self.cgb_mode && sprite.attribute.vram_bank) as usize;
 
-            let low_byte = self.read_byte_from_hardware_register(sprite_data_address);
-            let high_byte = self.read_byte_from_hardware_register(sprite_data_address + 1);
-
-            let pallete = if sprite.attribute.dmg_palette {
-                self.obp0_register
-            } else {
-                self.obp1_register
-            };
+            let low_byte = self.read_vram(vram_bank, sprite_data_address);
+            let high_byte = self.read_vram(vram_bank, sprite_data_address + 1);
 
             // Walk through each pixel to be drawn.
             for pixel_col in 0..8_u8 {
-                if sprite.x_position as u16 + pixel_col as u16 >= resolution::SCREEN_W as u16 {
-                    continue;
-                }
-
-                // Check  BG vs. OBJ priority
-                if !self.lcd_control_register.bg_and_window_enable
-                    && sprite.attribute.priority
-                    && self.out_frame_buffer[line as usize]
-                        [(sprite.x_position + pixel_col) as usize]
-                        == [0, 0, 0]
-                {
+                let screen_x = sprite.screen_x() + pixel_col as i16;
+                if screen_x < 0 || screen_x >= resolution::SCREEN_W as i16 {
                     continue;
                 }
 
@@ -536,19 +952,84 @@ impl PictureProcessingUnit {
                     pixel_bit_activation: pixel_num,
                 };
 
-                let color_id = pixel.get_color_id();
-                let color = pallete.get_color(color_id);
+                let obj_pixel = ObjPixel {
+                    color_id: pixel.get_color_id(),
+                    obp1_palette: sprite.attribute.dmg_palette,
+                    cgb_palette: sprite.attribute.cgb_palette,
+                    bg_priority: sprite.attribute.priority,
+                    sprite_id: sprite_id as u8,
+                };
 
-                if pallete.is_transparent(color) {
+                // Check  BG vs. OBJ priority
+                if !self.is_obj_pixel_visible(self.bg_scanline[screen_x as usize], obj_pixel) {
                     continue;
                 }
 
-                self.out_frame_buffer[line as usize][(sprite.x_position + pixel_col) as usize] =
-                    [color.rgb().0, color.rgb().1, color.rgb().2];
+                self.out_frame_buffer[line as usize][screen_x as usize] =
+                    self.get_obj_pixel_rgb(obj_pixel);
             }
         }
     }
 
+    /// # read_vram
+    /// Renderer access to a given VRAM bank, independent of VBK
+    fn read_vram(&self, vram_bank: usize, address: u16) -> u8 {
+        self.vram[vram_bank][(address - *address::VIDEO_RAM.start()) as usize]
+    }
+
+    /// # get_tile_attributes
+    /// CGB keeps the attributes of the BG map in VRAM bank 1
+    fn get_tile_attributes(&self, tile_map_address: u16) -> u8 {
+        if !self.cgb_mode {
+            return 0;
+        }
+        self.read_vram(1, tile_map_address)
+    }
+
+    fn get_bg_pixel_rgb(&self, pixel: BgPixel) -> [u8; 3] {
+        if self.cgb_mode {
+            return self
+                .bg_palette_ram
+                .get_rgb(pixel.cgb_palette, pixel.color_id);
+        }
+        // DMG: BG and Window are blank (white) when LCDC.0 is off
+        let color = if self.lcd_control_register.bg_and_window_enable {
+            self.bgp_register.get_color(pixel.color_id)
+        } else {
+            Color::White
+        };
+        [color.rgb().0, color.rgb().1, color.rgb().2]
+    }
+
+    fn get_obj_pixel_rgb(&self, pixel: ObjPixel) -> [u8; 3] {
+        if self.cgb_mode {
+            return self
+                .obj_palette_ram
+                .get_rgb(pixel.cgb_palette, pixel.color_id);
+        }
+        let pallete = if pixel.obp1_palette {
+            self.obp1_register
+        } else {
+            self.obp0_register
+        };
+        let color = pallete.get_color(pixel.color_id);
+        [color.rgb().0, color.rgb().1, color.rgb().2]
+    }
+
+    /// # is_obj_pixel_visible
+    /// BG color 1-3 hides the sprite when the OAM priority bit or (CGB) the BG attribute priority bit is set.
+    /// LCDC.0 off disables the BG on DMG and is the master priority switch on CGB, sprites are on top then.
+    fn is_obj_pixel_visible(&self, bg_pixel: BgPixel, obj_pixel: ObjPixel) -> bool {
+        if !self.lcd_control_register.obj_enable || obj_pixel.color_id == 0 {
+            return false;
+        }
+        if !self.lcd_control_register.bg_and_window_enable || bg_pixel.color_id == 0 {
+            return true;
+        }
+        let is_bg_priority = self.cgb_mode && bg_pixel.bg_priority;
+        !(obj_pixel.bg_priority || is_bg_priority)
+    }
+
     fn enter_to_new_mode(&mut self, ppu_state: u8, interrupt_needed: bool) {
         if self.lcd_stat_register.ppu_mode != ppu_state {
             self.lcd_stat_register.ppu_mode = ppu_state;
@@ -556,7 +1037,13 @@ impl PictureProcessingUnit {
             if ppu_state == PpuState::VBlankMode1 as u8 {
                 self.vblank_interrupt_req = true;
             }
-            self.lcd_interrupt_req = interrupt_needed;
+            if ppu_state == PpuState::HBlankMode0 as u8 {
+                self.hblank_dma_req = true;
+            }
+            // Do not drop the LYC request raised in the same batch of cycles
+            if interrupt_needed {
+                self.lcd_interrupt_req = true;
+            }
         }
     }
 
@@ -572,23 +1059,333 @@ impl PictureProcessingUnit {
     }
 
     fn draw_scanline(&mut self) {
-        if self.lcd_control_register.bg_and_window_enable {
+        // On CGB LCDC.0 only drops the BG priority, the BG is still drawn
+        if self.cgb_mode || self.lcd_control_register.bg_and_window_enable {
             self.draw_background_scanline();
             self.draw_window_scanline();
+        } else {
+            self.bg_scanline = [BgPixel::default(); resolution::SCREEN_W];
+            self.out_frame_buffer[self.ly_register as usize] = [[0xFF; 3]; resolution::SCREEN_W];
         }
 
         if self.lcd_control_register.obj_enable {
             self.draw_sprite_scanline();
         }
     }
+
+    /// # set_renderer
+    /// Switching the renderer restarts the current scanline
+    pub fn set_renderer(&mut self, renderer: PpuRenderer) {
+        self.renderer = renderer;
+        self.internal_scan_line_counter = 0;
+        self.ppu_fsm = if self.ly_register < resolution::SCREEN_H as u8 {
+            PpuState::OamScanMode2
+        } else {
+            PpuState::VBlankMode1
+        };
+    }
+
+    /// # pixel_fifo_dot
+    /// One dot of the FIFO renderer.
+    /// Mode 2 (80 dots) + Mode 3 (172 dots and longer) + Mode 0 (rest of the line) = 456 dots
+    fn pixel_fifo_dot(&mut self) {
+        match self.ppu_fsm {
+            PpuState::OamScanMode2 => {
+                if self.internal_scan_line_counter == 0 {
+                    self.enter_to_new_mode(
+                        PpuState::OamScanMode2 as u8,
+                        self.lcd_stat_register.enable_mode_2_interrupt,
+                    );
+                    self.check_conincidence_flag();
+                    // Window starts only on the lines after LY matched WY in this frame
+                    if self.ly_register == self.wy_register {
+                        self.pixel_fifo.window_y_triggered = true;
+                    }
+                }
+                self.internal_scan_line_counter += 1;
+                if self.internal_scan_line_counter == 80 {
+                    self.sprite_search();
+                    self.pixel_fifo.start_scanline(self.scx_register);
+                    self.ppu_fsm = PpuState::DrawingPixelsMode3;
+                    self.enter_to_new_mode(PpuState::DrawingPixelsMode3 as u8, false);
+                }
+            }
+            PpuState::DrawingPixelsMode3 => {
+                self.internal_scan_line_counter += 1;
+                self.pixel_fifo_drawing_dot();
+                debug_assert!(
+                    self.internal_scan_line_counter < 456,
+                    "Mode 3 did not end within the scanline"
+                );
+                if self.pixel_fifo.lx as usize == resolution::SCREEN_W {
+                    if self.pixel_fifo.window_drawn_on_line {
+                        self.internal_window_line_counter += 1;
+                    }
+                    self.ppu_fsm = PpuState::HBlankMode0;
+                    self.enter_to_new_mode(
+                        PpuState::HBlankMode0 as u8,
+                        self.lcd_stat_register.enable_mode_0_interrupt,
+                    );
+                }
+            }
+            PpuState::HBlankMode0 => {
+                self.internal_scan_line_counter += 1;
+                if self.internal_scan_line_counter == 456 {
+                    self.internal_scan_line_counter = 0;
+                    self.ly_register += 1;
+                    self.ppu_fsm = if self.ly_register == resolution::SCREEN_H as u8 {
+                        PpuState::VBlankMode1
+                    } else {
+                        PpuState::OamScanMode2
+                    };
+                }
+            }
+            PpuState::VBlankMode1 => {
+                if self.internal_scan_line_counter == 0 {
+                    self.enter_to_new_mode(
+                        PpuState::VBlankMode1 as u8,
+                        self.lcd_stat_register.enable_mode_1_interrupt,
+                    );
+                    self.check_conincidence_flag();
+                    self.internal_window_line_counter = 0;
+                    self.pixel_fifo.window_y_triggered = false;
+                }
+                self.internal_scan_line_counter += 1;
+                if self.internal_scan_line_counter == 456 {
+                    self.internal_scan_line_counter = 0;
+                    self.ly_register += 1;
+                    if self.ly_register == 154 {
+                        self.ly_register = 0;
+                        self.ppu_fsm = PpuState::OamScanMode2;
+                    }
+                }
+            }
+        }
+    }
+
+    /// # pixel_fifo_drawing_dot
+    /// Mode 3 is longer by the fine scroll (SCX % 8), by 6 dots when the window starts
+    /// and by 6-11 dots for every sprite on the line, the pixel output is stalled meanwhile.
+    fn pixel_fifo_drawing_dot(&mut self) {
+        if self.pixel_fifo.startup_dots > 0 {
+            self.pixel_fifo.startup_dots -= 1;
+            return;
+        }
+
+        self.window_trigger_check();
+
+        if self.pixel_fifo.sprite_pending.is_none() && self.pixel_fifo.discard_pixels == 0 {
+            self.pixel_fifo.sprite_pending = self.next_sprite_to_fetch();
+        }
+
+        if let Some(sprite_id) = self.pixel_fifo.sprite_pending {
+            // The background fetch in progress is finished first
+            let fifo = &self.pixel_fifo;
+            let is_fetcher_idle = fifo.fetcher_step == FetcherStep::Push
+                || (fifo.fetcher_step == FetcherStep::GetTile && fifo.fetcher_dots == 0);
+            if fifo.bg_fifo.is_empty() || !is_fetcher_idle {
+                self.fetcher_dot();
+                return;
+            }
+
+            self.pixel_fifo.sprite_fetch_dots += 1;
+            if self.pixel_fifo.sprite_fetch_dots == pixel_fifo::SPRITE_FETCH_DOTS {
+                self.fetch_sprite_row(sprite_id);
+                self.pixel_fifo.fetched_sprites |= 1_u16.rotate_left(sprite_id as u32);
+                self.pixel_fifo.sprite_fetch_dots = 0;
+                self.pixel_fifo.sprite_pending = None;
+            }
+            return;
+        }
+
+        self.fetcher_dot();
+        self.shift_out_pixel();
+    }
+
+    fn window_trigger_check(&mut self) {
+        let fifo = &self.pixel_fifo;
+        if fifo.fetching_window
+            || !fifo.window_y_triggered
+            || !self.lcd_control_register.window_enable
+            || fifo.discard_pixels > 0
+            || (fifo.lx as u16 + 7) < self.wx_register as u16
+        {
+            return;
+        }
+        self.pixel_fifo.bg_fifo.clear();
+        self.pixel_fifo.reset_fetcher();
+        self.pixel_fifo.fetching_window = true;
+        self.pixel_fifo.window_drawn_on_line = true;
+        // WX < 7 starts the window left of the screen, its first 7 - WX pixels are dropped
+        self.pixel_fifo.discard_pixels = 7_u8.saturating_sub(self.wx_register);
+    }
+
+    /// # next_sprite_to_fetch
+    /// First sprite of the OAM scan that starts at the current X and is not fetched yet
+    fn next_sprite_to_fetch(&self) -> Option<u8> {
+        if !self.lcd_control_register.obj_enable {
+            return None;
+        }
+        let lx = self.pixel_fifo.lx as i16;
+        self.sprite_buffer
+            .iter()
+            .enumerate()
+            .find(|(sprite_id, sprite)| {
+                (self
+                    .pixel_fifo
+                    .fetched_sprites
+                    .rotate_right(*sprite_id as u32)
+                    & 1)
+                    == 0
+                    && sprite.screen_x() > -8
+                    && sprite.screen_x() <= lx
+            })
+            .map(|(sprite_id, _)| sprite_id as u8)
+    }
+
+    fn fetch_sprite_row(&mut self, sprite_id: u8) {
+        let sprite = &self.sprite_buffer[sprite_id as usize];
+        let sprite_high = self.lcd_control_register.get_sprite_high_size() as i16;
+
+        let mut sprite_y = self.ly_register as i16 - sprite.screen_y();
+        if sprite.attribute.yflip {
+            sprite_y = sprite_high - 1 - sprite_y;
+        }
+
+        let sprite_data_address =
+            *address::VIDEO_RAM.start() + (sprite.tile_index as u16 * 16) + (sprite_y as u16 * 2);
+        let vram_bank = (self.cgb_mode && sprite.attribute.vram_bank) as usize;
+        let low_byte = self.read_vram(vram_bank, sprite_data_address);
+        let high_byte = self.read_vram(vram_bank, sprite_data_address + 1);
+
+        let mut row = [ObjPixel::default(); 8];
+        for (pixel_col, pixel) in row.iter_mut().enumerate() {
+            // Number of pixel (0-7) of this row of the sprite. Might be horizontally flipped.
+            let pixel_num = if sprite.attribute.xflip {
+                7 - pixel_col as u8
+            } else {
+                pixel_col as u8
+            };
+            *pixel = ObjPixel {
+                color_id: Pixel2bpp {
+                    low_byte,
+                    high_byte,
+                    pixel_bit_activation: pixel_num,
+                }
+                .get_color_id(),
+                obp1_palette: sprite.attribute.dmg_palette,
+                cgb_palette: sprite.attribute.cgb_palette,
+                bg_priority: sprite.attribute.priority,
+                sprite_id,
+            };
+        }
+
+        // Sprites partially left of the screen lose their first pixels
+        let first_pixel = (self.pixel_fifo.lx as i16 - sprite.screen_x()) as usize;
+        self.pixel_fifo
+            .merge_sprite_row(row, first_pixel, self.cgb_mode);
+    }
+
+    fn fetcher_dot(&mut self) {
+        if self.pixel_fifo.fetcher_step == FetcherStep::Push {
+            if self.pixel_fifo.bg_fifo.is_empty() {
+                self.pixel_fifo.push_tile_row();
+                self.pixel_fifo.fetcher_x = self.pixel_fifo.fetcher_x.wrapping_add(1);
+                self.pixel_fifo.fetcher_step = FetcherStep::GetTile;
+            }
+            return;
+        }
+
+        self.pixel_fifo.fetcher_dots += 1;
+        if self.pixel_fifo.fetcher_dots < 2 {
+            return;
+        }
+        self.pixel_fifo.fetcher_dots = 0;
+
+        match self.pixel_fifo.fetcher_step {
+            FetcherStep::GetTile => {
+                let tile_map_address = self.fetcher_tile_map_address();
+                self.pixel_fifo.tile_number = self.read_vram(0, tile_map_address);
+                self.pixel_fifo.tile_attributes = self.get_tile_attributes(tile_map_address);
+            }
+            FetcherStep::GetTileDataLow => {
+                let (vram_bank, address) = self.fetcher_tile_row_address();
+                self.pixel_fifo.tile_data_low = self.read_vram(vram_bank, address);
+            }
+            FetcherStep::GetTileDataHigh => {
+                let (vram_bank, address) = self.fetcher_tile_row_address();
+                self.pixel_fifo.tile_data_high = self.read_vram(vram_bank, address + 1);
+            }
+            FetcherStep::Push => (),
+        }
+        self.pixel_fifo.fetcher_step = self.pixel_fifo.fetcher_step.next();
+    }
+
+    fn fetcher_tile_map_address(&self) -> u16 {
+        // 32x32 grid of 8x8 pixel tiles, SCX is read on every fetch
+        if self.pixel_fifo.fetching_window {
+            let tile_map_address = self.lcd_control_register.get_window_tile_map_base_address();
+            let row = self.internal_window_line_counter as u16 / 8;
+            let col = self.pixel_fifo.fetcher_x as u16 & 0x1F;
+            return tile_map_address + row * 32 + col;
+        }
+        let tile_map_address = self.lcd_control_register.get_bg_tile_map_base_address();
+        let row = self.ly_register.wrapping_add(self.scy_register) as u16 / 8;
+        let col = (self.scx_register as u16 / 8 + self.pixel_fifo.fetcher_x as u16) & 0x1F;
+        tile_map_address + row * 32 + col
+    }
+
+    /// # fetcher_tile_row_address
+    /// VRAM bank and address of the tile row, CGB tiles can be flipped vertically
+    fn fetcher_tile_row_address(&self) -> (usize, u16) {
+        let cursor_y = if self.pixel_fifo.fetching_window {
+            self.internal_window_line_counter
+        } else {
+            self.ly_register.wrapping_add(self.scy_register)
+        };
+        let attribute = Attribute::from(self.pixel_fifo.tile_attributes);
+        let pixel_row_num = if attribute.yflip {
+            7 - cursor_y % 8
+        } else {
+            cursor_y % 8
+        };
+        // multiply by 2 because every row of 8 pixels is 2 bytes of data.
+        let address =
+            self.get_tile_data_address(self.pixel_fifo.tile_number) + pixel_row_num as u16 * 2;
+        (attribute.vram_bank as usize, address)
+    }
+
+    fn shift_out_pixel(&mut self) {
+        let Some(bg_pixel) = self.pixel_fifo.bg_fifo.pop_front() else {
+            return;
+        };
+        if self.pixel_fifo.discard_pixels > 0 {
+            self.pixel_fifo.discard_pixels -= 1;
+            return;
+        }
+        let obj_pixel = self.pixel_fifo.obj_fifo.pop_front();
+
+        // Palettes are applied when the pixel leaves the FIFO, so mid-scanline writes take effect
+        self.out_frame_buffer[self.ly_register as usize][self.pixel_fifo.lx as usize] =
+            self.mix_pixel(bg_pixel, obj_pixel);
+        self.pixel_fifo.lx += 1;
+    }
+
+    fn mix_pixel(&self, bg_pixel: BgPixel, obj_pixel: Option<ObjPixel>) -> [u8; 3] {
+        match obj_pixel {
+            Some(obj_pixel) if self.is_obj_pixel_visible(bg_pixel, obj_pixel) => {
+                self.get_obj_pixel_rgb(obj_pixel)
+            }
+            _ => self.get_bg_pixel_rgb(bg_pixel),
+        }
+    }
 }
 
 impl HardwareAccessible for PictureProcessingUnit {
     fn read_byte_from_hardware_register(&self, address: u16) -> u8 {
         match address {
             vram_address if address::VIDEO_RAM.contains(&vram_address) => {
-                let address = (vram_address - *address::VIDEO_RAM.start()) as usize;
-                self.vram[address]
+                self.read_vram(self.vram_bank, vram_address)
             }
             voam_address if address::OAM.contains(&voam_address) => {
                 let address = (voam_address - *address::OAM.start()) as usize;
@@ -607,7 +1404,15 @@ impl HardwareAccessible for PictureProcessingUnit {
             io_hardware_register::OBP1 => self.obp1_register.data,
             io_hardware_register::WY => self.wy_register,
             io_hardware_register::WX => self.wx_register,
-            _ => panic!("[PPU ERROR][Read] Unsupported address: [{:#06x?}]", address),
+            // Only bit 0 is used, the others read as 1
+            io_hardware_register::VBK if self.cgb_mode => 0xFE | self.vram_bank as u8,
+            io_hardware_register::BCPS if self.cgb_mode => self.bg_palette_ram.read_specification(),
+            io_hardware_register::BCPD if self.cgb_mode => self.bg_palette_ram.read_data(),
+            io_hardware_register::OCPS if self.cgb_mode => {
+                self.obj_palette_ram.read_specification()
+            }
+            io_hardware_register::OCPD if self.cgb_mode => self.obj_palette_ram.read_data(),
+            _ => memory::DEFAULT_INIT_VALUE,
         }
     }
 
@@ -615,7 +1420,7 @@ impl HardwareAccessible for PictureProcessingUnit {
         match address {
             vram_address if address::VIDEO_RAM.contains(&vram_address) => {
                 let address = (vram_address - *address::VIDEO_RAM.start()) as usize;
-                self.vram[address] = data
+                self.vram[self.vram_bank][address] = data
             }
             voam_address if address::OAM.contains(&voam_address) => {
                 let address = (voam_address - *address::OAM.start()) as usize;
@@ -636,10 +1441,16 @@ impl HardwareAccessible for PictureProcessingUnit {
             io_hardware_register::OBP1 => self.obp1_register.data = data,
             io_hardware_register::WY => self.wy_register = data,
             io_hardware_register::WX => self.wx_register = data,
-            _ => panic!(
-                "[PPU ERROR][Write] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            io_hardware_register::VBK if self.cgb_mode => self.vram_bank = (data & 0x01) as usize,
+            io_hardware_register::BCPS if self.cgb_mode => {
+                self.bg_palette_ram.write_specification(data)
+            }
+            io_hardware_register::BCPD if self.cgb_mode => self.bg_palette_ram.write_data(data),
+            io_hardware_register::OCPS if self.cgb_mode => {
+                self.obj_palette_ram.write_specification(data)
+            }
+            io_hardware_register::OCPD if self.cgb_mode => self.obj_palette_ram.write_data(data),
+            _ => (),
         }
     }
 }
@@ -650,6 +1461,13 @@ impl IoWorkingCycle for PictureProcessingUnit {
             return;
         }
 
+        if self.renderer == PpuRenderer::PixelFifo {
+            for _ in 0..cycles {
+                self.pixel_fifo_dot();
+            }
+            return;
+        }
+
         self.internal_scan_line_counter += cycles;
 
         let state = self.ppu_fsm;
@@ -726,6 +1544,116 @@ impl IoWorkingCycle for PictureProcessingUnit {
     }
 }
 
+impl Snapshot for PictureProcessingUnit {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for vram_bank in self.vram.iter() {
+            writer.write_bytes(vram_bank);
+        }
+        writer.write_bytes(&self.voam);
+        writer.write_u8(LcdControlRegister::into(self.lcd_control_register));
+        writer.write_u8(LcdStatusRegister::into(self.lcd_stat_register));
+        writer.write_u8(self.scy_register);
+        writer.write_u8(self.scx_register);
+        writer.write_u8(self.ly_register);
+        writer.write_u8(self.lyc_register);
+        writer.write_u8(self.bgp_register.data);
+        writer.write_u8(self.obp0_register.data);
+        writer.write_u8(self.obp1_register.data);
+        writer.write_u8(self.wy_register);
+        writer.write_u8(self.wx_register);
+        writer.write_bool(self.vblank_interrupt_req);
+        writer.write_bool(self.lcd_interrupt_req);
+        writer.write_u8(self.ppu_fsm as u8);
+        writer.write_u32(self.internal_scan_line_counter);
+        writer.write_u8(self.internal_window_line_counter);
+
+        writer.write_u8(self.sprite_buffer.len() as u8);
+        for sprite in self.sprite_buffer.iter() {
+            writer.write_u8(sprite.y_position);
+            writer.write_u8(sprite.x_position);
+            writer.write_u8(sprite.tile_index);
+            writer.write_u8(u8::from(&sprite.attribute));
+        }
+
+        writer.write_bool(self.cgb_mode);
+        writer.write_u8(self.vram_bank as u8);
+        for palette_ram in [&self.bg_palette_ram, &self.obj_palette_ram] {
+            writer.write_bytes(&palette_ram.data);
+            writer.write_u8(palette_ram.read_specification());
+        }
+        writer.write_bool(self.renderer == PpuRenderer::PixelFifo);
+        self.pixel_fifo.save_state(writer);
+
+        for line in self.out_frame_buffer.iter() {
+            for pixel in line.iter() {
+                writer.write_bytes(pixel);
+            }
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        for vram_bank in self.vram.iter_mut() {
+            reader.read_bytes(vram_bank)?;
+        }
+        reader.read_bytes(&mut self.voam)?;
+        self.lcd_control_register = LcdControlRegister::from(reader.read_u8()?);
+        self.lcd_stat_register = LcdStatusRegister::from(reader.read_u8()?);
+        self.scy_register = reader.read_u8()?;
+        self.scx_register = reader.read_u8()?;
+        self.ly_register = reader.read_u8()?;
+        self.lyc_register = reader.read_u8()?;
+        self.bgp_register.data = reader.read_u8()?;
+        self.obp0_register.data = reader.read_u8()?;
+        self.obp1_register.data = reader.read_u8()?;
+        self.wy_register = reader.read_u8()?;
+        self.wx_register = reader.read_u8()?;
+        self.vblank_interrupt_req = reader.read_bool()?;
+        self.lcd_interrupt_req = reader.read_bool()?;
+        self.ppu_fsm = PpuState::from_mode(reader.read_u8()?)
+            .ok_or_else(|| invalid_data_error("Invalid PPU mode"))?;
+        self.internal_scan_line_counter = reader.read_u32()?;
+        self.internal_window_line_counter = reader.read_u8()?;
+
+        self.sprite_buffer.clear();
+        let number_of_sprites = reader.read_u8()? as usize;
+        if number_of_sprites > graphics::MAX_SPRITES_PER_LINE {
+            return Err(invalid_data_error("Invalid number of sprites"));
+        }
+        for _ in 0..number_of_sprites {
+            let y_position = reader.read_u8()?;
+            let x_position = reader.read_u8()?;
+            let tile_index = reader.read_u8()?;
+            let attribute = Attribute::from(reader.read_u8()?);
+            self.sprite_buffer.push(Sprite {
+                attribute,
+                tile_index,
+                x_position,
+                y_position,
+            });
+        }
+
+        self.cgb_mode = reader.read_bool()?;
+        self.vram_bank = (reader.read_u8()? & 0x01) as usize;
+        for palette_ram in [&mut self.bg_palette_ram, &mut self.obj_palette_ram] {
+            reader.read_bytes(&mut palette_ram.data)?;
+            palette_ram.write_specification(reader.read_u8()?);
+        }
+        self.renderer = if reader.read_bool()? {
+            PpuRenderer::PixelFifo
+        } else {
+            PpuRenderer::Scanline
+        };
+        self.pixel_fifo.load_state(reader)?;
+
+        for line in self.out_frame_buffer.iter_mut() {
+            for pixel in line.iter_mut() {
+                reader.read_bytes(pixel)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod uint_test {
     use super::*;
@@ -760,8 +1688,8 @@ mod uint_test {
         ppu.lcd_stat_register.enable_ly_interrupt = true;
         ppu.next_to(204);
         assert_eq!(1, ppu.ly_register);
-        assert!(ppu.lcd_interrupt_req == true);
-        assert!(ppu.lcd_stat_register.lyc_flag == true);
+        assert!(ppu.lcd_interrupt_req);
+        assert!(ppu.lcd_stat_register.lyc_flag);
 
         //Switch to Mode 2
         ppu.next_to(0);
@@ -802,7 +1730,7 @@ mod uint_test {
         ppu.next_to(0);
         assert_eq!(PpuState::VBlankMode1, ppu.ppu_fsm);
         assert_eq!(ppu.lcd_stat_register.ppu_mode, 1);
-        assert!(ppu.vblank_interrupt_req == true);
+        assert!(ppu.vblank_interrupt_req);
 
         //Wait 10 scanlines
         for _ in 0..10 {
@@ -819,39 +1747,39 @@ mod uint_test {
     #[test]
     fn lcd_control_register_convert_test() {
         let mut register = LcdControlRegister::from(0x91);
-        assert!(register.lcd_enable == true);
-        assert!(register.window_tile_map_area == false);
-        assert!(register.window_enable == false);
-        assert!(register.bg_window_tile_data_area == true);
-        assert!(register.bg_tile_map_area == false);
-        assert!(register.obj_size == false);
-        assert!(register.obj_enable == false);
-        assert!(register.bg_and_window_enable == true);
+        assert!(register.lcd_enable);
+        assert!(!register.window_tile_map_area);
+        assert!(!register.window_enable);
+        assert!(register.bg_window_tile_data_area);
+        assert!(!register.bg_tile_map_area);
+        assert!(!register.obj_size);
+        assert!(!register.obj_enable);
+        assert!(register.bg_and_window_enable);
 
         register.obj_size = true;
         register.obj_enable = true;
-        assert_eq!(151 as u8, LcdControlRegister::into(register));
+        assert_eq!(151_u8, LcdControlRegister::into(register));
     }
 
     #[test]
     fn lcd_stat_register_convert_test() {
         let mut register = LcdStatusRegister::from(0x2B);
 
-        assert!(register.enable_ly_interrupt == false);
-        assert!(register.enable_mode_2_interrupt == true);
-        assert!(register.enable_mode_1_interrupt == false);
-        assert!(register.enable_mode_0_interrupt == true);
-        assert!(register.lyc_flag == false);
+        assert!(!register.enable_ly_interrupt);
+        assert!(register.enable_mode_2_interrupt);
+        assert!(!register.enable_mode_1_interrupt);
+        assert!(register.enable_mode_0_interrupt);
+        assert!(!register.lyc_flag);
         assert!(register.ppu_mode == 3);
 
         register.enable_ly_interrupt = true;
         register.ppu_mode = 2;
-        assert_eq!(0x6A as u8, LcdStatusRegister::into(register));
+        assert_eq!(0x6A_u8, LcdStatusRegister::into(register));
     }
 
     #[test]
     fn lcd_monochrome_color_palette_test() {
-        let mut palette_reg = PaletteRegister::new(PalleteMode::ObjPallete);
+        let mut palette_reg = PaletteRegister::new();
 
         //Set following colors
         // [3] White, [2] LightGray, [1] DarkGray, [0] Black
@@ -871,7 +1799,404 @@ mod uint_test {
                 palette_reg.get_color(color_id)
             );
         }
-        assert!(palette_reg.is_transparent(Color::White) == true);
-        assert!(palette_reg.is_transparent(Color::Black) == false);
+    }
+
+    fn pixel_fifo_ppu() -> PictureProcessingUnit {
+        let mut ppu = PictureProcessingUnit::new();
+        ppu.set_renderer(PpuRenderer::PixelFifo);
+        // LCD on, tile data at 0x8000, BG on
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0x91);
+        ppu.write_byte_to_hardware_register(io_hardware_register::BGP, 0xE4);
+        ppu.write_byte_to_hardware_register(io_hardware_register::OBP0, 0xE4);
+        ppu
+    }
+
+    fn mode_3_length(ppu: &mut PictureProcessingUnit) -> u32 {
+        while ppu.lcd_stat_register.ppu_mode != PpuState::DrawingPixelsMode3 as u8 {
+            ppu.next_to(1);
+        }
+        let mut dots = 0;
+        while ppu.lcd_stat_register.ppu_mode == PpuState::DrawingPixelsMode3 as u8 {
+            ppu.next_to(1);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn pixel_fifo_mode_3_length_test() {
+        let mut ppu = pixel_fifo_ppu();
+        assert_eq!(172, mode_3_length(&mut ppu));
+        assert_eq!(PpuState::HBlankMode0, ppu.ppu_fsm);
+
+        // Fine scroll
+        ppu.write_byte_to_hardware_register(io_hardware_register::SCX, 0x03);
+        assert_eq!(175, mode_3_length(&mut ppu));
+        ppu.write_byte_to_hardware_register(io_hardware_register::SCX, 0x00);
+
+        // Window starts at X = 80
+        ppu.write_byte_to_hardware_register(io_hardware_register::WY, ppu.ly_register + 1);
+        ppu.write_byte_to_hardware_register(io_hardware_register::WX, 80 + 7);
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0xB1);
+        assert_eq!(178, mode_3_length(&mut ppu));
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0x91);
+
+        // One sprite costs 6-11 dots
+        let line = ppu.ly_register + 1;
+        let oam = *address::OAM.start();
+        ppu.write_byte_to_hardware_register(oam, line + 16);
+        ppu.write_byte_to_hardware_register(oam + 1, 40 + 8);
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0x93);
+        let length = mode_3_length(&mut ppu);
+        assert!((178..=183).contains(&length));
+
+        // Scanline is still 456 dots long
+        while ppu.ppu_fsm != PpuState::OamScanMode2 {
+            ppu.next_to(1);
+        }
+        assert_eq!(line + 1, ppu.ly_register);
+        assert_eq!(0, ppu.internal_scan_line_counter);
+    }
+
+    #[test]
+    fn pixel_fifo_longest_mode_3_test() {
+        let mut ppu = pixel_fifo_ppu();
+        // Tile 0 is filled with color 3, it is used by the BG, the window and the sprites
+        for offset in 0..16 {
+            ppu.write_byte_to_hardware_register(*address::VIDEO_RAM.start() + offset, 0xFF);
+        }
+        // 10 sprites one tile apart, fine scroll 7 and the window from the left edge
+        let oam = *address::OAM.start();
+        for sprite_id in 0..10 {
+            ppu.write_byte_to_hardware_register(oam + sprite_id * 4, 16);
+            ppu.write_byte_to_hardware_register(oam + sprite_id * 4 + 1, sprite_id as u8 * 8 + 8);
+        }
+        ppu.write_byte_to_hardware_register(io_hardware_register::SCX, 0x07);
+        ppu.write_byte_to_hardware_register(io_hardware_register::WY, 0);
+        ppu.write_byte_to_hardware_register(io_hardware_register::WX, 5);
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0xB3);
+
+        // Longer than 289 dots, the whole line is still drawn
+        assert!(mode_3_length(&mut ppu) > 289);
+        assert!(ppu.out_frame_buffer[0]
+            .iter()
+            .all(|pixel| *pixel == [0x00; 3]));
+
+        while ppu.ppu_fsm != PpuState::OamScanMode2 {
+            ppu.next_to(1);
+        }
+        assert_eq!(1, ppu.ly_register);
+        assert_eq!(0, ppu.internal_scan_line_counter);
+    }
+
+    #[test]
+    fn pixel_fifo_invalid_state_test() {
+        let mut ppu = pixel_fifo_ppu();
+        // More than a tile of dropped pixels would stall the output
+        ppu.pixel_fifo.discard_pixels = 8;
+        let mut writer = StateWriter::new();
+        ppu.pixel_fifo.save_state(&mut writer);
+        let state = writer.into_inner();
+
+        let mut reader = StateReader::new(&state).unwrap();
+        assert!(PixelFifo::new().load_state(&mut reader).is_err());
+    }
+
+    #[test]
+    fn pixel_fifo_mid_scanline_palette_change_test() {
+        let mut ppu = pixel_fifo_ppu();
+        // Tile 0 is filled with color 3
+        for offset in 0..16 {
+            ppu.write_byte_to_hardware_register(*address::VIDEO_RAM.start() + offset, 0xFF);
+        }
+
+        while ppu.lcd_stat_register.ppu_mode != PpuState::DrawingPixelsMode3 as u8 {
+            ppu.next_to(1);
+        }
+        ppu.next_to(100);
+        // Color 3 turns from black to white in the middle of the line
+        ppu.write_byte_to_hardware_register(io_hardware_register::BGP, 0x24);
+        while ppu.lcd_stat_register.ppu_mode == PpuState::DrawingPixelsMode3 as u8 {
+            ppu.next_to(1);
+        }
+
+        let line = &ppu.out_frame_buffer[0];
+        assert_eq!([0x00, 0x00, 0x00], line[0]);
+        assert_eq!([0xFF, 0xFF, 0xFF], line[resolution::SCREEN_W - 1]);
+        let changed_at = line.iter().position(|pixel| *pixel == [0xFF; 3]).unwrap();
+        assert!(changed_at > 0 && changed_at < resolution::SCREEN_W - 1);
+    }
+
+    #[test]
+    fn pixel_fifo_sprite_priority_test() {
+        let mut ppu = pixel_fifo_ppu();
+        // Tile 1 is filled with color 1, tile 2 with color 2
+        for offset in 0..8 {
+            ppu.write_byte_to_hardware_register(0x8010 + offset * 2, 0xFF);
+            ppu.write_byte_to_hardware_register(0x8020 + offset * 2 + 1, 0xFF);
+        }
+        let oam = *address::OAM.start();
+        // Sprite 0: tile 1 at X = 4, sprite 1: tile 2 at X = 0, partially left of the screen
+        for (sprite_id, (x, tile)) in [(4 + 8, 1), (4, 2)].iter().enumerate() {
+            let sprite_address = oam + sprite_id as u16 * 4;
+            ppu.write_byte_to_hardware_register(sprite_address, 16);
+            ppu.write_byte_to_hardware_register(sprite_address + 1, *x);
+            ppu.write_byte_to_hardware_register(sprite_address + 2, *tile);
+        }
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0x93);
+
+        mode_3_length(&mut ppu);
+
+        let line = &ppu.out_frame_buffer[0];
+        let light_gray = [0xC0; 3];
+        let dark_gray = [0x60; 3];
+        let white = [0xFF; 3];
+        // Lower X wins, the sprite on the left shows 4 pixels
+        assert_eq!(dark_gray, line[0]);
+        assert_eq!(dark_gray, line[3]);
+        assert_eq!(light_gray, line[4]);
+        assert_eq!(light_gray, line[11]);
+        assert_eq!(white, line[12]);
+    }
+
+    #[test]
+    fn pixel_fifo_window_left_of_screen_test() {
+        let mut ppu = pixel_fifo_ppu();
+        // Tile 1: pixels 0-3 color 0, pixels 4-7 color 1, the window map at 0x9C00 is filled with it
+        for row in 0..8 {
+            ppu.write_byte_to_hardware_register(0x8010 + row * 2, 0x0F);
+        }
+        for col in 0..32 {
+            ppu.write_byte_to_hardware_register(0x9C00 + col, 1);
+        }
+        ppu.write_byte_to_hardware_register(io_hardware_register::WY, 0);
+        ppu.write_byte_to_hardware_register(io_hardware_register::WX, 3);
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0xF1);
+
+        mode_3_length(&mut ppu);
+
+        // Screen X 0 shows window X 4
+        let line = &ppu.out_frame_buffer[0];
+        let light_gray = [0xC0; 3];
+        let white = [0xFF; 3];
+        assert_eq!(light_gray, line[0]);
+        assert_eq!(light_gray, line[3]);
+        assert_eq!(white, line[4]);
+        assert_eq!(white, line[7]);
+        assert_eq!(light_gray, line[8]);
+    }
+
+    fn run_to_line(ppu: &mut PictureProcessingUnit, line: u8) {
+        while ppu.ly_register != line || ppu.ppu_fsm != PpuState::OamScanMode2 {
+            ppu.next_to(1);
+        }
+    }
+
+    /// Hand-built frame: sprite priority, flips, OBP1, the 10 sprite limit, 8x16 sprites,
+    /// OBJ-to-BG priority, LCDC.0 off, window with WX < 7 and the window line counter
+    #[test]
+    fn pixel_fifo_frame_features_test() {
+        let mut ppu = pixel_fifo_ppu();
+        let white = [0xFF; 3];
+        let light_gray = [0xC0; 3];
+        let dark_gray = [0x60; 3];
+        let black = [0x00; 3];
+
+        // Tiles: 1 - color 1, 2 - color 3, 3 - left half color 2, 4 - top row color 3, 5 - color 2,
+        // 7 - upper half like tile 3, lower half color 1
+        for row in 0..8_u16 {
+            let top_row = if row == 0 { 0xFF } else { 0x00 };
+            let (window_low, window_high) = if row < 4 { (0x00, 0xF0) } else { (0xFF, 0x00) };
+            let tiles: [(u16, u8, u8); 6] = [
+                (1, 0xFF, 0x00),
+                (2, 0xFF, 0xFF),
+                (3, 0x00, 0xF0),
+                (4, top_row, top_row),
+                (5, 0x00, 0xFF),
+                (7, window_low, window_high),
+            ];
+            for (tile, low, high) in tiles {
+                let address = 0x8000 + tile * 16 + row * 2;
+                ppu.write_byte_to_hardware_register(address, low);
+                ppu.write_byte_to_hardware_register(address + 1, high);
+            }
+        }
+        // BG map: tile 1 under the OBJ-to-BG priority sprites, tile 2 on the LCDC.0 off lines
+        ppu.write_byte_to_hardware_register(0x9800 + 6 * 32 + 2, 1);
+        ppu.write_byte_to_hardware_register(0x9800 + 7 * 32, 2);
+        // Window map at 0x9C00: row 0 tile 7, row 1 tile 2
+        for col in 0..32 {
+            ppu.write_byte_to_hardware_register(0x9C00 + col, 7);
+            ppu.write_byte_to_hardware_register(0x9C20 + col, 2);
+        }
+
+        let mut sprites = vec![
+            // Lines 0-7: lower X wins, X flip, OBP1
+            (0, 20, 1, 0x00),
+            (0, 16, 2, 0x00),
+            (0, 40, 3, 0x20),
+            (0, 60, 1, 0x10),
+        ];
+        // Line 8: 11 sprites, the last one is dropped
+        sprites.extend((0..11).map(|n| (8, n * 8, 2, 0x00)));
+        sprites.extend([
+            // Lines 24-39: 8x16 sprite, Y flipped, bit 0 of the tile index is ignored
+            (24, 100, 5, 0x40),
+            // Lines 48-55: behind BG color 1, over BG color 0
+            (48, 16, 2, 0x80),
+            (48, 24, 2, 0x80),
+            // Lines 56-63: shown with LCDC.0 off
+            (56, 8, 2, 0x00),
+        ]);
+        let oam = *address::OAM.start();
+        for (sprite_id, (line, x, tile, attribute)) in sprites.iter().enumerate() {
+            let sprite_address = oam + sprite_id as u16 * 4;
+            ppu.write_byte_to_hardware_register(sprite_address, line + 16);
+            ppu.write_byte_to_hardware_register(sprite_address + 1, x + 8);
+            ppu.write_byte_to_hardware_register(sprite_address + 2, *tile);
+            ppu.write_byte_to_hardware_register(sprite_address + 3, *attribute);
+        }
+        ppu.write_byte_to_hardware_register(io_hardware_register::OBP1, 0x1B);
+        ppu.write_byte_to_hardware_register(io_hardware_register::WY, 64);
+        ppu.write_byte_to_hardware_register(io_hardware_register::WX, 3);
+
+        // LCD, window at 0x9C00, window, tile data at 0x8000, OBJ and BG on
+        for (line, lcd_control) in [
+            (0, 0xF3),
+            (24, 0xF7),
+            (40, 0xF3),
+            (56, 0xF2),
+            (64, 0xF3),
+            (68, 0xD3),
+            (72, 0xF3),
+            (80, 0xF3),
+        ] {
+            run_to_line(&mut ppu, line);
+            ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, lcd_control);
+        }
+
+        let frame = &ppu.out_frame_buffer;
+        assert_eq!(black, frame[0][20]);
+        assert_eq!(light_gray, frame[0][24]);
+        assert_eq!(white, frame[0][40]);
+        assert_eq!(dark_gray, frame[0][44]);
+        assert_eq!(dark_gray, frame[0][60]);
+
+        assert_eq!(black, frame[8][79]);
+        assert_eq!(white, frame[8][80]);
+
+        assert_eq!(dark_gray, frame[24][100]);
+        assert_eq!(black, frame[39][100]);
+
+        assert_eq!(light_gray, frame[48][16]);
+        assert_eq!(black, frame[48][24]);
+
+        assert_eq!(white, frame[56][0]);
+        assert_eq!(black, frame[56][8]);
+
+        // Window X 4 at the left edge, the disabled lines are not counted by the window
+        assert_eq!(white, frame[64][0]);
+        assert_eq!(dark_gray, frame[64][4]);
+        assert_eq!(white, frame[68][0]);
+        assert_eq!(light_gray, frame[72][0]);
+        assert_eq!(light_gray, frame[72][4]);
+    }
+
+    #[test]
+    fn oam_scan_during_oam_dma_test() {
+        let mut ppu = PictureProcessingUnit::new();
+        let oam = *address::OAM.start();
+        ppu.write_byte_to_hardware_register(oam, 16 + 10);
+        ppu.write_byte_to_hardware_register(oam + 1, 8);
+        ppu.write_byte_to_hardware_register(io_hardware_register::LY, 10);
+
+        ppu.sprite_search();
+        assert_eq!(1, ppu.sprite_buffer.len());
+
+        ppu.set_oam_dma_active(true);
+        ppu.sprite_search();
+        assert_eq!(0, ppu.sprite_buffer.len());
+        // CPU side is handled by the IOMMU
+        assert_eq!(16 + 10, ppu.read_byte_from_hardware_register(oam));
+    }
+
+    #[test]
+    fn sprite_attribute_convert_test() {
+        let attribute = Attribute::from(0xA0);
+        assert!(attribute.priority);
+        assert!(!attribute.yflip);
+        assert!(attribute.xflip);
+        assert!(!attribute.dmg_palette);
+        assert_eq!(0xA0, u8::from(&attribute));
+    }
+
+    #[test]
+    fn color_palette_ram_test() {
+        let mut palette_ram = ColorPaletteRam::new();
+        palette_ram.write_specification(0x80 | 0x08);
+        assert_eq!(0xC8, palette_ram.read_specification());
+
+        // Palette 1: color 0 = red (0x001F), color 1 = green (0x03E0), color 2 = blue (0x7C00)
+        for byte in [0x1F, 0x00, 0xE0, 0x03, 0x00, 0x7C] {
+            palette_ram.write_data(byte);
+        }
+        assert_eq!(0xCE, palette_ram.read_specification());
+
+        assert_eq!([0xFF, 0x00, 0x00], palette_ram.get_rgb(1, 0));
+        assert_eq!([0x00, 0xFF, 0x00], palette_ram.get_rgb(1, 1));
+        assert_eq!([0x00, 0x00, 0xFF], palette_ram.get_rgb(1, 2));
+        assert_eq!([0x00, 0x00, 0x00], palette_ram.get_rgb(1, 3));
+
+        // Without auto increment the index stays
+        palette_ram.write_specification(0x3F);
+        palette_ram.write_data(0x10);
+        palette_ram.write_data(0x12);
+        assert_eq!(0x7F, palette_ram.read_specification());
+        assert_eq!(0x12, palette_ram.read_data());
+    }
+
+    fn cgb_ppu_with_attribute_tile(renderer: PpuRenderer) -> PictureProcessingUnit {
+        let mut ppu = PictureProcessingUnit::new();
+        ppu.set_cgb_mode(true);
+        ppu.set_renderer(renderer);
+        ppu.write_byte_to_hardware_register(io_hardware_register::LCD_CONTROL, 0x91);
+
+        // BG palette 2: color 0 = white, color 1 = red
+        ppu.write_byte_to_hardware_register(io_hardware_register::BCPS, 0x80 | 0x10);
+        for byte in [0xFF, 0x7F, 0x1F, 0x00] {
+            ppu.write_byte_to_hardware_register(io_hardware_register::BCPD, byte);
+        }
+
+        // Tile 0 in VRAM bank 1: only the leftmost pixel of each row has color 1
+        ppu.write_byte_to_hardware_register(io_hardware_register::VBK, 1);
+        assert_eq!(
+            0xFF,
+            ppu.read_byte_from_hardware_register(io_hardware_register::VBK)
+        );
+        for row in 0..8 {
+            ppu.write_byte_to_hardware_register(0x8000 + row * 2, 0x80);
+        }
+        // Attributes of the first map entry: bank 1, X flip, palette 2
+        ppu.write_byte_to_hardware_register(0x9800, 0x08 | 0x20 | 0x02);
+        ppu.write_byte_to_hardware_register(io_hardware_register::VBK, 0);
+        assert_eq!(0, ppu.read_byte_from_hardware_register(0x8000));
+        ppu
+    }
+
+    #[test]
+    fn cgb_tile_attributes_test() {
+        for renderer in [PpuRenderer::Scanline, PpuRenderer::PixelFifo] {
+            let mut ppu = cgb_ppu_with_attribute_tile(renderer);
+            for _ in 0..456 {
+                ppu.next_to(1);
+            }
+
+            let line = &ppu.out_frame_buffer[0];
+            // The flipped pixel is the rightmost one of the tile
+            assert_eq!([0xFF, 0xFF, 0xFF], line[0]);
+            assert_eq!([0xFF, 0x00, 0x00], line[7]);
+            // Next tile uses palette 0 which is black
+            assert_eq!([0x00, 0x00, 0x00], line[8]);
+        }
     }
 }
@@ -1,24 +1,176 @@
-use super::HardwareAccessible;
-use crate::constants::gb_memory_map::address;
+use super::{HardwareAccessible, IoWorkingCycle};
+use crate::constants::gb_memory_map::{address, memory};
+use crate::save_state::{Snapshot, StateReader, StateWriter};
+use crate::serial_device::{Disconnected, SerialClock, SerialDevice};
+
+mod serial_setup {
+    // 8192 Hz, 262144 Hz with the CGB fast clock, both doubled in the double speed mode
+    pub const BIT_CYCLES: u32 = 512;
+    pub const FAST_BIT_CYCLES: u32 = 16;
+    pub const BITS_PER_TRANSFER: u8 = 8;
+    // Nothing connected, the input line stays high
+    pub const DISCONNECTED_DATA: u8 = 0xFF;
+}
+
+mod control_bits {
+    pub const TRANSFER_START: u8 = 0x80;
+    pub const FAST_CLOCK: u8 = 0x02;
+    pub const INTERNAL_CLOCK: u8 = 0x01;
+    pub const DMG_UNUSED: u8 = 0x7E;
+    pub const CGB_UNUSED: u8 = 0x7C;
+}
 
 /// # SerialDataTransfer
 ///
-/// Only for blargg_tests
+/// Serial Link: SB is shifted out MSB first while the peer's byte is shifted in.
+/// With the internal clock the transfer takes 8 bit periods, with the external clock
+/// the peer's transfer drives it. The serial interrupt is requested at the end.
 ///
-/// Serial Link: The serial link works one character at a time.
-/// If you detect a value of 0x81 written to address 0xFF02, then log the content of address 0xFF01
-#[derive(Default)]
+/// The byte is exchanged with the `SerialDevice` when the transfer starts, a connected link
+/// (TCP or `LinkedPair`) replaces the device and replies later.
 pub struct SerialDataTransfer {
     data: u8,
     control: u8,
-    pub test_out_data: Vec<char>,
+    device: Box<dyn SerialDevice>,
     pub interrupt_req: bool,
+    is_cgb_hardware: bool,
+    is_link_connected: bool,
+    shift_cycles: u32,
+    shifted_bits: u8,
+    // Peer's byte of the internal clock transfer, not known yet when the link did not reply
+    incoming_data: Option<u8>,
+    // Byte to send to the peer, the internal clock transfer just started
+    link_request: Option<u8>,
+}
+
+impl Default for SerialDataTransfer {
+    fn default() -> Self {
+        Self {
+            data: 0,
+            control: 0,
+            device: Box::new(Disconnected),
+            interrupt_req: false,
+            is_cgb_hardware: false,
+            is_link_connected: false,
+            shift_cycles: 0,
+            shifted_bits: 0,
+            incoming_data: None,
+            link_request: None,
+        }
+    }
 }
 
 impl SerialDataTransfer {
-    fn write_data_to_test_buff_when_required(&mut self, control_data: u8) {
-        if control_data == 0x81 {
-            self.test_out_data.push(self.data as char);
+    /// # set_device
+    /// Returns the device connected before
+    pub fn set_device(&mut self, device: Box<dyn SerialDevice>) -> Box<dyn SerialDevice> {
+        std::mem::replace(&mut self.device, device)
+    }
+
+    /// # set_cgb_hardware
+    /// Fast clock bit exists only on the CGB
+    pub fn set_cgb_hardware(&mut self, is_cgb_hardware: bool) {
+        self.is_cgb_hardware = is_cgb_hardware;
+    }
+
+    /// # set_link_connected
+    /// Without the link the internal clock transfers receive 0xFF
+    pub fn set_link_connected(&mut self, is_link_connected: bool) {
+        self.is_link_connected = is_link_connected;
+        if !is_link_connected && self.is_internal_transfer_active() {
+            self.incoming_data
+                .get_or_insert(serial_setup::DISCONNECTED_DATA);
+        }
+    }
+
+    /// # take_link_request
+    /// Byte of the internal clock transfer which has to be sent to the peer
+    pub fn take_link_request(&mut self) -> Option<u8> {
+        self.link_request.take()
+    }
+
+    /// # set_link_reply
+    /// Peer's byte of the internal clock transfer
+    pub fn set_link_reply(&mut self, data: u8) {
+        if self.is_internal_transfer_active() {
+            self.incoming_data = Some(data);
+            self.transfer_completion();
+        }
+    }
+
+    /// # external_clock_transfer
+    /// Peer's internal clock transfer, returns the byte shifted out to the peer.
+    /// The interrupt is requested only when the transfer was started with the external clock.
+    pub fn external_clock_transfer(&mut self, data: u8) -> u8 {
+        let outgoing_data = self.data;
+        if self.is_internal_transfer_active() {
+            // Both sides drive the clock, the own transfer gets the peer's reply
+            return outgoing_data;
+        }
+        self.data = data;
+        if self.control & control_bits::TRANSFER_START != 0 {
+            self.control &= !control_bits::TRANSFER_START;
+            self.interrupt_req = true;
+        }
+        outgoing_data
+    }
+
+    fn is_internal_transfer_active(&self) -> bool {
+        let mask = control_bits::TRANSFER_START | control_bits::INTERNAL_CLOCK;
+        self.control & mask == mask
+    }
+
+    /// Internal clock or a device which drives the clock
+    fn is_clocked_transfer_active(&self) -> bool {
+        self.is_internal_transfer_active()
+            || (self.control & control_bits::TRANSFER_START != 0
+                && !self.is_link_connected
+                && self.device.clock_source() == SerialClock::External)
+    }
+
+    fn bit_cycles(&self) -> u32 {
+        if self.is_internal_transfer_active()
+            && self.is_cgb_hardware
+            && self.control & control_bits::FAST_CLOCK != 0
+        {
+            serial_setup::FAST_BIT_CYCLES
+        } else {
+            serial_setup::BIT_CYCLES
+        }
+    }
+
+    fn control_write(&mut self, control_data: u8) {
+        self.control = control_data & (control_bits::TRANSFER_START | self.writable_clock_bits());
+        self.incoming_data = None;
+        self.link_request = None;
+        if !self.is_clocked_transfer_active() {
+            return;
+        }
+        self.shift_cycles = 0;
+        self.shifted_bits = 0;
+        if self.is_link_connected {
+            self.link_request = Some(self.data);
+        } else {
+            self.incoming_data = Some(self.device.exchange(self.data));
+        }
+    }
+
+    fn writable_clock_bits(&self) -> u8 {
+        if self.is_cgb_hardware {
+            control_bits::FAST_CLOCK | control_bits::INTERNAL_CLOCK
+        } else {
+            control_bits::INTERNAL_CLOCK
+        }
+    }
+
+    fn transfer_completion(&mut self) {
+        if self.shifted_bits < serial_setup::BITS_PER_TRANSFER {
+            return;
+        }
+        if let Some(incoming_data) = self.incoming_data.take() {
+            self.data = incoming_data;
+            self.control &= !control_bits::TRANSFER_START;
+            self.shifted_bits = 0;
             self.interrupt_req = true;
         }
     }
@@ -28,11 +180,15 @@ impl HardwareAccessible for SerialDataTransfer {
     fn read_byte_from_hardware_register(&self, address: u16) -> u8 {
         match address {
             address::io_hardware_register::SERIAL_DATA => self.data,
-            address::io_hardware_register::SERIAL_CONTROL => self.control,
-            _ => panic!(
-                "[SERIAL ERROR][Read] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            address::io_hardware_register::SERIAL_CONTROL => {
+                let unused_bits = if self.is_cgb_hardware {
+                    control_bits::CGB_UNUSED
+                } else {
+                    control_bits::DMG_UNUSED
+                };
+                self.control | unused_bits
+            }
+            _ => memory::DEFAULT_INIT_VALUE,
         }
     }
 
@@ -42,24 +198,73 @@ impl HardwareAccessible for SerialDataTransfer {
                 self.data = data;
             }
             address::io_hardware_register::SERIAL_CONTROL => {
-                self.control = data;
-                self.write_data_to_test_buff_when_required(data);
+                self.control_write(data);
             }
-            _ => panic!(
-                "[SERIAL ERROR][Write] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            _ => (),
         }
     }
 }
 
+impl IoWorkingCycle for SerialDataTransfer {
+    fn next_to(&mut self, cycles: u32) {
+        if !self.is_clocked_transfer_active()
+            || self.shifted_bits == serial_setup::BITS_PER_TRANSFER
+        {
+            return;
+        }
+        let bit_cycles = self.bit_cycles();
+        self.shift_cycles += cycles;
+        while self.shift_cycles >= bit_cycles && self.shifted_bits < serial_setup::BITS_PER_TRANSFER
+        {
+            self.shift_cycles -= bit_cycles;
+            // Input line is high until the peer's byte is known
+            let input_bit = self.incoming_data.map_or(1, |incoming_data| {
+                incoming_data.rotate_right((7 - self.shifted_bits) as u32) & 1
+            });
+            self.data = (self.data << 1) | input_bit;
+            self.shifted_bits += 1;
+        }
+        self.transfer_completion();
+    }
+}
+
+impl Snapshot for SerialDataTransfer {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.data);
+        writer.write_u8(self.control);
+        writer.write_bool(self.interrupt_req);
+        writer.write_u32(self.shift_cycles);
+        writer.write_u8(self.shifted_bits);
+        // The device is not part of the state, a restored transfer completes with the byte got
+        writer.write_u8(
+            self.incoming_data
+                .unwrap_or(serial_setup::DISCONNECTED_DATA),
+        );
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        self.data = reader.read_u8()?;
+        self.control = reader.read_u8()?;
+        self.interrupt_req = reader.read_bool()?;
+        self.shift_cycles = reader.read_u32()?;
+        self.shifted_bits = reader.read_u8()?;
+        let incoming_data = reader.read_u8()?;
+        self.incoming_data = self.is_clocked_transfer_active().then_some(incoming_data);
+        self.link_request = None;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod ut {
     use super::*;
+    use crate::serial_device::{Loopback, TextCapture};
 
     #[test]
     fn serial_output_data_test() {
         let mut serial = SerialDataTransfer::default();
+        let capture = TextCapture::new();
+        serial.set_device(Box::new(capture.clone()));
         let exp_word: [char; 6] = ['S', 'E', 'R', 'I', 'A', 'L'];
 
         for e in exp_word {
@@ -76,12 +281,140 @@ mod ut {
                 address::io_hardware_register::SERIAL_CONTROL,
                 0x81,
             );
+            serial.next_to(8 * serial_setup::BIT_CYCLES);
         }
 
-        assert_eq!(exp_word.len(), serial.test_out_data.len());
+        assert_eq!(String::from_iter(exp_word), capture.text());
+    }
 
-        for i in 0..exp_word.len() {
-            assert_eq!(exp_word[i], serial.test_out_data[i]);
+    #[test]
+    fn loopback_device_test() {
+        let mut serial = SerialDataTransfer::default();
+        serial.set_device(Box::new(Loopback));
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_DATA, 0x5A);
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x81);
+        serial.next_to(8 * serial_setup::BIT_CYCLES);
+        assert!(serial.interrupt_req);
+        assert_eq!(
+            0x5A,
+            serial.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_DATA)
+        );
+    }
+
+    struct ClockingDevice;
+
+    impl SerialDevice for ClockingDevice {
+        fn exchange(&mut self, data: u8) -> u8 {
+            !data
         }
+
+        fn clock_source(&self) -> SerialClock {
+            SerialClock::External
+        }
+    }
+
+    #[test]
+    fn external_clock_device_test() {
+        let mut serial = SerialDataTransfer::default();
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_DATA, 0x0F);
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x80);
+        serial.next_to(8 * serial_setup::BIT_CYCLES);
+        assert!(!serial.interrupt_req);
+
+        serial.set_device(Box::new(ClockingDevice));
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x80);
+        serial.next_to(8 * serial_setup::BIT_CYCLES);
+        assert!(serial.interrupt_req);
+        assert_eq!(
+            0xF0,
+            serial.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_DATA)
+        );
+    }
+
+    #[test]
+    fn internal_clock_transfer_timing_test() {
+        let mut serial = SerialDataTransfer::default();
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_DATA, 0x0F);
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x81);
+        assert_eq!(
+            0xFF,
+            serial.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_CONTROL)
+        );
+
+        // 4 bits out, 1s shifted in from the disconnected line
+        serial.next_to(4 * serial_setup::BIT_CYCLES);
+        assert_eq!(
+            0xFF,
+            serial.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_DATA)
+        );
+        assert!(!serial.interrupt_req);
+
+        serial.next_to(4 * serial_setup::BIT_CYCLES - 1);
+        assert!(!serial.interrupt_req);
+        serial.next_to(1);
+        assert!(serial.interrupt_req);
+        assert_eq!(
+            0x7F,
+            serial.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_CONTROL)
+        );
+    }
+
+    #[test]
+    fn cgb_fast_clock_test() {
+        let mut serial = SerialDataTransfer::default();
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x83);
+        serial.next_to(8 * serial_setup::FAST_BIT_CYCLES);
+        assert!(!serial.interrupt_req);
+
+        serial.set_cgb_hardware(true);
+        serial.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x83);
+        assert_eq!(
+            0xFF,
+            serial.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_CONTROL)
+        );
+        serial.next_to(8 * serial_setup::FAST_BIT_CYCLES);
+        assert!(serial.interrupt_req);
+    }
+
+    #[test]
+    fn linked_transfer_test() {
+        let mut master = SerialDataTransfer::default();
+        let mut slave = SerialDataTransfer::default();
+        master.set_link_connected(true);
+        slave.set_link_connected(true);
+
+        slave.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_DATA, 0x5A);
+        slave.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x80);
+        // External clock transfer waits for the peer
+        slave.next_to(16 * serial_setup::BIT_CYCLES);
+        assert!(!slave.interrupt_req);
+
+        master.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_DATA, 0xC3);
+        master.write_byte_to_hardware_register(address::io_hardware_register::SERIAL_CONTROL, 0x81);
+        let request = master.take_link_request().unwrap();
+        assert_eq!(0xC3, request);
+
+        // Reply is late, the master waits after 8 bits
+        master.next_to(8 * serial_setup::BIT_CYCLES);
+        assert!(!master.interrupt_req);
+        assert_eq!(
+            0xFF,
+            master.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_CONTROL)
+        );
+
+        let reply = slave.external_clock_transfer(request);
+        assert_eq!(0x5A, reply);
+        assert!(slave.interrupt_req);
+        assert_eq!(
+            0xC3,
+            slave.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_DATA)
+        );
+
+        master.set_link_reply(reply);
+        assert!(master.interrupt_req);
+        assert_eq!(
+            0x5A,
+            master.read_byte_from_hardware_register(address::io_hardware_register::SERIAL_DATA)
+        );
     }
 }
@@ -1,5 +1,6 @@
 use super::{HardwareAccessible, IoWorkingCycle};
-use crate::constants::gb_memory_map::address;
+use crate::constants::gb_memory_map::{address, memory};
+use crate::save_state::{Snapshot, StateReader, StateWriter};
 
 mod timer_setup {
     pub const DIV_CLOCK_DIV: u32 = 255;
@@ -60,6 +61,12 @@ pub struct Timer {
 }
 
 impl Timer {
+    /// # set_div
+    /// DIV left by the boot ROM, it differs between the hardware models
+    pub fn set_div(&mut self, value: u8) {
+        self.div_counter_register = value;
+    }
+
     fn div_counter_update(&mut self, cycles: u32) {
         self.internal_div_counter += cycles;
         // It counts up at a frequency of 16382 Hz which means every 256 CPU clock cycles
@@ -94,10 +101,7 @@ impl HardwareAccessible for Timer {
             address::io_hardware_register::TIMER_TAC => {
                 TimerControlRegister::into(self.tac_register)
             }
-            _ => panic!(
-                "[TIMER ERROR][Read] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            _ => memory::DEFAULT_INIT_VALUE,
         }
     }
 
@@ -109,10 +113,7 @@ impl HardwareAccessible for Timer {
             address::io_hardware_register::TIMER_TAC => {
                 self.tac_register = TimerControlRegister::from(data)
             }
-            _ => panic!(
-                "[TIMER ERROR][Write] Unsupported address: [{:#06x?}]",
-                address
-            ),
+            _ => (),
         }
     }
 }
@@ -127,14 +128,36 @@ impl IoWorkingCycle for Timer {
     }
 }
 
+impl Snapshot for Timer {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.div_counter_register);
+        writer.write_u8(self.tima_counter_register);
+        writer.write_u8(self.modulo_register);
+        writer.write_u8(TimerControlRegister::into(self.tac_register));
+        writer.write_bool(self.interrupt_req);
+        writer.write_u32(self.internal_div_counter);
+        writer.write_u32(self.internal_tima_counter);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()> {
+        self.div_counter_register = reader.read_u8()?;
+        self.tima_counter_register = reader.read_u8()?;
+        self.modulo_register = reader.read_u8()?;
+        self.tac_register = TimerControlRegister::from(reader.read_u8()?);
+        self.interrupt_req = reader.read_bool()?;
+        self.internal_div_counter = reader.read_u32()?;
+        self.internal_tima_counter = reader.read_u32()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod ut {
     use super::*;
     #[test]
     fn tac_register_test() {
-        let mut tac = TimerControlRegister::default();
-        tac = TimerControlRegister::from(4);
-        assert!(tac.clock_enable == true);
+        let mut tac = TimerControlRegister::from(4);
+        assert!(tac.clock_enable);
         assert_eq!(0, tac.clock_select);
         assert_eq!(timer_setup::TIMA_CLOCK_DIV_0, tac.get_tima_clock_div());
 
@@ -142,7 +165,7 @@ mod ut {
         assert_eq!(0xFC, reg_val);
 
         tac = TimerControlRegister::from(0xFB);
-        assert!(tac.clock_enable == false);
+        assert!(!tac.clock_enable);
         assert_eq!(3, tac.clock_select);
         assert_eq!(timer_setup::TIMA_CLOCK_DIV_3, tac.get_tima_clock_div());
 
@@ -196,6 +219,6 @@ mod ut {
             timer.next_to(1024);
         }
         assert_eq!(0, timer.tima_counter_register);
-        assert!(timer.interrupt_req == true);
+        assert!(timer.interrupt_req);
     }
 }
@@ -0,0 +1,123 @@
+/// PNG files without external crates: 8 bit grayscale, zlib stored (not compressed) blocks
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const GRAYSCALE_COLOR_TYPE: u8 = 0;
+const BIT_DEPTH: u8 = 8;
+const NO_FILTER: u8 = 0;
+const MAX_STORED_BLOCK_SIZE: usize = 0xFFFF;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % MODULO;
+        b = (b + a) % MODULO;
+    }
+    b.rotate_left(16) | a
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let crc_start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[crc_start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate, 32K window, no dictionary
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_last_block = blocks.peek().is_none();
+        zlib.push(is_last_block as u8);
+        let length = block.len() as u16;
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+/// # encode_grayscale
+/// One byte per pixel, row by row
+pub fn encode_grayscale(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth, color type, compression, filter, interlace
+    header.extend_from_slice(&[BIT_DEPTH, GRAYSCALE_COLOR_TYPE, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    let mut scanlines = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width).take(height) {
+        scanlines.push(NO_FILTER);
+        scanlines.extend_from_slice(row);
+    }
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn checksum_test() {
+        assert_eq!(0xAE42_6082, crc32(b"IEND"));
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+        assert_eq!(0x11E6_0398, adler32(b"Wikipedia"));
+        assert_eq!(1, adler32(b""));
+    }
+
+    #[test]
+    fn encode_grayscale_test() {
+        let pixels = [0x00, 0x55, 0xAA, 0xFF, 0xFF, 0xAA];
+        let png = encode_grayscale(3, 2, &pixels);
+
+        assert_eq!(SIGNATURE, png[0..8]);
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!([0, 0, 0, 3, 0, 0, 0, 2, 8, 0, 0, 0, 0], png[16..29]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+
+        // IDAT: zlib header, one stored block with the filter bytes
+        let idat = &png[33 + 8..];
+        assert_eq!([0x78, 0x01, 0x01, 0x08, 0x00, 0xF7, 0xFF], idat[0..7]);
+        assert_eq!(
+            [0x00, 0x00, 0x55, 0xAA, 0x00, 0xFF, 0xFF, 0xAA],
+            idat[7..15]
+        );
+    }
+
+    #[test]
+    fn multiple_stored_blocks_test() {
+        let data = vec![0x11; MAX_STORED_BLOCK_SIZE + 1];
+        let zlib = zlib_stored(&data);
+        // Header, 2 block headers, data, Adler-32
+        assert_eq!(2 + 2 * 5 + data.len() + 4, zlib.len());
+        assert_eq!(0x00, zlib[2]);
+        assert_eq!(0x01, zlib[2 + 5 + MAX_STORED_BLOCK_SIZE]);
+    }
+}
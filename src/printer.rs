@@ -0,0 +1,473 @@
+use crate::png;
+use crate::serial_device::SerialDevice;
+use std::path::{Path, PathBuf};
+
+mod printer_setup {
+    pub const MAGIC: [u8; 2] = [0x88, 0x33];
+    pub const DEVICE_ID: u8 = 0x81;
+    pub const WIDTH: usize = 160;
+    // 2 rows of 20 tiles, 16 pixel rows
+    pub const BAND_SIZE: usize = 640;
+    pub const BAND_HEIGHT: usize = 16;
+    pub const MAX_BANDS: usize = 9;
+    pub const TILES_PER_ROW: usize = 20;
+    pub const TILE_SIZE: usize = 16;
+    // Paper feed of one margin unit, an approximation
+    pub const MARGIN_UNIT_HEIGHT: usize = 16;
+    // STATUS packets answered as busy after a PRINT
+    pub const PRINTING_STATUS_PACKETS: u8 = 4;
+}
+
+mod command {
+    pub const INIT: u8 = 0x01;
+    pub const PRINT: u8 = 0x02;
+    pub const DATA: u8 = 0x04;
+    pub const STATUS: u8 = 0x0F;
+}
+
+mod status_bits {
+    pub const CHECKSUM_ERROR: u8 = 0x01;
+    pub const PRINTING: u8 = 0x02;
+    pub const IMAGE_DATA_FULL: u8 = 0x04;
+    pub const UNPROCESSED_DATA: u8 = 0x08;
+}
+
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum PacketState {
+    Magic(usize),
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    KeepAlive,
+    Status,
+}
+
+/// # Printout
+/// 8 bit grayscale pixels, 160 pixels wide, margins included
+#[derive(PartialEq, Clone, Debug)]
+pub struct Printout {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Path of the written printout or the write error
+pub type PrintoutCallback = Box<dyn FnMut(std::result::Result<&Path, &std::io::Error>)>;
+
+/// # GameBoyPrinter
+/// Serial peripheral on the other end of the link cable, the Game Boy drives the clock.
+///
+/// Packet: 0x88 0x33, command, compression, length (LE), data, checksum (LE), 2 bytes
+/// answered with 0x81 and the status. INIT clears the buffer, DATA adds 640 byte bands
+/// (RLE compressed when asked), PRINT writes the buffer as `print_NNN.png`, STATUS polls.
+pub struct GameBoyPrinter {
+    output_directory: PathBuf,
+    state: PacketState,
+    command: u8,
+    is_compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    status: u8,
+    printing_status_packets: u8,
+    image_data: Vec<u8>,
+    number_of_printouts: u32,
+    last_printout: Option<Printout>,
+    printed_files: Vec<PathBuf>,
+    write_error: Option<std::io::Error>,
+    printout_callback: Option<PrintoutCallback>,
+}
+
+impl GameBoyPrinter {
+    /// # new
+    /// Printouts are written to the output directory
+    pub fn new(output_directory: impl AsRef<Path>) -> Self {
+        Self {
+            output_directory: output_directory.as_ref().to_path_buf(),
+            state: PacketState::Magic(0),
+            command: 0,
+            is_compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            status: 0,
+            printing_status_packets: 0,
+            image_data: Vec::new(),
+            number_of_printouts: 0,
+            last_printout: None,
+            printed_files: Vec::new(),
+            write_error: None,
+            printout_callback: None,
+        }
+    }
+
+    /// # set_printout_callback
+    /// Called after every printout, the printer is owned by the emulator once connected
+    pub fn set_printout_callback(
+        &mut self,
+        callback: impl FnMut(std::result::Result<&Path, &std::io::Error>) + 'static,
+    ) {
+        self.printout_callback = Some(Box::new(callback));
+    }
+
+    pub fn last_printout(&self) -> Option<&Printout> {
+        self.last_printout.as_ref()
+    }
+
+    pub fn printed_files(&self) -> &[PathBuf] {
+        &self.printed_files
+    }
+
+    /// # take_write_error
+    /// Last printout which could not be written
+    pub fn take_write_error(&mut self) -> Option<std::io::Error> {
+        self.write_error.take()
+    }
+
+    fn packet_byte(&mut self, data: u8) -> u8 {
+        let mut reply = 0x00;
+        self.state = match self.state {
+            PacketState::Magic(index) if data == printer_setup::MAGIC[index] => {
+                if index + 1 == printer_setup::MAGIC.len() {
+                    PacketState::Command
+                } else {
+                    PacketState::Magic(index + 1)
+                }
+            }
+            // Out of sync, waits for the next packet
+            PacketState::Magic(_) => PacketState::Magic(0),
+            PacketState::Command => {
+                self.command = data;
+                self.checksum = data as u16;
+                PacketState::Compression
+            }
+            PacketState::Compression => {
+                self.is_compressed = data & 1 == 1;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                PacketState::LengthLow
+            }
+            PacketState::LengthLow => {
+                self.length = data as u16;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                PacketState::LengthHigh
+            }
+            PacketState::LengthHigh => {
+                self.length |= (data as u16).rotate_left(8);
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                self.data.clear();
+                if self.length == 0 {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::Data => {
+                self.data.push(data);
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                if self.data.len() == self.length as usize {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::ChecksumLow => {
+                self.checksum = self.checksum.wrapping_sub(data as u16);
+                PacketState::ChecksumHigh
+            }
+            PacketState::ChecksumHigh => {
+                self.checksum = self.checksum.wrapping_sub((data as u16).rotate_left(8));
+                self.packet_handling();
+                PacketState::KeepAlive
+            }
+            PacketState::KeepAlive => {
+                reply = printer_setup::DEVICE_ID;
+                PacketState::Status
+            }
+            PacketState::Status => {
+                reply = self.status;
+                PacketState::Magic(0)
+            }
+        };
+        reply
+    }
+
+    fn packet_handling(&mut self) {
+        if self.checksum != 0 {
+            self.status |= status_bits::CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !status_bits::CHECKSUM_ERROR;
+
+        match self.command {
+            command::INIT => {
+                self.image_data.clear();
+                self.status = 0;
+                self.printing_status_packets = 0;
+            }
+            command::DATA => {
+                let data = std::mem::take(&mut self.data);
+                if self.is_compressed {
+                    Self::decompress(&data, &mut self.image_data);
+                } else {
+                    self.image_data.extend_from_slice(&data);
+                }
+                let max_size = printer_setup::MAX_BANDS * printer_setup::BAND_SIZE;
+                self.image_data.truncate(max_size);
+                if !self.image_data.is_empty() {
+                    self.status |= status_bits::UNPROCESSED_DATA;
+                }
+                if self.image_data.len() == max_size {
+                    self.status |= status_bits::IMAGE_DATA_FULL;
+                }
+            }
+            command::PRINT if self.data.len() >= 4 => {
+                let (sheets, margins, palette) = (self.data[0], self.data[1], self.data[2]);
+                // Zero sheets only feeds the paper
+                if sheets != 0 {
+                    self.print(margins, palette);
+                }
+                self.image_data.clear();
+                self.status &= !(status_bits::UNPROCESSED_DATA | status_bits::IMAGE_DATA_FULL);
+                self.status |= status_bits::PRINTING;
+                self.printing_status_packets = printer_setup::PRINTING_STATUS_PACKETS;
+            }
+            command::STATUS if self.printing_status_packets != 0 => {
+                self.printing_status_packets -= 1;
+                if self.printing_status_packets == 0 {
+                    self.status &= !status_bits::PRINTING;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Control byte bit 7: run of (n & 0x7F) + 2 copies of the next byte, else n + 1 literal bytes
+    fn decompress(data: &[u8], output: &mut Vec<u8>) {
+        let mut index = 0;
+        while index < data.len() {
+            let control = data[index];
+            index += 1;
+            if control & 0x80 != 0 {
+                let Some(value) = data.get(index) else {
+                    break;
+                };
+                let length = (control & 0x7F) as usize + 2;
+                output.extend(std::iter::repeat_n(*value, length));
+                index += 1;
+            } else {
+                let end = (index + control as usize + 1).min(data.len());
+                output.extend_from_slice(&data[index..end]);
+                index = end;
+            }
+        }
+    }
+
+    fn print(&mut self, margins: u8, palette: u8) {
+        let top_rows = margins.rotate_right(4) as usize & 0x0F;
+        let top_rows = top_rows * printer_setup::MARGIN_UNIT_HEIGHT;
+        let bottom_rows = (margins & 0x0F) as usize * printer_setup::MARGIN_UNIT_HEIGHT;
+        let image_rows =
+            self.image_data.len() / printer_setup::BAND_SIZE * printer_setup::BAND_HEIGHT;
+        let height = top_rows + image_rows + bottom_rows;
+
+        let mut pixels = vec![SHADES[0]; printer_setup::WIDTH * height];
+        for y in 0..image_rows {
+            for x in 0..printer_setup::WIDTH {
+                let tile_row = y / 8;
+                let tile_index = tile_row * printer_setup::TILES_PER_ROW + x / 8;
+                let byte_index = tile_index * printer_setup::TILE_SIZE + (y % 8) * 2;
+                let bit = 7 - (x % 8) as u32;
+                let low = self.image_data[byte_index].rotate_right(bit) & 1;
+                let high = self.image_data[byte_index + 1].rotate_right(bit) & 1;
+                let color = (high << 1) | low;
+                let shade = palette.rotate_right(color as u32 * 2) & 0x03;
+                pixels[(top_rows + y) * printer_setup::WIDTH + x] = SHADES[shade as usize];
+            }
+        }
+
+        self.number_of_printouts += 1;
+        let path = self
+            .output_directory
+            .join(format!("print_{:03}.png", self.number_of_printouts));
+        let png = png::encode_grayscale(printer_setup::WIDTH, height, &pixels);
+        match std::fs::write(&path, png) {
+            Ok(()) => {
+                if let Some(callback) = self.printout_callback.as_mut() {
+                    callback(Ok(&path));
+                }
+                self.printed_files.push(path);
+            }
+            Err(error) => {
+                if let Some(callback) = self.printout_callback.as_mut() {
+                    callback(Err(&error));
+                }
+                self.write_error = Some(error);
+            }
+        }
+        self.last_printout = Some(Printout {
+            width: printer_setup::WIDTH,
+            height,
+            pixels,
+        });
+    }
+}
+
+impl SerialDevice for GameBoyPrinter {
+    fn exchange(&mut self, data: u8) -> u8 {
+        self.packet_byte(data)
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    /// Empty directory of the test, removed by the test at the end
+    fn output_directory(test_name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("r_gb_emu_{}_{}", test_name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Sends the packet, returns the device ID and the status
+    fn send_packet(
+        printer: &mut GameBoyPrinter,
+        command: u8,
+        compression: u8,
+        data: &[u8],
+    ) -> (u8, u8) {
+        let length = data.len() as u16;
+        let mut packet = vec![0x88, 0x33, command, compression];
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(data);
+        let checksum = packet[2..]
+            .iter()
+            .fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+        packet.extend_from_slice(&checksum.to_le_bytes());
+
+        for byte in packet {
+            assert_eq!(0x00, printer.exchange(byte));
+        }
+        (printer.exchange(0x00), printer.exchange(0x00))
+    }
+
+    #[test]
+    fn decompress_test() {
+        let mut output = Vec::new();
+        GameBoyPrinter::decompress(&[0x81, 0xAA, 0x01, 0x11, 0x22, 0x80, 0x33], &mut output);
+        assert_eq!(vec![0xAA, 0xAA, 0xAA, 0x11, 0x22, 0x33, 0x33], output);
+    }
+
+    #[test]
+    fn status_test() {
+        let output_directory = output_directory("printer_status_test");
+        let mut printer = GameBoyPrinter::new(&output_directory);
+        assert_eq!(
+            (0x81, 0x00),
+            send_packet(&mut printer, command::INIT, 0, &[])
+        );
+        assert_eq!(
+            (0x81, 0x00),
+            send_packet(&mut printer, command::STATUS, 0, &[])
+        );
+
+        let band = [0x00; printer_setup::BAND_SIZE];
+        assert_eq!(
+            (0x81, 0x08),
+            send_packet(&mut printer, command::DATA, 0, &band)
+        );
+        // Empty DATA packet ends the image
+        assert_eq!(
+            (0x81, 0x08),
+            send_packet(&mut printer, command::DATA, 0, &[])
+        );
+
+        // Bad checksum
+        for byte in [0x88, 0x33, command::STATUS, 0, 0, 0, 0x00, 0x00] {
+            printer.exchange(byte);
+        }
+        assert_eq!(0x81, printer.exchange(0x00));
+        assert_eq!(0x09, printer.exchange(0x00));
+        assert_eq!(
+            (0x81, 0x08),
+            send_packet(&mut printer, command::STATUS, 0, &[])
+        );
+
+        // Out of sync bytes are ignored
+        assert_eq!(0x00, printer.exchange(0x12));
+        assert_eq!(
+            (0x81, 0x08),
+            send_packet(&mut printer, command::STATUS, 0, &[])
+        );
+        let _ = std::fs::remove_dir_all(&output_directory);
+    }
+
+    #[test]
+    fn print_test() {
+        let output_directory = output_directory("printer_print_test");
+        let mut printer = GameBoyPrinter::new(&output_directory);
+        let callback_files = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let files = callback_files.clone();
+        printer.set_printout_callback(move |path| {
+            files.borrow_mut().push(path.unwrap().to_path_buf());
+        });
+
+        send_packet(&mut printer, command::INIT, 0, &[]);
+        // First tile row: color 3 in the first pixel column, color 1 elsewhere
+        let mut band = vec![0x00; printer_setup::BAND_SIZE];
+        for tile_line in 0..8 {
+            band[tile_line * 2] = 0xFF;
+            band[tile_line * 2 + 1] = 0x80;
+        }
+        // Same band compressed: literal 16 bytes of the first tile, run of 624 zeros
+        let mut compressed_band = vec![0x0F];
+        compressed_band.extend_from_slice(&band[0..16]);
+        for _ in 0..4 {
+            compressed_band.extend_from_slice(&[0xFF, 0x00]);
+        }
+        compressed_band.extend_from_slice(&[0xEA, 0x00]);
+        send_packet(&mut printer, command::DATA, 0, &band);
+        send_packet(&mut printer, command::DATA, 1, &compressed_band);
+        send_packet(&mut printer, command::DATA, 0, &[]);
+
+        // 1 sheet, 1 unit margin before, none after, palette 3-2-1-0, exposure
+        let (_, status) = send_packet(&mut printer, command::PRINT, 0, &[0x01, 0x10, 0xE4, 0x40]);
+        assert_eq!(0x02, status & 0x02);
+
+        let printout = printer.last_printout().unwrap();
+        let top = printer_setup::MARGIN_UNIT_HEIGHT;
+        assert_eq!(160, printout.width);
+        assert_eq!(top + 2 * printer_setup::BAND_HEIGHT, printout.height);
+        assert_eq!(0xFF, printout.pixels[0]);
+        assert_eq!(0x00, printout.pixels[top * 160]);
+        assert_eq!(0xAA, printout.pixels[top * 160 + 1]);
+        assert_eq!(0xFF, printout.pixels[top * 160 + 8]);
+        assert_eq!(0x00, printout.pixels[(top + 16) * 160]);
+
+        let png = std::fs::read(&printer.printed_files()[0]).unwrap();
+        assert_eq!(b"\x89PNG", &png[0..4]);
+        assert!(printer.take_write_error().is_none());
+        assert_eq!(printer.printed_files(), callback_files.borrow().as_slice());
+
+        // Busy until it is polled a few times
+        for _ in 1..printer_setup::PRINTING_STATUS_PACKETS {
+            assert_eq!(
+                (0x81, 0x02),
+                send_packet(&mut printer, command::STATUS, 0, &[])
+            );
+        }
+        assert_eq!(
+            (0x81, 0x00),
+            send_packet(&mut printer, command::STATUS, 0, &[])
+        );
+        let _ = std::fs::remove_dir_all(&output_directory);
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{Error, ErrorKind};
+
+/// # Save state blob layout
+///
+/// [0..4] Magic "RGBS"
+///
+/// [4..6] Version (u16 little endian)
+///
+/// [6..]  Sections: Cpu, IOMMU, PPU, Joypad, Cartridge
+pub const SAVE_STATE_MAGIC: [u8; 4] = *b"RGBS";
+pub const SAVE_STATE_VERSION: u16 = 12;
+
+/// # Snapshot trait
+/// Every part of the machine which keeps a state writes it in a fixed order
+/// and reads it back in the same order.
+pub trait Snapshot {
+    fn save_state(&self, writer: &mut StateWriter);
+    fn load_state(&mut self, reader: &mut StateReader) -> std::io::Result<()>;
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut writer = Self::default();
+        writer.write_bytes(&SAVE_STATE_MAGIC);
+        writer.write_u16(SAVE_STATE_VERSION);
+        writer
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Fixed size data, the reader has to know the length
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Variable size data, prefixed with its length
+    pub fn write_vec(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.write_bytes(bytes);
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> std::io::Result<Self> {
+        let mut reader = Self { data, position: 0 };
+
+        let mut magic = [0_u8; 4];
+        reader.read_bytes(&mut magic)?;
+        if magic != SAVE_STATE_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a save state"));
+        }
+
+        let version = reader.read_u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported save state version: {}", version),
+            ));
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, length: usize) -> std::io::Result<&'a [u8]> {
+        let end = self.position + length;
+        if end > self.data.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Save state is truncated",
+            ));
+        }
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> std::io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> std::io::Result<bool> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> std::io::Result<u16> {
+        let mut bytes = [0_u8; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub fn read_u32(&mut self) -> std::io::Result<u32> {
+        let mut bytes = [0_u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_bytes(&mut self, out: &mut [u8]) -> std::io::Result<()> {
+        out.copy_from_slice(self.take(out.len())?);
+        Ok(())
+    }
+
+    pub fn read_vec(&mut self) -> std::io::Result<Vec<u8>> {
+        let length = self.read_u32()? as usize;
+        Ok(self.take(length)?.to_vec())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.data.len()
+    }
+}
+
+pub fn invalid_data_error(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn write_and_read_state_test() {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0xAB);
+        writer.write_bool(true);
+        writer.write_u16(0x1234);
+        writer.write_u32(0xDEADBEEF);
+        writer.write_bytes(&[1, 2, 3]);
+        writer.write_vec(&[4, 5]);
+        let data = writer.into_inner();
+
+        let mut reader = StateReader::new(&data).unwrap();
+        assert_eq!(0xAB, reader.read_u8().unwrap());
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(0x1234, reader.read_u16().unwrap());
+        assert_eq!(0xDEADBEEF, reader.read_u32().unwrap());
+        let mut bytes = [0_u8; 3];
+        reader.read_bytes(&mut bytes).unwrap();
+        assert_eq!([1, 2, 3], bytes);
+        assert_eq!(vec![4, 5], reader.read_vec().unwrap());
+        assert!(reader.is_finished());
+
+        // Truncated data
+        assert!(reader.read_u8().is_err());
+    }
+
+    #[test]
+    fn invalid_header_test() {
+        assert!(StateReader::new(b"RGB").is_err());
+        assert!(StateReader::new(b"XXXX\x01\x00").is_err());
+        assert!(StateReader::new(b"RGBS\xFF\x00").is_err());
+
+        let mut header = SAVE_STATE_MAGIC.to_vec();
+        header.extend_from_slice(&(SAVE_STATE_VERSION - 1).to_le_bytes());
+        assert!(StateReader::new(&header).is_err());
+
+        let mut header = SAVE_STATE_MAGIC.to_vec();
+        header.extend_from_slice(&SAVE_STATE_VERSION.to_le_bytes());
+        assert!(StateReader::new(&header).is_ok());
+    }
+}
@@ -0,0 +1,90 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// # SerialClock
+/// Internal: the device answers the transfers clocked by the Game Boy.
+/// External: the device drives the clock, transfers started with the external clock run too.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum SerialClock {
+    Internal,
+    External,
+}
+
+/// # SerialDevice
+/// Peripheral on the other end of the link cable. The TCP link and `LinkedPair` are wired
+/// to the serial unit directly, their replies arrive later.
+pub trait SerialDevice {
+    /// # exchange
+    /// Byte shifted out by the Game Boy, returns the byte shifted in at the same time
+    fn exchange(&mut self, data: u8) -> u8;
+
+    fn clock_source(&self) -> SerialClock {
+        SerialClock::Internal
+    }
+}
+
+/// # Disconnected
+/// No cable, the input line stays high
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Disconnected;
+
+impl SerialDevice for Disconnected {
+    fn exchange(&mut self, _data: u8) -> u8 {
+        0xFF
+    }
+}
+
+/// # TextCapture
+/// Disconnected cable which keeps the sent bytes as text, test ROMs (blargg) print their results so.
+/// Clones share the text, keep one to read it after the device was connected.
+#[derive(Default, Clone, Debug)]
+pub struct TextCapture {
+    text: Rc<RefCell<String>>,
+}
+
+impl TextCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> String {
+        self.text.borrow().clone()
+    }
+}
+
+impl SerialDevice for TextCapture {
+    fn exchange(&mut self, data: u8) -> u8 {
+        self.text.borrow_mut().push(data as char);
+        0xFF
+    }
+}
+
+/// # Loopback
+/// Output wired to the input, the Game Boy receives its own byte
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Loopback;
+
+impl SerialDevice for Loopback {
+    fn exchange(&mut self, data: u8) -> u8 {
+        data
+    }
+}
+
+#[cfg(test)]
+mod ut {
+    use super::*;
+
+    #[test]
+    fn built_in_devices_test() {
+        assert_eq!(0xFF, Disconnected.exchange(0x12));
+        assert_eq!(0x12, Loopback.exchange(0x12));
+        assert_eq!(SerialClock::Internal, Loopback.clock_source());
+
+        let capture = TextCapture::new();
+        let mut device = capture.clone();
+        for byte in b"Passed" {
+            assert_eq!(0xFF, device.exchange(*byte));
+        }
+        assert_eq!("Passed", capture.text());
+    }
+}
@@ -1,8 +1,14 @@
+use r_gb_emu::debugger::{DebugCommand, Debugger, HookAction, RunTarget, StopReason, WatchKind};
+use r_gb_emu::emulator_constants::{resolution, HardwareModel, PpuRenderer};
+use r_gb_emu::gdb_stub::GdbStub;
+use r_gb_emu::link::{LinkedPair, TcpLink};
+use r_gb_emu::printer::GameBoyPrinter;
+use r_gb_emu::serial_device::{Loopback, TextCapture};
 use r_gb_emu::GameBoyEmulator;
-use std::fs;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
 
 /// This is test for gameboy doctor
 #[test]
@@ -27,12 +33,20 @@ fn cpu_instruction_behavior_test() {
     // }
 
     // println!("cycles {}", sum_of_cycles);
-    // println!("Test output: {}", gameboy.serial_out());
+    // println!("Test output: {}", capture.text());
+}
+
+/// Test ROMs print their results on the serial port
+fn connect_text_capture(gameboy: &mut GameBoyEmulator) -> TextCapture {
+    let capture = TextCapture::new();
+    gameboy.connect_serial_device(capture.clone());
+    capture
 }
 
 #[test]
 fn cpu_08_misc_instrs_test() {
     let mut gameboy = GameBoyEmulator::new();
+    let capture = connect_text_capture(&mut gameboy);
     gameboy.load_cartridge("roms/08-misc instrs.gb").unwrap();
 
     let exp_test_result = String::from("08-misc instrs\n\n\nPassed\n");
@@ -41,12 +55,13 @@ fn cpu_08_misc_instrs_test() {
         let _ = gameboy.emulate_step();
     }
 
-    assert_eq!(exp_test_result, gameboy.serial_out());
+    assert_eq!(exp_test_result, capture.text());
 }
 
 #[test]
 fn cpu_07_jr_jp_call_ret_rst_test() {
     let mut gameboy = GameBoyEmulator::new();
+    let capture = connect_text_capture(&mut gameboy);
     gameboy
         .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
         .unwrap();
@@ -57,5 +72,475 @@ fn cpu_07_jr_jp_call_ret_rst_test() {
         let _ = gameboy.emulate_step();
     }
 
-    assert_eq!(exp_test_result, gameboy.serial_out());
+    assert_eq!(exp_test_result, capture.text());
+}
+
+#[test]
+fn boot_rom_test() {
+    // Minimal boot ROM: LCD on, NOPs and unmap itself at 0x00FE like the real one
+    let mut boot_rom = vec![0x00; 0x100];
+    boot_rom[0x00..0x07].copy_from_slice(&[0x31, 0xFE, 0xFF, 0x3E, 0x91, 0xE0, 0x40]);
+    boot_rom[0xFC..0x100].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+    let boot_rom_path = std::env::temp_dir().join("r_gb_emu_boot_rom_test.bin");
+    std::fs::write(&boot_rom_path, &boot_rom).unwrap();
+
+    let mut gameboy = GameBoyEmulator::new();
+    let capture = connect_text_capture(&mut gameboy);
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    gameboy
+        .load_boot_rom(boot_rom_path.to_str().unwrap())
+        .unwrap();
+    let _ = std::fs::remove_file(&boot_rom_path);
+
+    assert!(gameboy.get_log().contains("PC:0000 PCMEM:31,FE,FF,3E"));
+    while !gameboy.get_log().contains("PC:0100") {
+        gameboy.emulate_step().unwrap();
+    }
+    // Cartridge is visible again
+    assert_eq!("NOP", gameboy.disassemble(0x00FE).0);
+
+    for _ in 1..400000 {
+        let _ = gameboy.emulate_step();
+    }
+    assert_eq!(
+        String::from("07-jr,jp,call,ret,rst\n\n\nPassed\n"),
+        capture.text()
+    );
+}
+
+#[test]
+fn hardware_model_test() {
+    // Test ROM supports CGB
+    let mut gameboy = GameBoyEmulator::new();
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    assert_eq!(HardwareModel::Cgb, gameboy.hardware_model());
+    assert!(gameboy.get_log().starts_with("A:11 F:80 B:00"));
+
+    let mut gameboy = GameBoyEmulator::with_hardware_model(HardwareModel::Dmg0);
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    assert!(!gameboy.is_cgb_mode());
+    assert!(gameboy.get_log().starts_with("A:01 F:00 B:FF C:13"));
+
+    let mut gameboy = GameBoyEmulator::with_hardware_model(HardwareModel::Agb);
+    let capture = connect_text_capture(&mut gameboy);
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    assert_eq!(HardwareModel::Agb, gameboy.hardware_model());
+    assert!(gameboy.is_cgb_mode());
+    assert!(gameboy.get_log().starts_with("A:11 F:00 B:01"));
+
+    for _ in 1..400000 {
+        let _ = gameboy.emulate_step();
+    }
+    assert_eq!(
+        String::from("07-jr,jp,call,ret,rst\n\n\nPassed\n"),
+        capture.text()
+    );
+}
+
+#[test]
+fn debugger_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    let mut debugger = Debugger::new();
+
+    // 0x0100: NOP, JP $0213
+    let report = debugger.execute(&mut gameboy, &DebugCommand::Step(1));
+    assert!(report.starts_with("0101: JP $0213"));
+
+    debugger.execute(&mut gameboy, &DebugCommand::Break(0x0213));
+    match debugger.run(&mut gameboy, RunTarget::Frame) {
+        StopReason::Breakpoint(address) => assert_eq!(0x0213, address),
+        stop_reason => panic!("Unexpected stop: {}", stop_reason),
+    }
+    assert_eq!(0x0213, gameboy.cpu_registers().pc);
+    debugger.execute(&mut gameboy, &DebugCommand::Delete(0x0213));
+
+    // Test name goes out through the serial port
+    gameboy.set_watchpoint(0xFF01, WatchKind::Write);
+    let stop_reason = loop {
+        match debugger.run(&mut gameboy, RunTarget::Frame) {
+            StopReason::TargetReached => continue,
+            stop_reason => break stop_reason,
+        }
+    };
+    match stop_reason {
+        StopReason::Watchpoint(hit) => {
+            assert_eq!(0xFF01, hit.address);
+            assert!(hit.is_write);
+            assert_eq!(b'0', hit.value);
+        }
+        stop_reason => panic!("Unexpected stop: {}", stop_reason),
+    }
+    assert!(gameboy.remove_watchpoint(0xFF01));
+
+    assert!(matches!(
+        debugger.run(&mut gameboy, RunTarget::VBlank),
+        StopReason::TargetReached
+    ));
+    assert_eq!(144, gameboy.peek_byte(0xFF44));
+
+    let dump = debugger.execute(
+        &mut gameboy,
+        &DebugCommand::Memory {
+            address: 0x0100,
+            length: 4,
+        },
+    );
+    assert_eq!("0100: 00 C3 13 02", dump);
+}
+
+#[test]
+fn memory_hook_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    let capture = connect_text_capture(&mut gameboy);
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+
+    // Serial data register written by the test for every character
+    let serial_text = Rc::new(RefCell::new(String::new()));
+    let hook_text = serial_text.clone();
+    let id = gameboy.add_memory_hook(0xFF01..=0xFF01, WatchKind::Write, move |access| {
+        hook_text.borrow_mut().push(access.value as char);
+        HookAction::Continue
+    });
+    let mut debugger = Debugger::new();
+    gameboy.set_watchpoint(0xFF02, WatchKind::Change);
+
+    // Transfer start flips SC to 0x81
+    match debugger.run(&mut gameboy, RunTarget::Steps(400000)) {
+        StopReason::Watchpoint(hit) => assert_eq!([0x81, 0x7F], [hit.value, hit.old_value]),
+        stop_reason => panic!("Unexpected stop: {}", stop_reason),
+    }
+    assert_eq!("0", serial_text.borrow().as_str());
+    assert!(gameboy.remove_watchpoint(0xFF02));
+
+    debugger.run(&mut gameboy, RunTarget::Steps(400000));
+    assert_eq!(capture.text(), serial_text.borrow().as_str());
+    assert!(gameboy.remove_memory_hook(id));
+}
+
+#[test]
+fn save_state_resume_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+
+    for _ in 1..100000 {
+        let _ = gameboy.emulate_step();
+    }
+
+    let state = gameboy.save_state();
+    let capture = connect_text_capture(&mut gameboy);
+
+    let mut exp_log = String::new();
+    for _ in 1..300000 {
+        exp_log.push_str(&gameboy.get_log());
+        let _ = gameboy.emulate_step();
+    }
+
+    let mut resumed_gameboy = GameBoyEmulator::new();
+    resumed_gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    resumed_gameboy.load_state(&state).unwrap();
+    let resumed_capture = connect_text_capture(&mut resumed_gameboy);
+
+    let mut log = String::new();
+    for _ in 1..300000 {
+        log.push_str(&resumed_gameboy.get_log());
+        let _ = resumed_gameboy.emulate_step();
+    }
+
+    assert!(exp_log == log);
+    assert!(!capture.text().is_empty());
+    assert_eq!(capture.text(), resumed_capture.text());
+    assert_eq!(gameboy.save_state(), resumed_gameboy.save_state());
+
+    // Corrupted state does not change the machine
+    assert!(resumed_gameboy
+        .load_state(&state[..state.len() - 1])
+        .is_err());
+    assert_eq!(gameboy.save_state(), resumed_gameboy.save_state());
+}
+
+#[test]
+fn headless_run_frame_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    let capture = connect_text_capture(&mut gameboy);
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+
+    let exp_test_result = String::from("07-jr,jp,call,ret,rst\n\n\nPassed\n");
+    let mut frame_buffer = vec![0; resolution::SCREEN_W * resolution::SCREEN_H];
+
+    // 10 s of the emulated time, no wall clock pacing
+    for _ in 0..600 {
+        gameboy.run_frame(&mut frame_buffer).unwrap();
+        if capture.text() == exp_test_result {
+            break;
+        }
+    }
+
+    assert_eq!(exp_test_result, capture.text());
+}
+
+#[test]
+fn pixel_fifo_renderer_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    let capture = connect_text_capture(&mut gameboy);
+    gameboy.set_ppu_renderer(PpuRenderer::PixelFifo);
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+
+    let exp_test_result = String::from("07-jr,jp,call,ret,rst\n\n\nPassed\n");
+    let mut frame_buffer = vec![0; resolution::SCREEN_W * resolution::SCREEN_H];
+
+    for _ in 0..600 {
+        gameboy.run_frame(&mut frame_buffer).unwrap();
+        if capture.text() == exp_test_result {
+            break;
+        }
+    }
+
+    assert_eq!(exp_test_result, capture.text());
+    // The test prints its result on the screen as well
+    assert!(frame_buffer.iter().any(|pixel| *pixel != frame_buffer[0]));
+}
+
+fn gdb_request(stream: &mut TcpStream, packet: &str) -> String {
+    let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    stream
+        .write_all(format!("${}#{:02x}", packet, checksum).as_bytes())
+        .unwrap();
+
+    // Ack, then $reply#checksum
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    while reply.len() < 3 || reply[reply.len() - 3] != b'#' {
+        stream.read_exact(&mut byte).unwrap();
+        reply.push(byte[0]);
+    }
+    let reply = String::from_utf8(reply).unwrap();
+    assert!(reply.starts_with("+$"), "Unexpected reply {}", reply);
+    reply[2..reply.len() - 3].to_string()
+}
+
+#[test]
+fn gdb_stub_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let client = std::thread::spawn(move || {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        assert_eq!("S05", gdb_request(&mut stream, "?"));
+        // 0x0100: NOP, JP $0213
+        assert_eq!("0001", gdb_request(&mut stream, "p5"));
+        assert_eq!("S05", gdb_request(&mut stream, "s"));
+        assert_eq!("0101", gdb_request(&mut stream, "p5"));
+        assert_eq!("c313", gdb_request(&mut stream, "m101,2"));
+
+        assert_eq!("OK", gdb_request(&mut stream, "Z0,213,1"));
+        assert_eq!("S05", gdb_request(&mut stream, "c"));
+        assert_eq!("1302", gdb_request(&mut stream, "p5"));
+        assert_eq!("OK", gdb_request(&mut stream, "z0,213,1"));
+
+        // Test name goes out through the serial port
+        assert_eq!("OK", gdb_request(&mut stream, "Z2,ff01,1"));
+        assert_eq!("T05watch:ff01;", gdb_request(&mut stream, "c"));
+        assert_eq!("OK", gdb_request(&mut stream, "z2,ff01,1"));
+
+        assert_eq!("OK", gdb_request(&mut stream, "Mc000,2:55aa"));
+        assert_eq!("55aa", gdb_request(&mut stream, "mc000,2"));
+        assert_eq!("OK", gdb_request(&mut stream, "P4=00df"));
+        assert_eq!("OK", gdb_request(&mut stream, "D"));
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    GdbStub::new().serve(&mut gameboy, stream).unwrap();
+    client.join().unwrap();
+
+    assert_eq!(0xDF00, gameboy.cpu_registers().sp);
+    assert_eq!(0x55, gameboy.peek_byte(0xC000));
+}
+
+/// DI, SB=data, SC=control, waits for the end of the transfer and stores SB to 0xC100.
+/// The clock master starts a bit later, so the peer is ready like in the games.
+fn load_serial_transfer_program(gameboy: &mut GameBoyEmulator, data: u8, control: u8) {
+    let delay = if control & 0x01 != 0 { 0x40 } else { 0x01 };
+    let program = [
+        0xF3, 0x06, delay, 0x05, 0x20, 0xFD, 0x3E, data, 0xE0, 0x01, 0x3E, control, 0xE0, 0x02,
+        0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA, 0xF0, 0x01, 0xEA, 0x00, 0xC1, 0x18, 0xFE,
+    ];
+    for (offset, byte) in program.iter().enumerate() {
+        gameboy.poke_byte(0xC000 + offset as u16, *byte);
+    }
+    gameboy.poke_byte(0xC100, 0x00);
+    let mut registers = gameboy.cpu_registers();
+    registers.pc = 0xC000;
+    gameboy.set_cpu_registers(&registers);
+}
+
+#[test]
+fn tcp_link_cable_test() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let master_link = TcpLink::connect(listener.local_addr().unwrap()).unwrap();
+    let slave_link = TcpLink::from_stream(listener.accept().unwrap().0).unwrap();
+
+    let mut master = GameBoyEmulator::new();
+    let mut slave = GameBoyEmulator::new();
+    for gameboy in [&mut master, &mut slave] {
+        gameboy
+            .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+            .unwrap();
+    }
+    master.connect_link(master_link);
+    slave.connect_link(slave_link);
+    load_serial_transfer_program(&mut master, 0x24, 0x81);
+    load_serial_transfer_program(&mut slave, 0x42, 0x80);
+
+    for _ in 0..1000000 {
+        master.emulate_step().unwrap();
+        slave.emulate_step().unwrap();
+        if master.peek_byte(0xC100) != 0 && slave.peek_byte(0xC100) != 0 {
+            break;
+        }
+    }
+    assert_eq!(0x42, master.peek_byte(0xC100));
+    assert_eq!(0x24, slave.peek_byte(0xC100));
+
+    // Peer is gone, the next transfer receives 0xFF
+    drop(slave);
+    load_serial_transfer_program(&mut master, 0x24, 0x81);
+    for _ in 0..100000 {
+        master.emulate_step().unwrap();
+        if master.peek_byte(0xC100) != 0 {
+            break;
+        }
+    }
+    assert!(!master.is_link_connected());
+    assert_eq!(0xFF, master.peek_byte(0xC100));
+}
+
+#[test]
+fn linked_pair_test() {
+    let mut first = GameBoyEmulator::new();
+    let mut second = GameBoyEmulator::new();
+    for gameboy in [&mut first, &mut second] {
+        gameboy
+            .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+            .unwrap();
+    }
+    load_serial_transfer_program(&mut first, 0x42, 0x80);
+    load_serial_transfer_program(&mut second, 0x24, 0x81);
+    let mut linked_pair = LinkedPair::new(first, second);
+
+    // Transfer takes 8 bits of 512 cycles
+    linked_pair.run_frame().unwrap();
+    assert_eq!(0x24, linked_pair.first().peek_byte(0xC100));
+    assert_eq!(0x42, linked_pair.second().peek_byte(0xC100));
+
+    // Roles swapped, the first one drives the clock
+    load_serial_transfer_program(linked_pair.first_mut(), 0x11, 0x81);
+    load_serial_transfer_program(linked_pair.second_mut(), 0x22, 0x80);
+    linked_pair.run_frame().unwrap();
+    assert_eq!(0x22, linked_pair.first().peek_byte(0xC100));
+    assert_eq!(0x11, linked_pair.second().peek_byte(0xC100));
+
+    // Unplugged cable, nothing is shifted in
+    let (mut first, _) = linked_pair.into_inner();
+    load_serial_transfer_program(&mut first, 0x11, 0x81);
+    first
+        .run_frame(&mut [0; resolution::SCREEN_W * resolution::SCREEN_H])
+        .unwrap();
+    assert_eq!(0xFF, first.peek_byte(0xC100));
+}
+
+#[test]
+fn printer_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    let output_directory =
+        std::env::temp_dir().join(format!("r_gb_emu_printer_test_{}", std::process::id()));
+    std::fs::create_dir_all(&output_directory).unwrap();
+    gameboy.connect_serial_device(GameBoyPrinter::new(&output_directory));
+
+    // Sends 10 bytes from 0xC200 with the internal clock, the replies go to 0xC300
+    let program = [
+        0xF3, 0x21, 0x00, 0xC2, 0x11, 0x00, 0xC3, 0x06, 0x0A, 0x2A, 0xE0, 0x01, 0x3E, 0x81, 0xE0,
+        0x02, 0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA, 0xF0, 0x01, 0x12, 0x13, 0x05, 0x20, 0xEC, 0x18,
+        0xFE,
+    ];
+    // STATUS packet
+    let packet = [0x88, 0x33, 0x0F, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x00, 0x00];
+    for (offset, byte) in program.iter().enumerate() {
+        gameboy.poke_byte(0xC000 + offset as u16, *byte);
+    }
+    for (offset, byte) in packet.iter().enumerate() {
+        gameboy.poke_byte(0xC200 + offset as u16, *byte);
+    }
+    let mut registers = gameboy.cpu_registers();
+    registers.pc = 0xC000;
+    gameboy.set_cpu_registers(&registers);
+
+    gameboy
+        .run_frame(&mut [0; resolution::SCREEN_W * resolution::SCREEN_H])
+        .unwrap();
+    let replies: Vec<u8> = (0..10)
+        .map(|offset| gameboy.peek_byte(0xC300 + offset))
+        .collect();
+    assert_eq!(vec![0, 0, 0, 0, 0, 0, 0, 0, 0x81, 0x00], replies);
+    gameboy.disconnect_serial_device();
+    let _ = std::fs::remove_dir_all(&output_directory);
+}
+
+#[test]
+fn loopback_device_test() {
+    let mut gameboy = GameBoyEmulator::new();
+    gameboy
+        .load_cartridge("roms/07-jr,jp,call,ret,rst.gb")
+        .unwrap();
+    gameboy.connect_serial_device(Loopback);
+
+    // Same program as the printer test, the bytes come back
+    let program = [
+        0xF3, 0x21, 0x00, 0xC2, 0x11, 0x00, 0xC3, 0x06, 0x0A, 0x2A, 0xE0, 0x01, 0x3E, 0x81, 0xE0,
+        0x02, 0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA, 0xF0, 0x01, 0x12, 0x13, 0x05, 0x20, 0xEC, 0x18,
+        0xFE,
+    ];
+    for (offset, byte) in program.iter().enumerate() {
+        gameboy.poke_byte(0xC000 + offset as u16, *byte);
+    }
+    for offset in 0..10 {
+        gameboy.poke_byte(0xC200 + offset, 0x30 + offset as u8);
+    }
+    let mut registers = gameboy.cpu_registers();
+    registers.pc = 0xC000;
+    gameboy.set_cpu_registers(&registers);
+
+    gameboy
+        .run_frame(&mut [0; resolution::SCREEN_W * resolution::SCREEN_H])
+        .unwrap();
+    for offset in 0..10 {
+        assert_eq!(0x30 + offset as u8, gameboy.peek_byte(0xC300 + offset));
+    }
 }